@@ -43,12 +43,13 @@
 use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
+use camino::Utf8PathBuf;
 use clap::Parser;
 use compute_api::responses::ComputeConfig;
 use compute_tools::compute::{
     BUILD_TAG, ComputeNode, ComputeNodeParams, forward_termination_signal,
 };
-use compute_tools::extension_server::get_pg_version_string;
+use compute_tools::extension_server::{default_pg_config_path, get_pg_version_string};
 use compute_tools::logger::*;
 use compute_tools::params::*;
 use compute_tools::spec::*;
@@ -65,10 +66,29 @@ struct Cli {
     #[arg(short = 'b', long, default_value = "postgres", env = "POSTGRES_PATH")]
     pub pgbin: String,
 
+    /// Path to `pg_config`. Defaults to the `pg_config` in the same directory as `pgbin`.
+    #[arg(long, env = "PG_CONFIG")]
+    pub pg_config: Option<Utf8PathBuf>,
+
     /// The base URL for the remote extension storage proxy gateway.
     #[arg(short = 'r', long, value_parser = Self::parse_remote_ext_base_url)]
     pub remote_ext_base_url: Option<Url>,
 
+    /// Timeout in seconds for connecting to the remote extension storage proxy
+    /// gateway.
+    #[arg(long, default_value = "10")]
+    pub remote_ext_connect_timeout: u64,
+
+    /// Timeout in seconds for a single remote extension download request,
+    /// including reading the response body.
+    #[arg(long, default_value = "60")]
+    pub remote_ext_request_timeout: u64,
+
+    /// Directory to unpack downloaded remote extension archives in before they
+    /// are moved into the postgres installation. Defaults to the data directory.
+    #[arg(long)]
+    pub remote_ext_staging_dir: Option<Utf8PathBuf>,
+
     /// The port to bind the external listening HTTP server to. Clients running
     /// outside the compute will talk to the compute through this port. Keep
     /// the previous name for this argument around for a smoother release
@@ -174,16 +194,25 @@ fn main() -> Result<()> {
 
     let config = get_config(&cli)?;
 
+    let pg_config = match &cli.pg_config {
+        Some(pg_config) => pg_config.to_string(),
+        None => default_pg_config_path(&cli.pgbin)?,
+    };
+
     let compute_node = ComputeNode::new(
         ComputeNodeParams {
             compute_id: cli.compute_id,
             connstr,
             pgdata: cli.pgdata.clone(),
             pgbin: cli.pgbin.clone(),
-            pgversion: get_pg_version_string(&cli.pgbin),
+            pgversion: get_pg_version_string(&pg_config)?,
+            pg_config,
             external_http_port: cli.external_http_port,
             internal_http_port: cli.internal_http_port,
             remote_ext_base_url: cli.remote_ext_base_url.clone(),
+            remote_ext_connect_timeout: Duration::from_secs(cli.remote_ext_connect_timeout),
+            remote_ext_request_timeout: Duration::from_secs(cli.remote_ext_request_timeout),
+            remote_ext_staging_dir: cli.remote_ext_staging_dir,
             resize_swap_on_bind: cli.resize_swap_on_bind,
             set_disk_quota_for_fs: cli.set_disk_quota_for_fs,
             #[cfg(target_os = "linux")]
@@ -179,7 +179,7 @@ async fn prepare(&self, initdb_user: &str) -> Result<(), anyhow::Error> {
             .await
             .context("create pgdata directory")?;
 
-        let pg_version = get_pg_version(self.pgbin.as_ref());
+        let pg_version = get_pg_version(self.pg_bin_dir.join("pg_config").as_ref())?;
 
         postgres_initdb::do_run_initdb(postgres_initdb::RunInitdbArgs {
             superuser: initdb_user,
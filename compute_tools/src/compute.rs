@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
 use chrono::{DateTime, Utc};
 use compute_api::privilege::Privilege;
 use compute_api::responses::{
@@ -26,7 +27,7 @@
 use std::process::{Command, Stdio};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
-use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, RwLock};
 use std::time::{Duration, Instant};
 use std::{env, fs};
 use tokio::spawn;
@@ -40,10 +41,11 @@
 
 use crate::configurator::launch_configurator;
 use crate::disk_quota::set_disk_quota;
+use crate::extension_server::PgConfig;
 use crate::installed_extensions::get_installed_extensions;
 use crate::logger::startup_context_from_env;
 use crate::lsn_lease::launch_lsn_lease_bg_task_for_static;
-use crate::metrics::COMPUTE_CTL_UP;
+use crate::metrics::{COMPUTE_CTL_UP, REMOTE_EXT_COLD_START_DOWNLOAD_SECONDS};
 use crate::monitor::launch_monitor;
 use crate::pg_helpers::*;
 use crate::pgbouncer::*;
@@ -94,6 +96,8 @@ pub struct ComputeNodeParams {
     pub pgdata: String,
     pub pgbin: String,
     pub pgversion: String,
+    /// Path to the `pg_config` of the postgres install.
+    pub pg_config: String,
 
     /// The port that the compute's external HTTP server listens on
     pub external_http_port: u16,
@@ -102,6 +106,13 @@ pub struct ComputeNodeParams {
 
     /// the address of extension storage proxy gateway
     pub remote_ext_base_url: Option<Url>,
+    /// Timeout for establishing a connection to the extension storage proxy gateway
+    pub remote_ext_connect_timeout: Duration,
+    /// Timeout for a whole extension download request, including reading the body
+    pub remote_ext_request_timeout: Duration,
+    /// Directory to unpack downloaded extension archives in before moving them into
+    /// place. Defaults to `pgdata`.
+    pub remote_ext_staging_dir: Option<Utf8PathBuf>,
 
     /// Interval for installed extensions collection
     pub installed_extensions_collection_interval: Arc<AtomicU64>,
@@ -126,6 +137,10 @@ pub struct ComputeNode {
 
     // key: ext_archive_name, value: started download time, download_completed?
     pub ext_download_progress: RwLock<HashMap<String, (DateTime<Utc>, bool)>>,
+    /// HTTP client shared by all requests to the extension storage proxy gateway
+    ext_download_client: reqwest::Client,
+    /// Output of `params.pg_config`, resolved the first time it is needed.
+    resolved_pg_config: OnceLock<PgConfig>,
     pub compute_ctl_config: ComputeCtlConfig,
 
     /// Handle to the extension stats collection task
@@ -418,6 +433,11 @@ pub fn new(params: ComputeNodeParams, config: ComputeConfig) -> Result<Self> {
         conn_conf.options(&options);
         tokio_conn_conf.options(&options);
 
+        let ext_download_client = extension_server::build_client(
+            params.remote_ext_connect_timeout,
+            params.remote_ext_request_timeout,
+        )?;
+
         let mut new_state = ComputeState::new();
         if let Some(spec) = config.spec {
             let pspec = ParsedSpec::try_from(spec).map_err(|msg| anyhow::anyhow!(msg))?;
@@ -431,11 +451,22 @@ pub fn new(params: ComputeNodeParams, config: ComputeConfig) -> Result<Self> {
             state: Mutex::new(new_state),
             state_changed: Condvar::new(),
             ext_download_progress: RwLock::new(HashMap::new()),
+            ext_download_client,
+            resolved_pg_config: OnceLock::new(),
             compute_ctl_config: config.compute_ctl_config,
             extension_stats_task: Mutex::new(None),
         })
     }
 
+    /// The output of `pg_config` that extension installs need.
+    pub fn pg_config(&self) -> Result<&PgConfig> {
+        if let Some(pg_config) = self.resolved_pg_config.get() {
+            return Ok(pg_config);
+        }
+        let pg_config = PgConfig::resolve(&self.params.pg_config)?;
+        Ok(self.resolved_pg_config.get_or_init(|| pg_config))
+    }
+
     /// Top-level control flow of compute_ctl. Returns a process exit code we should
     /// exit with.
     pub fn run(self) -> Result<Option<i32>> {
@@ -868,8 +899,10 @@ async fn download_preload_extensions(&self, spec: &ComputeSpec) -> Result<()> {
             return Ok(());
         };
 
+        let download_start = Instant::now();
+
         // First, create control files for all available extensions
-        extension_server::create_control_files(remote_extensions, &self.params.pgbin);
+        extension_server::create_control_files(remote_extensions, self.pg_config()?)?;
 
         let library_load_start_time = Utc::now();
         let remote_ext_metrics = self.prepare_preload_libraries(spec).await?;
@@ -888,7 +921,15 @@ async fn download_preload_extensions(&self, spec: &ComputeSpec) -> Result<()> {
             "Loading shared_preload_libraries took {:?}ms",
             library_load_time
         );
-        info!("{:?}", remote_ext_metrics);
+
+        let download_duration = download_start.elapsed();
+        REMOTE_EXT_COLD_START_DOWNLOAD_SECONDS.observe(download_duration.as_secs_f64());
+        info!(
+            num_ext_downloaded = remote_ext_metrics.num_ext_downloaded,
+            largest_ext_size = remote_ext_metrics.largest_ext_size,
+            total_ext_download_size = remote_ext_metrics.total_ext_download_size,
+            "downloaded extensions for compute start in {download_duration:?}"
+        );
 
         Ok(())
     }
@@ -2060,6 +2101,7 @@ pub async fn download_extension(
                 .ok_or(DownloadError::BadInput(anyhow::anyhow!(
                     "Remote extensions storage is not configured",
                 )))?;
+        let pg_config = self.pg_config().map_err(DownloadError::Other)?;
 
         let ext_archive_name = ext_path.object_name().expect("bad path");
 
@@ -2115,13 +2157,17 @@ pub async fn download_extension(
         info!("downloading new extension {ext_archive_name}");
 
         let download_size = extension_server::download_extension(
+            &self.ext_download_client,
             &real_ext_name,
             &ext_path,
             remote_ext_base_url,
-            &self.params.pgbin,
+            pg_config,
+            self.params
+                .remote_ext_staging_dir
+                .as_deref()
+                .unwrap_or(Utf8Path::new(&self.params.pgdata)),
         )
-        .await
-        .map_err(DownloadError::Other);
+        .await;
 
         if download_size.is_ok() {
             self.ext_download_progress
@@ -2292,13 +2338,48 @@ pub async fn prepare_preload_libraries(
 
         info!("Downloading to shared preload libraries: {:?}", &libs_vec);
 
-        let mut download_tasks = Vec::new();
+        let mut ext_paths = HashMap::new();
         for library in &libs_vec {
             let (ext_name, ext_path) =
                 remote_extensions.get_ext(library, true, &BUILD_TAG, &self.params.pgversion)?;
-            download_tasks.push(self.download_extension(ext_name, ext_path));
+            ext_paths.insert(ext_name, ext_path);
+        }
+        let ext_names: Vec<String> = ext_paths.keys().cloned().collect();
+
+        // Extensions are downloaded after the remote extensions they require.
+        let stages = match remote_extensions.install_order(&ext_names, &self.params.pgversion) {
+            Ok(stages) => stages,
+            Err(e) => {
+                warn!("not downloading the extensions required by preloaded libraries: {e:#}");
+                vec![ext_names]
+            }
+        };
+        let mut results = Vec::new();
+        for stage in stages {
+            let mut download_tasks = Vec::new();
+            for ext_name in stage {
+                let ext_path = match ext_paths.remove(&ext_name) {
+                    Some(ext_path) => ext_path,
+                    // Only required by the preloaded extensions. A missing extension is
+                    // reported by postgres when it is needed, so it should not keep the
+                    // compute from starting.
+                    None => match remote_extensions.get_ext(
+                        &ext_name,
+                        false,
+                        &BUILD_TAG,
+                        &self.params.pgversion,
+                    ) {
+                        Ok((_, ext_path)) => ext_path,
+                        Err(e) => {
+                            warn!("skipping download of extension {ext_name}: {e:#}");
+                            continue;
+                        }
+                    },
+                };
+                download_tasks.push(self.download_extension(ext_name, ext_path));
+            }
+            results.extend(join_all(download_tasks).await);
         }
-        let results = join_all(download_tasks).await;
 
         let mut remote_ext_metrics = RemoteExtensionMetrics {
             num_ext_downloaded: 0,
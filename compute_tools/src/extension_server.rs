@@ -70,51 +70,117 @@
         }
     }
 }
+
+An extension may list the postgres major versions it is built for in
+"supported_pg_versions", e.g. [15, 16]. It is then neither downloaded nor are
+its control files written on computes of other versions.
 */
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str;
+use std::time::Duration;
 
-use crate::metrics::{REMOTE_EXT_REQUESTS_TOTAL, UNKNOWN_HTTP_STATUS};
-use anyhow::{Context, Result, bail};
+use crate::compute::BUILD_TAG;
+use crate::metrics::{
+    REMOTE_EXT_CONTROL_FILE_CONFLICTS, REMOTE_EXT_REQUESTS_TOTAL, UNKNOWN_HTTP_STATUS,
+};
+use anyhow::{Context, Result};
 use bytes::Bytes;
-use compute_api::spec::RemoteExtSpec;
+use camino::Utf8Path;
+use compute_api::responses::{AvailableExtension, ExtensionSource};
+use compute_api::spec::{RemoteExtSpec, control_file_requires};
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
 use postgres_versioninfo::PgMajorVersion;
 use regex::Regex;
 use remote_storage::*;
 use reqwest::StatusCode;
 use tar::Archive;
+use tokio_util::sync::CancellationToken;
 use tracing::info;
 use tracing::log::warn;
 use url::Url;
+use walkdir::WalkDir;
 use zstd::stream::read::Decoder;
 
-fn get_pg_config(argument: &str, pgbin: &str) -> String {
-    // gives the result of `pg_config [argument]`
-    // where argument is a flag like `--version` or `--sharedir`
-    let pgconfig = pgbin
-        .strip_suffix("postgres")
-        .expect("bad pgbin")
-        .to_owned()
-        + "/pg_config";
+/// The `pg_config` in the same directory as the `postgres` binary `pgbin`, for installs that
+/// do not configure its path explicitly.
+pub fn default_pg_config_path(pgbin: &str) -> Result<String> {
+    let bindir = pgbin.strip_suffix("postgres").with_context(|| {
+        format!("cannot derive the pg_config path from pgbin {pgbin:?}, configure it explicitly")
+    })?;
+    Ok(format!("{bindir}/pg_config"))
+}
+
+/// The output of `pg_config` that extension installs need. It does not change while
+/// compute_ctl runs, so it is resolved once instead of for every extension.
+#[derive(Clone, Debug)]
+pub struct PgConfig {
+    pub version: PgMajorVersion,
+    /// `pg_config --bindir`
+    pub bindir: String,
+    /// `pg_config --includedir-server`
+    pub includedir_server: String,
+    /// `pg_config --pkglibdir`, where extension libraries are installed.
+    pub pkglibdir: String,
+    /// `pg_config --sharedir`, whose `extension` directory holds the control files.
+    pub sharedir: String,
+}
+
+impl PgConfig {
+    /// Runs the `pg_config` binary at `pg_config` for each of the values.
+    pub fn resolve(pg_config: &str) -> Result<Self> {
+        let pg_config = Path::new(pg_config);
+        Ok(Self {
+            // pg_config --version returns a (platform specific) human readable string
+            // such as "PostgreSQL 15.4". We parse this to v14/v15/v16 etc.
+            version: parse_pg_version(&run_pg_config(pg_config, "--version")?),
+            bindir: run_pg_config(pg_config, "--bindir")?,
+            includedir_server: run_pg_config(pg_config, "--includedir-server")?,
+            pkglibdir: run_pg_config(pg_config, "--pkglibdir")?,
+            sharedir: run_pg_config(pg_config, "--sharedir")?,
+        })
+    }
+
+    /// Top-level directories of an extension archive, and the local directory their contents
+    /// are installed into.
+    fn install_dirs(&self) -> [(&'static str, PathBuf); 4] {
+        [
+            ("bin", PathBuf::from(&self.bindir)),
+            ("include", PathBuf::from(&self.includedir_server)),
+            ("lib", PathBuf::from(&self.pkglibdir)),
+            ("share", PathBuf::from(&self.sharedir)),
+        ]
+    }
+
+    fn extension_dir(&self) -> PathBuf {
+        Path::new(&self.sharedir).join("extension")
+    }
+}
+
+fn run_pg_config(pgconfig: &Path, argument: &str) -> Result<String> {
     let config_output = std::process::Command::new(pgconfig)
         .arg(argument)
         .output()
-        .expect("pg_config error");
-    std::str::from_utf8(&config_output.stdout)
-        .expect("pg_config error")
-        .trim()
-        .to_string()
+        .with_context(|| format!("failed to run {pgconfig:?} {argument}"))?;
+    anyhow::ensure!(
+        config_output.status.success(),
+        "{pgconfig:?} {argument} failed: {}",
+        String::from_utf8_lossy(&config_output.stderr).trim()
+    );
+    let output = str::from_utf8(&config_output.stdout)
+        .with_context(|| format!("{pgconfig:?} {argument} returned invalid UTF-8"))?;
+    Ok(output.trim().to_string())
 }
 
-pub fn get_pg_version(pgbin: &str) -> PgMajorVersion {
+pub fn get_pg_version(pg_config: &str) -> Result<PgMajorVersion> {
     // pg_config --version returns a (platform specific) human readable string
     // such as "PostgreSQL 15.4". We parse this to v14/v15/v16 etc.
-    let human_version = get_pg_config("--version", pgbin);
-    parse_pg_version(&human_version)
+    let human_version = run_pg_config(Path::new(pg_config), "--version")?;
+    Ok(parse_pg_version(&human_version))
 }
 
-pub fn get_pg_version_string(pgbin: &str) -> String {
-    get_pg_version(pgbin).v_str()
+pub fn get_pg_version_string(pg_config: &str) -> Result<String> {
+    Ok(get_pg_version(pg_config)?.v_str())
 }
 
 fn parse_pg_version(human_version: &str) -> PgMajorVersion {
@@ -140,94 +206,326 @@ fn parse_pg_version(human_version: &str) -> PgMajorVersion {
     panic!("Unsuported postgres version {human_version}");
 }
 
+/// Build the HTTP client used for all requests to the extension storage proxy
+/// gateway. The user-agent lets the gateway tell our requests apart from others.
+pub fn build_client(
+    connect_timeout: Duration,
+    request_timeout: Duration,
+) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(format!("neon-compute-ctl/{}", *BUILD_TAG))
+        .connect_timeout(connect_timeout)
+        .timeout(request_timeout)
+        .build()
+        .context("failed to build remote extensions HTTP client")
+}
+
 // download the archive for a given extension,
 // unzip it, and place files in the appropriate locations (share/lib)
 pub async fn download_extension(
+    fetcher: &impl ExtensionFetcher,
     ext_name: &str,
     ext_path: &RemotePath,
     remote_ext_base_url: &Url,
-    pgbin: &str,
-) -> Result<u64> {
+    pg_config: &PgConfig,
+    staging_dir: &Utf8Path,
+) -> Result<u64, DownloadError> {
     info!("Download extension {:?} from {:?}", ext_name, ext_path);
 
-    // TODO add retry logic
     let download_buffer =
-        match download_extension_tar(remote_ext_base_url, &ext_path.to_string()).await {
+        match download_extension_tar(fetcher, remote_ext_base_url, &ext_path.to_string()).await {
             Ok(buffer) => buffer,
+            // Let the caller know that it makes sense to retry
+            Err(DownloadError::Timeout) => {
+                warn!("timed out downloading extension {:?}", ext_name);
+                return Err(DownloadError::Timeout);
+            }
             Err(error_message) => {
-                return Err(anyhow::anyhow!(
+                return Err(DownloadError::Other(anyhow::anyhow!(
                     "error downloading extension {:?}: {:?}",
                     ext_name,
                     error_message
-                ));
+                )));
             }
         };
 
     let download_size = download_buffer.len() as u64;
     info!("Download size {:?}", download_size);
+
+    let _install_lock = lock_extension_install(ext_name, pg_config)
+        .await
+        .map_err(DownloadError::Other)?;
+    unpack_extension(ext_name, ext_path, &download_buffer, pg_config, staging_dir)
+        .map_err(DownloadError::Other)?;
+
+    Ok(download_size)
+}
+
+/// How long an install waits for another install of the same extension to finish.
+const INSTALL_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+const INSTALL_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Takes an exclusive lock on installing `ext_name`, so that computes sharing the postgres
+/// installation, e.g. through a shared volume, install the extension one at a time instead of
+/// racing to move the same files into place. The lock file lives next to the control files and
+/// the lock is released when the returned handle is dropped.
+async fn lock_extension_install(
+    ext_name: &str,
+    pg_config: &PgConfig,
+) -> Result<Flock<std::fs::File>> {
+    let lock_path = pg_config
+        .extension_dir()
+        .join(format!(".{ext_name}.install.lock"));
+    lock_file(&lock_path, INSTALL_LOCK_TIMEOUT).await
+}
+
+/// Flocks `path`, creating it if needed, giving up after `timeout`.
+async fn lock_file(path: &Path, timeout: Duration) -> Result<Flock<std::fs::File>> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("failed to open lock file {path:?}"))?;
+    loop {
+        match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(locked) => return Ok(locked),
+            Err((unlocked, Errno::EAGAIN)) => file = unlocked,
+            Err((_, e)) => return Err(e).with_context(|| format!("failed to lock {path:?}")),
+        }
+        if tokio::time::Instant::now() >= deadline {
+            anyhow::bail!(
+                "timed out after {timeout:?} waiting for another install to release {path:?}"
+            );
+        }
+        tokio::time::sleep(INSTALL_LOCK_POLL_INTERVAL).await;
+    }
+}
+
+// unzip the downloaded archive and move files to the appropriate locations (share/lib/...)
+fn unpack_extension(
+    ext_name: &str,
+    ext_path: &RemotePath,
+    download_buffer: &Bytes,
+    pg_config: &PgConfig,
+    staging_dir: &Utf8Path,
+) -> Result<()> {
+    let install_dirs = pg_config.install_dirs();
+
+    unpack_archive(download_buffer, staging_dir, &install_dirs)?;
+    info!("Download + unzip {:?} completed successfully", &ext_path);
+    info!("done moving extension {ext_name}");
+    Ok(())
+}
+
+// The archive is unpacked into a fresh temporary directory under `staging_dir`, which
+// is removed once the files are moved into place, or when unpacking fails.
+fn unpack_archive(
+    download_buffer: &Bytes,
+    staging_dir: &Utf8Path,
+    install_dirs: &[(&str, PathBuf)],
+) -> Result<()> {
     // it's unclear whether it is more performant to decompress into memory or not
     // TODO: decompressing into memory can be avoided
     let decoder = Decoder::new(download_buffer.as_ref())?;
     let mut archive = Archive::new(decoder);
 
-    let unzip_dest = pgbin
-        .strip_suffix("/bin/postgres")
-        .expect("bad pgbin")
-        .to_string()
-        + "/download_extensions";
-    archive.unpack(&unzip_dest)?;
-    info!("Download + unzip {:?} completed successfully", &ext_path);
+    std::fs::create_dir_all(staging_dir)
+        .with_context(|| format!("failed to create extension staging directory {staging_dir}"))?;
+    let unzip_dest = camino_tempfile::Builder::new()
+        .prefix("download_extensions-")
+        .tempdir_in(staging_dir)
+        .with_context(|| format!("failed to create a temporary directory in {staging_dir}"))?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        check_archive_entry(&entry)?;
+        entry.unpack_in(unzip_dest.path())?;
+    }
 
-    let sharedir_paths = (
-        unzip_dest.to_string() + "/share/extension",
-        Path::new(&get_pg_config("--sharedir", pgbin)).join("extension"),
-    );
-    let libdir_paths = (
-        unzip_dest.to_string() + "/lib",
-        Path::new(&get_pg_config("--pkglibdir", pgbin)).to_path_buf(),
-    );
-    // move contents of the libdir / sharedir in unzipped archive to the correct local paths
-    for paths in [sharedir_paths, libdir_paths] {
-        let (zip_dir, real_dir) = paths;
-
-        let dir = match std::fs::read_dir(&zip_dir) {
-            Ok(dir) => dir,
-            Err(e) => match e.kind() {
-                // In the event of a SQL-only extension, there would be nothing
-                // to move from the lib/ directory, so note that in the log and
-                // move on.
-                std::io::ErrorKind::NotFound => {
-                    info!("nothing to move from {}", zip_dir);
-                    continue;
-                }
-                _ => return Err(anyhow::anyhow!(e)),
-            },
+    install_unpacked_files(unzip_dest.path().as_std_path(), install_dirs)?;
+
+    let unzip_dest_path = unzip_dest.path().to_owned();
+    if let Err(e) = unzip_dest.close() {
+        warn!("failed to remove extension staging directory {unzip_dest_path}: {e}");
+    }
+    Ok(())
+}
+
+/// Fails for an archive entry that would be written outside of the directory the archive is
+/// unpacked into, through `..` or an absolute path, or that links outside of it.
+fn check_archive_entry<R: std::io::Read>(entry: &tar::Entry<R>) -> Result<()> {
+    let path = entry.path()?;
+    if archive_path_depth(Path::new(""), &path).is_none() {
+        anyhow::bail!(
+            "refusing to unpack extension archive: entry {path:?} escapes the destination directory"
+        );
+    }
+    if let Some(target) = entry.link_name()? {
+        // symlinks are resolved from the directory they are in, hard links from the root.
+        let base = if entry.header().entry_type().is_symlink() {
+            path.parent().unwrap_or(Path::new(""))
+        } else {
+            Path::new("")
         };
+        if archive_path_depth(base, &target).is_none() {
+            anyhow::bail!(
+                "refusing to unpack extension archive: entry {path:?} links to {target:?} outside of the destination directory"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Depth of `path`, relative to `base`, below the archive root. `None` if it is absolute or
+/// goes above the root.
+fn archive_path_depth(base: &Path, path: &Path) -> Option<usize> {
+    use std::path::Component;
+    let mut depth = 0usize;
+    for component in base.components().chain(path.components()) {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => depth = depth.checked_sub(1)?,
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(depth)
+}
+
+// move every file under the known top-level directories of the unzipped archive to the
+// same relative path under the matching local directory, creating subdirectories as needed.
+// If any file cannot be installed, the files installed so far are removed again and the files
+// they replaced are put back, so that a failed attempt does not leave a partially installed
+// extension behind for a retry to miss.
+fn install_unpacked_files(unzip_dest: &Path, install_dirs: &[(&str, PathBuf)]) -> Result<()> {
+    let mut moves = Vec::new();
+    for entry in std::fs::read_dir(unzip_dest)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some((_, real_dir)) = install_dirs.iter().find(|(dir, _)| name == *dir) else {
+            warn!("skipping unexpected {name:?} in extension archive");
+            continue;
+        };
+        let zip_dir = entry.path();
 
         info!("mv {zip_dir:?}/*  {real_dir:?}");
 
-        for file in dir {
-            let old_file = file?.path();
-            let new_file =
-                Path::new(&real_dir).join(old_file.file_name().context("error parsing file")?);
-            info!("moving {old_file:?} to {new_file:?}");
-
-            // extension download failed: Directory not empty (os error 39)
-            match std::fs::rename(old_file, new_file) {
-                Ok(()) => info!("move succeeded"),
-                Err(e) => {
-                    warn!("move failed, probably because the extension already exists: {e}")
-                }
+        for file in WalkDir::new(&zip_dir).min_depth(1) {
+            let file = file?;
+            if file.file_type().is_dir() {
+                continue;
             }
+            let old_file = file.path().to_owned();
+            let new_file = real_dir.join(old_file.strip_prefix(&zip_dir)?);
+            moves.push((old_file, new_file));
         }
     }
-    info!("done moving extension {ext_name}");
-    Ok(download_size)
+
+    moves.sort();
+
+    // installed files, with the backup of the file each one replaced
+    let mut installed: Vec<(&Path, Option<PathBuf>)> = Vec::with_capacity(moves.len());
+    let mut result = Ok(());
+    for (old_file, new_file) in &moves {
+        let backup = match backup_file(new_file) {
+            Ok(backup) => backup,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        let res = install_file(old_file, new_file);
+        installed.push((new_file, backup));
+        if let Err(e) = res {
+            result = Err(e);
+            break;
+        }
+    }
+
+    if result.is_err() {
+        for (file, backup) in installed.iter().rev() {
+            match std::fs::remove_file(file) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("failed to remove partially installed {file:?}: {e}"),
+            }
+            let Some(backup) = backup else {
+                continue;
+            };
+            if let Err(e) = std::fs::rename(backup, file) {
+                warn!("failed to restore {file:?} from {backup:?}: {e}");
+            }
+        }
+    } else {
+        for backup in installed.iter().filter_map(|(_, backup)| backup.as_ref()) {
+            if let Err(e) = std::fs::remove_file(backup) {
+                warn!("failed to remove backup {backup:?}: {e}");
+            }
+        }
+    }
+    result
+}
+
+/// Moves an existing `file` aside, so that it can be put back if the installation fails.
+fn backup_file(file: &Path) -> Result<Option<PathBuf>> {
+    if !file.try_exists()? {
+        return Ok(None);
+    }
+    let mut backup = file.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    std::fs::rename(file, &backup)
+        .with_context(|| format!("failed to move {file:?} aside to {backup:?}"))?;
+    Ok(Some(backup))
+}
+
+fn install_file(old_file: &Path, new_file: &Path) -> Result<()> {
+    if let Some(parent) = new_file.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {parent:?}"))?;
+    }
+    info!("moving {old_file:?} to {new_file:?}");
+    move_file(old_file, new_file)
+        .with_context(|| format!("failed to move {old_file:?} to {new_file:?}"))
+}
+
+/// Renames `from` to `to`, falling back to copying when the staging directory is on a
+/// different filesystem than the postgres installation. The copied source is left behind
+/// for the caller to remove along with the rest of the staging directory.
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to).map(|_| ())
+        }
+        res => res,
+    }
+}
+
+/// Placeholders that control files from the spec may contain, so that a single spec entry
+/// can serve computes of several postgres major versions.
+struct ControlFileVars {
+    /// `{pg_version}`: the major version number, e.g. `16`.
+    pg_version: u32,
+    /// `{libdir}`: the directory extension libraries are installed in, `pg_config --pkglibdir`.
+    libdir: String,
+}
+
+impl ControlFileVars {
+    fn render(&self, control_content: &str) -> String {
+        control_content
+            .replace("{pg_version}", &self.pg_version.to_string())
+            .replace("{libdir}", &self.libdir)
+    }
 }
 
 // Create extension control files from spec
-pub fn create_control_files(remote_extensions: &RemoteExtSpec, pgbin: &str) {
-    let local_sharedir = Path::new(&get_pg_config("--sharedir", pgbin)).join("extension");
+pub fn create_control_files(remote_extensions: &RemoteExtSpec, pg_config: &PgConfig) -> Result<()> {
+    let local_sharedir = pg_config.extension_dir();
+    let vars = ControlFileVars {
+        pg_version: pg_config.version.major_version_num(),
+        libdir: pg_config.pkglibdir.clone(),
+    };
     for (ext_name, ext_data) in remote_extensions.extension_data.iter() {
         // Check if extension is present in public or custom.
         // If not, then it is not allowed to be used by this compute.
@@ -241,29 +539,151 @@ pub fn create_control_files(remote_extensions: &RemoteExtSpec, pgbin: &str) {
             }
         }
 
+        // without control files, CREATE EXTENSION reports the extension as unavailable
+        // instead of failing on files built for another postgres version.
+        if !ext_data.supports_pg_version(vars.pg_version) {
+            warn!(
+                "extension {} is not available for postgres {}, only for {:?}. skipping its control files.",
+                ext_name,
+                vars.pg_version,
+                ext_data
+                    .supported_pg_versions
+                    .as_deref()
+                    .unwrap_or_default()
+            );
+            continue;
+        }
+
+        let overwrite = remote_extensions
+            .overwrite_control_files
+            .as_ref()
+            .is_some_and(|overwrite| overwrite.contains(ext_name));
+
         for (control_name, control_content) in &ext_data.control_data {
+            let control_content = vars.render(control_content);
             let control_path = local_sharedir.join(control_name);
             if !control_path.exists() {
                 info!("writing file {:?}{:?}", control_path, control_content);
-                std::fs::write(control_path, control_content).unwrap();
+                std::fs::write(control_path, &control_content).unwrap();
+            } else if overwrite {
+                warn!(
+                    "control file {:?} exists both locally and remotely. overwriting it with the remote version as requested by the spec.",
+                    control_path
+                );
+                std::fs::write(control_path, &control_content).unwrap();
+                REMOTE_EXT_CONTROL_FILE_CONFLICTS
+                    .with_label_values(&[ext_name.as_str(), "kept_remote"])
+                    .inc();
             } else {
                 warn!(
                     "control file {:?} exists both locally and remotely. ignoring the remote version.",
                     control_path
                 );
+                REMOTE_EXT_CONTROL_FILE_CONFLICTS
+                    .with_label_values(&[ext_name.as_str(), "kept_local"])
+                    .inc();
             }
         }
     }
+    Ok(())
+}
+
+/// Lists the extensions whose control files are in the sharedir, as written by
+/// [`create_control_files`] or shipped with the image.
+pub fn list_available_extensions(
+    remote_extensions: Option<&RemoteExtSpec>,
+    pg_config: &PgConfig,
+) -> Result<Vec<AvailableExtension>> {
+    let local_sharedir = pg_config.extension_dir();
+    let vars = remote_extensions.map(|_| ControlFileVars {
+        pg_version: pg_config.version.major_version_num(),
+        libdir: pg_config.pkglibdir.clone(),
+    });
+
+    let mut extensions = Vec::new();
+    for entry in std::fs::read_dir(&local_sharedir)
+        .with_context(|| format!("failed to read {local_sharedir:?}"))?
+    {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        // secondary control files, `name--version.control`, only override the primary one.
+        let Some(name) = file_name.strip_suffix(".control") else {
+            continue;
+        };
+        if name.contains("--") {
+            continue;
+        }
+
+        let content = std::fs::read_to_string(entry.path())
+            .with_context(|| format!("failed to read {:?}", entry.path()))?;
+        // a control file from the spec is only written if the image does not have one, or the
+        // spec asks to overwrite it, so it is remote if it still has the spec's content.
+        let remote_content = remote_extensions.and_then(|remote_extensions| {
+            remote_extensions
+                .extension_data
+                .values()
+                .find_map(|ext_data| ext_data.control_data.get(file_name))
+        });
+        let source = match (remote_content, &vars) {
+            (Some(remote_content), Some(vars)) if vars.render(remote_content) == content => {
+                ExtensionSource::Remote
+            }
+            _ => ExtensionSource::Image,
+        };
+
+        extensions.push(parse_control_file(name, &content, source));
+    }
+    extensions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(extensions)
+}
+
+/// Reads the parameters of a control file that describe the extension.
+fn parse_control_file(name: &str, content: &str, source: ExtensionSource) -> AvailableExtension {
+    let mut extension = AvailableExtension {
+        name: name.to_string(),
+        default_version: None,
+        trusted: false,
+        requires: control_file_requires(content),
+        source,
+    };
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('\'');
+        match key.trim() {
+            "default_version" => extension.default_version = Some(value.to_string()),
+            "trusted" => {
+                extension.trusted = matches!(
+                    value.to_ascii_lowercase().as_str(),
+                    "true" | "on" | "yes" | "1"
+                )
+            }
+            _ => {}
+        }
+    }
+    extension
 }
 
 // Do request to extension storage proxy, e.g.,
 // curl http://pg-ext-s3-gateway.pg-ext-s3-gateway.svc.cluster.local/latest/v15/extensions/anon.tar.zst
 // using HTTP GET and return the response body as bytes.
-async fn download_extension_tar(remote_ext_base_url: &Url, ext_path: &str) -> Result<Bytes> {
-    let uri = remote_ext_base_url.join(ext_path).with_context(|| {
-        format!(
-            "failed to create the remote extension URI for {ext_path} using {remote_ext_base_url}"
-        )
+async fn download_extension_tar(
+    fetcher: &impl ExtensionFetcher,
+    remote_ext_base_url: &Url,
+    ext_path: &str,
+) -> Result<Bytes, DownloadError> {
+    let uri = remote_ext_base_url.join(ext_path).map_err(|e| {
+        DownloadError::BadInput(anyhow::anyhow!(
+            "failed to create the remote extension URI for {ext_path} using {remote_ext_base_url}: {e}"
+        ))
     })?;
     let filename = Path::new(ext_path)
         .file_name()
@@ -274,58 +694,546 @@ async fn download_extension_tar(remote_ext_base_url: &Url, ext_path: &str) -> Re
 
     info!("Downloading extension file '{}' from uri {}", filename, uri);
 
-    match do_extension_server_request(uri).await {
+    let result = utils::backoff::retry(
+        || async {
+            let result = fetcher.get(uri.clone()).await;
+            match &result {
+                Ok(_) => REMOTE_EXT_REQUESTS_TOTAL
+                    .with_label_values(&[&StatusCode::OK.to_string(), &filename, "ok"])
+                    .inc(),
+                Err(e) => REMOTE_EXT_REQUESTS_TOTAL
+                    .with_label_values(&[&e.status, &filename, e.kind.as_str()])
+                    .inc(),
+            }
+            result
+        },
+        |e: &FetchError| !e.is_retryable(),
+        1,
+        DOWNLOAD_MAX_RETRIES,
+        &format!("downloading extension file '{filename}'"),
+        &CancellationToken::new(),
+    )
+    .await
+    .expect("extension downloads are not cancelled");
+
+    match result {
         Ok(resp) => {
             info!("Successfully downloaded remote extension data {}", ext_path);
-            REMOTE_EXT_REQUESTS_TOTAL
-                .with_label_values(&[&StatusCode::OK.to_string(), &filename])
-                .inc();
             Ok(resp)
         }
-        Err((msg, status)) => {
-            REMOTE_EXT_REQUESTS_TOTAL
-                .with_label_values(&[&status, &filename])
-                .inc();
-            bail!(msg);
+        Err(FetchError { kind, message, .. }) => {
+            if kind == FetchErrorKind::Timeout {
+                warn!("{message}");
+                Err(DownloadError::Timeout)
+            } else {
+                Err(DownloadError::Other(anyhow::anyhow!(message)))
+            }
+        }
+    }
+}
+
+/// How many times a failed request for an extension archive is retried, if the failure
+/// could be temporary.
+const DOWNLOAD_MAX_RETRIES: u32 = 3;
+
+/// Why a request to the remote extensions server failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchErrorKind {
+    /// Sending the request or reading the response timed out.
+    Timeout,
+    /// The server could not be reached: DNS resolution, the TCP connection or the TLS
+    /// handshake failed.
+    Connect,
+    /// The connection failed while the request was sent or the response received.
+    Request,
+    /// The response body could not be read.
+    Body,
+    /// The server answered that it is temporarily unavailable.
+    Unavailable,
+    /// The server answered with an unexpected status code.
+    Status,
+    /// Anything else, like a request that could not be built. Retrying would fail the same way.
+    Other,
+}
+
+impl FetchErrorKind {
+    fn from_reqwest(e: &reqwest::Error) -> Self {
+        // a timeout while connecting is both, report it as a timeout.
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_connect() {
+            Self::Connect
+        } else if e.is_body() || e.is_decode() {
+            Self::Body
+        } else if e.is_request() {
+            Self::Request
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Whether the same request could succeed if it is sent again.
+    pub fn is_retryable(self) -> bool {
+        match self {
+            Self::Timeout | Self::Connect | Self::Request | Self::Body | Self::Unavailable => true,
+            Self::Status | Self::Other => false,
+        }
+    }
+
+    /// Metric label of the failure.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Connect => "connect",
+            Self::Request => "request",
+            Self::Body => "body",
+            Self::Unavailable => "unavailable",
+            Self::Status => "status",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Failure of a single request to the remote extensions server.
+#[derive(Debug)]
+pub struct FetchError {
+    pub kind: FetchErrorKind,
+    pub message: String,
+    /// Stringified HTTP status code, used as a metric label.
+    pub status: String,
+}
+
+impl FetchError {
+    fn from_reqwest(e: reqwest::Error, context: &str, status: String) -> Self {
+        let kind = FetchErrorKind::from_reqwest(&e);
+        FetchError {
+            kind,
+            message: format!("{context} ({}): {e:?}", kind.as_str()),
+            status,
         }
     }
+
+    /// Whether the request could succeed if it is sent again.
+    pub fn is_retryable(&self) -> bool {
+        self.kind.is_retryable()
+    }
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Performs requests to the remote extensions server. Implemented by
+/// [`reqwest::Client`]; tests substitute fakes returning canned responses.
+pub trait ExtensionFetcher: Sync {
+    fn get(&self, uri: Url) -> impl Future<Output = Result<Bytes, FetchError>> + Send;
 }
 
 // Do a single remote extensions server request.
-// Return result or (error message + stringified status code) in case of any failures.
-async fn do_extension_server_request(uri: Url) -> Result<Bytes, (String, String)> {
-    let resp = reqwest::get(uri).await.map_err(|e| {
-        (
-            format!("could not perform remote extensions server request: {e:?}"),
-            UNKNOWN_HTTP_STATUS.to_string(),
-        )
-    })?;
-    let status = resp.status();
-
-    match status {
-        StatusCode::OK => match resp.bytes().await {
-            Ok(resp) => Ok(resp),
-            Err(e) => Err((
-                format!("could not read remote extensions server response: {e:?}"),
-                // It's fine to return and report error with status as 200 OK,
-                // because we still failed to read the response.
-                status.to_string(),
-            )),
-        },
-        StatusCode::SERVICE_UNAVAILABLE => Err((
-            "remote extensions server is temporarily unavailable".to_string(),
-            status.to_string(),
-        )),
-        _ => Err((
-            format!("unexpected remote extensions server response status code: {status}"),
-            status.to_string(),
-        )),
+impl ExtensionFetcher for reqwest::Client {
+    async fn get(&self, uri: Url) -> Result<Bytes, FetchError> {
+        let resp = reqwest::Client::get(self, uri).send().await.map_err(|e| {
+            FetchError::from_reqwest(
+                e,
+                "could not perform remote extensions server request",
+                UNKNOWN_HTTP_STATUS.to_string(),
+            )
+        })?;
+        let status = resp.status();
+
+        match status {
+            StatusCode::OK => match resp.bytes().await {
+                Ok(resp) => Ok(resp),
+                Err(e) => Err(FetchError::from_reqwest(
+                    e,
+                    "could not read remote extensions server response",
+                    // It's fine to return and report error with status as 200 OK,
+                    // because we still failed to read the response.
+                    status.to_string(),
+                )),
+            },
+            StatusCode::SERVICE_UNAVAILABLE => Err(FetchError {
+                kind: FetchErrorKind::Unavailable,
+                message: "remote extensions server is temporarily unavailable".to_string(),
+                status: status.to_string(),
+            }),
+            _ => Err(FetchError {
+                kind: FetchErrorKind::Status,
+                message: format!(
+                    "unexpected remote extensions server response status code: {status}"
+                ),
+                status: status.to_string(),
+            }),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_pg_version;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Returns the queued responses in order, one per request.
+    struct FakeFetcher {
+        responses: Mutex<Vec<Result<Bytes, FetchError>>>,
+    }
+
+    impl FakeFetcher {
+        fn new(mut responses: Vec<Result<Bytes, FetchError>>) -> Self {
+            responses.reverse();
+            Self {
+                responses: Mutex::new(responses),
+            }
+        }
+    }
+
+    impl ExtensionFetcher for FakeFetcher {
+        async fn get(&self, _uri: Url) -> Result<Bytes, FetchError> {
+            self.responses
+                .lock()
+                .unwrap()
+                .pop()
+                .expect("unexpected request")
+        }
+    }
+
+    fn fetch_error(kind: FetchErrorKind, status: StatusCode) -> FetchError {
+        FetchError {
+            kind,
+            message: "fake failure".to_string(),
+            status: status.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_extension_tar() {
+        let base_url = Url::parse("http://localhost/latest/").unwrap();
+        let timeout = || Err(fetch_error(FetchErrorKind::Timeout, StatusCode::OK));
+        let fetcher = FakeFetcher::new(vec![
+            // temporary failures are retried
+            Err(fetch_error(
+                FetchErrorKind::Unavailable,
+                StatusCode::SERVICE_UNAVAILABLE,
+            )),
+            timeout(),
+            Ok(Bytes::from_static(b"archive")),
+            // others are not
+            Err(fetch_error(FetchErrorKind::Status, StatusCode::NOT_FOUND)),
+            // until the retries run out
+            timeout(),
+            timeout(),
+            timeout(),
+            timeout(),
+        ]);
+
+        let bytes = download_extension_tar(&fetcher, &base_url, "v17/extensions/anon.tar.zst")
+            .await
+            .unwrap();
+        assert_eq!(bytes, Bytes::from_static(b"archive"));
+
+        let err = download_extension_tar(&fetcher, &base_url, "v17/extensions/anon.tar.zst")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DownloadError::Other(_)), "{err}");
+
+        let err = download_extension_tar(&fetcher, &base_url, "v17/extensions/anon.tar.zst")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DownloadError::Timeout), "{err}");
+        assert!(fetcher.responses.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_error_kind() {
+        // nothing listens on the port once the listener is dropped.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let client = build_client(Duration::from_secs(5), Duration::from_secs(5)).unwrap();
+        let uri = Url::parse(&format!("http://{addr}/latest/v17/extensions/anon.tar.zst")).unwrap();
+        let err = ExtensionFetcher::get(&client, uri).await.unwrap_err();
+        assert_eq!(err.kind, FetchErrorKind::Connect, "{}", err.message);
+        assert!(err.is_retryable());
+        assert_eq!(err.status, UNKNOWN_HTTP_STATUS);
+
+        assert!(!FetchErrorKind::Status.is_retryable());
+        assert!(FetchErrorKind::Unavailable.is_retryable());
+    }
+
+    fn build_archive(files: &[(&str, &str)]) -> Bytes {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, data.as_bytes())
+                .unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+        Bytes::from(zstd::encode_all(tar.as_slice(), 0).unwrap())
+    }
+
+    #[test]
+    fn test_render_control_file() {
+        let vars = ControlFileVars {
+            pg_version: 16,
+            libdir: "/usr/local/lib/postgresql".to_string(),
+        };
+        assert_eq!(
+            vars.render(
+                "default_version = '1.{pg_version}'\nmodule_pathname = '{libdir}/anon'\ndirectory = '$libdir/anon'"
+            ),
+            "default_version = '1.16'\nmodule_pathname = '/usr/local/lib/postgresql/anon'\ndirectory = '$libdir/anon'"
+        );
+    }
+
+    #[test]
+    fn test_parse_control_file() {
+        let extension = parse_control_file(
+            "anon",
+            "# PostgreSQL Anonymizer (anon) extension \ncomment = 'Data anonymization tools' \ndefault_version = '1.1.0' \ndirectory='extension/anon' \nrelocatable = false \nrequires = 'pgcrypto, pg_trgm' \nsuperuser = false \nmodule_pathname = '$libdir/anon' \ntrusted = true \n",
+            ExtensionSource::Remote,
+        );
+        assert_eq!(extension.name, "anon");
+        assert_eq!(extension.default_version.as_deref(), Some("1.1.0"));
+        assert!(extension.trusted);
+        assert_eq!(extension.requires, ["pgcrypto", "pg_trgm"]);
+        assert_eq!(extension.source, ExtensionSource::Remote);
+
+        let extension = parse_control_file("plain", "# trusted = true\n", ExtensionSource::Image);
+        assert_eq!(extension.default_version, None);
+        assert!(!extension.trusted);
+        assert!(extension.requires.is_empty());
+    }
+
+    #[test]
+    fn test_unpack_archive_nested_layout() {
+        let staging_dir = camino_tempfile::tempdir().unwrap();
+        let install_root = camino_tempfile::tempdir().unwrap();
+        let install_dirs = ["bin", "lib", "share"]
+            .map(|dir| (dir, install_root.path().join(dir).into_std_path_buf()));
+
+        let archive = build_archive(&[
+            ("share/extension/foo.control", "control"),
+            ("share/extension/foo/foo--1.0.sql", "sql"),
+            ("share/tsearch_data/foo.stop", "stop"),
+            ("lib/foo.so", "lib"),
+            ("lib/foo/plugin.so", "plugin"),
+            ("bin/foo_tool", "tool"),
+            ("doc/README", "readme"),
+        ]);
+        unpack_archive(&archive, staging_dir.path(), &install_dirs).unwrap();
+
+        for (path, data) in [
+            ("share/extension/foo.control", "control"),
+            ("share/extension/foo/foo--1.0.sql", "sql"),
+            ("share/tsearch_data/foo.stop", "stop"),
+            ("lib/foo.so", "lib"),
+            ("lib/foo/plugin.so", "plugin"),
+            ("bin/foo_tool", "tool"),
+        ] {
+            let installed = std::fs::read_to_string(install_root.path().join(path)).unwrap();
+            assert_eq!(installed, data, "{path}");
+        }
+        assert!(!install_root.path().join("doc").exists());
+
+        let leftovers = std::fs::read_dir(staging_dir.path()).unwrap().count();
+        assert_eq!(leftovers, 0, "staging directory was not cleaned up");
+    }
+
+    #[test]
+    fn test_unpack_archive_rollback() {
+        let staging_dir = camino_tempfile::tempdir().unwrap();
+        let install_root = camino_tempfile::tempdir().unwrap();
+        let install_dirs = [(
+            "share",
+            install_root.path().join("share").into_std_path_buf(),
+        )];
+
+        let extension_dir = install_root.path().join("share/extension");
+        std::fs::create_dir_all(&extension_dir).unwrap();
+        std::fs::write(extension_dir.join("foo.control"), "old control").unwrap();
+        // a file where the archive needs a directory, so that installing it fails
+        std::fs::write(extension_dir.join("zzz"), "in the way").unwrap();
+
+        let archive = build_archive(&[
+            ("share/extension/foo.control", "new control"),
+            ("share/extension/foo--1.0.sql", "sql"),
+            ("share/extension/zzz/foo--1.1.sql", "sql"),
+        ]);
+        unpack_archive(&archive, staging_dir.path(), &install_dirs).unwrap_err();
+
+        // the pre-existing control file is put back, and new files are removed
+        let control = std::fs::read_to_string(extension_dir.join("foo.control")).unwrap();
+        assert_eq!(control, "old control");
+        assert!(!extension_dir.join("foo--1.0.sql").exists());
+        let mut files = std::fs::read_dir(&extension_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["foo.control", "zzz"]);
+    }
+
+    #[tokio::test]
+    async fn test_lock_file() {
+        let dir = camino_tempfile::tempdir().unwrap();
+        let path = dir.path().join(".foo.install.lock").into_std_path_buf();
+
+        let held = lock_file(&path, Duration::ZERO).await.unwrap();
+        let err = lock_file(&path, Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{err}");
+
+        // the lock is free again once the holder is done
+        let waiter = tokio::spawn(async move { lock_file(&path, Duration::from_secs(10)).await });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(held);
+        waiter.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_unpack_archive_path_traversal() {
+        let staging_dir = camino_tempfile::tempdir().unwrap();
+        let install_root = camino_tempfile::tempdir().unwrap();
+        let install_dirs = [("lib", install_root.path().join("lib").into_std_path_buf())];
+
+        // the tar builder refuses `..` paths, so write the name into the header directly.
+        let mut builder = tar::Builder::new(Vec::new());
+        let data = b"evil";
+        let mut header = tar::Header::new_gnu();
+        let name = b"lib/../../escaped.so";
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, data.as_slice()).unwrap();
+        let tar = builder.into_inner().unwrap();
+        let archive = Bytes::from(zstd::encode_all(tar.as_slice(), 0).unwrap());
+
+        let err = unpack_archive(&archive, staging_dir.path(), &install_dirs).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("escapes the destination directory"),
+            "{err}"
+        );
+        assert!(!staging_dir.path().join("escaped.so").exists());
+        assert!(!install_root.path().join("lib").exists());
+        let leftovers = std::fs::read_dir(staging_dir.path()).unwrap().count();
+        assert_eq!(leftovers, 0, "staging directory was not cleaned up");
+
+        assert_eq!(
+            archive_path_depth(Path::new(""), Path::new("lib/./foo/../bar.so")),
+            Some(2)
+        );
+        assert_eq!(
+            archive_path_depth(Path::new("lib"), Path::new("../../etc/passwd")),
+            None
+        );
+        assert_eq!(
+            archive_path_depth(Path::new(""), Path::new("/etc/passwd")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unpack_archive_cleans_up_on_error() {
+        let staging_dir = camino_tempfile::tempdir().unwrap();
+
+        unpack_archive(
+            &Bytes::from_static(b"not a zstd archive"),
+            staging_dir.path(),
+            &[],
+        )
+        .unwrap_err();
+
+        let leftovers = std::fs::read_dir(staging_dir.path()).unwrap().count();
+        assert_eq!(leftovers, 0, "staging directory was not cleaned up");
+    }
+
+    #[test]
+    fn test_unpack_archive_truncated() {
+        let staging_dir = camino_tempfile::tempdir().unwrap();
+        let install_root = camino_tempfile::tempdir().unwrap();
+        let install_dirs = [("lib", install_root.path().join("lib").into_std_path_buf())];
+
+        // the first files unpack fine before the archive ends mid-file
+        let big = "x".repeat(64 * 1024);
+        let archive = build_archive(&[
+            ("lib/foo.so", "lib"),
+            ("lib/bar.so", "lib"),
+            ("lib/big.so", &big),
+        ]);
+        let tar = zstd::decode_all(archive.as_ref()).unwrap();
+        let truncated = zstd::encode_all(&tar[..tar.len() / 2], 0).unwrap();
+
+        unpack_archive(&Bytes::from(truncated), staging_dir.path(), &install_dirs).unwrap_err();
+
+        assert!(!install_root.path().join("lib").exists());
+        let leftovers = std::fs::read_dir(staging_dir.path()).unwrap().count();
+        assert_eq!(leftovers, 0, "staging directory was not cleaned up");
+    }
+
+    #[test]
+    fn test_unpack_archive_install_failure() {
+        let staging_dir = camino_tempfile::tempdir().unwrap();
+        let install_root = camino_tempfile::tempdir().unwrap();
+        let install_dirs =
+            ["lib", "share"].map(|dir| (dir, install_root.path().join(dir).into_std_path_buf()));
+
+        // a directory in the way of one of the files makes its move fail
+        std::fs::create_dir_all(install_root.path().join("share/extension/foo.control")).unwrap();
+        let archive = build_archive(&[
+            ("lib/foo.so", "lib"),
+            ("lib/foo/plugin.so", "plugin"),
+            ("share/extension/foo.control", "control"),
+            ("share/extension/foo--1.0.sql", "sql"),
+        ]);
+        unpack_archive(&archive, staging_dir.path(), &install_dirs).unwrap_err();
+
+        for path in [
+            "lib/foo.so",
+            "lib/foo/plugin.so",
+            "share/extension/foo--1.0.sql",
+        ] {
+            assert!(!install_root.path().join(path).exists(), "{path}");
+        }
+        let leftovers = std::fs::read_dir(staging_dir.path()).unwrap().count();
+        assert_eq!(leftovers, 0, "staging directory was not cleaned up");
+    }
+
+    #[test]
+    fn test_pg_config_resolve() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bindir = camino_tempfile::tempdir().unwrap();
+        let pg_config = bindir.path().join("pg_config");
+        std::fs::write(
+            &pg_config,
+            "#!/bin/sh\nif [ \"$1\" = --version ]; then echo 'PostgreSQL 16.4'; else echo \"$1 output\"; fi\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&pg_config, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let resolved = PgConfig::resolve(pg_config.as_str()).unwrap();
+        assert_eq!(resolved.version, PgMajorVersion::PG16);
+        assert_eq!(resolved.sharedir, "--sharedir output");
+        assert_eq!(resolved.pkglibdir, "--pkglibdir output");
+        assert_eq!(
+            resolved.extension_dir(),
+            Path::new("--sharedir output/extension")
+        );
+
+        let missing = bindir.path().join("missing");
+        PgConfig::resolve(missing.as_str()).unwrap_err();
+    }
 
     #[test]
     fn test_parse_pg_version() {
@@ -3,6 +3,7 @@
 use axum::extract::State;
 use axum::response::{IntoResponse, Response};
 use http::StatusCode;
+use remote_storage::DownloadError;
 use serde::Deserialize;
 
 use crate::compute::{BUILD_TAG, ComputeNode};
@@ -55,6 +56,8 @@ pub(in crate::http) async fn download_extension(
     match ext {
         Ok((ext_name, ext_path)) => match compute.download_extension(ext_name, ext_path).await {
             Ok(_) => StatusCode::OK.into_response(),
+            // Timeouts are transient, so let the caller know it can retry
+            Err(e @ DownloadError::Timeout) => JsonResponse::error(StatusCode::GATEWAY_TIMEOUT, e),
             Err(e) => JsonResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e),
         },
         Err(e) => JsonResponse::error(StatusCode::NOT_FOUND, e),
@@ -3,10 +3,12 @@
 use axum::extract::State;
 use axum::response::Response;
 use compute_api::requests::ExtensionInstallRequest;
-use compute_api::responses::{ComputeStatus, ExtensionInstallResponse};
+use compute_api::responses::{AvailableExtensions, ComputeStatus, ExtensionInstallResponse};
 use http::StatusCode;
+use tokio::task;
 
 use crate::compute::ComputeNode;
+use crate::extension_server;
 use crate::http::JsonResponse;
 use crate::http::extract::Json;
 
@@ -41,3 +43,35 @@ pub(in crate::http) async fn install_extension(
         ),
     }
 }
+
+/// List the extensions available on the compute, from the image or the remote extensions.
+pub(in crate::http) async fn list_available_extensions(
+    State(compute): State<Arc<ComputeNode>>,
+) -> Response {
+    let remote_extensions = {
+        let state = compute.state.lock().unwrap();
+        state
+            .pspec
+            .as_ref()
+            .and_then(|pspec| pspec.spec.remote_extensions.clone())
+    };
+
+    let res = task::spawn_blocking(move || {
+        extension_server::list_available_extensions(
+            remote_extensions.as_ref(),
+            compute.pg_config()?,
+        )
+    })
+    .await;
+
+    match res {
+        Ok(Ok(extensions)) => {
+            JsonResponse::success(StatusCode::OK, AvailableExtensions { extensions })
+        }
+        Ok(Err(e)) => JsonResponse::error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to list available extensions: {e:#}"),
+        ),
+        Err(e) => JsonResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
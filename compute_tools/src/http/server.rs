@@ -91,6 +91,10 @@ fn from(server: &Server) -> Self {
                     .route("/configure", post(configure::configure))
                     .route("/database_schema", get(database_schema::get_schema_dump))
                     .route("/dbs_and_roles", get(dbs_and_roles::get_catalog_objects))
+                    .route(
+                        "/extensions/available",
+                        get(extensions::list_available_extensions),
+                    )
                     .route("/insights", get(insights::get_insights))
                     .route("/metrics.json", get(metrics_json::get_metrics))
                     .route("/status", get(status::get_status))
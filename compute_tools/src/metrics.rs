@@ -1,8 +1,9 @@
 use metrics::core::{AtomicF64, AtomicU64, Collector, GenericCounter, GenericGauge};
 use metrics::proto::MetricFamily;
 use metrics::{
-    IntCounter, IntCounterVec, IntGaugeVec, UIntGaugeVec, register_gauge, register_int_counter,
-    register_int_counter_vec, register_int_gauge_vec, register_uint_gauge_vec,
+    Histogram, IntCounter, IntCounterVec, IntGaugeVec, UIntGaugeVec, register_gauge,
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    register_uint_gauge_vec,
 };
 use once_cell::sync::Lazy;
 
@@ -57,7 +58,29 @@ pub fn as_str(&self) -> &str {
     register_int_counter_vec!(
         "compute_ctl_remote_ext_requests_total",
         "Total number of requests made by compute_ctl to download extensions from S3 proxy by status",
-        &["http_status", "filename"]
+        &["http_status", "filename", "reason"]
+    )
+    .expect("failed to define a metric")
+});
+
+/// Number of times an extension control file from the spec conflicted with an existing
+/// local one, by which of the two versions was kept.
+pub(crate) static REMOTE_EXT_CONTROL_FILE_CONFLICTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "compute_ctl_remote_ext_control_file_conflicts_total",
+        "Total number of extension control files that exist both locally and remotely",
+        &["extension_name", "decision"]
+    )
+    .expect("failed to define a metric")
+});
+
+/// Time spent at compute start creating the remote extension control files and downloading
+/// the extensions of `shared_preload_libraries`, all extensions together.
+pub(crate) static REMOTE_EXT_COLD_START_DOWNLOAD_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "compute_ctl_remote_ext_cold_start_download_seconds",
+        "Time spent downloading all extensions needed to start compute",
+        vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0],
     )
     .expect("failed to define a metric")
 });
@@ -120,6 +143,8 @@ pub fn collect() -> Vec<MetricFamily> {
     metrics.extend(INSTALLED_EXTENSIONS.collect());
     metrics.extend(CPLANE_REQUESTS_TOTAL.collect());
     metrics.extend(REMOTE_EXT_REQUESTS_TOTAL.collect());
+    metrics.extend(REMOTE_EXT_CONTROL_FILE_CONFLICTS.collect());
+    metrics.extend(REMOTE_EXT_COLD_START_DOWNLOAD_SECONDS.collect());
     metrics.extend(DB_MIGRATION_FAILED.collect());
     metrics.extend(AUDIT_LOG_DIR_SIZE.collect());
     metrics.extend(PG_CURR_DOWNTIME_MS.collect());
@@ -256,6 +256,32 @@ pub struct InstalledExtensions {
     pub extensions: Vec<InstalledExtension>,
 }
 
+/// Where the control file of an available extension comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtensionSource {
+    /// Shipped with the compute image.
+    Image,
+    /// Written from the remote extensions of the spec. The rest of the extension's files are
+    /// downloaded when it is first used.
+    Remote,
+}
+
+/// An extension that `CREATE EXTENSION` can use on this compute.
+#[derive(Clone, Debug, Serialize)]
+pub struct AvailableExtension {
+    pub name: String,
+    pub default_version: Option<String>,
+    pub trusted: bool,
+    pub requires: Vec<String>,
+    pub source: ExtensionSource,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AvailableExtensions {
+    pub extensions: Vec<AvailableExtension>,
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct ExtensionInstallResult {
     pub extension: PgIdent,
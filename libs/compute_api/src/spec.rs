@@ -216,15 +216,139 @@ pub struct RemoteExtSpec {
     pub custom_extensions: Option<Vec<String>>,
     pub library_index: HashMap<String, String>,
     pub extension_data: HashMap<String, ExtensionData>,
+
+    /// Extensions whose control files from the spec should replace any local
+    /// control files of the same name, e.g. to pin a newer version than the one
+    /// bundled with the compute image. By default, local control files win.
+    #[serde(default)]
+    pub overwrite_control_files: Option<Vec<String>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ExtensionData {
+    /// Control file contents by file name. `{pg_version}` (the major version, e.g. `16`) and
+    /// `{libdir}` (`pg_config --pkglibdir`) are substituted when the files are written.
     pub control_data: HashMap<String, String>,
     pub archive_path: String,
+    /// Postgres major versions the extension is built for, e.g. `[15, 16]`. All versions if
+    /// unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supported_pg_versions: Option<Vec<u32>>,
+}
+
+impl ExtensionData {
+    /// Whether the extension can be installed on postgres of the given major version.
+    pub fn supports_pg_version(&self, pg_major_version: u32) -> bool {
+        self.supported_pg_versions
+            .as_ref()
+            .is_none_or(|versions| versions.contains(&pg_major_version))
+    }
+
+    /// Extensions named by the `requires` parameter of the control files.
+    pub fn requires(&self) -> Vec<String> {
+        let mut requires: Vec<String> = self
+            .control_data
+            .values()
+            .flat_map(|control| control_file_requires(control))
+            .collect();
+        requires.sort();
+        requires.dedup();
+        requires
+    }
+}
+
+/// Extensions named by the `requires` parameter of a control file, in the order they are listed.
+pub fn control_file_requires(control: &str) -> Vec<String> {
+    let mut requires = Vec::new();
+    for line in control.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key.trim() != "requires" {
+            continue;
+        }
+        let value = value.trim().trim_matches('\'');
+        requires.extend(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+        );
+    }
+    requires
 }
 
 impl RemoteExtSpec {
+    /// Order in which to install `ext_names` and the remote extensions they require, directly
+    /// or not. Each stage only requires extensions of earlier stages, so the extensions of a
+    /// stage can be installed concurrently. Required extensions that are not in
+    /// `extension_data` are expected to be installed locally and are left out, as are the ones
+    /// [`Self::get_ext`] would refuse for `pg_major_version`.
+    pub fn install_order(
+        &self,
+        ext_names: &[String],
+        pg_major_version: &str,
+    ) -> anyhow::Result<Vec<Vec<String>>> {
+        let mut depths = HashMap::new();
+        let mut path = Vec::new();
+        for ext_name in ext_names {
+            self.install_depth(ext_name, pg_major_version, &mut depths, &mut path)?;
+        }
+
+        let mut stages: Vec<Vec<String>> = Vec::new();
+        for (ext_name, depth) in depths {
+            if stages.len() <= depth {
+                stages.resize_with(depth + 1, Vec::new);
+            }
+            stages[depth].push(ext_name);
+        }
+        for stage in &mut stages {
+            stage.sort();
+        }
+        Ok(stages)
+    }
+
+    /// Number of stages that must be installed before `ext_name`. `path` holds the extensions
+    /// whose depth is being computed, to detect cycles.
+    fn install_depth(
+        &self,
+        ext_name: &str,
+        pg_major_version: &str,
+        depths: &mut HashMap<String, usize>,
+        path: &mut Vec<String>,
+    ) -> anyhow::Result<usize> {
+        if let Some(depth) = depths.get(ext_name) {
+            return Ok(*depth);
+        }
+        if let Some(start) = path.iter().position(|e| e == ext_name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(ext_name.to_string());
+            return Err(anyhow!(
+                "extension dependency cycle: {}",
+                cycle.join(" -> ")
+            ));
+        }
+
+        let mut depth = 0;
+        if let Some(ext_data) = self.extension_data.get(ext_name) {
+            path.push(ext_name.to_string());
+            for required in ext_data.requires() {
+                if self.check_ext(&required, pg_major_version).is_ok() {
+                    depth = depth
+                        .max(self.install_depth(&required, pg_major_version, depths, path)? + 1);
+                }
+            }
+            path.pop();
+        }
+        depths.insert(ext_name.to_string(), depth);
+        Ok(depth)
+    }
+
     pub fn get_ext(
         &self,
         ext_name: &str,
@@ -246,6 +370,15 @@ pub fn get_ext(
                 .ok_or(anyhow::anyhow!("library {} is not found", lib_raw_name))?;
         }
 
+        self.check_ext(real_ext_name, pg_major_version)?;
+        Ok((
+            real_ext_name.to_string(),
+            Self::build_remote_path(build_tag, pg_major_version, real_ext_name)?,
+        ))
+    }
+
+    /// Checks that the extension is allowed for this compute and built for its postgres version.
+    fn check_ext(&self, real_ext_name: &str, pg_major_version: &str) -> anyhow::Result<()> {
         // Check if extension is present in public or custom.
         // If not, then it is not allowed to be used by this compute.
         if !self
@@ -260,16 +393,28 @@ pub fn get_ext(
             return Err(anyhow::anyhow!("extension {} is not found", real_ext_name));
         }
 
-        match self.extension_data.get(real_ext_name) {
-            Some(_ext_data) => Ok((
-                real_ext_name.to_string(),
-                Self::build_remote_path(build_tag, pg_major_version, real_ext_name)?,
-            )),
-            None => Err(anyhow::anyhow!(
+        let Some(ext_data) = self.extension_data.get(real_ext_name) else {
+            return Err(anyhow::anyhow!(
                 "real_ext_name {} is not found",
                 real_ext_name
-            )),
+            ));
+        };
+        // `pg_major_version` is formatted like `v17`.
+        let version = pg_major_version
+            .strip_prefix('v')
+            .and_then(|v| v.parse::<u32>().ok());
+        if let Some(version) = version.filter(|&v| !ext_data.supports_pg_version(v)) {
+            return Err(anyhow::anyhow!(
+                "extension {} is not available for postgres {}, only for {:?}",
+                real_ext_name,
+                version,
+                ext_data
+                    .supported_pg_versions
+                    .as_deref()
+                    .unwrap_or_default()
+            ));
         }
+        Ok(())
     }
 
     /// Get the architecture-specific portion of the remote extension path. We
@@ -580,6 +725,126 @@ fn allow_installing_remote_extensions() {
             .expect("Library should be found");
     }
 
+    fn ext_with_requires(requires: &[(&str, &str)]) -> RemoteExtSpec {
+        let public_extensions = requires.iter().map(|(name, _)| name.to_string()).collect();
+        let extension_data = requires
+            .iter()
+            .map(|(name, requires)| {
+                let control = format!(
+                    "# {name} extension\ndefault_version = '1.0'\nrequires = '{requires}'\n"
+                );
+                (
+                    name.to_string(),
+                    ExtensionData {
+                        control_data: HashMap::from([(format!("{name}.control"), control)]),
+                        archive_path: String::new(),
+                        supported_pg_versions: None,
+                    },
+                )
+            })
+            .collect();
+        RemoteExtSpec {
+            public_extensions: Some(public_extensions),
+            extension_data,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn remote_extension_install_order() {
+        let rspec = ext_with_requires(&[
+            ("anon", "pgcrypto, postgis"),
+            ("postgis", ""),
+            ("postgis_topology", "postgis"),
+            ("pgcrypto", ""),
+        ]);
+        assert_eq!(
+            rspec.extension_data["anon"].requires(),
+            vec!["pgcrypto".to_string(), "postgis".to_string()]
+        );
+
+        let order = rspec
+            .install_order(&["anon".to_string(), "postgis_topology".to_string()], "v17")
+            .unwrap();
+        assert_eq!(
+            order,
+            vec![
+                vec!["pgcrypto".to_string(), "postgis".to_string()],
+                vec!["anon".to_string(), "postgis_topology".to_string()],
+            ]
+        );
+
+        // requirements that are not remote are installed locally
+        let rspec = ext_with_requires(&[("anon", "pgcrypto")]);
+        let order = rspec.install_order(&["anon".to_string()], "v17").unwrap();
+        assert_eq!(order, vec![vec!["anon".to_string()]]);
+
+        // and so are requirements that this compute may not install from remote
+        let mut rspec = ext_with_requires(&[
+            ("anon", "pgcrypto, postgis"),
+            ("pgcrypto", ""),
+            ("postgis", ""),
+        ]);
+        rspec.public_extensions = Some(vec!["anon".to_string(), "postgis".to_string()]);
+        rspec
+            .extension_data
+            .get_mut("postgis")
+            .unwrap()
+            .supported_pg_versions = Some(vec![16]);
+        let order = rspec.install_order(&["anon".to_string()], "v17").unwrap();
+        assert_eq!(order, vec![vec!["anon".to_string()]]);
+        let order = rspec.install_order(&["anon".to_string()], "v16").unwrap();
+        assert_eq!(
+            order,
+            vec![vec!["postgis".to_string()], vec!["anon".to_string()]]
+        );
+
+        let rspec = ext_with_requires(&[("a", "b"), ("b", "c"), ("c", "a")]);
+        let err = rspec.install_order(&["a".to_string()], "v17").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "extension dependency cycle: a -> b -> c -> a"
+        );
+    }
+
+    #[test]
+    fn remote_extension_pg_versions() {
+        let rspec: RemoteExtSpec = serde_json::from_value(serde_json::json!({
+            "public_extensions": ["ext", "anyver"],
+            "library_index": {},
+            "extension_data": {
+                "ext": {
+                    "control_data": {
+                        "ext.control": ""
+                    },
+                    "archive_path": "",
+                    "supported_pg_versions": [16, 17]
+                },
+                "anyver": {
+                    "control_data": {
+                        "anyver.control": ""
+                    },
+                    "archive_path": ""
+                }
+            },
+        }))
+        .unwrap();
+
+        rspec
+            .get_ext("ext", false, "latest", "v16")
+            .expect("Extension supports v16");
+        let err = rspec
+            .get_ext("ext", false, "latest", "v14")
+            .expect_err("Extension does not support v14");
+        assert_eq!(
+            err.to_string(),
+            "extension ext is not available for postgres 14, only for [16, 17]"
+        );
+        rspec
+            .get_ext("anyver", false, "latest", "v14")
+            .expect("Extension supports all versions");
+    }
+
     #[test]
     fn remote_extension_path() {
         let rspec: RemoteExtSpec = serde_json::from_value(serde_json::json!({
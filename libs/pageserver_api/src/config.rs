@@ -216,7 +216,9 @@ pub struct ConfigToml {
     #[serde_as(as = "serde_with::DisplayFromStr")]
     pub log_format: LogFormat,
     pub concurrent_tenant_warmup: NonZeroUsize,
+    pub startup_priority_tenants: Vec<utils::id::TenantId>,
     pub concurrent_tenant_size_logical_size_queries: NonZeroUsize,
+    pub concurrent_tenant_shutdown: NonZeroUsize,
     #[serde(with = "humantime_serde")]
     pub metric_collection_interval: Duration,
     pub metric_collection_endpoint: Option<reqwest::Url>,
@@ -651,6 +653,8 @@ pub mod defaults {
 
     pub const DEFAULT_CONCURRENT_TENANT_SIZE_LOGICAL_SIZE_QUERIES: usize = 1;
 
+    pub const DEFAULT_CONCURRENT_TENANT_SHUTDOWN: usize = 64;
+
     pub const DEFAULT_METRIC_COLLECTION_INTERVAL: &str = "10 min";
     pub const DEFAULT_METRIC_COLLECTION_ENDPOINT: Option<reqwest::Url> = None;
     pub const DEFAULT_SYNTHETIC_SIZE_CALCULATION_INTERVAL: &str = "10 min";
@@ -722,10 +726,13 @@ fn default() -> Self {
 
             concurrent_tenant_warmup: (NonZeroUsize::new(DEFAULT_CONCURRENT_TENANT_WARMUP)
                 .expect("Invalid default constant")),
+            startup_priority_tenants: Vec::new(),
             concurrent_tenant_size_logical_size_queries: NonZeroUsize::new(
                 DEFAULT_CONCURRENT_TENANT_SIZE_LOGICAL_SIZE_QUERIES,
             )
             .unwrap(),
+            concurrent_tenant_shutdown: NonZeroUsize::new(DEFAULT_CONCURRENT_TENANT_SHUTDOWN)
+                .expect("Invalid default constant"),
             metric_collection_interval: (humantime::parse_duration(
                 DEFAULT_METRIC_COLLECTION_INTERVAL,
             )
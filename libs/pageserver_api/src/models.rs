@@ -1769,6 +1769,16 @@ pub struct SecondaryProgress {
     pub bytes_total: u64,
 }
 
+/// Progress of a tenant shard that is still in [`TenantState::Attaching`]: how many of
+/// its timelines have been loaded from remote storage so far.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AttachProgress {
+    /// The number of timelines loaded so far
+    pub timelines_loaded: usize,
+    /// The number of timelines found in remote storage, or zero if the listing has not finished yet
+    pub timelines_total: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TenantScanRemoteStorageShard {
     pub tenant_shard_id: TenantShardId,
@@ -2,7 +2,7 @@
 
 use crate::client::SocketConfig;
 use crate::config::{Host, SslMode};
-use crate::tls::MakeTlsConnect;
+use crate::tls::{MakeTlsConnect, NoTls};
 use crate::{Error, cancel_query_raw, connect_socket};
 
 pub(crate) async fn cancel_query<T>(
@@ -17,6 +17,17 @@ pub(crate) async fn cancel_query<T>(
 {
     let hostname = match &config.host {
         Host::Tcp(host) => &**host,
+        Host::Unix(path) => {
+            let socket = connect_socket::connect_unix_socket(path, config.connect_timeout).await?;
+            return cancel_query_raw::cancel_query_raw(
+                socket,
+                SslMode::Disable,
+                NoTls,
+                process_id,
+                secret_key,
+            )
+            .await;
+        }
     };
     let tls = tls
         .make_tls_connect(hostname)
@@ -19,8 +19,8 @@
 use crate::simple_query::SimpleQueryStream;
 use crate::types::{Oid, Type};
 use crate::{
-    CancelToken, Error, ReadyForQueryStatus, SimpleQueryMessage, Transaction, TransactionBuilder,
-    query, simple_query,
+    CancelToken, Error, ReadyForQueryStatus, SimpleQueryMessage, Statement, Transaction,
+    TransactionBuilder, query, simple_query,
 };
 
 pub struct Responses {
@@ -81,6 +81,90 @@ pub(crate) struct CachedTypeInfo {
     pub(crate) types: HashMap<Oid, Type>,
 }
 
+/// A cache of named prepared statements used by `query_raw_txt`, keyed by query text.
+///
+/// Disabled while `capacity` is zero.
+#[derive(Default)]
+pub(crate) struct StatementCache {
+    capacity: usize,
+    statements: HashMap<String, CachedStatement>,
+    /// Statements dropped from the cache that are still allocated on the server.
+    /// They are closed ahead of the next parse, whether or not that parse succeeds.
+    closing: Vec<Statement>,
+    /// Names new statements and tracks recency for eviction.
+    counter: u64,
+}
+
+struct CachedStatement {
+    statement: Statement,
+    last_used: u64,
+}
+
+impl StatementCache {
+    pub(crate) fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub(crate) fn get(&mut self, query: &str) -> Option<Statement> {
+        self.counter += 1;
+        let cached = self.statements.get_mut(query)?;
+        cached.last_used = self.counter;
+        Some(cached.statement.clone())
+    }
+
+    pub(crate) fn next_name(&mut self) -> String {
+        self.counter += 1;
+        format!("__neon_s{}", self.counter)
+    }
+
+    /// Evicts the least recently used statement if the cache is full, queueing it to be closed.
+    pub(crate) fn make_room(&mut self) {
+        if self.statements.len() < self.capacity {
+            return;
+        }
+        let oldest = self
+            .statements
+            .iter()
+            .min_by_key(|(_, cached)| cached.last_used)
+            .map(|(query, _)| query.clone());
+        if let Some(oldest) = oldest {
+            self.retire(&oldest);
+        }
+    }
+
+    pub(crate) fn insert(&mut self, query: String, statement: Statement) {
+        self.counter += 1;
+        self.statements.insert(
+            query,
+            CachedStatement {
+                statement,
+                last_used: self.counter,
+            },
+        );
+    }
+
+    /// Forgets a statement that no longer exists on the server.
+    pub(crate) fn remove(&mut self, query: &str) {
+        self.statements.remove(query);
+    }
+
+    /// Forgets a statement that is still allocated on the server, queueing it to be closed.
+    pub(crate) fn retire(&mut self, query: &str) {
+        if let Some(cached) = self.statements.remove(query) {
+            self.closing.push(cached.statement);
+        }
+    }
+
+    pub(crate) fn take_closing(&mut self) -> Vec<Statement> {
+        std::mem::take(&mut self.closing)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.statements.clear();
+        self.closing.clear();
+    }
+}
+
 pub struct InnerClient {
     sender: mpsc::UnboundedSender<FrontendMessage>,
     responses: Responses,
@@ -90,6 +174,10 @@ pub struct InnerClient {
 }
 
 impl InnerClient {
+    pub(crate) fn responses(&mut self) -> &mut Responses {
+        &mut self.responses
+    }
+
     pub fn start(&mut self) -> Result<PartialQuery, Error> {
         self.responses.waiting += 1;
         Ok(PartialQuery(Some(self)))
@@ -176,6 +264,7 @@ pub struct SocketConfig {
 pub struct Client {
     inner: InnerClient,
     cached_typeinfo: CachedTypeInfo,
+    statement_cache: StatementCache,
 
     socket_config: SocketConfig,
     ssl_mode: SslMode,
@@ -204,6 +293,7 @@ pub(crate) fn new(
                 buffer: Default::default(),
             },
             cached_typeinfo: Default::default(),
+            statement_cache: Default::default(),
 
             socket_config,
             ssl_mode,
@@ -236,12 +326,29 @@ pub async fn query_raw_txt<S, I>(
         query::query_txt(
             &mut self.inner,
             &mut self.cached_typeinfo,
+            &mut self.statement_cache,
             statement,
             params,
         )
         .await
     }
 
+    /// Keep up to `size` named prepared statements for `query_raw_txt`, keyed by query text,
+    /// so that repeated queries skip parsing. Zero, the default, disables the cache.
+    pub fn set_statement_cache_size(&mut self, size: usize) {
+        self.statement_cache.capacity = size;
+        if size == 0 {
+            self.statement_cache.clear();
+        }
+    }
+
+    /// Forgets all cached prepared statements.
+    ///
+    /// Must be called after they have been deallocated on the server, e.g. by `DISCARD ALL`.
+    pub fn clear_statement_cache(&mut self) {
+        self.statement_cache.clear();
+    }
+
     /// Executes a sequence of SQL statements using the simple query protocol, returning the resulting rows.
     ///
     /// Statements should be separated by semicolons. If an error occurs, execution of the sequence will stop at that
@@ -281,7 +388,9 @@ pub async fn batch_execute(&mut self, query: &str) -> Result<ReadyForQueryStatus
     }
 
     pub async fn discard_all(&mut self) -> Result<ReadyForQueryStatus, Error> {
-        self.batch_execute("discard all").await
+        let status = self.batch_execute("discard all").await?;
+        self.clear_statement_cache();
+        Ok(status)
     }
 
     /// Begins a new database transaction.
@@ -354,3 +463,116 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Client").finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+    use futures_util::FutureExt;
+    use postgres_protocol2::message::frontend;
+    use tokio::sync::mpsc;
+    use tokio_util::codec::Decoder;
+
+    use super::{Client, SocketConfig, StatementCache};
+    use crate::Statement;
+    use crate::codec::{BackendMessage, FrontendMessage, PostgresCodec};
+    use crate::config::{Host, SslMode};
+    use crate::error::SqlState;
+
+    fn backend_message(buf: &mut BytesMut, tag: u8, body: &[u8]) {
+        buf.put_u8(tag);
+        buf.put_i32(body.len() as i32 + 4);
+        buf.put_slice(body);
+    }
+
+    fn error_response(buf: &mut BytesMut, code: &str) {
+        let body = format!("SERROR\0C{code}\0Mtest error\0\0");
+        backend_message(buf, b'E', body.as_bytes());
+    }
+
+    #[test]
+    fn statement_cache_evicts_least_recently_used() {
+        let mut cache = StatementCache {
+            capacity: 2,
+            ..Default::default()
+        };
+        assert!(cache.get("select 1").is_none());
+
+        for query in ["select 1", "select 2"] {
+            cache.make_room();
+            let name = cache.next_name();
+            cache.insert(query.to_owned(), Statement::new(name, vec![]));
+        }
+        assert!(cache.take_closing().is_empty());
+        let name_1 = cache.get("select 1").unwrap().name().to_owned();
+        let name_2 = cache.get("select 2").unwrap().name().to_owned();
+        assert_ne!(name_1, name_2);
+
+        // "select 1" was used least recently
+        cache.get("select 2");
+        cache.make_room();
+        let name = cache.next_name();
+        cache.insert("select 3".to_owned(), Statement::new(name, vec![]));
+        let closing = cache.take_closing();
+        assert_eq!(closing.len(), 1);
+        assert_eq!(closing[0].name(), name_1);
+        assert!(cache.get("select 1").is_none());
+        assert!(cache.get("select 2").is_some());
+        assert!(cache.get("select 3").is_some());
+    }
+
+    #[test]
+    fn stale_statement_is_closed_when_reparse_fails() {
+        let (sender, mut frontend_rx) = mpsc::unbounded_channel();
+        let (backend_tx, receiver) = mpsc::channel(2);
+        let mut client = Client::new(
+            sender,
+            receiver,
+            SocketConfig {
+                host_addr: None,
+                host: Host::Tcp("localhost".to_owned()),
+                port: 5432,
+                connect_timeout: None,
+            },
+            SslMode::Disable,
+            0,
+            0,
+        );
+        client.set_statement_cache_size(1);
+        client
+            .statement_cache
+            .insert("select 1".to_owned(), Statement::new("__neon_s1", vec![]));
+
+        // the cached plan changed its result type, then the re-parse fails.
+        let mut buf = BytesMut::new();
+        error_response(&mut buf, "0A000");
+        backend_message(&mut buf, b'Z', b"I");
+        backend_message(&mut buf, b'3', b"");
+        error_response(&mut buf, "42601");
+        backend_message(&mut buf, b'Z', b"I");
+        while let Some(message) = PostgresCodec.decode(&mut buf).unwrap() {
+            let BackendMessage::Normal { messages } = message else {
+                panic!("unexpected async message");
+            };
+            backend_tx.try_send(messages).unwrap();
+        }
+
+        let err = client
+            .query_raw_txt("select 1", Vec::<Option<&str>>::new())
+            .now_or_never()
+            .expect("all responses are buffered")
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), Some(&SqlState::SYNTAX_ERROR));
+
+        let mut sent = BytesMut::new();
+        while let Ok(FrontendMessage::Raw(bytes)) = frontend_rx.try_recv() {
+            sent.extend_from_slice(&bytes);
+        }
+        let mut close = BytesMut::new();
+        frontend::close(b'S', "__neon_s1", &mut close).unwrap();
+        assert!(sent.windows(close.len()).any(|window| window == close));
+
+        assert!(client.statement_cache.get("select 1").is_none());
+        assert!(client.statement_cache.take_closing().is_empty());
+    }
+}
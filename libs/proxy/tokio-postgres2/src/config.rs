@@ -1,6 +1,7 @@
 //! Connection configuration.
 
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::time::Duration;
 use std::{fmt, str};
 
@@ -8,13 +9,13 @@
 use postgres_protocol2::message::frontend::StartupMessageParams;
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::net::TcpStream;
+use tokio::net::{TcpStream, UnixStream};
 
-use crate::connect::connect;
+use crate::connect::{connect, connect_unix};
 use crate::connect_raw::{RawConnection, connect_raw};
 use crate::connect_tls::connect_tls;
 use crate::maybe_tls_stream::MaybeTlsStream;
-use crate::tls::{MakeTlsConnect, TlsConnect, TlsStream};
+use crate::tls::{MakeTlsConnect, NoTlsStream, TlsConnect, TlsStream};
 use crate::{Client, Connection, Error};
 
 /// TLS configuration.
@@ -55,6 +56,8 @@ pub enum ReplicationMode {
 pub enum Host {
     /// A TCP hostname.
     Tcp(String),
+    /// A path to a Unix domain socket.
+    Unix(PathBuf),
 }
 
 /// Precomputed keys which may override password during auth.
@@ -70,11 +73,13 @@ pub struct Config {
     pub(crate) host_addr: Option<IpAddr>,
     pub(crate) host: Host,
     pub(crate) port: u16,
+    pub(crate) tls_server_name: Option<String>,
 
     pub(crate) password: Option<Vec<u8>>,
     pub(crate) auth_keys: Option<Box<AuthKeys>>,
     pub(crate) ssl_mode: SslMode,
     pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) startup_timeout: Option<Duration>,
     pub(crate) channel_binding: ChannelBinding,
     pub(crate) server_params: StartupMessageParams,
 
@@ -85,14 +90,27 @@ pub struct Config {
 impl Config {
     /// Creates a new configuration.
     pub fn new(host: String, port: u16) -> Config {
+        Config::with_host(Host::Tcp(host), port)
+    }
+
+    /// Creates a new configuration for a server listening on the Unix domain socket at `path`.
+    ///
+    /// Such configurations must be connected with [`Config::connect_unix`].
+    pub fn new_unix(path: PathBuf) -> Config {
+        Config::with_host(Host::Unix(path), 0)
+    }
+
+    fn with_host(host: Host, port: u16) -> Config {
         Config {
             host_addr: None,
-            host: Host::Tcp(host),
+            host,
             port,
+            tls_server_name: None,
             password: None,
             auth_keys: None,
             ssl_mode: SslMode::Prefer,
             connect_timeout: None,
+            startup_timeout: None,
             channel_binding: ChannelBinding::Prefer,
             server_params: StartupMessageParams::default(),
 
@@ -199,6 +217,20 @@ pub fn get_port(&self) -> u16 {
         self.port
     }
 
+    /// Sets the name the server's TLS certificate is verified against.
+    ///
+    /// Defaults to the TCP host, which does not work if the host is an IP address that the
+    /// certificate does not list.
+    pub fn tls_server_name(&mut self, tls_server_name: String) -> &mut Config {
+        self.tls_server_name = Some(tls_server_name);
+        self
+    }
+
+    /// Gets the TLS server name, if one has been set with the `tls_server_name` method.
+    pub fn get_tls_server_name(&self) -> Option<&str> {
+        self.tls_server_name.as_deref()
+    }
+
     /// Sets the timeout applied to socket-level connection attempts.
     ///
     /// Note that hostnames can resolve to multiple IP addresses, and this timeout will apply to each address of each
@@ -214,6 +246,21 @@ pub fn get_connect_timeout(&self) -> Option<&Duration> {
         self.connect_timeout.as_ref()
     }
 
+    /// Sets the timeout for the TLS handshake and the startup and authentication exchange that
+    /// follow a successful socket connection, so that a server that accepts connections but
+    /// does not respond fails with [`Error::is_startup_timeout`] instead of hanging.
+    ///
+    /// Defaults to no limit.
+    pub fn startup_timeout(&mut self, startup_timeout: Duration) -> &mut Config {
+        self.startup_timeout = Some(startup_timeout);
+        self
+    }
+
+    /// Gets the startup timeout, if one has been set with the `startup_timeout` method.
+    pub fn get_startup_timeout(&self) -> Option<&Duration> {
+        self.startup_timeout.as_ref()
+    }
+
     /// Sets the channel binding behavior.
     ///
     /// Defaults to `prefer`.
@@ -240,6 +287,15 @@ pub async fn connect<T>(
         connect(tls, self).await
     }
 
+    /// Opens a connection to a PostgreSQL database over a Unix domain socket.
+    ///
+    /// TLS is never used on these connections, regardless of the configured `ssl_mode`.
+    pub async fn connect_unix(
+        &self,
+    ) -> Result<(Client, Connection<UnixStream, NoTlsStream>), Error> {
+        connect_unix(self).await
+    }
+
     pub async fn tls_and_authenticate<S, T>(
         &self,
         stream: S,
@@ -280,7 +336,9 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             .field("ssl_mode", &self.ssl_mode)
             .field("host", &self.host)
             .field("port", &self.port)
+            .field("tls_server_name", &self.tls_server_name)
             .field("connect_timeout", &self.connect_timeout)
+            .field("startup_timeout", &self.startup_timeout)
             .field("channel_binding", &self.channel_binding)
             .field("server_params", &self.server_params)
             .finish()
@@ -1,16 +1,17 @@
-use std::net::IpAddr;
-
 use postgres_protocol2::message::backend::Message;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream};
 use tokio::sync::mpsc;
+use tokio::time;
+use tracing::{Instrument, info_span};
 
 use crate::client::SocketConfig;
 use crate::codec::BackendMessage;
-use crate::config::Host;
+use crate::config::{Host, SslMode};
 use crate::connect_raw::connect_raw;
-use crate::connect_socket::connect_socket;
+use crate::connect_socket::{connect_socket, connect_unix_socket};
 use crate::connect_tls::connect_tls;
-use crate::tls::{MakeTlsConnect, TlsConnect};
+use crate::tls::{MakeTlsConnect, NoTls, NoTlsStream, TlsConnect};
 use crate::{Client, Config, Connection, Error, RawConnection};
 
 pub async fn connect<T>(
@@ -21,43 +22,91 @@ pub async fn connect<T>(
     T: MakeTlsConnect<TcpStream>,
 {
     let hostname = match &config.host {
-        Host::Tcp(host) => host.as_str(),
+        Host::Tcp(host) => config.tls_server_name.as_deref().unwrap_or(host.as_str()),
+        Host::Unix(_) => {
+            return Err(Error::config(
+                "unix socket hosts must be connected with connect_unix".into(),
+            ));
+        }
     };
 
     let tls = tls
         .make_tls_connect(hostname)
         .map_err(|e| Error::tls(e.into()))?;
 
-    match connect_once(config.host_addr, &config.host, config.port, tls, config).await {
-        Ok((client, connection)) => Ok((client, connection)),
-        Err(e) => Err(e),
+    // Each phase gets its own span, so that slow connects can be broken down.
+    let socket = connect_socket(
+        config.host_addr,
+        &config.host,
+        config.port,
+        config.connect_timeout,
+    )
+    .await?;
+
+    connect_once(socket, config.ssl_mode, tls, config).await
+}
+
+pub async fn connect_unix(
+    config: &Config,
+) -> Result<(Client, Connection<UnixStream, NoTlsStream>), Error> {
+    let Host::Unix(path) = &config.host else {
+        return Err(Error::config(
+            "connect_unix requires a unix socket host".into(),
+        ));
+    };
+
+    let socket = connect_unix_socket(path, config.connect_timeout).await?;
+
+    // the socket never leaves the host, so there is nothing for TLS to protect.
+    connect_once(socket, SslMode::Disable, NoTls, config).await
+}
+
+async fn connect_once<S, T>(
+    socket: S,
+    ssl_mode: SslMode,
+    tls: T,
+    config: &Config,
+) -> Result<(Client, Connection<S, T::Stream>), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: TlsConnect<S>,
+{
+    let startup = startup(socket, ssl_mode, tls, config);
+    match config.startup_timeout {
+        Some(timeout) => time::timeout(timeout, startup)
+            .await
+            .map_err(|_| Error::startup_timeout())?,
+        None => startup.await,
     }
 }
 
-async fn connect_once<T>(
-    host_addr: Option<IpAddr>,
-    host: &Host,
-    port: u16,
+async fn startup<S, T>(
+    socket: S,
+    ssl_mode: SslMode,
     tls: T,
     config: &Config,
-) -> Result<(Client, Connection<TcpStream, T::Stream>), Error>
+) -> Result<(Client, Connection<S, T::Stream>), Error>
 where
-    T: TlsConnect<TcpStream>,
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: TlsConnect<S>,
 {
-    let socket = connect_socket(host_addr, host, port, config.connect_timeout).await?;
-    let stream = connect_tls(socket, config.ssl_mode, tls).await?;
+    let stream = connect_tls(socket, ssl_mode, tls)
+        .instrument(info_span!("tls_handshake"))
+        .await?;
     let RawConnection {
         stream,
         parameters,
         delayed_notice,
         process_id,
         secret_key,
-    } = connect_raw(stream, config).await?;
+    } = connect_raw(stream, config)
+        .instrument(info_span!("startup"))
+        .await?;
 
     let socket_config = SocketConfig {
-        host_addr,
-        host: host.clone(),
-        port,
+        host_addr: config.host_addr,
+        host: config.host.clone(),
+        port: config.port,
         connect_timeout: config.connect_timeout,
     };
 
@@ -67,7 +116,7 @@ async fn connect_once<T>(
         client_tx,
         client_rx,
         socket_config,
-        config.ssl_mode,
+        ssl_mode,
         process_id,
         secret_key,
     );
@@ -1,10 +1,12 @@
 use std::future::Future;
 use std::io;
 use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::time::Duration;
 
-use tokio::net::{self, TcpStream};
+use tokio::net::{self, TcpStream, UnixStream};
 use tokio::time;
+use tracing::{Instrument, info_span};
 
 use crate::Error;
 use crate::config::Host;
@@ -20,6 +22,7 @@ pub(crate) async fn connect_socket(
             let addrs = match host_addr {
                 Some(addr) => vec![SocketAddr::new(addr, port)],
                 None => net::lookup_host((&**host, port))
+                    .instrument(info_span!("resolve_host"))
                     .await
                     .map_err(Error::connect)?
                     .collect(),
@@ -28,14 +31,16 @@ pub(crate) async fn connect_socket(
             let mut last_err = None;
 
             for addr in addrs {
-                let stream =
-                    match connect_with_timeout(TcpStream::connect(addr), connect_timeout).await {
-                        Ok(stream) => stream,
-                        Err(e) => {
-                            last_err = Some(e);
-                            continue;
-                        }
-                    };
+                let stream = match connect_with_timeout(TcpStream::connect(addr), connect_timeout)
+                    .instrument(info_span!("tcp_connect", %addr))
+                    .await
+                {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        last_err = Some(e);
+                        continue;
+                    }
+                };
 
                 stream.set_nodelay(true).map_err(Error::connect)?;
 
@@ -49,9 +54,22 @@ pub(crate) async fn connect_socket(
                 ))
             }))
         }
+        Host::Unix(_) => Err(Error::connect(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unix socket hosts must be connected with connect_unix_socket",
+        ))),
     }
 }
 
+pub(crate) async fn connect_unix_socket(
+    path: &Path,
+    connect_timeout: Option<Duration>,
+) -> Result<UnixStream, Error> {
+    connect_with_timeout(UnixStream::connect(path), connect_timeout)
+        .instrument(info_span!("unix_connect", path = %path.display()))
+        .await
+}
+
 async fn connect_with_timeout<F, T>(connect: F, timeout: Option<Duration>) -> Result<T, Error>
 where
     F: Future<Output = io::Result<T>>,
@@ -373,6 +373,7 @@ enum Kind {
     Config,
     Connect,
     Timeout,
+    StartupTimeout,
 }
 
 struct ErrorInner {
@@ -409,6 +410,9 @@ fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
             Kind::Config => fmt.write_str("invalid configuration")?,
             Kind::Connect => fmt.write_str("error connecting to server")?,
             Kind::Timeout => fmt.write_str("timeout waiting for server")?,
+            Kind::StartupTimeout => {
+                fmt.write_str("timeout waiting for server to complete connection startup")?
+            }
         };
         if let Some(ref cause) = self.0.cause {
             write!(fmt, ": {cause}")?;
@@ -441,6 +445,12 @@ pub fn is_closed(&self) -> bool {
         self.0.kind == Kind::Closed
     }
 
+    /// Determines if the server accepted the connection but did not complete the startup
+    /// exchange within the configured `startup_timeout`.
+    pub fn is_startup_timeout(&self) -> bool {
+        self.0.kind == Kind::StartupTimeout
+    }
+
     /// Returns the SQLSTATE error code associated with the error.
     ///
     /// This is a convenience method that downcasts the cause to a `DbError` and returns its code.
@@ -509,6 +519,10 @@ pub(crate) fn connect(e: io::Error) -> Error {
         Error::new(Kind::Connect, Some(Box::new(e)))
     }
 
+    pub(crate) fn startup_timeout() -> Error {
+        Error::new(Kind::StartupTimeout, None)
+    }
+
     #[doc(hidden)]
     pub fn __private_api_timeout() -> Error {
         Error::new(Kind::Timeout, None)
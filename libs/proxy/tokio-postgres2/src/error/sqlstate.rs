@@ -36,11 +36,21 @@ pub fn code(&self) -> &str {
     /// 08P01
     pub const PROTOCOL_VIOLATION: SqlState = SqlState(*b"08P01");
 
+    // Class 0A - Feature Not Supported
+
+    /// 0A000
+    pub const FEATURE_NOT_SUPPORTED: SqlState = SqlState(*b"0A000");
+
     // Class 22 - Data Exception
 
     /// 22023
     pub const INVALID_PARAMETER_VALUE: SqlState = SqlState(*b"22023");
 
+    // Class 26 - Invalid SQL Statement Name
+
+    /// 26000
+    pub const INVALID_SQL_STATEMENT_NAME: SqlState = SqlState(*b"26000");
+
     // Class 3D - Invalid Catalog Name
 
     /// 3D000
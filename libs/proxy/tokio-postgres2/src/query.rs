@@ -7,12 +7,14 @@
 use postgres_protocol2::message::frontend;
 use postgres_types2::Format;
 
-use crate::client::{CachedTypeInfo, InnerClient, Responses};
-use crate::{Error, ReadyForQueryStatus, Row, Statement};
+use crate::client::{CachedTypeInfo, InnerClient, PartialQuery, Responses, StatementCache};
+use crate::error::SqlState;
+use crate::{Column, Error, ReadyForQueryStatus, Row, Statement};
 
 pub async fn query_txt<'a, S, I>(
     client: &'a mut InnerClient,
     typecache: &mut CachedTypeInfo,
+    statements: &mut StatementCache,
     query: &str,
     params: I,
 ) -> Result<RowStream<'a>, Error>
@@ -21,9 +23,6 @@ pub async fn query_txt<'a, S, I>(
     I: IntoIterator<Item = Option<S>>,
     I::IntoIter: ExactSizeIterator,
 {
-    let params = params.into_iter();
-    let mut client = client.start()?;
-
     // Flow:
     // 1. Parse the query
     // 2. Inspect the row description for OIDs
@@ -35,20 +34,98 @@ pub async fn query_txt<'a, S, I>(
     // 1. Parse the typeinfo query
     // 2. Execute the query on each OID
     // 3. If the result does not match an OID we know, repeat 2.
+    //
+    // With the statement cache enabled, the query is parsed into a named statement
+    // and steps 1-3 are skipped the next time the same query text is executed.
+
+    if !statements.enabled() {
+        let mut partial = client.start()?;
+        let columns = parse_describe(&mut partial, typecache, &[], "", query).await?;
+        bind_execute(partial, "", params).await?;
+        return Ok(RowStream::new(
+            client.responses(),
+            Statement::new("", columns),
+            None,
+        ));
+    }
+
+    // we might need to bind twice if the cached statement turns out to be gone.
+    let params: Vec<Option<S>> = params.into_iter().collect();
+
+    if let Some(statement) = statements.get(query) {
+        let partial = client.start()?;
+        match bind_execute(partial, statement.name(), params.iter().map(Option::as_ref)).await {
+            Ok(()) => return Ok(RowStream::new(client.responses(), statement, Some(true))),
+            // the statement was deallocated behind our back, e.g. by `DEALLOCATE ALL`,
+            // or DDL changed its result type ("cached plan must not change result type").
+            Err(e)
+                if e.code() == Some(&SqlState::INVALID_SQL_STATEMENT_NAME)
+                    || e.code() == Some(&SqlState::FEATURE_NOT_SUPPORTED) =>
+            {
+                if e.code() == Some(&SqlState::FEATURE_NOT_SUPPORTED) {
+                    // still allocated on the server, so it is closed with the next parse.
+                    statements.retire(query);
+                } else {
+                    statements.remove(query);
+                }
+
+                // nothing ran, so we can prepare it again unless this aborted a transaction.
+                match client.responses().next().await? {
+                    Message::ReadyForQuery(status)
+                        if ReadyForQueryStatus::from(status) == ReadyForQueryStatus::Idle => {}
+                    _ => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    statements.make_room();
+    let close = statements.take_closing();
+    let name = statements.next_name();
+    let mut partial = client.start()?;
+    let columns = parse_describe(&mut partial, typecache, &close, &name, query).await?;
+    let statement = Statement::new(name, columns);
+    statements.insert(query.to_owned(), statement.clone());
+
+    bind_execute(partial, statement.name(), params.iter().map(Option::as_ref)).await?;
+    Ok(RowStream::new(client.responses(), statement, Some(false)))
+}
 
+/// Parses `query` into the prepared statement `name` and returns its columns.
+///
+/// The statements in `close` are closed first in the same round trip, so they are
+/// deallocated on the server even if the parse fails.
+async fn parse_describe(
+    client: &mut PartialQuery<'_>,
+    typecache: &mut CachedTypeInfo,
+    close: &[Statement],
+    name: &str,
+    query: &str,
+) -> Result<Vec<Column>, Error> {
     // parse the query and get type info
     let responses = client.send_with_flush(|buf| {
+        for statement in close {
+            frontend::close(b'S', statement.name(), buf).map_err(Error::encode)?;
+        }
         frontend::parse(
-            "",                 // unnamed prepared statement
+            name,               // prepared statement
             query,              // query to parse
             std::iter::empty(), // give no type info
             buf,
         )
         .map_err(Error::encode)?;
-        frontend::describe(b'S', "", buf).map_err(Error::encode)?;
+        frontend::describe(b'S', name, buf).map_err(Error::encode)?;
         Ok(())
     })?;
 
+    for _ in close {
+        match responses.next().await? {
+            Message::CloseComplete => {}
+            _ => return Err(Error::unexpected_message()),
+        }
+    }
+
     match responses.next().await? {
         Message::ParseComplete => {}
         _ => return Err(Error::unexpected_message()),
@@ -65,14 +142,21 @@ pub async fn query_txt<'a, S, I>(
         _ => return Err(Error::unexpected_message()),
     };
 
-    let columns =
-        crate::prepare::parse_row_description(&mut client, typecache, row_description).await?;
+    crate::prepare::parse_row_description(client, typecache, row_description).await
+}
 
+/// Binds the prepared statement `name` to the unnamed portal and executes it.
+async fn bind_execute<S, I>(client: PartialQuery<'_>, name: &str, params: I) -> Result<(), Error>
+where
+    S: AsRef<str>,
+    I: IntoIterator<Item = Option<S>>,
+    I::IntoIter: ExactSizeIterator,
+{
     let responses = client.send_with_sync(|buf| {
         // Bind, pass params as text, retrieve as text
         match frontend::bind(
             "",                 // empty string selects the unnamed portal
-            "",                 // unnamed prepared statement
+            name,               // prepared statement
             std::iter::empty(), // all parameters use the default format (text)
             params,
             |param, buf| match param {
@@ -97,17 +181,9 @@ pub async fn query_txt<'a, S, I>(
     })?;
 
     match responses.next().await? {
-        Message::BindComplete => {}
-        _ => return Err(Error::unexpected_message()),
+        Message::BindComplete => Ok(()),
+        _ => Err(Error::unexpected_message()),
     }
-
-    Ok(RowStream {
-        responses,
-        statement: Statement::new("", columns),
-        command_tag: None,
-        status: ReadyForQueryStatus::Unknown,
-        output_format: Format::Text,
-    })
 }
 
 /// A stream of table rows.
@@ -117,6 +193,25 @@ pub struct RowStream<'a> {
     pub statement: Statement,
     pub command_tag: Option<String>,
     pub status: ReadyForQueryStatus,
+    /// Whether the statement came from the statement cache, or `None` if the cache is disabled.
+    pub statement_cache_hit: Option<bool>,
+}
+
+impl<'a> RowStream<'a> {
+    fn new(
+        responses: &'a mut Responses,
+        statement: Statement,
+        statement_cache_hit: Option<bool>,
+    ) -> Self {
+        RowStream {
+            responses,
+            statement,
+            command_tag: None,
+            status: ReadyForQueryStatus::Unknown,
+            output_format: Format::Text,
+            statement_cache_hit,
+        }
+    }
 }
 
 impl Stream for RowStream<'_> {
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::sync::Arc;
 
@@ -6,7 +7,7 @@
 use postgres_protocol2::message::backend::Field;
 
 struct StatementInner {
-    name: &'static str,
+    name: Cow<'static, str>,
     columns: Vec<Column>,
 }
 
@@ -17,12 +18,15 @@ struct StatementInner {
 pub struct Statement(Arc<StatementInner>);
 
 impl Statement {
-    pub(crate) fn new(name: &'static str, columns: Vec<Column>) -> Statement {
-        Statement(Arc::new(StatementInner { name, columns }))
+    pub(crate) fn new(name: impl Into<Cow<'static, str>>, columns: Vec<Column>) -> Statement {
+        Statement(Arc::new(StatementInner {
+            name: name.into(),
+            columns,
+        }))
     }
 
     pub(crate) fn name(&self) -> &str {
-        self.0.name
+        &self.0.name
     }
 
     /// Returns information about the columns returned when the statement is queried.
@@ -26,7 +26,7 @@
 use remote_storage::{RemotePath, RemoteStorageConfig};
 use reqwest::Url;
 use storage_broker::Uri;
-use utils::id::{NodeId, TimelineId};
+use utils::id::{NodeId, TenantId, TimelineId};
 use utils::logging::{LogFormat, SecretString};
 
 use crate::tenant::storage_layer::inmemory_layer::IndexEntry;
@@ -129,6 +129,11 @@ pub struct PageServerConf {
     /// A lower value implicitly deprioritizes loading such tenants, vs. other work in the system.
     pub concurrent_tenant_warmup: ConfigurableSemaphore,
 
+    /// Tenants attached at startup that are loaded right away, ahead of the
+    /// `concurrent_tenant_warmup` queue. The other tenants are loaded by the warmup in the
+    /// background, or on first access, whichever comes first.
+    pub startup_priority_tenants: Vec<TenantId>,
+
     /// Number of concurrent [`TenantShard::gather_size_inputs`](crate::tenant::TenantShard::gather_size_inputs) allowed.
     pub concurrent_tenant_size_logical_size_queries: ConfigurableSemaphore,
     /// Limit of concurrent [`TenantShard::gather_size_inputs`] issued by module `eviction_task`.
@@ -138,6 +143,9 @@ pub struct PageServerConf {
     /// [`TenantShard::gather_size_inputs`]: crate::tenant::TenantShard::gather_size_inputs
     pub eviction_task_immitated_concurrent_logical_size_queries: ConfigurableSemaphore,
 
+    /// Number of attached tenants which are shut down concurrently when the pageserver stops.
+    pub concurrent_tenant_shutdown: ConfigurableSemaphore,
+
     // How often to collect metrics and send them to the metrics endpoint.
     pub metric_collection_interval: Duration,
     // How often to send unchanged cached metrics to the metrics endpoint.
@@ -411,7 +419,9 @@ pub fn parse_and_validate(
             l0_flush,
             virtual_file_io_mode,
             concurrent_tenant_warmup,
+            startup_priority_tenants,
             concurrent_tenant_size_logical_size_queries,
+            concurrent_tenant_shutdown,
             virtual_file_io_engine,
             tenant_config,
             no_sync,
@@ -455,6 +465,7 @@ pub fn parse_and_validate(
             broker_endpoint,
             broker_keepalive_interval,
             log_format,
+            startup_priority_tenants,
             metric_collection_interval,
             metric_collection_endpoint,
             metric_collection_bucket,
@@ -506,6 +517,7 @@ pub fn parse_and_validate(
                 // re-use `concurrent_tenant_size_logical_size_queries`
                 concurrent_tenant_size_logical_size_queries,
             ),
+            concurrent_tenant_shutdown: ConfigurableSemaphore::new(concurrent_tenant_shutdown),
             virtual_file_io_engine: match virtual_file_io_engine {
                 Some(v) => v,
                 None => match crate::virtual_file::io_engine_feature_test()
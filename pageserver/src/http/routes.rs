@@ -30,7 +30,7 @@
 use pageserver_api::models::{
     DetachBehavior, DownloadRemoteLayersTaskSpawnRequest, IngestAuxFilesRequest,
     ListAuxFilesRequest, LocationConfig, LocationConfigListResponse, LocationConfigMode, LsnLease,
-    LsnLeaseRequest, OffloadedTimelineInfo, PageTraceEvent, ShardParameters, StatusResponse,
+    LsnLeaseRequest, OffloadedTimelineInfo, PageTraceEvent, StatusResponse,
     TenantConfigPatchRequest, TenantConfigRequest, TenantDetails, TenantInfo,
     TenantLocationConfigRequest, TenantLocationConfigResponse, TenantScanRemoteStorageResponse,
     TenantScanRemoteStorageShard, TenantShardLocation, TenantShardSplitRequest,
@@ -65,8 +65,9 @@
 use crate::task_mgr::TaskKind;
 use crate::tenant::config::LocationConf;
 use crate::tenant::mgr::{
-    GetActiveTenantError, GetTenantError, TenantManager, TenantMapError, TenantMapInsertError,
-    TenantSlot, TenantSlotError, TenantSlotUpsertError, TenantStateError, UpsertLocationError,
+    GetActiveTenantError, GetTenantError, ReloadTenantError, TenantManager, TenantMapError,
+    TenantMapInsertError, TenantSlot, TenantSlotError, TenantSlotUpsertError, TenantStateError,
+    UpsertLocationError,
 };
 use crate::tenant::remote_timeline_client::index::GcCompactionState;
 use crate::tenant::remote_timeline_client::{
@@ -83,7 +84,7 @@
 };
 use crate::tenant::{
     GetTimelineError, LogicalSizeCalculationCause, OffloadedTimeline, PageReconstructError,
-    remote_timeline_client,
+    TenantConfigUpdateError, remote_timeline_client,
 };
 use crate::{DEFAULT_PG_VERSION, disk_usage_eviction_task, tenant};
 
@@ -253,6 +254,18 @@ fn from(tse: TenantStateError) -> ApiError {
     }
 }
 
+impl From<ReloadTenantError> for ApiError {
+    fn from(e: ReloadTenantError) -> ApiError {
+        match e {
+            e @ ReloadTenantError::NotBroken(..) => {
+                ApiError::PreconditionFailed(format!("{e}").into_boxed_str())
+            }
+            ReloadTenantError::SlotError(e) => e.into(),
+            ReloadTenantError::Other(e) => ApiError::InternalServerError(e),
+        }
+    }
+}
+
 impl From<GetTenantError> for ApiError {
     fn from(tse: GetTenantError) -> ApiError {
         match tse {
@@ -267,6 +280,14 @@ fn from(tse: GetTenantError) -> ApiError {
                 // that it can attach the tenant to another PS and we'd be in split-brain.
                 ApiError::ResourceUnavailable("Tenant not yet active".into())
             }
+            GetTenantError::WillNotBecomeActive(_, TenantState::Stopping { .. }) => {
+                ApiError::ShuttingDown
+            }
+            GetTenantError::WillNotBecomeActive(_, TenantState::Broken { reason, .. }) => {
+                ApiError::InternalServerError(anyhow!("tenant is broken: {}", reason))
+            }
+            GetTenantError::WillNotBecomeActive(..) => ApiError::Conflict(format!("{tse}")),
+            GetTenantError::NotAttaching(..) => ApiError::Conflict(format!("{tse}")),
             GetTenantError::MapState(e) => ApiError::ResourceUnavailable(format!("{e}").into()),
         }
     }
@@ -1168,6 +1189,39 @@ async fn tenant_reset_handler(
     json_response(StatusCode::OK, ())
 }
 
+async fn tenant_checkpoint_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let state = get_state(&request);
+    let result = state
+        .tenant_manager
+        .checkpoint_tenant(tenant_shard_id)
+        .await?;
+
+    json_response(StatusCode::OK, result)
+}
+
+async fn tenant_reload_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Warn);
+    let state = get_state(&request);
+    state
+        .tenant_manager
+        .reload_tenant(tenant_shard_id, &ctx)
+        .await?;
+
+    json_response(StatusCode::OK, ())
+}
+
 async fn tenant_list_handler(
     request: Request<Body>,
     _cancel: CancellationToken,
@@ -1887,27 +1941,11 @@ async fn update_tenant_config_handler(
         .get_attached_tenant_shard(tenant_shard_id)?;
     tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
 
-    // This is a legacy API that only operates on attached tenants: the preferred
-    // API to use is the location_config/ endpoint, which lets the caller provide
-    // the full LocationConf.
-    let location_conf = LocationConf::attached_single(
-        new_tenant_conf.clone(),
-        tenant.get_generation(),
-        ShardParameters::from(tenant.get_shard_identity()),
-    );
-
     tenant
-        .get_shard_identity()
-        .assert_equal(location_conf.shard); // not strictly necessary since we construct it above
-
-    crate::tenant::TenantShard::persist_tenant_config(state.conf, &tenant_shard_id, &location_conf)
+        .set_tenant_config(new_tenant_conf)
         .await
         .map_err(|e| ApiError::InternalServerError(anyhow::anyhow!(e)))?;
 
-    let _ = tenant
-        .update_tenant_config(|_crnt| Ok(new_tenant_conf.clone()))
-        .expect("Closure returns Ok()");
-
     json_response(StatusCode::OK, ())
 }
 
@@ -1929,30 +1967,16 @@ async fn patch_tenant_config_handler(
     tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
 
     let updated = tenant
-        .update_tenant_config(|crnt| {
-            crnt.apply_patch(request_data.config.clone())
-                .map_err(anyhow::Error::new)
-        })
-        .map_err(ApiError::BadRequest)?;
-
-    // This is a legacy API that only operates on attached tenants: the preferred
-    // API to use is the location_config/ endpoint, which lets the caller provide
-    // the full LocationConf.
-    let location_conf = LocationConf::attached_single(
-        updated,
-        tenant.get_generation(),
-        ShardParameters::from(tenant.get_shard_identity()),
-    );
-
-    tenant
-        .get_shard_identity()
-        .assert_equal(location_conf.shard); // not strictly necessary since we construct it above
-
-    crate::tenant::TenantShard::persist_tenant_config(state.conf, &tenant_shard_id, &location_conf)
+        .patch_tenant_config(request_data.config)
         .await
-        .map_err(|e| ApiError::InternalServerError(anyhow::anyhow!(e)))?;
+        .map_err(|e| match e {
+            TenantConfigUpdateError::InvalidPatch(_) => ApiError::BadRequest(anyhow::anyhow!(e)),
+            TenantConfigUpdateError::Persist(_) => {
+                ApiError::InternalServerError(anyhow::anyhow!(e))
+            }
+        })?;
 
-    json_response(StatusCode::OK, ())
+    json_response(StatusCode::OK, updated)
 }
 
 async fn put_tenant_location_config_handler(
@@ -3101,6 +3125,21 @@ async fn secondary_status_handler(
     json_response(StatusCode::OK, progress)
 }
 
+async fn tenant_attach_progress_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+    let state = get_state(&request);
+
+    let progress = state
+        .tenant_manager
+        .tenant_attach_progress(tenant_shard_id)?;
+
+    json_response(StatusCode::OK, progress)
+}
+
 async fn handler_404(_: Request<Body>) -> Result<Response<Body>, ApiError> {
     json_response(
         StatusCode::NOT_FOUND,
@@ -3969,6 +4008,12 @@ pub fn make_router(
         .post("/v1/tenant/:tenant_shard_id/reset", |r| {
             api_handler(r, tenant_reset_handler)
         })
+        .post("/v1/tenant/:tenant_shard_id/checkpoint", |r| {
+            api_handler(r, tenant_checkpoint_handler)
+        })
+        .post("/v1/tenant/:tenant_shard_id/reload", |r| {
+            api_handler(r, tenant_reload_handler)
+        })
         .post(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/preserve_initdb_archive",
             |r| api_handler(r, timeline_preserve_initdb_handler),
@@ -4090,6 +4135,9 @@ pub fn make_router(
         .get("/v1/tenant/:tenant_shard_id/secondary/status", |r| {
             api_handler(r, secondary_status_handler)
         })
+        .get("/v1/tenant/:tenant_shard_id/attach_progress", |r| {
+            api_handler(r, tenant_attach_progress_handler)
+        })
         .post("/v1/tenant/:tenant_shard_id/secondary/download", |r| {
             api_handler(r, secondary_download_handler)
         })
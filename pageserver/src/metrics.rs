@@ -2389,6 +2389,7 @@ pub(crate) struct TenantManagerMetrics {
     tenant_slots_secondary: UIntGauge,
     tenant_slots_inprogress: UIntGauge,
     pub(crate) tenant_slot_writes: IntCounter,
+    pub(crate) slow_map_writes: IntCounter,
     pub(crate) unexpected_errors: IntCounter,
 }
 
@@ -2453,6 +2454,11 @@ pub(crate) fn slots_total(&self) -> u64 {
             "Writes to a tenant slot, including all of create/attach/detach/delete"
         )
         .expect("failed to define a metric"),
+        slow_map_writes: register_int_counter!(
+            "pageserver_tenant_manager_slow_map_writes_total",
+            "Number of times the tenant map was write-locked for long enough to stall readers"
+        )
+        .expect("failed to define a metric"),
         unexpected_errors: register_int_counter!(
             "pageserver_tenant_manager_unexpected_errors_total",
             "Number of unexpected conditions encountered: nonzero value indicates a non-fatal bug."
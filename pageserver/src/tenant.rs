@@ -66,7 +66,6 @@
 use utils::circuit_breaker::CircuitBreaker;
 use utils::crashsafe::path_with_suffix_extension;
 use utils::sync::gate::{Gate, GateGuard};
-use utils::timeout::{TimeoutCancellableError, timeout_cancellable};
 use utils::try_rcu::ArcSwapExt;
 use utils::zstd::{create_zst_tarball, extract_zst_tarball};
 use utils::{backoff, completion, failpoint_support, fs_ext, pausable_failpoint};
@@ -339,6 +338,10 @@ pub struct TenantShard {
 
     eviction_task_tenant_state: tokio::sync::Mutex<EvictionTaskTenantState>,
 
+    /// Serializes tenant config updates made through the mgmt API, so that the config persisted
+    /// to disk always matches the last one applied in memory.
+    tenant_conf_update_lock: tokio::sync::Mutex<()>,
+
     /// Track repeated failures to compact, so that we can back off.
     /// Overhead of mutex is acceptable because compaction is done with a multi-second period.
     compaction_circuit_breaker: std::sync::Mutex<CircuitBreaker>,
@@ -384,6 +387,9 @@ pub struct TenantShard {
     /// proceeding.
     pub(crate) gc_block: gc_block::GcBlock,
 
+    /// Timelines loaded so far while the tenant is [`TenantState::Attaching`].
+    pub(crate) attach_progress: std::sync::Mutex<models::AttachProgress>,
+
     l0_flush_global_state: L0FlushGlobalState,
 
     pub(crate) feature_resolver: TenantFeatureResolver,
@@ -404,6 +410,14 @@ pub(crate) enum WalRedoManager {
 #[error("pageserver is shutting down")]
 pub(crate) struct GlobalShutDown;
 
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum TenantConfigUpdateError {
+    #[error("invalid tenant config patch: {0}")]
+    InvalidPatch(humantime::DurationError),
+    #[error("failed to persist tenant config: {0}")]
+    Persist(std::io::Error),
+}
+
 impl WalRedoManager {
     pub(crate) fn new(mgr: PostgresRedoManager) -> Result<Arc<Self>, GlobalShutDown> {
         let id = WalredoManagerId::next();
@@ -1788,6 +1802,10 @@ async fn attach(
         // and build a layer map that contains an entry for each remote and local
         // layer file.
         let sorted_timelines = tree_sort_timelines(timeline_ancestors, |m| m.ancestor_timeline())?;
+        *self.attach_progress.lock().unwrap() = models::AttachProgress {
+            timelines_loaded: 0,
+            timelines_total: sorted_timelines.len(),
+        };
         for (timeline_id, remote_metadata) in sorted_timelines {
             let (index_part, remote_client, previous_heatmap) = remote_index_and_client
                 .remove(&timeline_id)
@@ -1821,6 +1839,7 @@ async fn attach(
                         timeline_id, self.tenant_shard_id
                     )
                 })?;
+            self.attach_progress.lock().unwrap().timelines_loaded += 1;
 
             match effect {
                 TimelineInitAndSyncResult::ReadyToActivate => {
@@ -3802,6 +3821,19 @@ pub(crate) async fn wait_to_become_active(
         &self,
         timeout: Duration,
     ) -> Result<(), GetActiveTenantError> {
+        self.wait_until_active(Some(timeout), None).await
+    }
+
+    /// Wait for the tenant to become active, giving up once `timeout` has elapsed (if any)
+    /// or `cancel` fires, e.g. because the client that asked for the tenant went away.
+    ///
+    /// Shutting down the tenant always ends the wait.
+    pub(crate) async fn wait_until_active(
+        &self,
+        timeout: Option<Duration>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(), GetActiveTenantError> {
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
         let mut receiver = self.state.subscribe();
         loop {
             let current_state = receiver.borrow_and_update().clone();
@@ -3809,21 +3841,24 @@ pub(crate) async fn wait_to_become_active(
                 TenantState::Attaching | TenantState::Activating(_) => {
                     // in these states, there's a chance that we can reach ::Active
                     self.activate_now();
-                    match timeout_cancellable(timeout, &self.cancel, receiver.changed()).await {
-                        Ok(r) => {
-                            r.map_err(
+                    tokio::select! {
+                        changed = receiver.changed() => {
+                            changed.map_err(
                             |_e: tokio::sync::watch::error::RecvError|
                                 // Tenant existed but was dropped: report it as non-existent
                                 GetActiveTenantError::NotFound(GetTenantError::ShardNotFound(self.tenant_shard_id))
-                        )?
+                            )?
+                        }
+                        _ = self.cancel.cancelled() => {
+                            return Err(GetActiveTenantError::Cancelled);
                         }
-                        Err(TimeoutCancellableError::Cancelled) => {
+                        _ = async { cancel.expect("checked by precondition").cancelled().await }, if cancel.is_some() => {
                             return Err(GetActiveTenantError::Cancelled);
                         }
-                        Err(TimeoutCancellableError::Timeout) => {
+                        _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {
                             return Err(GetActiveTenantError::WaitForActiveTimeout {
                                 latest_state: Some(self.current_state()),
-                                wait_time: timeout,
+                                wait_time: timeout.unwrap_or_default(),
                             });
                         }
                     }
@@ -4276,6 +4311,61 @@ pub fn update_tenant_config<
         Ok(updated.tenant_conf.clone())
     }
 
+    /// Replaces the tenant config with `new_tenant_conf`, persisting it before applying it.
+    pub(crate) async fn set_tenant_config(
+        &self,
+        new_tenant_conf: pageserver_api::models::TenantConfig,
+    ) -> Result<(), TenantConfigUpdateError> {
+        let _guard = self.tenant_conf_update_lock.lock().await;
+        self.persist_and_apply_tenant_config(new_tenant_conf)
+            .await
+            .map(|_| ())
+    }
+
+    /// Merges `patch` into the current tenant config, persisting the result before applying it.
+    ///
+    /// Returns the tenant config after the merge. Concurrent patches are serialized, so that
+    /// fields patched by one caller are never clobbered by another.
+    pub(crate) async fn patch_tenant_config(
+        &self,
+        patch: pageserver_api::models::TenantConfigPatch,
+    ) -> Result<pageserver_api::models::TenantConfig, TenantConfigUpdateError> {
+        let _guard = self.tenant_conf_update_lock.lock().await;
+        let updated = self
+            .tenant_conf
+            .load()
+            .tenant_conf
+            .clone()
+            .apply_patch(patch)
+            .map_err(TenantConfigUpdateError::InvalidPatch)?;
+        self.persist_and_apply_tenant_config(updated).await
+    }
+
+    /// Must be called with `tenant_conf_update_lock` held.
+    async fn persist_and_apply_tenant_config(
+        &self,
+        new_tenant_conf: pageserver_api::models::TenantConfig,
+    ) -> Result<pageserver_api::models::TenantConfig, TenantConfigUpdateError> {
+        // This is a legacy API that only operates on attached tenants: the preferred
+        // API to use is the location_config/ endpoint, which lets the caller provide
+        // the full LocationConf.
+        let location_conf = LocationConf::attached_single(
+            new_tenant_conf.clone(),
+            self.get_generation(),
+            models::ShardParameters::from(self.get_shard_identity()),
+        );
+
+        self.get_shard_identity().assert_equal(location_conf.shard); // not strictly necessary since we construct it above
+
+        Self::persist_tenant_config(self.conf, &self.tenant_shard_id, &location_conf)
+            .await
+            .map_err(TenantConfigUpdateError::Persist)?;
+
+        Ok(self
+            .update_tenant_config(|_crnt| Ok(new_tenant_conf.clone()))
+            .expect("Closure returns Ok()"))
+    }
+
     pub(crate) fn set_new_location_config(&self, new_conf: AttachedTenantConf) {
         let new_tenant_conf = new_conf.tenant_conf.clone();
 
@@ -4468,6 +4558,7 @@ fn new(
             cached_logical_sizes: tokio::sync::Mutex::new(HashMap::new()),
             cached_synthetic_tenant_size: Arc::new(AtomicU64::new(0)),
             eviction_task_tenant_state: tokio::sync::Mutex::new(EvictionTaskTenantState::default()),
+            tenant_conf_update_lock: tokio::sync::Mutex::new(()),
             compaction_circuit_breaker: std::sync::Mutex::new(CircuitBreaker::new(
                 format!("compaction-{tenant_shard_id}"),
                 5,
@@ -4491,6 +4582,7 @@ fn new(
             tenant_conf: Arc::new(ArcSwap::from_pointee(attached_conf)),
             ongoing_timeline_detach: std::sync::Mutex::default(),
             gc_block: Default::default(),
+            attach_progress: std::sync::Mutex::default(),
             l0_flush_global_state,
             basebackup_cache,
             feature_resolver: TenantFeatureResolver::new(
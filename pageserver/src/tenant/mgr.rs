@@ -13,7 +13,7 @@
 use futures::StreamExt;
 use itertools::Itertools;
 use pageserver_api::key::Key;
-use pageserver_api::models::{DetachBehavior, LocationConfigMode};
+use pageserver_api::models::{AttachProgress, DetachBehavior, LocationConfigMode};
 use pageserver_api::shard::{
     ShardCount, ShardIdentity, ShardIndex, ShardNumber, ShardStripeSize, TenantShardId,
 };
@@ -50,8 +50,8 @@
 };
 use crate::tenant::span::debug_assert_current_span_has_tenant_id;
 use crate::tenant::storage_layer::inmemory_layer;
-use crate::tenant::timeline::ShutdownMode;
 use crate::tenant::timeline::layer_manager::LayerManagerLockHolder;
+use crate::tenant::timeline::{FlushLayerError, ShutdownMode};
 use crate::tenant::{
     AttachedTenantConf, GcError, LoadConfigError, SpawnMode, TenantShard, TenantState,
 };
@@ -199,6 +199,81 @@ pub(crate) fn len(&self) -> usize {
     }
 }
 
+/// Read-lock the tenants map, recovering it if a panic poisoned the lock.
+///
+/// The map is only modified in short critical sections that leave it consistent, so a
+/// panic elsewhere while the lock was held is no reason to fail every tenant operation
+/// from then on.
+fn read_tenants(
+    tenants: &std::sync::RwLock<TenantsMap>,
+) -> std::sync::RwLockReadGuard<'_, TenantsMap> {
+    tenants.read().unwrap_or_else(|poisoned| {
+        recover_poisoned_tenants(tenants);
+        poisoned.into_inner()
+    })
+}
+
+/// Write-lock the tenants map, recovering it if a panic poisoned the lock.
+/// See [`read_tenants`].
+fn write_tenants(tenants: &std::sync::RwLock<TenantsMap>) -> TenantsWriteGuard<'_> {
+    let guard = tenants.write().unwrap_or_else(|poisoned| {
+        recover_poisoned_tenants(tenants);
+        poisoned.into_inner()
+    });
+    TenantsWriteGuard {
+        guard,
+        locked_at: std::time::Instant::now(),
+    }
+}
+
+/// Holding the tenants map write lock for longer than this is reported.
+const SLOW_MAP_WRITE_THRESHOLD: Duration = Duration::from_millis(10);
+
+/// Write guard of the tenants map that reports critical sections longer than
+/// [`SLOW_MAP_WRITE_THRESHOLD`].
+///
+/// The map sits behind a synchronous lock, which readers on async worker threads take without
+/// yielding. That is cheap as long as writers only swap slots under the lock and do anything
+/// slow (I/O, shutting tenants down) outside of it, behind a [`TenantSlot::InProgress`] slot.
+/// A slow write means some path broke that rule.
+struct TenantsWriteGuard<'a> {
+    guard: std::sync::RwLockWriteGuard<'a, TenantsMap>,
+    locked_at: std::time::Instant,
+}
+
+impl Deref for TenantsWriteGuard<'_> {
+    type Target = TenantsMap;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl std::ops::DerefMut for TenantsWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl Drop for TenantsWriteGuard<'_> {
+    fn drop(&mut self) {
+        let held = self.locked_at.elapsed();
+        if held > SLOW_MAP_WRITE_THRESHOLD {
+            METRICS.slow_map_writes.inc();
+            warn!(
+                held_ms = held.as_millis(),
+                "tenants map was write-locked for too long"
+            );
+        }
+    }
+}
+
+fn recover_poisoned_tenants(tenants: &std::sync::RwLock<TenantsMap>) {
+    error!("tenants map lock was poisoned by a panic, recovering");
+    METRICS.unexpected_errors.inc();
+    tenants.clear_poison();
+}
+
 /// Precursor to deletion of a tenant dir: we do a fast rename to a tmp path, and then
 /// the slower actual deletion in the background.
 ///
@@ -387,20 +462,13 @@ async fn init_load_generations(
 /// Given a directory discovered in the pageserver's tenants/ directory, attempt
 /// to load a tenant config from it.
 ///
-/// If we cleaned up something expected (like an empty dir or a temp dir), return None.
+/// If we cleaned up something expected (like an empty dir), return None.
 fn load_tenant_config(
     conf: &'static PageServerConf,
     tenant_shard_id: TenantShardId,
     dentry: Utf8DirEntry,
 ) -> Option<Result<LocationConf, LoadConfigError>> {
     let tenant_dir_path = dentry.path().to_path_buf();
-    if crate::is_temporary(&tenant_dir_path) {
-        info!("Found temporary tenant directory, removing: {tenant_dir_path}");
-        // No need to use safe_remove_tenant_dir_all because this is already
-        // a temporary path
-        std::fs::remove_dir_all(&tenant_dir_path).fatal_err("delete temporary tenant dir");
-        return None;
-    }
 
     // This case happens if we crash during attachment before writing a config into the dir
     let is_empty = tenant_dir_path
@@ -422,6 +490,7 @@ fn load_tenant_config(
 /// seconds even on reasonably fast drives.
 async fn init_load_tenant_configs(
     conf: &'static PageServerConf,
+    background_purges: &BackgroundPurges,
 ) -> HashMap<TenantShardId, Result<LocationConf, LoadConfigError>> {
     let tenants_dir = conf.tenants_path();
 
@@ -440,6 +509,30 @@ async fn init_load_tenant_configs(
 
     let mut join_set = JoinSet::new();
     for dentry in dentries {
+        // Tenant directories moved out of the way with a TEMP_FILE_SUFFIX name, such as by
+        // [`safe_rename_tenant_dir`], are only left behind if the pageserver stopped before
+        // purging them. Nothing can be using them before the tenants are loaded.
+        if crate::is_temporary(dentry.path()) {
+            join_set.spawn_blocking(move || {
+                let tmp_path = dentry.path();
+                info!("Found temporary tenant directory, removing: {tmp_path}");
+                // No need to use safe_remove_tenant_dir_all because this is already
+                // a temporary path
+                std::fs::remove_dir_all(tmp_path).fatal_err("delete temporary tenant dir");
+                None
+            });
+            continue;
+        }
+
+        // Hidden entries are not ours, leave them alone.
+        if dentry.file_name().starts_with('.') {
+            debug!(
+                "Skipping hidden entry in tenants dir: '{}'",
+                dentry.file_name()
+            );
+            continue;
+        }
+
         let tenant_shard_id = match dentry.file_name().parse::<TenantShardId>() {
             Ok(id) => id,
             Err(_) => {
@@ -452,18 +545,35 @@ async fn init_load_tenant_configs(
         };
 
         join_set.spawn_blocking(move || {
-            (
-                tenant_shard_id,
-                load_tenant_config(conf, tenant_shard_id, dentry),
-            )
+            load_tenant_config(conf, tenant_shard_id, dentry)
+                .map(|tenant_config| (tenant_shard_id, tenant_config))
         });
     }
 
     while let Some(r) = join_set.join_next().await {
-        let (tenant_shard_id, tenant_config) = r.expect("Panic in config load task");
-        if let Some(tenant_config) = tenant_config {
-            configs.insert(tenant_shard_id, tenant_config);
+        let Some((tenant_shard_id, tenant_config)) = r.expect("Panic in config load task") else {
+            continue;
+        };
+
+        // Attaching a tenant writes its config before anything else, so a directory without a
+        // config was left by a crash during attach, after it started downloading. Anything in
+        // it can be downloaded again if the tenant is attached here again.
+        if let Err(LoadConfigError::NotFound(_)) = &tenant_config {
+            let tenant_dir_path = conf.tenant_path(&tenant_shard_id);
+            warn!(
+                "tenant directory {tenant_dir_path:?} has no config, it was left by an interrupted attach: removing it"
+            );
+            match safe_rename_tenant_dir(&tenant_dir_path).await {
+                Ok(tmp_path) => background_purges.spawn(tmp_path),
+                Err(e) => {
+                    error!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                    "Failed to move incomplete tenant directory '{tenant_dir_path}': {e:?}");
+                }
+            }
+            continue;
         }
+
+        configs.insert(tenant_shard_id, tenant_config);
     }
 
     configs
@@ -506,7 +616,7 @@ pub async fn init_tenant_mgr(
     init_order: InitializationOrder,
 ) -> anyhow::Result<()> {
     debug_assert!(matches!(
-        *tenant_manager.tenants.read().unwrap(),
+        *read_tenants(&tenant_manager.tenants),
         TenantsMap::Initializing
     ));
     let mut tenants = BTreeMap::new();
@@ -533,7 +643,7 @@ pub async fn init_tenant_mgr(
     );
 
     // Scan local filesystem for attached tenants
-    let tenant_configs = init_load_tenant_configs(conf).await;
+    let tenant_configs = init_load_tenant_configs(conf, background_purges).await;
 
     // Determine which tenants are to be secondary or attached, and in which generation
     let tenant_modes = init_load_generations(conf, &tenant_configs, resources, cancel).await?;
@@ -658,20 +768,37 @@ pub async fn init_tenant_mgr(
         let tenant_dir_path = conf.tenant_path(&tenant_shard_id);
         let shard_identity = location_conf.shard;
         let slot = match location_conf.mode {
-            LocationMode::Attached(attached_conf) => TenantSlot::Attached(
-                tenant_spawn(
-                    conf,
-                    tenant_shard_id,
-                    &tenant_dir_path,
-                    resources.clone(),
-                    AttachedTenantConf::new(location_conf.tenant_conf, attached_conf),
-                    shard_identity,
-                    Some(init_order.clone()),
-                    SpawnMode::Lazy,
-                    &ctx,
+            LocationMode::Attached(attached_conf) => {
+                // Priority tenants skip the warmup queue, the others wait for a warmup permit
+                // or for a client to ask for them.
+                let spawn_mode = if conf
+                    .startup_priority_tenants
+                    .contains(&tenant_shard_id.tenant_id)
+                {
+                    info!(
+                        tenant_id = %tenant_shard_id.tenant_id,
+                        shard_id = %tenant_shard_id.shard_slug(),
+                        "Loading priority tenant ahead of warmup"
+                    );
+                    SpawnMode::Eager
+                } else {
+                    SpawnMode::Lazy
+                };
+                TenantSlot::Attached(
+                    tenant_spawn(
+                        conf,
+                        tenant_shard_id,
+                        &tenant_dir_path,
+                        resources.clone(),
+                        AttachedTenantConf::new(location_conf.tenant_conf, attached_conf),
+                        shard_identity,
+                        Some(init_order.clone()),
+                        spawn_mode,
+                        &ctx,
+                    )
+                    .expect("global shutdown during init_tenant_mgr cannot happen"),
                 )
-                .expect("global shutdown during init_tenant_mgr cannot happen"),
-            ),
+            }
             LocationMode::Secondary(secondary_conf) => {
                 info!(
                     tenant_id = %tenant_shard_id.tenant_id,
@@ -693,7 +820,7 @@ pub async fn init_tenant_mgr(
 
     info!("Processed {} local tenants at startup", tenants.len());
 
-    let mut tenant_map = tenant_manager.tenants.write().unwrap();
+    let mut tenant_map = write_tenants(&tenant_manager.tenants);
     *tenant_map = TenantsMap::Open(tenants);
 
     Ok(())
@@ -770,7 +897,7 @@ pub(crate) fn get_attached_tenant_shard(
         &self,
         tenant_shard_id: TenantShardId,
     ) -> Result<Arc<TenantShard>, GetTenantError> {
-        let locked = self.tenants.read().unwrap();
+        let locked = read_tenants(&self.tenants);
 
         let peek_slot = tenant_map_peek_slot(&locked, &tenant_shard_id, TenantSlotPeekMode::Read)?;
 
@@ -783,11 +910,47 @@ pub(crate) fn get_attached_tenant_shard(
         }
     }
 
+    /// Like [`Self::get_attached_tenant_shard`], but also requires the tenant to be Active.
+    ///
+    /// Does not wait: a tenant that is still attaching or activating yields
+    /// [`GetTenantError::NotActive`], which callers may retry, whereas a tenant that is
+    /// Broken or Stopping yields [`GetTenantError::WillNotBecomeActive`].
+    pub(crate) fn get_active_tenant_shard(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<Arc<TenantShard>, GetTenantError> {
+        let tenant = self.get_attached_tenant_shard(tenant_shard_id)?;
+        match tenant.current_state() {
+            TenantState::Active => Ok(tenant),
+            TenantState::Attaching | TenantState::Activating(_) => {
+                Err(GetTenantError::NotActive(tenant_shard_id))
+            }
+            state @ (TenantState::Broken { .. } | TenantState::Stopping { .. }) => {
+                Err(GetTenantError::WillNotBecomeActive(tenant_shard_id, state))
+            }
+        }
+    }
+
+    /// How far along a tenant shard is in loading its timelines, for polling an attach
+    /// instead of waiting for the tenant to become active.
+    ///
+    /// Fails with [`GetTenantError::NotAttaching`] once the tenant has left [`TenantState::Attaching`].
+    pub(crate) fn tenant_attach_progress(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<AttachProgress, GetTenantError> {
+        let tenant = self.get_attached_tenant_shard(tenant_shard_id)?;
+        match tenant.current_state() {
+            TenantState::Attaching => Ok(tenant.attach_progress.lock().unwrap().clone()),
+            state => Err(GetTenantError::NotAttaching(tenant_shard_id, state)),
+        }
+    }
+
     pub(crate) fn get_secondary_tenant_shard(
         &self,
         tenant_shard_id: TenantShardId,
     ) -> Option<Arc<SecondaryTenant>> {
-        let locked = self.tenants.read().unwrap();
+        let locked = read_tenants(&self.tenants);
 
         let peek_slot = tenant_map_peek_slot(&locked, &tenant_shard_id, TenantSlotPeekMode::Read)
             .ok()
@@ -801,7 +964,7 @@ pub(crate) fn get_secondary_tenant_shard(
 
     /// Whether the `TenantManager` is responsible for the tenant shard
     pub(crate) fn manages_tenant_shard(&self, tenant_shard_id: TenantShardId) -> bool {
-        let locked = self.tenants.read().unwrap();
+        let locked = read_tenants(&self.tenants);
 
         let peek_slot = tenant_map_peek_slot(&locked, &tenant_shard_id, TenantSlotPeekMode::Read)
             .ok()
@@ -831,7 +994,7 @@ enum FastPathModified {
         // then we do not need to set the slot to InProgress, we can just call into the
         // existng tenant.
         let fast_path_taken = {
-            let locked = self.tenants.read().unwrap();
+            let locked = read_tenants(&self.tenants);
             let peek_slot =
                 tenant_map_peek_slot(&locked, &tenant_shard_id, TenantSlotPeekMode::Write)?;
             match (&new_location_config.mode, peek_slot) {
@@ -1126,7 +1289,7 @@ fn tenant_map_acquire_slot(
         use TenantSlotAcquireMode::*;
         METRICS.tenant_slot_writes.inc();
 
-        let mut locked = self.tenants.write().unwrap();
+        let mut locked = write_tenants(&self.tenants);
         let span = tracing::info_span!("acquire_slot", tenant_id=%tenant_shard_id.tenant_id, shard_id = %tenant_shard_id.shard_slug());
         let _guard = span.enter();
 
@@ -1209,11 +1372,65 @@ pub(crate) async fn reset_tenant(
             anyhow::bail!("Tenant not found when trying to reset");
         };
 
-        let Some(tenant) = old_slot.get_attached() else {
+        let Some(tenant) = old_slot.get_attached().cloned() else {
             slot_guard.revert();
             anyhow::bail!("Tenant is not in attached state");
         };
 
+        self.restart_tenant(tenant_shard_id, slot_guard, tenant, drop_cache, ctx)
+            .await
+    }
+
+    /// Re-load a tenant that is in [`TenantState::Broken`], e.g. after the operator has fixed
+    /// whatever caused it to fail loading.  The tenant goes through Attaching and Activating
+    /// again, with the same LocationConf it was last attached with.
+    ///
+    /// Holding the tenant's slot for the duration of the reload guards against racing with a
+    /// concurrent detach: whichever of the two acquires the slot second gets
+    /// [`TenantSlotError::InProgress`].
+    #[instrument(skip_all, fields(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug()))]
+    pub(crate) async fn reload_tenant(
+        &self,
+        tenant_shard_id: TenantShardId,
+        ctx: &RequestContext,
+    ) -> Result<(), ReloadTenantError> {
+        let mut slot_guard =
+            self.tenant_map_acquire_slot(&tenant_shard_id, TenantSlotAcquireMode::MustExist)?;
+        let tenant = slot_guard
+            .get_old_value()
+            .as_ref()
+            .expect("requested MustExist")
+            .get_attached()
+            .cloned();
+
+        let Some(tenant) = tenant else {
+            slot_guard.revert();
+            return Err(ReloadTenantError::Other(anyhow::anyhow!(
+                "Tenant is not in attached state"
+            )));
+        };
+
+        let state = tenant.current_state();
+        if !matches!(state, TenantState::Broken { .. }) {
+            slot_guard.revert();
+            return Err(ReloadTenantError::NotBroken(tenant_shard_id, state));
+        }
+
+        tracing::info!("Reloading broken tenant");
+        self.restart_tenant(tenant_shard_id, slot_guard, tenant, false, ctx)
+            .await
+            .map_err(ReloadTenantError::Other)
+    }
+
+    /// Shut down the tenant in `slot_guard` and spawn it again from its on-disk config.
+    async fn restart_tenant(
+        &self,
+        tenant_shard_id: TenantShardId,
+        mut slot_guard: SlotGuard<'_>,
+        tenant: Arc<TenantShard>,
+        drop_cache: bool,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
         let (_guard, progress) = utils::completion::channel();
         match tenant.shutdown(progress, ShutdownMode::Hard).await {
             Ok(()) => {
@@ -1263,7 +1480,7 @@ pub(crate) async fn reset_tenant(
     }
 
     pub(crate) fn get_attached_active_tenant_shards(&self) -> Vec<Arc<TenantShard>> {
-        let locked = self.tenants.read().unwrap();
+        let locked = read_tenants(&self.tenants);
         match &*locked {
             TenantsMap::Initializing => Vec::new(),
             TenantsMap::Open(map) | TenantsMap::ShuttingDown(map) => map
@@ -1283,7 +1500,7 @@ pub(crate) fn foreach_secondary_tenants<F>(&self, mut func: F)
         // TODO: let the callback return a hint to drop out of the loop early
         F: FnMut(&TenantShardId, &Arc<SecondaryTenant>),
     {
-        let locked = self.tenants.read().unwrap();
+        let locked = read_tenants(&self.tenants);
 
         let map = match &*locked {
             TenantsMap::Initializing | TenantsMap::ShuttingDown(_) => return,
@@ -1302,7 +1519,7 @@ pub(crate) fn foreach_secondary_tenants<F>(&self, mut func: F)
 
     /// Total list of all tenant slots: this includes attached, secondary, and InProgress.
     pub(crate) fn list(&self) -> Vec<(TenantShardId, TenantSlot)> {
-        let locked = self.tenants.read().unwrap();
+        let locked = read_tenants(&self.tenants);
         match &*locked {
             TenantsMap::Initializing => Vec::new(),
             TenantsMap::Open(map) | TenantsMap::ShuttingDown(map) => {
@@ -1312,7 +1529,7 @@ pub(crate) fn list(&self) -> Vec<(TenantShardId, TenantSlot)> {
     }
 
     pub(crate) fn get(&self, tenant_shard_id: TenantShardId) -> Option<TenantSlot> {
-        let locked = self.tenants.read().unwrap();
+        let locked = read_tenants(&self.tenants);
         match &*locked {
             TenantsMap::Initializing => None,
             TenantsMap::Open(map) | TenantsMap::ShuttingDown(map) => {
@@ -1578,7 +1795,7 @@ pub(crate) async fn do_shard_split(
         for child_shard_id in &child_shards {
             let child_shard_id = *child_shard_id;
             let child_shard = {
-                let locked = self.tenants.read().unwrap();
+                let locked = read_tenants(&self.tenants);
                 let peek_slot =
                     tenant_map_peek_slot(&locked, &child_shard_id, TenantSlotPeekMode::Read)?;
                 peek_slot.and_then(|s| s.get_attached()).cloned()
@@ -1817,18 +2034,22 @@ pub(crate) async fn shutdown(&self) {
 
     async fn shutdown_all_tenants0(&self) {
         let mut join_set = JoinSet::new();
+        // Attached tenants flush their layers on shutdown: bound how many do it at once.
+        let shutdown_permits = self.conf.concurrent_tenant_shutdown.inner().clone();
+        // Which tenant each shutdown task belongs to, so that failures can be attributed.
+        let mut shutdown_tasks = HashMap::new();
 
         #[cfg(all(debug_assertions, not(test)))]
         {
             // Check that our metrics properly tracked the size of the tenants map.  This is a convenient location to check,
             // as it happens implicitly at the end of tests etc.
-            let m = self.tenants.read().unwrap();
+            let m = read_tenants(&self.tenants);
             debug_assert_eq!(METRICS.slots_total(), m.len() as u64);
         }
 
         // Atomically, 1. create the shutdown tasks and 2. prevent creation of new tenants.
         let (total_in_progress, total_attached) = {
-            let mut m = self.tenants.write().unwrap();
+            let mut m = write_tenants(&self.tenants);
             match &mut *m {
                 TenantsMap::Initializing => {
                     *m = TenantsMap::ShuttingDown(BTreeMap::default());
@@ -1845,8 +2066,14 @@ async fn shutdown_all_tenants0(&self) {
                             TenantSlot::Attached(t) => {
                                 shutdown_state
                                     .insert(tenant_shard_id, TenantSlot::Attached(t.clone()));
-                                join_set.spawn(
+                                let shutdown_permits = shutdown_permits.clone();
+                                let handle = join_set.spawn(
                                     async move {
+                                        let _permit = shutdown_permits
+                                            .acquire()
+                                            .await
+                                            .expect("concurrent_tenant_shutdown semaphore is never closed");
+
                                         let res = {
                                             let (_guard, shutdown_progress) = completion::channel();
                                             t.shutdown(shutdown_progress, ShutdownMode::FreezeAndFlush).await
@@ -1863,6 +2090,7 @@ async fn shutdown_all_tenants0(&self) {
                                     }
                                     .instrument(info_span!("shutdown", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug())),
                                 );
+                                shutdown_tasks.insert(handle.id(), tenant_shard_id);
 
                                 total_attached += 1;
                             }
@@ -1902,8 +2130,10 @@ async fn shutdown_all_tenants0(&self) {
         let started_at = std::time::Instant::now();
 
         info!(
-            "Waiting for {} InProgress tenants and {} Attached tenants to shut down",
-            total_in_progress, total_attached
+            "Waiting for {} InProgress tenants and {} Attached tenants to shut down, {} at a time",
+            total_in_progress,
+            total_attached,
+            self.conf.concurrent_tenant_shutdown.initial_permits()
         );
 
         let total = join_set.len();
@@ -1914,15 +2144,22 @@ async fn shutdown_all_tenants0(&self) {
 
         while !join_set.is_empty() {
             tokio::select! {
-                Some(joined) = join_set.join_next() => {
+                Some(joined) = join_set.join_next_with_id() => {
                     match joined {
-                        Ok(()) => {},
+                        Ok(_) => {},
                         Err(join_error) if join_error.is_cancelled() => {
                             unreachable!("we are not cancelling any of the tasks");
                         }
                         Err(join_error) if join_error.is_panic() => {
                             // cannot really do anything, as this panic is likely a bug
                             panicked += 1;
+                            if let Some(tenant_shard_id) = shutdown_tasks.get(&join_error.id()) {
+                                error!(
+                                    tenant_id=%tenant_shard_id.tenant_id,
+                                    shard_id=%tenant_shard_id.shard_slug(),
+                                    "tenant shutdown panicked: {join_error}"
+                                );
+                            }
                         }
                         Err(join_error) => {
                             warn!("unknown kind of JoinError: {join_error}");
@@ -2027,7 +2264,7 @@ async fn detach_tenant0(
     pub(crate) fn list_tenants(
         &self,
     ) -> Result<Vec<(TenantShardId, TenantState, Generation)>, TenantMapListError> {
-        let tenants = self.tenants.read().unwrap();
+        let tenants = read_tenants(&self.tenants);
         let m = match &*tenants {
             TenantsMap::Initializing => return Err(TenantMapListError::Initializing),
             TenantsMap::Open(m) | TenantsMap::ShuttingDown(m) => m,
@@ -2166,21 +2403,18 @@ pub(crate) async fn complete_detaching_timeline_ancestor(
         if let Some(reparented) = resp.completed() {
             // finally ask the restarted tenant to complete the detach
             //
-            // rationale for 9999s: we don't really have a timetable here; if retried, the caller
+            // rationale for no timeout: we don't really have a timetable here; if retried, the caller
             // will get an 503.
-            tenant
-                .wait_to_become_active(std::time::Duration::from_secs(9999))
-                .await
-                .map_err(|e| {
-                    use GetActiveTenantError::{Cancelled, WillNotBecomeActive};
-                    use pageserver_api::models::TenantState;
-                    match e {
-                        Cancelled | WillNotBecomeActive(TenantState::Stopping { .. }) => {
-                            Error::ShuttingDown
-                        }
-                        other => Error::Complete(other.into()),
+            tenant.wait_until_active(None, None).await.map_err(|e| {
+                use GetActiveTenantError::{Cancelled, WillNotBecomeActive};
+                use pageserver_api::models::TenantState;
+                match e {
+                    Cancelled | WillNotBecomeActive(TenantState::Stopping { .. }) => {
+                        Error::ShuttingDown
                     }
-                })?;
+                    other => Error::Complete(other.into()),
+                }
+            })?;
 
             utils::pausable_failpoint!(
                 "timeline-detach-ancestor::after_activating_before_finding-pausable"
@@ -2213,7 +2447,7 @@ pub(crate) fn resolve_attached_shard(
         tenant_id: &TenantId,
         selector: ShardSelector,
     ) -> ShardResolveResult {
-        let tenants = self.tenants.read().unwrap();
+        let tenants = read_tenants(&self.tenants);
         let mut want_shard: Option<ShardIndex> = None;
         let mut any_in_progress = None;
 
@@ -2290,7 +2524,7 @@ pub(crate) fn resolve_attached_shard(
     /// This function is quite expensive: callers are expected to cache the result and
     /// limit how often they call it.
     pub(crate) fn calculate_utilization(&self) -> Result<(u64, u32), TenantMapListError> {
-        let tenants = self.tenants.read().unwrap();
+        let tenants = read_tenants(&self.tenants);
         let m = match &*tenants {
             TenantsMap::Initializing => return Err(TenantMapListError::Initializing),
             TenantsMap::Open(m) | TenantsMap::ShuttingDown(m) => m,
@@ -2341,7 +2575,7 @@ pub(crate) async fn immediate_gc(
         ctx: &RequestContext,
     ) -> Result<GcResult, ApiError> {
         let tenant = {
-            let guard = self.tenants.read().unwrap();
+            let guard = read_tenants(&self.tenants);
             guard
                 .get(&tenant_shard_id)
                 .cloned()
@@ -2353,7 +2587,9 @@ pub(crate) async fn immediate_gc(
         // Use tenant's pitr setting
         let pitr = tenant.get_pitr_interval();
 
-        tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+        tenant
+            .wait_until_active(Some(ACTIVE_TENANT_TIMEOUT), Some(&cancel))
+            .await?;
 
         // Run in task_mgr to avoid race with tenant_detach operation
         let ctx: RequestContext =
@@ -2407,6 +2643,44 @@ pub(crate) async fn immediate_gc(
         })
     }
 
+    /// Freezes and flushes the open in-memory layer of every timeline of an active tenant shard,
+    /// so that everything ingested so far is in layer files on local disk.
+    ///
+    /// Fails if the tenant shard is not [`TenantState::Active`].
+    pub(crate) async fn checkpoint_tenant(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<TenantCheckpointResult, ApiError> {
+        let tenant = self.get_active_tenant_shard(tenant_shard_id)?;
+        let _gate_guard = tenant.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+
+        let started_at = std::time::Instant::now();
+        let mut result = TenantCheckpointResult::default();
+        for timeline in tenant.list_timelines() {
+            let disk_consistent_lsn = timeline.get_disk_consistent_lsn();
+            timeline.freeze_and_flush().await.map_err(|e| match e {
+                FlushLayerError::Cancelled => ApiError::ShuttingDown,
+                other => ApiError::InternalServerError(anyhow::anyhow!(other)),
+            })?;
+            result.timelines += 1;
+            result.wal_bytes_flushed += timeline
+                .get_disk_consistent_lsn()
+                .0
+                .saturating_sub(disk_consistent_lsn.0);
+        }
+        result.elapsed_ms = started_at.elapsed().as_millis() as u64;
+
+        info!(
+            tenant_id = %tenant_shard_id.tenant_id,
+            shard_id = %tenant_shard_id.shard_slug(),
+            timelines = result.timelines,
+            wal_bytes_flushed = result.wal_bytes_flushed,
+            elapsed_ms = result.elapsed_ms,
+            "checkpointed tenant"
+        );
+        Ok(result)
+    }
+
     /// Stops and removes the tenant from memory, if it's not [`TenantState::Stopping`] already, bails otherwise.
     /// Allows to remove other tenant resources manually, via `tenant_cleanup`.
     /// If the cleanup fails, tenant will stay in memory in [`TenantState::Broken`] state, and another removal
@@ -2482,6 +2756,16 @@ async fn remove_tenant_from_memory<V, F>(
     }
 }
 
+/// Outcome of [`TenantManager::checkpoint_tenant`].
+#[derive(Debug, Default, serde::Serialize)]
+pub(crate) struct TenantCheckpointResult {
+    /// Number of timelines flushed.
+    pub(crate) timelines: usize,
+    /// How far the disk consistent LSNs of the timelines moved, in total.
+    pub(crate) wal_bytes_flushed: u64,
+    pub(crate) elapsed_ms: u64,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum GetTenantError {
     /// NotFound is a TenantId rather than TenantShardId, because this error type is used from
@@ -2492,9 +2776,19 @@ pub(crate) enum GetTenantError {
     #[error("Tenant {0} not found")]
     ShardNotFound(TenantShardId),
 
+    /// The tenant exists locally, but is still loading or activating.
     #[error("Tenant {0} is not active")]
     NotActive(TenantShardId),
 
+    /// The tenant exists locally, but is in a state from which it cannot become active
+    /// without intervention (Broken, or Stopping because it is being detached).
+    #[error("Tenant {0} will not become active. Current state: {1}")]
+    WillNotBecomeActive(TenantShardId, TenantState),
+
+    /// Attach progress was requested for a tenant that has already finished (or never started) attaching.
+    #[error("Tenant {0} is not attaching. Current state: {1}")]
+    NotAttaching(TenantShardId, TenantState),
+
     // Initializing or shutting down: cannot authoritatively say whether we have this tenant
     #[error("Tenant map is not available: {0}")]
     MapState(#[from] TenantMapError),
@@ -2555,6 +2849,16 @@ pub(crate) enum TenantStateError {
     Other(#[from] anyhow::Error),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ReloadTenantError {
+    #[error("Tenant {0} is not broken. Current state: {1}")]
+    NotBroken(TenantShardId, TenantState),
+    #[error(transparent)]
+    SlotError(#[from] TenantSlotError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum TenantMapListError {
     #[error("tenant map is still initiailizing")]
@@ -2706,7 +3010,7 @@ fn upsert(mut self, new_value: TenantSlot) -> Result<(), TenantSlotUpsertError>
         }
 
         let replaced: Option<TenantSlot> = {
-            let mut locked = self.tenants.write().unwrap();
+            let mut locked = write_tenants(&self.tenants);
 
             if let TenantSlot::InProgress(_) = new_value {
                 // It is never expected to try and upsert InProgress via this path: it should
@@ -2822,7 +3126,7 @@ fn drop(&mut self) {
         // Our old value is already shutdown, or it never existed: it is safe
         // for us to fully release the TenantSlot back into an empty state
 
-        let mut locked = self.tenants.write().unwrap();
+        let mut locked = write_tenants(&self.tenants);
 
         let m = match &mut *locked {
             TenantsMap::Initializing => {
@@ -2915,11 +3219,13 @@ mod tests {
     use std::sync::Arc;
 
     use camino::Utf8PathBuf;
+    use pageserver_api::shard::TenantShardId;
     use storage_broker::BrokerClientChannel;
     use tracing::Instrument;
+    use utils::id::TenantId;
 
     use super::super::harness::TenantHarness;
-    use super::TenantsMap;
+    use super::{TenantsMap, init_load_tenant_configs, read_tenants, write_tenants};
     use crate::{
         basebackup_cache::BasebackupCache,
         tenant::{
@@ -3022,4 +3328,85 @@ async fn shutdown_awaits_in_progress_tenant() {
         remove_tenant_from_memory_task.await.unwrap().unwrap();
         shutdown_task.await.unwrap();
     }
+
+    #[test]
+    fn poisoned_tenants_lock_is_recovered() {
+        let tenants = std::sync::RwLock::new(TenantsMap::Open(BTreeMap::new()));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _guard = tenants.write().unwrap();
+                panic!("poison the tenants lock");
+            })
+            .join()
+            .unwrap_err();
+        });
+        assert!(tenants.is_poisoned());
+
+        assert!(matches!(*read_tenants(&tenants), TenantsMap::Open(_)));
+        assert!(!tenants.is_poisoned());
+
+        *write_tenants(&tenants) = TenantsMap::ShuttingDown(BTreeMap::new());
+        assert!(matches!(
+            *read_tenants(&tenants),
+            TenantsMap::ShuttingDown(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn load_skips_temporary_and_hidden_dirs() {
+        let h = TenantHarness::create("load_skips_temporary_and_hidden_dirs")
+            .await
+            .unwrap();
+        let tenants_path = h.conf.tenants_path();
+
+        // a tenant dir renamed for deletion, with the pageserver stopping before purging it
+        let tmp_path = tenants_path.join(format!(
+            "{}.Ab12Cd34{}",
+            h.tenant_shard_id,
+            crate::TEMP_FILE_SUFFIX
+        ));
+        std::fs::create_dir_all(tmp_path.join("timelines")).unwrap();
+        let hidden_path = tenants_path.join(".snapshot");
+        std::fs::create_dir_all(&hidden_path).unwrap();
+
+        let configs = init_load_tenant_configs(h.conf, &BackgroundPurges::default()).await;
+        assert!(configs.keys().all(|id| *id == h.tenant_shard_id));
+        assert!(!tmp_path.exists());
+        assert!(hidden_path.exists());
+    }
+
+    #[tokio::test]
+    async fn load_removes_empty_and_incomplete_tenant_dirs() {
+        let h = TenantHarness::create("load_removes_empty_and_incomplete_tenant_dirs")
+            .await
+            .unwrap();
+
+        // directories of attaches interrupted before and after creating the timelines dir
+        let empty_id = TenantShardId::unsharded(TenantId::generate());
+        let empty_path = h.conf.tenant_path(&empty_id);
+        std::fs::create_dir_all(&empty_path).unwrap();
+        let incomplete_id = TenantShardId::unsharded(TenantId::generate());
+        let incomplete_path = h.conf.tenant_path(&incomplete_id);
+        std::fs::create_dir_all(h.conf.timelines_path(&incomplete_id)).unwrap();
+
+        let background_purges = BackgroundPurges::default();
+        let configs = init_load_tenant_configs(h.conf, &background_purges).await;
+        assert!(!configs.contains_key(&empty_id));
+        assert!(!configs.contains_key(&incomplete_id));
+        assert!(!empty_path.exists());
+        assert!(!incomplete_path.exists());
+
+        // the incomplete directory was moved aside and purged in the background
+        background_purges.shutdown().await;
+        let incomplete_name = incomplete_id.to_string();
+        let leftovers = std::fs::read_dir(h.conf.tenants_path())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(&incomplete_name)
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
 }
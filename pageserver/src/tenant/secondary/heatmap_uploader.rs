@@ -23,7 +23,7 @@
 use crate::metrics::SECONDARY_MODE;
 use crate::tenant::TenantShard;
 use crate::tenant::config::AttachmentMode;
-use crate::tenant::mgr::{GetTenantError, TenantManager};
+use crate::tenant::mgr::TenantManager;
 use crate::tenant::remote_timeline_client::remote_heatmap_path;
 use crate::tenant::span::debug_assert_current_span_has_tenant_id;
 use crate::tenant::tasks::{BackgroundLoopKind, warn_when_period_overrun};
@@ -285,10 +285,7 @@ fn on_command(
             "Starting heatmap write on command");
         let tenant = self
             .tenant_manager
-            .get_attached_tenant_shard(*tenant_shard_id)?;
-        if !tenant.is_active() {
-            return Err(GetTenantError::NotActive(*tenant_shard_id).into());
-        }
+            .get_active_tenant_shard(*tenant_shard_id)?;
 
         Ok(UploadPending {
             // Ignore our state for last digest: this forces an upload even if nothing has changed
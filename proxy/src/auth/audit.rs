@@ -0,0 +1,47 @@
+//! Structured audit trail of client authentication attempts.
+//!
+//! Audit events are emitted under a dedicated tracing target, [`AUDIT_TARGET`],
+//! so that they can be filtered and shipped separately from operational logs.
+//! They must never carry passwords or any other secrets.
+
+use tracing::info;
+
+use crate::auth::AuthError;
+use crate::auth::backend::ComputeUserInfo;
+use crate::context::{AuthMethod, RequestContext};
+use crate::error::ReportableError;
+
+/// Tracing target of all audit events.
+pub(crate) const AUDIT_TARGET: &str = "proxy::audit";
+
+/// Record the outcome of a single authentication attempt.
+pub(crate) fn auth_attempt(
+    ctx: &RequestContext,
+    user_info: &ComputeUserInfo,
+    method: AuthMethod,
+    result: Result<(), &AuthError>,
+) {
+    match result {
+        Ok(()) => info!(
+            target: AUDIT_TARGET,
+            session_id = %ctx.session_id(),
+            peer_addr = %ctx.peer_addr(),
+            endpoint = %user_info.endpoint,
+            user = %user_info.user,
+            auth_method = ?method,
+            outcome = "success",
+            "authentication attempt",
+        ),
+        Err(e) => info!(
+            target: AUDIT_TARGET,
+            session_id = %ctx.session_id(),
+            peer_addr = %ctx.peer_addr(),
+            endpoint = %user_info.endpoint,
+            user = %user_info.user,
+            auth_method = ?method,
+            outcome = "failure",
+            error_kind = e.get_error_kind().to_metric_label(),
+            "authentication attempt",
+        ),
+    }
+}
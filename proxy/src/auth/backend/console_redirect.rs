@@ -35,10 +35,82 @@ pub(crate) enum ConsoleRedirectError {
 
 #[derive(Debug)]
 pub struct ConsoleRedirectBackend {
-    console_uri: reqwest::Url,
+    console_uri: RedirectUriTemplate,
     api: cplane_proxy_v1::NeonControlPlaneClient,
 }
 
+/// Console redirect URI, which may contain `{endpoint}` and `{session}` placeholders
+/// that are substituted for every client. A URI without any placeholders gets the
+/// session ID appended to it.
+#[derive(Debug, Clone)]
+pub struct RedirectUriTemplate(String);
+
+#[derive(Debug, Error)]
+pub enum RedirectUriTemplateError {
+    #[error("unknown placeholder {{{0}}} in redirect URI template")]
+    UnknownPlaceholder(String),
+
+    #[error("unterminated placeholder in redirect URI template")]
+    UnterminatedPlaceholder,
+
+    #[error("redirect URI template must contain a {{session}} placeholder")]
+    MissingSessionPlaceholder,
+
+    #[error("redirect URI template does not produce a valid URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+}
+
+impl RedirectUriTemplate {
+    const ENDPOINT: &str = "endpoint";
+    const SESSION: &str = "session";
+
+    /// Substitute all placeholders in the template.
+    pub(crate) fn render(&self, endpoint: Option<&str>, session_id: &str) -> String {
+        let endpoint = urlencoding::encode(endpoint.unwrap_or_default());
+        self.0
+            .replace("{endpoint}", &endpoint)
+            .replace("{session}", session_id)
+    }
+}
+
+impl std::str::FromStr for RedirectUriTemplate {
+    type Err = RedirectUriTemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Backwards compatibility: the session ID used to be appended to the plain URI.
+        let template = if s.contains('{') {
+            s.to_owned()
+        } else {
+            format!("{s}{{{}}}", Self::SESSION)
+        };
+
+        let mut has_session = false;
+        let mut rest = template.as_str();
+        while let Some((_, after)) = rest.split_once('{') {
+            let (name, after) = after
+                .split_once('}')
+                .ok_or(RedirectUriTemplateError::UnterminatedPlaceholder)?;
+            match name {
+                Self::SESSION => has_session = true,
+                Self::ENDPOINT => {}
+                _ => {
+                    return Err(RedirectUriTemplateError::UnknownPlaceholder(
+                        name.to_owned(),
+                    ));
+                }
+            }
+            rest = after;
+        }
+        if !has_session {
+            return Err(RedirectUriTemplateError::MissingSessionPlaceholder);
+        }
+
+        let template = RedirectUriTemplate(template);
+        reqwest::Url::parse(&template.render(Some("endpoint"), "session"))?;
+        Ok(template)
+    }
+}
+
 impl fmt::Debug for cplane_proxy_v1::NeonControlPlaneClient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "NeonControlPlaneClient")
@@ -61,21 +133,16 @@ fn get_error_kind(&self) -> crate::error::ErrorKind {
     }
 }
 
-fn hello_message(
-    redirect_uri: &reqwest::Url,
-    session_id: &str,
-    duration: std::time::Duration,
-) -> String {
+fn hello_message(redirect_uri: &str, duration: std::time::Duration) -> String {
     let formatted_duration = humantime::format_duration(duration).to_string();
     format!(
         concat![
             "Welcome to Neon!\n",
             "Authenticate by visiting (will expire in {duration}):\n",
-            "    {redirect_uri}{session_id}\n\n",
+            "    {redirect_uri}\n\n",
         ],
         duration = formatted_duration,
         redirect_uri = redirect_uri,
-        session_id = session_id,
     )
 }
 
@@ -84,7 +151,10 @@ pub(crate) fn new_psql_session_id() -> String {
 }
 
 impl ConsoleRedirectBackend {
-    pub fn new(console_uri: reqwest::Url, api: cplane_proxy_v1::NeonControlPlaneClient) -> Self {
+    pub fn new(
+        console_uri: RedirectUriTemplate,
+        api: cplane_proxy_v1::NeonControlPlaneClient,
+    ) -> Self {
         Self { console_uri, api }
     }
 
@@ -121,7 +191,7 @@ async fn wake_compute(
 async fn authenticate(
     ctx: &RequestContext,
     auth_config: &'static AuthenticationConfig,
-    link_uri: &reqwest::Url,
+    link_uri: &RedirectUriTemplate,
     client: &mut PqStream<impl AsyncRead + AsyncWrite + Unpin>,
 ) -> auth::Result<(NodeInfo, AuthInfo, ComputeUserInfo)> {
     ctx.set_auth_method(crate::context::AuthMethod::ConsoleRedirect);
@@ -137,9 +207,9 @@ async fn authenticate(
     };
 
     let span = info_span!("console_redirect", psql_session_id = &psql_session_id);
+    let redirect_uri = link_uri.render(ctx.requested_endpoint().as_deref(), &psql_session_id);
     let greeting = hello_message(
-        link_uri,
-        &psql_session_id,
+        &redirect_uri,
         auth_config.console_redirect_confirmation_timeout,
     );
 
@@ -198,6 +268,8 @@ async fn authenticate(
         port: db_info.port,
         ssl_mode,
         host_addr: None,
+        unix_socket: None,
+        tls_server_name: None,
     };
     let auth_info =
         AuthInfo::for_console_redirect(&db_info.dbname, &db_info.user, db_info.password.as_deref());
@@ -217,9 +289,59 @@ async fn authenticate(
     Ok((
         NodeInfo {
             conn_info,
+            read_endpoints: None,
             aux: db_info.aux,
         },
         auth_info,
         user_info,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_uri_template() {
+        let legacy: RedirectUriTemplate = "http://localhost:3000/psql_session/".parse().unwrap();
+        assert_eq!(
+            legacy.render(Some("ep-foo"), "abcd"),
+            "http://localhost:3000/psql_session/abcd"
+        );
+
+        let template: RedirectUriTemplate =
+            "https://console.example.com/{endpoint}/psql_session/{session}"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            template.render(Some("ep-foo"), "abcd"),
+            "https://console.example.com/ep-foo/psql_session/abcd"
+        );
+
+        let template: RedirectUriTemplate =
+            "https://console.example.com/login?session={session}&ep={endpoint}"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            template.render(None, "abcd"),
+            "https://console.example.com/login?session=abcd&ep="
+        );
+
+        assert!(matches!(
+            "https://console.example.com/{endpoint}/".parse::<RedirectUriTemplate>(),
+            Err(RedirectUriTemplateError::MissingSessionPlaceholder)
+        ));
+        assert!(matches!(
+            "https://console.example.com/{project}/{session}".parse::<RedirectUriTemplate>(),
+            Err(RedirectUriTemplateError::UnknownPlaceholder(_))
+        ));
+        assert!(matches!(
+            "https://console.example.com/{session".parse::<RedirectUriTemplate>(),
+            Err(RedirectUriTemplateError::UnterminatedPlaceholder)
+        ));
+        assert!(matches!(
+            "not a url/{session}".parse::<RedirectUriTemplate>(),
+            Err(RedirectUriTemplateError::InvalidUrl(_))
+        ));
+    }
+}
@@ -23,6 +23,7 @@
 use crate::control_plane::errors::GetEndpointJwksError;
 use crate::http::read_body_with_limit;
 use crate::intern::RoleNameInt;
+use crate::metrics::Metrics;
 use crate::types::{EndpointId, RoleName};
 
 // TODO(conrad): make these configurable.
@@ -37,6 +38,13 @@
 const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
 const JWKS_FETCH_RETRIES: u32 = 3;
 
+/// How long to remember that a key ID was not found in the JWKS.
+pub const DEFAULT_JWKS_NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(60);
+/// How long past `MAX_RENEW` a JWKS that cannot be refetched keeps being used.
+pub const DEFAULT_JWKS_STALE_TTL: Duration = Duration::from_secs(1800);
+/// Upper bound of negatively cached key IDs, per endpoint and role.
+const MAX_UNKNOWN_KEY_IDS: usize = 1024;
+
 /// How to get the JWT auth rules
 pub(crate) trait FetchAuthRules: Clone + Send + Sync + 'static {
     fn fetch_auth_rules(
@@ -67,11 +75,19 @@ pub struct JwkCache {
     client: reqwest_middleware::ClientWithMiddleware,
 
     map: ClashMap<(EndpointId, RoleName), Arc<JwkCacheEntryLock>>,
+
+    /// How long to skip refetching the JWKS for a key ID that was not found in it.
+    negative_ttl: Duration,
+
+    /// How long past `MAX_RENEW` to keep using a JWKS that cannot be refetched.
+    stale_ttl: Duration,
 }
 
 pub(crate) struct JwkCacheEntry {
     /// Should refetch at least every hour to verify when old keys have been removed.
     /// Should refetch when new key IDs are seen only every 5 minutes or so
+    ///
+    /// This is when the oldest of the key sets was retrieved.
     last_retrieved: Instant,
 
     /// cplane will return multiple JWKs urls that we need to scrape.
@@ -83,7 +99,7 @@ fn find_jwk_and_audience(
         &self,
         key_id: &str,
         role_name: &RoleName,
-    ) -> Option<(&jose_jwk::Jwk, Option<&str>)> {
+    ) -> Option<(&jose_jwk::Jwk, Option<&str>, Instant)> {
         self.key_sets
             .values()
             // make sure our requested role has access to the key set
@@ -92,7 +108,7 @@ fn find_jwk_and_audience(
             .find_map(|key_set| {
                 key_set
                     .find_key(key_id)
-                    .map(|jwk| (jwk, key_set.audience.as_deref()))
+                    .map(|jwk| (jwk, key_set.audience.as_deref(), key_set.retrieved))
             })
     }
 }
@@ -101,6 +117,8 @@ struct KeySet {
     jwks: jose_jwk::JwkSet,
     audience: Option<String>,
     role_names: Vec<RoleNameInt>,
+    /// Older than the entry when the JWKS could not be refetched and the previous one was kept.
+    retrieved: Instant,
 }
 
 impl KeySet {
@@ -115,6 +133,14 @@ fn find_key(&self, key_id: &str) -> Option<&jose_jwk::Jwk> {
 pub(crate) struct JwkCacheEntryLock {
     cached: ArcSwapOption<JwkCacheEntry>,
     lookup: tokio::sync::Semaphore,
+
+    /// Key IDs that were recently not found in the JWKS, and when that happened.
+    /// Stops a burst of tokens with an unknown key ID from refetching the JWKS.
+    unknown_key_ids: ClashMap<String, Instant>,
+
+    /// When a JWKS could last not be refetched. While the identity provider is down, requests
+    /// use the stale JWKS instead of each waiting for a refetch to fail.
+    renewal_failed_at: parking_lot::Mutex<Option<Instant>>,
 }
 
 impl Default for JwkCacheEntryLock {
@@ -122,6 +148,8 @@ fn default() -> Self {
         JwkCacheEntryLock {
             cached: ArcSwapOption::empty(),
             lookup: tokio::sync::Semaphore::new(1),
+            unknown_key_ids: ClashMap::default(),
+            renewal_failed_at: parking_lot::Mutex::new(None),
         }
     }
 }
@@ -149,8 +177,7 @@ async fn fetch_jwks(
 
     let resp = match resp {
         Ok(r) => r,
-        // TODO: should we re-insert JWKs if we want to keep this JWKs URL?
-        // I expect these failures would be quite sparse.
+        // the caller keeps using the JWKs it fetched before, up to the stale TTL.
         Err(e) => {
             tracing::warn!(url=?jwks_url, error=?e, "could not fetch JWKs");
             return None;
@@ -230,6 +257,7 @@ fn try_acquire_permit(self: &Arc<Self>) -> Option<JwkRenewalPermit<'_>> {
         JwkRenewalPermit::try_acquire_permit(self)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn renew_jwks<F: FetchAuthRules>(
         &self,
         _permit: JwkRenewalPermit<'_>,
@@ -237,24 +265,38 @@ async fn renew_jwks<F: FetchAuthRules>(
         client: &reqwest_middleware::ClientWithMiddleware,
         endpoint: EndpointId,
         auth_rules: &F,
+        stale_ttl: Duration,
     ) -> Result<Arc<JwkCacheEntry>, JwtError> {
         // double check that no one beat us to updating the cache.
         let now = Instant::now();
         let guard = self.cached.load_full();
-        if let Some(cached) = guard {
+        if let Some(cached) = &guard {
             let last_update = now.duration_since(cached.last_retrieved);
             if last_update < Duration::from_secs(300) {
-                return Ok(cached);
+                return Ok(Arc::clone(cached));
+            }
+
+            // the identity provider was unreachable moments ago, don't wait on it again.
+            let failed_recently = self
+                .renewal_failed_at
+                .lock()
+                .is_some_and(|failed_at| now.duration_since(failed_at) < MIN_RENEW);
+            if failed_recently && last_update < MAX_RENEW + stale_ttl {
+                return Ok(Arc::clone(cached));
             }
         }
 
         let rules = auth_rules.fetch_auth_rules(ctx, endpoint).await?;
         let mut key_sets =
             ahash::HashMap::with_capacity_and_hasher(rules.len(), ahash::RandomState::new());
+        let mut last_retrieved = now;
+        let mut failed = false;
 
         // TODO(conrad): run concurrently
         // TODO(conrad): strip the JWKs urls (should be checked by cplane as well - cloud#16284)
         for rule in rules {
+            Metrics::get().proxy.jwks_refetches_total.inc();
+            let jwks_url = rule.jwks_url.clone();
             if let Some(jwks) = fetch_jwks(client, rule.jwks_url).await {
                 key_sets.insert(
                     rule.id,
@@ -262,13 +304,48 @@ async fn renew_jwks<F: FetchAuthRules>(
                         jwks,
                         audience: rule.audience,
                         role_names: rule.role_names,
+                        retrieved: now,
+                    },
+                );
+                continue;
+            }
+
+            // keep using the keys we have for a while, so that a short outage of the identity
+            // provider does not fail every JWT. Fail closed once they are too old.
+            failed = true;
+            let stale = guard
+                .as_ref()
+                .and_then(|cached| cached.key_sets.get(&rule.id))
+                .filter(|key_set| now.duration_since(key_set.retrieved) < MAX_RENEW + stale_ttl);
+            if let Some(stale) = stale {
+                tracing::warn!(
+                    url = ?jwks_url,
+                    age = ?now.duration_since(stale.retrieved),
+                    "keeping the JWKs fetched before",
+                );
+                last_retrieved = last_retrieved.min(stale.retrieved);
+                key_sets.insert(
+                    rule.id,
+                    KeySet {
+                        jwks: stale.jwks.clone(),
+                        audience: rule.audience,
+                        role_names: rule.role_names,
+                        retrieved: stale.retrieved,
                     },
                 );
             }
         }
+        *self.renewal_failed_at.lock() = failed.then_some(now);
+
+        // key IDs that have been published since we last looked should no longer be rejected.
+        self.unknown_key_ids.retain(|key_id, _| {
+            !key_sets
+                .values()
+                .any(|key_set| key_set.find_key(key_id).is_some())
+        });
 
         let entry = Arc::new(JwkCacheEntry {
-            last_retrieved: now,
+            last_retrieved,
             key_sets,
         });
         self.cached.swap(Some(Arc::clone(&entry)));
@@ -276,12 +353,31 @@ async fn renew_jwks<F: FetchAuthRules>(
         Ok(entry)
     }
 
+    fn is_unknown_key_id(&self, key_id: &str, negative_ttl: Duration) -> bool {
+        self.unknown_key_ids
+            .get(key_id)
+            .is_some_and(|seen| seen.elapsed() < negative_ttl)
+    }
+
+    fn remember_unknown_key_id(&self, key_id: &str, negative_ttl: Duration) {
+        if self.unknown_key_ids.len() >= MAX_UNKNOWN_KEY_IDS {
+            self.unknown_key_ids
+                .retain(|_, seen| seen.elapsed() < negative_ttl);
+            if self.unknown_key_ids.len() >= MAX_UNKNOWN_KEY_IDS {
+                return;
+            }
+        }
+        self.unknown_key_ids
+            .insert(key_id.to_owned(), Instant::now());
+    }
+
     async fn get_or_update_jwk_cache<F: FetchAuthRules>(
         self: &Arc<Self>,
         ctx: &RequestContext,
         client: &reqwest_middleware::ClientWithMiddleware,
         endpoint: EndpointId,
         fetch: &F,
+        stale_ttl: Duration,
     ) -> Result<Arc<JwkCacheEntry>, JwtError> {
         let now = Instant::now();
         let guard = self.cached.load_full();
@@ -290,7 +386,9 @@ async fn get_or_update_jwk_cache<F: FetchAuthRules>(
         let Some(cached) = guard else {
             let _paused = ctx.latency_timer_pause(crate::metrics::Waiting::Compute);
             let permit = self.acquire_permit().await;
-            return self.renew_jwks(permit, ctx, client, endpoint, fetch).await;
+            return self
+                .renew_jwks(permit, ctx, client, endpoint, fetch, stale_ttl)
+                .await;
         };
 
         let last_update = now.duration_since(cached.last_retrieved);
@@ -301,7 +399,9 @@ async fn get_or_update_jwk_cache<F: FetchAuthRules>(
             let permit = self.acquire_permit().await;
 
             // it's been too long since we checked the keys. wait for them to update.
-            return self.renew_jwks(permit, ctx, client, endpoint, fetch).await;
+            return self
+                .renew_jwks(permit, ctx, client, endpoint, fetch, stale_ttl)
+                .await;
         }
 
         // every 5 minutes we should spawn a job to eagerly update the token.
@@ -315,7 +415,7 @@ async fn get_or_update_jwk_cache<F: FetchAuthRules>(
                 let ctx = ctx.clone();
                 tokio::spawn(async move {
                     if let Err(e) = entry
-                        .renew_jwks(permit, &ctx, &client, endpoint, &fetch)
+                        .renew_jwks(permit, &ctx, &client, endpoint, &fetch, stale_ttl)
                         .await
                     {
                         tracing::warn!(error=?e, "could not fetch JWKs in background job");
@@ -337,6 +437,8 @@ async fn check_jwt<F: FetchAuthRules>(
         endpoint: EndpointId,
         role_name: &RoleName,
         fetch: &F,
+        negative_ttl: Duration,
+        stale_ttl: Duration,
     ) -> Result<ComputeCredentialKeys, JwtError> {
         // JWT compact form is defined to be
         // <B64(Header)> || . || <B64(Payload)> || . || <B64(Signature)>
@@ -364,25 +466,43 @@ async fn check_jwt<F: FetchAuthRules>(
         let kid = header.key_id.ok_or(JwtError::MissingKeyId)?;
 
         let mut guard = self
-            .get_or_update_jwk_cache(ctx, client, endpoint.clone(), fetch)
+            .get_or_update_jwk_cache(ctx, client, endpoint.clone(), fetch, stale_ttl)
             .await?;
 
         // get the key from the JWKs if possible. If not, wait for the keys to update.
-        let (jwk, expected_audience) = loop {
+        // A renewal that could not refetch returns stale keys, so only renew once.
+        let mut renewed = false;
+        let (jwk, expected_audience, retrieved) = loop {
             match guard.find_jwk_and_audience(&kid, role_name) {
                 Some(jwk) => break jwk,
-                None if guard.last_retrieved.elapsed() > MIN_RENEW => {
+                // we have recently looked for this key and did not find it.
+                None if self.is_unknown_key_id(&kid, negative_ttl) => {
+                    return Err(JwtError::JwkNotFound);
+                }
+                None if !renewed && guard.last_retrieved.elapsed() > MIN_RENEW => {
                     let _paused = ctx.latency_timer_pause(crate::metrics::Waiting::Compute);
 
+                    renewed = true;
                     let permit = self.acquire_permit().await;
                     guard = self
-                        .renew_jwks(permit, ctx, client, endpoint.clone(), fetch)
+                        .renew_jwks(permit, ctx, client, endpoint.clone(), fetch, stale_ttl)
                         .await?;
                 }
-                _ => return Err(JwtError::JwkNotFound),
+                _ => {
+                    self.remember_unknown_key_id(&kid, negative_ttl);
+                    return Err(JwtError::JwkNotFound);
+                }
             }
         };
 
+        if retrieved.elapsed() > MAX_RENEW {
+            Metrics::get().proxy.jwks_stale_served_total.inc();
+            tracing::info!(
+                age = ?retrieved.elapsed(),
+                "checking JWT with stale JWKs, the identity provider is unreachable",
+            );
+        }
+
         if !jwk.is_supported(&header.algorithm) {
             return Err(JwtError::SignatureAlgorithmNotSupported);
         }
@@ -454,13 +574,46 @@ pub(crate) async fn check_jwt<F: FetchAuthRules>(
         });
 
         entry
-            .check_jwt(ctx, jwt, &self.client, endpoint, role_name, fetch)
+            .check_jwt(
+                ctx,
+                jwt,
+                &self.client,
+                endpoint,
+                role_name,
+                fetch,
+                self.negative_ttl,
+                self.stale_ttl,
+            )
             .await
     }
+
+    /// Drop the cached JWKS for every role of `endpoint`, so the next JWT check refetches them.
+    /// Returns the number of entries removed.
+    pub fn invalidate_endpoint(&self, endpoint: &EndpointId) -> usize {
+        let before = self.map.len();
+        self.map.retain(|(ep, _), _| ep != endpoint);
+        before.saturating_sub(self.map.len())
+    }
+
+    /// Drop every cached JWKS. Returns the number of entries removed.
+    pub fn invalidate_all(&self) -> usize {
+        let before = self.map.len();
+        self.map.clear();
+        before
+    }
 }
 
 impl Default for JwkCache {
     fn default() -> Self {
+        Self::new(DEFAULT_JWKS_NEGATIVE_CACHE_TTL, DEFAULT_JWKS_STALE_TTL)
+    }
+}
+
+impl JwkCache {
+    /// `negative_ttl` is how long a key ID that was not found in the JWKS is
+    /// rejected without refetching the JWKS. `stale_ttl` is how long past its usual
+    /// refetch a JWKS keeps being used when it cannot be refetched.
+    pub fn new(negative_ttl: Duration, stale_ttl: Duration) -> Self {
         let client = Client::builder()
             .user_agent(JWKS_USER_AGENT)
             .redirect(redirect::Policy::none())
@@ -480,6 +633,8 @@ fn default() -> Self {
         JwkCache {
             client,
             map: ClashMap::default(),
+            negative_ttl,
+            stale_ttl,
         }
     }
 }
@@ -1085,6 +1240,27 @@ async fn check_jwt_happy_path() {
         }
     }
 
+    #[test]
+    fn invalidate_cache_entries() {
+        let jwk_cache = JwkCache::default();
+        let ep1 = EndpointId::from("ep1");
+        let ep2 = EndpointId::from("ep2");
+        for ep in [&ep1, &ep2] {
+            for role in ["anonymous", "authenticated"] {
+                jwk_cache
+                    .map
+                    .insert((ep.clone(), RoleName::from(role)), Arc::default());
+            }
+        }
+
+        assert_eq!(jwk_cache.invalidate_endpoint(&ep1), 2);
+        assert_eq!(jwk_cache.invalidate_endpoint(&ep1), 0);
+        assert!(jwk_cache.map.iter().all(|e| e.key().0 == ep2));
+
+        assert_eq!(jwk_cache.invalidate_all(), 2);
+        assert!(jwk_cache.map.is_empty());
+    }
+
     /// AWS Cognito escapes the `/` in the URL.
     #[tokio::test]
     async fn check_jwt_regression_cognito_issuer() {
@@ -1221,6 +1397,181 @@ async fn check_jwt_unknown_role() {
         );
     }
 
+    #[tokio::test]
+    async fn check_jwt_unknown_key_id_negative_cache() {
+        let (_, jwk1) = new_rsa_jwk(RS1, "1".into());
+        let (key2, jwk2) = new_rsa_jwk(RS2, "2".into());
+        let jwt = new_rsa_jwt("2".into(), key2);
+
+        let jwks = Arc::new(std::sync::Mutex::new(jose_jwk::JwkSet { keys: vec![jwk1] }));
+        let fetches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let jwks_addr = {
+            let jwks = Arc::clone(&jwks);
+            let fetches = Arc::clone(&fetches);
+            jwks_server(move |path| match path {
+                "/" => {
+                    fetches.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    Some(serde_json::to_vec(&*jwks.lock().unwrap()).unwrap())
+                }
+                _ => None,
+            })
+            .await
+        };
+        let fetch_count = || fetches.load(std::sync::atomic::Ordering::Relaxed);
+
+        let role = RoleName::from("authenticated");
+        let rules = vec![AuthRule {
+            id: String::new(),
+            jwks_url: format!("http://{jwks_addr}/").parse().unwrap(),
+            audience: None,
+            role_names: vec![RoleNameInt::from(&role)],
+        }];
+
+        let fetch = Fetch(rules);
+        let jwk_cache = JwkCache::new(Duration::from_secs(600), DEFAULT_JWKS_STALE_TTL);
+        let ep = EndpointId::from("ep");
+        let ctx = RequestContext::test();
+
+        // the key is not published yet
+        let err = jwk_cache
+            .check_jwt(&ctx, ep.clone(), &role, &fetch, &jwt)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, JwtError::JwkNotFound), "got {err:?}");
+        assert_eq!(fetch_count(), 1);
+
+        // pretend the cached keys are old enough to be renewed.
+        let entry = Arc::clone(&*jwk_cache.map.get(&(ep.clone(), role.clone())).unwrap());
+        entry.cached.store(Some(Arc::new(JwkCacheEntry {
+            last_retrieved: Instant::now() - AUTO_RENEW - Duration::from_secs(1),
+            key_sets: ahash::HashMap::default(),
+        })));
+
+        // hold the renewal permit: a lookup that wants to refetch would wait for it.
+        let permit = entry.acquire_permit().await;
+
+        // the unknown key id is negatively cached, so we don't refetch.
+        let err = tokio::time::timeout(
+            Duration::from_secs(5),
+            jwk_cache.check_jwt(&ctx, ep.clone(), &role, &fetch, &jwt),
+        )
+        .await
+        .expect("negatively cached key id should not wait for a JWKS renewal")
+        .unwrap_err();
+        assert!(matches!(err, JwtError::JwkNotFound), "got {err:?}");
+        assert_eq!(fetch_count(), 1);
+
+        // once a renewal sees the newly published key, it is accepted.
+        jwks.lock().unwrap().keys.push(jwk2);
+        entry
+            .renew_jwks(
+                permit,
+                &ctx,
+                &jwk_cache.client,
+                ep.clone(),
+                &fetch,
+                jwk_cache.stale_ttl,
+            )
+            .await
+            .unwrap();
+        assert_eq!(fetch_count(), 2);
+
+        jwk_cache
+            .check_jwt(&ctx, ep, &role, &fetch, &jwt)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_jwt_stale_jwks() {
+        let (key, jwk) = new_rsa_jwk(RS1, "1".into());
+        let jwt = new_rsa_jwt("1".into(), key);
+
+        let jwks = jose_jwk::JwkSet { keys: vec![jwk] };
+        let available = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let fetches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let jwks_addr = {
+            let available = Arc::clone(&available);
+            let fetches = Arc::clone(&fetches);
+            jwks_server(move |path| match path {
+                "/" => {
+                    fetches.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    available
+                        .load(std::sync::atomic::Ordering::Relaxed)
+                        .then(|| serde_json::to_vec(&jwks).unwrap())
+                }
+                _ => None,
+            })
+            .await
+        };
+        let fetch_count = || fetches.load(std::sync::atomic::Ordering::Relaxed);
+
+        let role = RoleName::from("authenticated");
+        let rules = vec![AuthRule {
+            id: "rule".to_owned(),
+            jwks_url: format!("http://{jwks_addr}/").parse().unwrap(),
+            audience: None,
+            role_names: vec![RoleNameInt::from(&role)],
+        }];
+
+        let fetch = Fetch(rules);
+        let jwk_cache = JwkCache::default();
+        let ep = EndpointId::from("ep");
+        let ctx = RequestContext::test();
+
+        jwk_cache
+            .check_jwt(&ctx, ep.clone(), &role, &fetch, &jwt)
+            .await
+            .unwrap();
+        assert_eq!(fetch_count(), 1);
+
+        // the identity provider goes down, and the keys are due to be refetched.
+        available.store(false, std::sync::atomic::Ordering::Relaxed);
+        let entry = Arc::clone(&*jwk_cache.map.get(&(ep.clone(), role.clone())).unwrap());
+        let age_keys = |age: Duration| {
+            let cached = entry.cached.load_full().unwrap();
+            let retrieved = Instant::now() - age;
+            let key_set = &cached.key_sets["rule"];
+            let key_sets = [(
+                "rule".to_owned(),
+                KeySet {
+                    jwks: key_set.jwks.clone(),
+                    audience: None,
+                    role_names: key_set.role_names.clone(),
+                    retrieved,
+                },
+            )];
+            entry.cached.store(Some(Arc::new(JwkCacheEntry {
+                last_retrieved: retrieved,
+                key_sets: key_sets.into_iter().collect(),
+            })));
+        };
+        age_keys(MAX_RENEW + Duration::from_secs(1));
+
+        // the stale keys are still used.
+        jwk_cache
+            .check_jwt(&ctx, ep.clone(), &role, &fetch, &jwt)
+            .await
+            .unwrap();
+        assert_eq!(fetch_count(), 2);
+
+        // and the identity provider is not asked again right away.
+        jwk_cache
+            .check_jwt(&ctx, ep.clone(), &role, &fetch, &jwt)
+            .await
+            .unwrap();
+        assert_eq!(fetch_count(), 2);
+
+        // until they are too old.
+        age_keys(MAX_RENEW + DEFAULT_JWKS_STALE_TTL);
+        let err = jwk_cache
+            .check_jwt(&ctx, ep, &role, &fetch, &jwt)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, JwtError::JwkNotFound), "got {err:?}");
+        assert_eq!(fetch_count(), 3);
+    }
+
     #[tokio::test]
     async fn check_jwt_invalid_claims() {
         let (key, jwk) = new_ec_jwk("1".into());
@@ -1,6 +1,7 @@
 use std::net::SocketAddr;
 
 use arc_swap::ArcSwapOption;
+use camino::Utf8PathBuf;
 use postgres_client::config::SslMode;
 use tokio::sync::Semaphore;
 
@@ -20,10 +21,21 @@ pub struct LocalBackend {
     pub(crate) initialize: Semaphore,
     pub(crate) compute_ctl: ComputeCtlApi,
     pub(crate) node_info: NodeInfo,
+    /// Statements run on every new postgres connection, after `auth.init()`.
+    /// They run one at a time, in order, and the first failure discards the connection.
+    pub(crate) session_setup: Vec<String>,
+    /// The extension is expected to be installed already, so skip install and grant.
+    pub(crate) skip_extension_install: bool,
 }
 
 impl LocalBackend {
-    pub fn new(postgres_addr: SocketAddr, compute_ctl: ApiUrl) -> Self {
+    pub fn new(
+        postgres_addr: SocketAddr,
+        postgres_socket: Option<Utf8PathBuf>,
+        compute_ctl: ApiUrl,
+        session_setup: Vec<String>,
+        skip_extension_install: bool,
+    ) -> Self {
         LocalBackend {
             initialize: Semaphore::new(1),
             compute_ctl: ComputeCtlApi {
@@ -35,7 +47,10 @@ pub fn new(postgres_addr: SocketAddr, compute_ctl: ApiUrl) -> Self {
                     host: postgres_addr.ip().to_string().into(),
                     port: postgres_addr.port(),
                     ssl_mode: SslMode::Disable,
+                    unix_socket: postgres_socket,
+                    tls_server_name: None,
                 },
+                read_endpoints: None,
                 // TODO(conrad): make this better reflect compute info rather than endpoint info.
                 aux: MetricsAuxInfo {
                     endpoint_id: EndpointIdTag::get_interner().get_or_intern("local"),
@@ -45,6 +60,8 @@ pub fn new(postgres_addr: SocketAddr, compute_ctl: ApiUrl) -> Self {
                     cold_start_info: ColdStartInfo::WarmCached,
                 },
             },
+            session_setup,
+            skip_extension_install,
         }
     }
 }
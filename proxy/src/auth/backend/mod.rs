@@ -6,8 +6,8 @@
 
 use std::sync::Arc;
 
-pub use console_redirect::ConsoleRedirectBackend;
 pub(crate) use console_redirect::ConsoleRedirectError;
+pub use console_redirect::{ConsoleRedirectBackend, RedirectUriTemplate};
 use local::LocalBackend;
 use postgres_client::config::AuthKeys;
 use serde::{Deserialize, Serialize};
@@ -31,7 +31,7 @@
 use crate::proxy::wake_compute::WakeComputeBackend;
 use crate::rate_limiter::EndpointRateLimiter;
 use crate::stream::Stream;
-use crate::types::{EndpointCacheKey, EndpointId, RoleName};
+use crate::types::{DbName, EndpointCacheKey, EndpointId, RoleName};
 use crate::{scram, stream};
 
 /// Alternative to [`std::borrow::Cow`] but doesn't need `T: ToOwned` as we don't need that functionality
@@ -70,10 +70,13 @@ impl std::fmt::Display for Backend<'_, ()> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ControlPlane(api, ()) => match &**api {
-                ControlPlaneClient::ProxyV1(endpoint) => fmt
-                    .debug_tuple("ControlPlane::ProxyV1")
-                    .field(&endpoint.url())
-                    .finish(),
+                ControlPlaneClient::ProxyV1(endpoint) => {
+                    let mut tuple = fmt.debug_tuple("ControlPlane::ProxyV1");
+                    for url in endpoint.urls() {
+                        tuple.field(&url);
+                    }
+                    tuple.finish()
+                }
                 #[cfg(any(test, feature = "testing"))]
                 ControlPlaneClient::PostgresMock(endpoint) => {
                     let url = endpoint.url();
@@ -91,6 +94,15 @@ fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                             .finish(),
                     }
                 }
+                ControlPlaneClient::Failover(members) => {
+                    let mut tuple = fmt.debug_tuple("ControlPlane::Failover");
+                    for member in members.iter() {
+                        let client = ControlPlaneClient::from(member.clone());
+                        let backend = Backend::ControlPlane(MaybeOwned::Owned(client), ());
+                        tuple.field(&format_args!("{backend}"));
+                    }
+                    tuple.finish()
+                }
                 #[cfg(test)]
                 ControlPlaneClient::Test(_) => fmt.debug_tuple("ControlPlane::Test").finish(),
             },
@@ -201,6 +213,7 @@ async fn auth_quirks(
     ctx: &RequestContext,
     api: &impl control_plane::ControlPlaneApi,
     user_info: ComputeUserInfoMaybeEndpoint,
+    dbname: &DbName,
     client: &mut stream::PqStream<Stream<impl AsyncRead + AsyncWrite + Unpin>>,
     allow_cleartext: bool,
     config: &'static AuthenticationConfig,
@@ -211,6 +224,12 @@ async fn auth_quirks(
     // We now expect to see a very specific payload in the place of password.
     let (info, unauthenticated_password) = match user_info.try_into() {
         Err(info) => {
+            // Websocket connections, the only ones that allow cleartext, are already encrypted
+            // by the HTTP layer. SCRAM below does not expose the password, so it is always fine.
+            let encrypted = allow_cleartext || matches!(client.get_ref(), Stream::Tls { .. });
+            if config.cleartext_password_requires_tls && !encrypted {
+                return Err(auth::AuthError::CleartextPasswordRequiresTls);
+            }
             let (info, password) =
                 hacks::password_hack_no_authentication(ctx, info, client).await?;
             ctx.set_endpoint_id(info.endpoint.clone());
@@ -230,6 +249,7 @@ async fn auth_quirks(
         config.ip_allowlist_check_enabled,
         config.is_vpc_acccess_proxy,
     )?;
+    access_controls.check_database(dbname)?;
 
     access_controls.connection_attempt_rate_limit(ctx, &info.endpoint, &endpoint_rate_limiter)?;
 
@@ -318,6 +338,7 @@ pub(crate) fn get_user(&self) -> &str {
     pub(crate) async fn authenticate(
         self,
         ctx: &RequestContext,
+        dbname: &DbName,
         client: &mut stream::PqStream<Stream<impl AsyncRead + AsyncWrite + Unpin>>,
         allow_cleartext: bool,
         config: &'static AuthenticationConfig,
@@ -335,6 +356,7 @@ pub(crate) async fn authenticate(
                     ctx,
                     &*api,
                     user_info.clone(),
+                    dbname,
                     client,
                     allow_cleartext,
                     config,
@@ -347,8 +369,7 @@ pub(crate) async fn authenticate(
                         // The password could have been changed, so we invalidate the cache.
                         // We should only invalidate the cache if the TTL might have expired.
                         if e.is_password_failed() {
-                            #[allow(irrefutable_let_patterns)]
-                            if let ControlPlaneClient::ProxyV1(api) = &*api {
+                            if let Some(api) = api.proxy_v1() {
                                 if let Some(ep) = &user_info.endpoint_id {
                                     api.caches
                                         .project_info
@@ -398,6 +419,7 @@ pub(crate) async fn get_endpoint_access_control(
             Self::Local(_) => Ok(EndpointAccessControl {
                 allowed_ips: Arc::new(vec![]),
                 allowed_vpce: Arc::new(vec![]),
+                allowed_databases: Arc::new(vec![]),
                 flags: AccessBlockerFlags::default(),
                 rate_limits: EndpointRateLimitConfig::default(),
             }),
@@ -447,10 +469,12 @@ mod tests {
     use crate::scram::ServerSecret;
     use crate::scram::threadpool::ThreadPool;
     use crate::stream::{PqStream, Stream};
+    use crate::types::DbName;
 
     struct Auth {
         ips: Vec<IpPattern>,
         vpc_endpoint_ids: Vec<String>,
+        databases: Vec<DbName>,
         access_blocker_flags: AccessBlockerFlags,
         secret: AuthSecret,
     }
@@ -476,6 +500,7 @@ async fn get_endpoint_access_control(
             Ok(EndpointAccessControl {
                 allowed_ips: Arc::new(self.ips.clone()),
                 allowed_vpce: Arc::new(self.vpc_endpoint_ids.clone()),
+                allowed_databases: Arc::new(self.databases.clone()),
                 flags: self.access_blocker_flags,
                 rate_limits: EndpointRateLimitConfig::default(),
             })
@@ -508,6 +533,21 @@ async fn wake_compute(
         is_auth_broker: false,
         accept_jwts: false,
         console_redirect_confirmation_timeout: std::time::Duration::from_secs(5),
+        audit_log: false,
+        cleartext_password_requires_tls: false,
+    });
+
+    static CONFIG_REQUIRE_TLS: Lazy<AuthenticationConfig> = Lazy::new(|| AuthenticationConfig {
+        jwks_cache: JwkCache::default(),
+        thread_pool: ThreadPool::new(1),
+        scram_protocol_timeout: std::time::Duration::from_secs(5),
+        ip_allowlist_check_enabled: true,
+        is_vpc_acccess_proxy: false,
+        is_auth_broker: false,
+        accept_jwts: false,
+        console_redirect_confirmation_timeout: std::time::Duration::from_secs(5),
+        audit_log: false,
+        cleartext_password_requires_tls: true,
     });
 
     async fn read_message(r: &mut (impl AsyncRead + Unpin), b: &mut BytesMut) -> PgMessage {
@@ -528,6 +568,7 @@ async fn auth_quirks_scram() {
         let api = Auth {
             ips: vec![],
             vpc_endpoint_ids: vec![],
+            databases: vec![],
             access_blocker_flags: AccessBlockerFlags::default(),
             secret: AuthSecret::Scram(ServerSecret::build("my-secret-password").await.unwrap()),
         };
@@ -587,6 +628,7 @@ async fn auth_quirks_scram() {
             &ctx,
             &api,
             user_info,
+            &"neondb".into(),
             &mut stream,
             false,
             &CONFIG,
@@ -610,6 +652,7 @@ async fn auth_quirks_cleartext() {
         let api = Auth {
             ips: vec![],
             vpc_endpoint_ids: vec![],
+            databases: vec![],
             access_blocker_flags: AccessBlockerFlags::default(),
             secret: AuthSecret::Scram(ServerSecret::build("my-secret-password").await.unwrap()),
         };
@@ -644,6 +687,7 @@ async fn auth_quirks_cleartext() {
             &ctx,
             &api,
             user_info,
+            &"neondb".into(),
             &mut stream,
             true,
             &CONFIG,
@@ -664,6 +708,7 @@ async fn auth_quirks_password_hack() {
         let api = Auth {
             ips: vec![],
             vpc_endpoint_ids: vec![],
+            databases: vec![],
             access_blocker_flags: AccessBlockerFlags::default(),
             secret: AuthSecret::Scram(ServerSecret::build("my-secret-password").await.unwrap()),
         };
@@ -699,6 +744,7 @@ async fn auth_quirks_password_hack() {
             &ctx,
             &api,
             user_info,
+            &"neondb".into(),
             &mut stream,
             true,
             &CONFIG,
@@ -711,4 +757,114 @@ async fn auth_quirks_password_hack() {
 
         handle.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn auth_quirks_password_hack_requires_tls() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut stream = PqStream::new_skip_handshake(Stream::from_raw(server));
+
+        let ctx = RequestContext::test();
+        let api = Auth {
+            ips: vec![],
+            vpc_endpoint_ids: vec![],
+            databases: vec![],
+            access_blocker_flags: AccessBlockerFlags::default(),
+            secret: AuthSecret::Scram(ServerSecret::build("my-secret-password").await.unwrap()),
+        };
+
+        let user_info = ComputeUserInfoMaybeEndpoint {
+            user: "conrad".into(),
+            endpoint_id: None,
+            options: NeonOptions::default(),
+        };
+
+        let endpoint_rate_limiter = Arc::new(EndpointRateLimiter::new_with_shards(
+            EndpointRateLimiter::DEFAULT,
+            64,
+        ));
+
+        let res = auth_quirks(
+            &ctx,
+            &api,
+            user_info,
+            &"neondb".into(),
+            &mut stream,
+            false,
+            &CONFIG_REQUIRE_TLS,
+            endpoint_rate_limiter,
+        )
+        .await;
+
+        assert!(matches!(
+            res,
+            Err(crate::auth::AuthError::CleartextPasswordRequiresTls)
+        ));
+    }
+
+    #[tokio::test]
+    async fn auth_quirks_database_not_allowed() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut stream = PqStream::new_skip_handshake(Stream::from_raw(server));
+
+        let ctx = RequestContext::test();
+        let api = Auth {
+            ips: vec![],
+            vpc_endpoint_ids: vec![],
+            databases: vec!["neondb".into()],
+            access_blocker_flags: AccessBlockerFlags::default(),
+            secret: AuthSecret::Scram(ServerSecret::build("my-secret-password").await.unwrap()),
+        };
+
+        let user_info = ComputeUserInfoMaybeEndpoint {
+            user: "conrad".into(),
+            endpoint_id: Some("endpoint".into()),
+            options: NeonOptions::default(),
+        };
+
+        let endpoint_rate_limiter = Arc::new(EndpointRateLimiter::new_with_shards(
+            EndpointRateLimiter::DEFAULT,
+            64,
+        ));
+
+        // rejected before the client is asked for a password.
+        let res = auth_quirks(
+            &ctx,
+            &api,
+            user_info,
+            &"postgres".into(),
+            &mut stream,
+            false,
+            &CONFIG,
+            endpoint_rate_limiter,
+        )
+        .await;
+
+        assert!(matches!(
+            res,
+            Err(crate::auth::AuthError::DatabaseNotAllowed(db)) if db == "postgres"
+        ));
+    }
+
+    #[test]
+    fn database_allowlist() {
+        let mut access_control = EndpointAccessControl {
+            allowed_ips: Arc::new(vec![]),
+            allowed_vpce: Arc::new(vec![]),
+            allowed_databases: Arc::new(vec![]),
+            flags: AccessBlockerFlags::default(),
+            rate_limits: EndpointRateLimitConfig::default(),
+        };
+        access_control.check_database(&"any".into()).unwrap();
+
+        access_control.allowed_databases = Arc::new(vec!["neondb".into()]);
+        access_control.check_database(&"neondb".into()).unwrap();
+        let err = access_control
+            .check_database(&"postgres".into())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::auth::AuthError::DatabaseNotAllowed(ref db) if db.as_str() == "postgres"
+        ));
+        assert!(err.to_string().contains("'postgres'"));
+    }
 }
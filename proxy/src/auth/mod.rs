@@ -1,5 +1,6 @@
 //! Client authentication mechanisms.
 
+pub(crate) mod audit;
 pub mod backend;
 pub use backend::Backend;
 
@@ -24,6 +25,7 @@
 use crate::auth::backend::jwt::JwtError;
 use crate::control_plane;
 use crate::error::{ReportableError, UserFacingError};
+use crate::types::DbName;
 
 /// Convenience wrapper for the authentication error.
 pub(crate) type Result<T> = std::result::Result<T, AuthError>;
@@ -84,6 +86,9 @@ pub(crate) enum AuthError {
     )]
     VpcEndpointIdNotAllowed(String),
 
+    #[error("Database '{0}' is not allowed to be accessed through this endpoint.")]
+    DatabaseNotAllowed(DbName),
+
     #[error("Too many connections to this endpoint. Please try again later.")]
     TooManyConnections,
 
@@ -95,6 +100,12 @@ pub(crate) enum AuthError {
 
     #[error(transparent)]
     Jwt(#[from] JwtError),
+
+    #[error(
+        "Cleartext password authentication requires an encrypted connection. \
+        Please connect with SSL (sslmode=require)."
+    )]
+    CleartextPasswordRequiresTls,
 }
 
 impl AuthError {
@@ -146,10 +157,12 @@ fn to_string_client(&self) -> String {
             Self::IpAddressNotAllowed(_) => self.to_string(),
             Self::NetworkNotAllowed => self.to_string(),
             Self::VpcEndpointIdNotAllowed(_) => self.to_string(),
+            Self::DatabaseNotAllowed(_) => self.to_string(),
             Self::TooManyConnections => self.to_string(),
             Self::UserTimeout(_) => self.to_string(),
             Self::ConfirmationTimeout(_) => self.to_string(),
             Self::Jwt(_) => self.to_string(),
+            Self::CleartextPasswordRequiresTls => self.to_string(),
         }
     }
 }
@@ -169,10 +182,12 @@ fn get_error_kind(&self) -> crate::error::ErrorKind {
             Self::IpAddressNotAllowed(_) => crate::error::ErrorKind::User,
             Self::NetworkNotAllowed => crate::error::ErrorKind::User,
             Self::VpcEndpointIdNotAllowed(_) => crate::error::ErrorKind::User,
+            Self::DatabaseNotAllowed(_) => crate::error::ErrorKind::User,
             Self::TooManyConnections => crate::error::ErrorKind::RateLimit,
             Self::UserTimeout(_) => crate::error::ErrorKind::User,
             Self::ConfirmationTimeout(_) => crate::error::ErrorKind::User,
             Self::Jwt(_) => crate::error::ErrorKind::User,
+            Self::CleartextPasswordRequiresTls => crate::error::ErrorKind::User,
         }
     }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::pin::pin;
 use std::str::FromStr;
@@ -28,14 +29,18 @@
 };
 use crate::control_plane::locks::ApiLocks;
 use crate::control_plane::messages::{EndpointJwksResponse, JwksSettings};
+use crate::control_plane::read_endpoints::ReadEndpointPolicy;
+use crate::error::ClientErrorVerbosity;
 use crate::ext::TaskExt;
-use crate::http::health_server::AppMetrics;
+use crate::http::health_server::{AppMetrics, VersionInfo};
 use crate::intern::RoleNameInt;
 use crate::metrics::{Metrics, ThreadPoolMetrics};
+use crate::proxy::wake_compute::RecentWakes;
 use crate::rate_limiter::{EndpointRateLimiter, LeakyBucketConfig, RateBucketInfo};
 use crate::scram::threadpool::ThreadPool;
 use crate::serverless::cancel_set::CancelSet;
-use crate::serverless::{self, GlobalConnPoolOptions};
+use crate::serverless::circuit_breaker::ComputeCircuitBreaker;
+use crate::serverless::{self, GlobalConnPoolOptions, PoolBudget, PoolReusePolicy};
 use crate::tls::client_config::compute_client_config_with_root_certs;
 use crate::types::RoleName;
 use crate::url::ApiUrl;
@@ -73,6 +78,9 @@ struct LocalProxyCliArgs {
     /// Address of the postgres server
     #[clap(long, default_value = "127.0.0.1:5432")]
     postgres: SocketAddr,
+    /// Path of the postgres server's Unix domain socket. When set, it is used instead of `--postgres`.
+    #[clap(long)]
+    postgres_socket: Option<Utf8PathBuf>,
     /// Address of the internal compute-ctl api service
     #[clap(long, default_value = "http://127.0.0.1:3081/")]
     compute_ctl: ApiUrl,
@@ -82,6 +90,14 @@ struct LocalProxyCliArgs {
     /// Path of the local proxy PID file
     #[clap(long, default_value = "./local_proxy.pid")]
     pid_path: Utf8PathBuf,
+    /// Statement to run on every new postgres connection, after `auth.init()`.
+    /// Can be given multiple times, the statements then run in the order given.
+    #[clap(long)]
+    session_setup: Vec<String>,
+    /// Skip installing `pg_session_jwt` and granting usage on it through compute_ctl,
+    /// for computes where the extension is already installed in the image.
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    skip_extension_install: bool,
 }
 
 #[derive(clap::Args, Clone, Copy, Debug)]
@@ -187,6 +203,7 @@ pub async fn run() -> anyhow::Result<()> {
         refresh_config_notify,
     ));
 
+    let conn_pools = serverless::ConnPools::new(&config.http_config);
     maintenance_tasks.spawn(crate::http::health_server::task_main(
         metrics_listener,
         AppMetrics {
@@ -194,6 +211,12 @@ pub async fn run() -> anyhow::Result<()> {
             neon_metrics,
             proxy: crate::metrics::Metrics::get(),
         },
+        VersionInfo {
+            git_version: GIT_VERSION,
+            auth_backend: auth_backend.to_string(),
+            wss: true,
+            metric_collection: config.metric_collection.is_some(),
+        },
     ));
 
     let task = serverless::task_main(
@@ -203,6 +226,7 @@ pub async fn run() -> anyhow::Result<()> {
         shutdown.clone(),
         Arc::new(CancellationHandler::new(&config.connect_to_compute)),
         endpoint_rate_limiter,
+        conn_pools,
     );
 
     match futures::future::select(pin!(maintenance_tasks.join_next()), pin!(task)).await {
@@ -240,6 +264,22 @@ fn build_config(args: &LocalProxyCliArgs) -> anyhow::Result<&'static ProxyConfig
         &Metrics::get().proxy.connect_compute_lock,
     );
 
+    // local_proxy only serves a single endpoint, so the per-endpoint lock is left disabled.
+    let config::ConcurrencyLockOptions {
+        shards,
+        limiter,
+        epoch,
+        timeout,
+    } = config::ConcurrencyLockOptions::DEFAULT_OPTIONS_ENDPOINT_CONNECT_COMPUTE_LOCK.parse()?;
+    let endpoint_connect_compute_locks = ApiLocks::new(
+        "endpoint_connect_compute_lock",
+        limiter,
+        shards,
+        timeout,
+        epoch,
+        &Metrics::get().proxy.endpoint_connect_compute_lock,
+    );
+
     let http_config = HttpConfig {
         accept_websockets: false,
         pool_options: GlobalConnPoolOptions {
@@ -249,18 +289,39 @@ fn build_config(args: &LocalProxyCliArgs) -> anyhow::Result<&'static ProxyConfig
             opt_in: false,
 
             max_conns_per_endpoint: args.sql_over_http.sql_over_http_pool_max_total_conns,
+            min_conns_per_endpoint: 0,
             max_total_conns: args.sql_over_http.sql_over_http_pool_max_total_conns,
+            reuse_policy: PoolReusePolicy::default(),
+            statement_cache_size: 0,
         },
         cancel_set: CancelSet::new(args.sql_over_http.sql_over_http_cancel_set_shards),
         client_conn_threshold: args.sql_over_http.sql_over_http_client_conn_threshold,
         max_request_size_bytes: args.sql_over_http.sql_over_http_max_request_size_bytes,
         max_response_size_bytes: args.sql_over_http.sql_over_http_max_response_size_bytes,
+        pool_reset_queries: Vec::new(),
+        pool_budget: PoolBudget::new(None),
+        compute_circuit_breaker: ComputeCircuitBreaker::new(None),
+        local_proxy_compression: None,
+        sticky_sessions: None,
+        listen: None,
+        row_streaming: None,
+        statement_filter: None,
+        named_pools: HashMap::new(),
+        recent_connections: 0,
+        shared_pool_dbnames: Vec::new(),
+        expose_backend_pid: false,
     };
 
     let compute_config = ComputeConfig {
         retry: RetryConfig::parse(RetryConfig::CONNECT_TO_COMPUTE_DEFAULT_VALUES)?,
-        tls: Arc::new(compute_client_config_with_root_certs()?),
+        tls: Arc::new(compute_client_config_with_root_certs(None)?),
         timeout: Duration::from_secs(2),
+        startup_timeout: None,
+        slow_connect_threshold: None,
+        statement_timeout: None,
+        max_query_duration: None,
+        read_endpoint_policy: ReadEndpointPolicy::default(),
+        recent_wakes: RecentWakes::new(None),
     };
 
     Ok(Box::leak(Box::new(ProxyConfig {
@@ -276,20 +337,34 @@ fn build_config(args: &LocalProxyCliArgs) -> anyhow::Result<&'static ProxyConfig
             is_auth_broker: false,
             accept_jwts: true,
             console_redirect_confirmation_timeout: Duration::ZERO,
+            audit_log: false,
+            cleartext_password_requires_tls: false,
         },
         proxy_protocol_v2: config::ProxyProtocolV2::Rejected,
+        client_tcp_keepalive: None,
         handshake_timeout: Duration::from_secs(10),
+        client_idle_timeout: None,
+        close_idle_sessions_on_shutdown: false,
+        startup_params_limits: config::StartupParamsLimits::DEFAULT,
+        client_connection_limit: None,
+        client_error_verbosity: ClientErrorVerbosity::Default,
         wake_compute_retry_config: RetryConfig::parse(RetryConfig::WAKE_COMPUTE_DEFAULT_VALUES)?,
         connect_compute_locks,
+        endpoint_connect_compute_locks,
         connect_to_compute: compute_config,
     })))
 }
 
 /// auth::Backend is created at proxy startup, and lives forever.
 fn build_auth_backend(args: &LocalProxyCliArgs) -> &'static auth::Backend<'static, ()> {
-    let auth_backend = crate::auth::Backend::Local(crate::auth::backend::MaybeOwned::Owned(
-        LocalBackend::new(args.postgres, args.compute_ctl.clone()),
-    ));
+    let auth_backend =
+        crate::auth::Backend::Local(crate::auth::backend::MaybeOwned::Owned(LocalBackend::new(
+            args.postgres,
+            args.postgres_socket.clone(),
+            args.compute_ctl.clone(),
+            args.session_setup.clone(),
+            args.skip_extension_install,
+        )));
 
     Box::leak(Box::new(auth_backend))
 }
@@ -25,6 +25,7 @@
 use utils::sentry_init::init_sentry;
 
 use crate::context::RequestContext;
+use crate::error::ClientErrorVerbosity;
 use crate::metrics::{Metrics, ThreadPoolMetrics};
 use crate::pglb::TlsRequired;
 use crate::pqproto::FeStartupPacket;
@@ -75,7 +76,7 @@ fn cli() -> clap::Command {
 }
 
 pub async fn run() -> anyhow::Result<()> {
-    let _logging_guard = crate::logging::init().await?;
+    let _logging_guard = crate::logging::init(None).await?;
     let _panic_hook_guard = utils::logging::replace_panic_hook_with_tracing_panic_hook();
     let _sentry_guard = init_sentry(Some(GIT_VERSION.into()), &[]);
 
@@ -97,7 +98,7 @@ pub async fn run() -> anyhow::Result<()> {
     };
 
     let compute_tls_config =
-        Arc::new(crate::tls::client_config::compute_client_config_with_root_certs()?);
+        Arc::new(crate::tls::client_config::compute_client_config_with_root_certs(None)?);
 
     // Start listening for incoming client connections
     let proxy_address: SocketAddr = args
@@ -276,7 +277,9 @@ async fn ssl_handshake<S: AsyncRead + AsyncWrite + Unpin>(
                 ?unexpected,
                 "unexpected startup packet, rejecting connection"
             );
-            Err(stream.throw_error(TlsRequired, None).await)?
+            Err(stream
+                .throw_error(TlsRequired, None, ClientErrorVerbosity::Default)
+                .await)?
         }
     }
 }
@@ -26,20 +26,30 @@
 use crate::batch::BatchQueue;
 use crate::cancellation::{CancellationHandler, CancellationProcessor};
 use crate::config::{
-    self, AuthenticationConfig, CacheOptions, ComputeConfig, HttpConfig, ProjectInfoCacheOptions,
-    ProxyConfig, ProxyProtocolV2, remote_storage_from_toml,
+    self, AuthenticationConfig, CacheOptions, ClientConnectionLimit, ComputeConfig, HttpConfig,
+    ProjectInfoCacheOptions, ProxyConfig, ProxyProtocolV2, StartupParamsLimits, TcpKeepaliveConfig,
+    named_pool_from_str, remote_storage_from_toml,
 };
 use crate::context::parquet::ParquetUploadArgs;
-use crate::http::health_server::AppMetrics;
+use crate::control_plane::read_endpoints::ReadEndpointPolicy;
+use crate::error::ClientErrorVerbosity;
+use crate::http::admin_server::ComputeWaker;
+use crate::http::health_server::{AppMetrics, VersionInfo};
 use crate::metrics::Metrics;
+use crate::proxy::wake_compute::RecentWakes;
 use crate::rate_limiter::{EndpointRateLimiter, RateBucketInfo, WakeComputeRateLimiter};
 use crate::redis::connection_with_credentials_provider::ConnectionWithCredentialsProvider;
 use crate::redis::kv_ops::RedisKVClient;
 use crate::redis::{elasticache, notifications};
 use crate::scram::threadpool::ThreadPool;
-use crate::serverless::GlobalConnPoolOptions;
 use crate::serverless::cancel_set::CancelSet;
-use crate::tls::client_config::compute_client_config_with_root_certs;
+use crate::serverless::circuit_breaker::{CircuitBreakerConfig, ComputeCircuitBreaker};
+use crate::serverless::compression::ContentEncoding;
+use crate::serverless::{
+    GlobalConnPoolOptions, ListenConfig, PoolBudget, PoolReusePolicy, RowStreamingConfig,
+    StatementFilter, StickySessionConfig,
+};
+use crate::tls::client_config::{ComputeClientCert, compute_client_config_with_root_certs};
 #[cfg(any(test, feature = "testing"))]
 use crate::url::ApiUrl;
 use crate::{auth, control_plane, http, serverless, usage_metrics};
@@ -77,13 +87,32 @@ struct ProxyCliArgs {
     /// listen for management callback connection on ip:port
     #[clap(short, long, default_value = "127.0.0.1:7000")]
     mgmt: SocketAddr,
+    /// path to the TLS key of the management listener. With mgmt-tls-key, mgmt-tls-cert and
+    /// mgmt-tls-client-ca, management connections must use TLS with a trusted client certificate.
+    #[clap(long)]
+    mgmt_tls_key: Option<PathBuf>,
+    /// path to the TLS cert of the management listener
+    #[clap(long)]
+    mgmt_tls_cert: Option<PathBuf>,
+    /// path to the CAs that sign the client certificates accepted by the management listener
+    #[clap(long)]
+    mgmt_tls_client_ca: Option<PathBuf>,
+    /// listen for admin http requests on ip:port. Uses the mgmt TLS settings, so with them,
+    /// admin requests need a trusted client certificate too. Admin routes are not served if unset.
+    #[clap(long)]
+    mgmt_http: Option<SocketAddr>,
     /// listen for incoming http connections (metrics, etc) on ip:port
     #[clap(long, default_value = "127.0.0.1:7001")]
     http: SocketAddr,
+    /// OTLP/HTTP endpoint to export traces to, e.g. `http://collector:4318/v1/traces`.
+    /// Overrides OTEL_EXPORTER_OTLP_ENDPOINT.
+    #[clap(long)]
+    otel_endpoint: Option<String>,
     /// listen for incoming wss connections on ip:port
     #[clap(long)]
     wss: Option<SocketAddr>,
-    /// redirect unauthenticated users to the given uri in case of console redirect auth
+    /// redirect unauthenticated users to the given uri in case of console redirect auth.
+    /// May contain `{endpoint}` and `{session}` placeholders, otherwise the session ID is appended.
     #[clap(short, long, default_value = "http://localhost:3000/psql_session/")]
     uri: String,
     /// cloud API endpoint for authenticating users
@@ -93,6 +122,20 @@ struct ProxyCliArgs {
         default_value = "http://localhost:3000/authenticate_proxy_request/"
     )]
     auth_endpoint: String,
+    /// more cloud API endpoints serving the same API as the auth endpoint, to spread auth
+    /// requests across. Endpoints that fail too many requests are skipped for a while.
+    /// Only used by the control-plane auth backend.
+    #[clap(long, value_delimiter = ',')]
+    balanced_auth_endpoint: Vec<String>,
+    /// weights of the auth endpoint and of each balanced auth endpoint, in order.
+    /// Endpoints without a weight get a weight of 1.
+    #[clap(long, value_delimiter = ',')]
+    auth_endpoint_weight: Vec<u32>,
+    /// cloud API endpoints to fall back to, in order, when the auth endpoint is unreachable.
+    /// Requests fail over on the first transport error, only the last endpoint retries them.
+    /// Only used by the control-plane auth backend.
+    #[clap(long, value_delimiter = ',')]
+    fallback_auth_endpoint: Vec<String>,
     /// JWT used to connect to control plane.
     #[clap(
         long,
@@ -101,6 +144,16 @@ struct ProxyCliArgs {
         env = "NEON_PROXY_TO_CONTROLPLANE_TOKEN"
     )]
     control_plane_token: Arc<str>,
+    /// timeout for establishing a connection to the auth endpoint
+    #[clap(long, default_value = "5s", value_parser = humantime::parse_duration)]
+    control_plane_connect_timeout: Duration,
+    /// timeout for a single request to the auth endpoint, excluding retries
+    #[clap(long, default_value = "5s", value_parser = humantime::parse_duration)]
+    control_plane_request_timeout: Duration,
+    /// for how long after the first attempt a failed request to the auth endpoint is retried,
+    /// with exponential backoff. Zero disables the retries.
+    #[clap(long, default_value = "5s", value_parser = humantime::parse_duration)]
+    control_plane_retry_duration: Duration,
     /// if this is not local proxy, this toggles whether we accept jwt or passwords for http
     #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
     is_auth_broker: bool,
@@ -120,9 +173,46 @@ struct ProxyCliArgs {
     /// path to directory with TLS certificates for client postgres connections
     #[clap(long)]
     certs_dir: Option<PathBuf>,
+    /// path to the TLS key the proxy authenticates with when connecting to compute
+    ///
+    /// With compute-tls-client-key and compute-tls-client-cert, computes can require TLS client
+    /// certificates and verify that connections come from the proxy.
+    #[clap(long)]
+    compute_tls_client_key: Option<PathBuf>,
+    /// path to the TLS cert the proxy authenticates with when connecting to compute
+    #[clap(long)]
+    compute_tls_client_cert: Option<PathBuf>,
     /// timeout for the TLS handshake
     #[clap(long, default_value = "15s", value_parser = humantime::parse_duration)]
     handshake_timeout: tokio::time::Duration,
+    /// close client connections that leave compute waiting for their next query for this long,
+    /// after sending them an error. Queries that run for long do not count as idle.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    client_idle_timeout: Option<tokio::time::Duration>,
+    /// on shutdown, close client connections as soon as compute is waiting on them, after sending
+    /// them an error, instead of waiting for the clients to disconnect.
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    close_idle_sessions_on_shutdown: bool,
+    /// most parameters a client can connect with, in the startup message or the connection
+    /// string of SQL over HTTP requests
+    #[clap(long, default_value_t = StartupParamsLimits::DEFAULT.max_count)]
+    max_startup_params: usize,
+    /// most bytes of startup parameter names and values, in total. Startup messages are
+    /// also limited to 10000 bytes.
+    #[clap(long, default_value_t = StartupParamsLimits::DEFAULT.max_size)]
+    max_startup_params_size: usize,
+    /// most postgres client connections handled at once, by the TCP listener. Connections above
+    /// the limit wait for up to `--client-connection-queue-timeout`, and are then closed with a
+    /// "too many connections" error (SQLSTATE 53300) before anything is read from them.
+    #[clap(long)]
+    max_client_connections: Option<usize>,
+    /// how long a connection above `--max-client-connections` waits for another one to close.
+    /// Zero rejects it right away.
+    #[clap(long, default_value = "0s", value_parser = humantime::parse_duration)]
+    client_connection_queue_timeout: tokio::time::Duration,
+    /// how much error detail is sent to clients
+    #[clap(value_enum, long, default_value_t = ClientErrorVerbosity::Default)]
+    client_error_verbosity: ClientErrorVerbosity,
     /// cache for `wake_compute` api method (use `size=0` to disable)
     #[clap(long, default_value = config::CacheOptions::CACHE_DEFAULT_OPTIONS)]
     wake_compute_cache: String,
@@ -132,13 +222,20 @@ struct ProxyCliArgs {
     /// lock for `connect_compute` api method. example: "shards=32,permits=4,epoch=10m,timeout=1s". (use `permits=0` to disable).
     #[clap(long, default_value = config::ConcurrencyLockOptions::DEFAULT_OPTIONS_CONNECT_COMPUTE_LOCK)]
     connect_compute_lock: String,
+    /// per-endpoint lock for serverless `connect_compute`, checked alongside `connect_compute_lock`. example: "shards=32,permits=4,epoch=10m,timeout=1s". (use `permits=0` to disable).
+    #[clap(long, default_value = config::ConcurrencyLockOptions::DEFAULT_OPTIONS_ENDPOINT_CONNECT_COMPUTE_LOCK)]
+    endpoint_connect_compute_lock: String,
     #[clap(flatten)]
     sql_over_http: SqlOverHttpArgs,
     /// timeout for scram authentication protocol
     #[clap(long, default_value = "15s", value_parser = humantime::parse_duration)]
     scram_protocol_timeout: tokio::time::Duration,
-    /// size of the threadpool for password hashing
-    #[clap(long, default_value_t = 4)]
+    /// size of the threadpool for password hashing.
+    ///
+    /// The pool runs its own threads next to the tokio runtime, so a pool larger than the spare
+    /// CPUs slows down everything else during an auth storm. Watch `proxy_scram_pool_queued_jobs`
+    /// and `proxy_scram_pool_job_wait_seconds` to size it.
+    #[clap(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..))]
     scram_thread_pool_size: u8,
     /// Endpoint rate limiter max number of requests per second.
     ///
@@ -191,6 +288,18 @@ struct ProxyCliArgs {
     /// how often metrics should be sent to a collection endpoint
     #[clap(long)]
     metric_collection_interval: Option<String>,
+    /// randomly shorten or lengthen each metric collection interval by up to this percentage,
+    /// and delay the first push by a random part of an interval, so that proxies deployed
+    /// together do not push at the same time
+    #[clap(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=100))]
+    metric_collection_interval_jitter_percent: u8,
+    /// refuse to start if the metric collection endpoint does not accept a push
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    require_metric_collection: bool,
+    /// how many collection intervals of metrics that failed to send are kept, to be resent
+    /// after the next successful push
+    #[clap(long, default_value_t = 60)]
+    metric_collection_unsent_buffer_size: usize,
     /// interval for backup metric collection
     #[clap(long, default_value = "10m", value_parser = humantime::parse_duration)]
     metric_backup_collection_interval: std::time::Duration,
@@ -207,10 +316,26 @@ struct ProxyCliArgs {
     /// Whether to retry the connection to the compute node
     #[clap(long, default_value = config::RetryConfig::CONNECT_TO_COMPUTE_DEFAULT_VALUES)]
     connect_to_compute_retry: String,
+    /// How long a compute may take to complete the TLS handshake and postgres startup once
+    /// the TCP connection to it is established, for SQL over HTTP connections. Unbounded if unset.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    connect_to_compute_startup_timeout: Option<tokio::time::Duration>,
+    /// Log a warning, with the latency breakdown, for connects to compute that take longer
+    /// than this. Disabled if unset.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    connect_to_compute_slow_log_threshold: Option<tokio::time::Duration>,
+    /// After a failed connect to a compute woken less than this long ago, retry on the same
+    /// compute node info once instead of waking the compute again. Always wakes if unset.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    wake_compute_freshness_window: Option<tokio::time::Duration>,
     /// Whether to retry the wake_compute request
     #[clap(long, default_value = config::RetryConfig::WAKE_COMPUTE_DEFAULT_VALUES)]
     wake_compute_retry: String,
 
+    /// How to pick among a compute's read endpoints for new read-only serverless connections.
+    #[clap(value_enum, long, default_value_t = ReadEndpointPolicy::RoundRobin)]
+    read_endpoint_policy: ReadEndpointPolicy,
+
     /// Configure if this is a private access proxy for the POC: In that case the proxy will ignore the IP allowlist
     #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
     is_private_access_proxy: bool,
@@ -219,16 +344,75 @@ struct ProxyCliArgs {
     #[clap(value_enum, long, default_value_t = ProxyProtocolV2::Rejected)]
     proxy_protocol_v2: ProxyProtocolV2,
 
+    /// Whether to enable TCP keepalive on accepted client connections.
+    #[clap(long, default_value_t = true, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    client_tcp_keepalive: bool,
+    /// How long a client connection must be idle before the first keepalive probe is sent.
+    #[clap(long, default_value = "1m", value_parser = humantime::parse_duration)]
+    client_tcp_keepalive_idle: Duration,
+    /// Time between keepalive probes on client connections.
+    #[clap(long, default_value = "15s", value_parser = humantime::parse_duration)]
+    client_tcp_keepalive_interval: Duration,
+    /// Number of unanswered keepalive probes after which a client connection is dropped.
+    #[clap(long, default_value_t = 4)]
+    client_tcp_keepalive_count: u32,
+
     /// Time the proxy waits for the webauth session to be confirmed by the control plane.
     // TODO: rename to `console_redirect_confirmation_timeout`.
     #[clap(long, default_value = "2m", value_parser = humantime::parse_duration)]
     webauth_confirmation_timeout: std::time::Duration,
 
+    /// How long to reject JWTs signed with a key ID that was not found in the JWKS,
+    /// before looking it up again.
+    #[clap(long, default_value = "1m", value_parser = humantime::parse_duration)]
+    jwks_negative_cache_ttl: std::time::Duration,
+
+    /// How long past its hourly refetch a JWKS keeps being used while the identity provider
+    /// cannot be reached. JWTs signed with its keys are rejected after that.
+    #[clap(long, default_value = "30m", value_parser = humantime::parse_duration)]
+    jwks_stale_ttl: std::time::Duration,
+
+    /// Emit a structured audit event for every password authentication attempt,
+    /// under the `proxy::audit` log target.
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    audit_log: bool,
+
+    /// Refuse cleartext password authentication on connections that are not encrypted.
+    /// SCRAM authentication is still allowed, since it does not expose the password.
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    cleartext_password_requires_tls: bool,
+
     #[clap(flatten)]
     pg_sni_router: PgSniRouterArgs,
 }
 
-#[derive(clap::Args, Clone, Copy, Debug)]
+/// `DISCARD ALL` without `DEALLOCATE ALL` and `DISCARD PLANS`, for use with the statement cache.
+const DISCARD_ALL_KEEP_STATEMENTS: &str = "CLOSE ALL; SET SESSION AUTHORIZATION DEFAULT; RESET ALL; \
+    UNLISTEN *; SELECT pg_advisory_unlock_all(); DISCARD TEMP; DISCARD SEQUENCES";
+
+/// Statements that reset a serverless connection before it goes back to the pool.
+fn pool_reset_queries(args: &SqlOverHttpArgs) -> Vec<String> {
+    let query = match args.sql_over_http_pool_reset_query.as_str() {
+        "" => return Vec::new(),
+        // DISCARD ALL would also deallocate the cached prepared statements.
+        query
+            if args.sql_over_http_statement_cache_size > 0
+                && query.eq_ignore_ascii_case("DISCARD ALL") =>
+        {
+            DISCARD_ALL_KEEP_STATEMENTS
+        }
+        query => query,
+    };
+    let mut queries = vec![query.to_owned()];
+    // the reset also clears the statement_timeout set on connect, so restore it.
+    // This is a separate statement, as DISCARD ALL cannot be part of a multi-statement query.
+    if let Some(timeout) = args.sql_over_http_statement_timeout {
+        queries.push(format!("SET statement_timeout = {}", timeout.as_millis()));
+    }
+    queries
+}
+
+#[derive(clap::Args, Clone, Debug)]
 struct SqlOverHttpArgs {
     /// timeout for http connection requests
     #[clap(long, default_value = "15s", value_parser = humantime::parse_duration)]
@@ -242,10 +426,35 @@ struct SqlOverHttpArgs {
     #[clap(long, default_value_t = 20)]
     sql_over_http_pool_max_conns_per_endpoint: usize,
 
+    /// How many pooled connections of each endpoint are kept open however long they are idle.
+    /// This keeps the compute of every endpoint that used the pool from suspending, so it is
+    /// meant for deployments serving few, latency sensitive endpoints.
+    #[clap(long, default_value_t = 0)]
+    sql_over_http_pool_min_conns_per_endpoint: usize,
+
     /// How many connections to pool for each endpoint. Excess connections are discarded
     #[clap(long, default_value_t = 20000)]
     sql_over_http_pool_max_total_conns: usize,
 
+    /// How many connections to pool across all connection pools. When exceeded, the least
+    /// recently used idle connection of any endpoint is closed to make room for a new one.
+    /// Per-endpoint limits still apply. Unlimited if unset.
+    #[clap(long)]
+    sql_over_http_pool_max_conns_all_pools: Option<usize>,
+
+    /// How many consecutive failed connection attempts to a compute, within
+    /// `sql_over_http_circuit_breaker_window`, make new attempts fail fast. Disabled (0) by default.
+    #[clap(long, default_value_t = 0)]
+    sql_over_http_circuit_breaker_threshold: u32,
+
+    /// Window in which consecutive compute connection failures are counted.
+    #[clap(long, default_value = "30s", value_parser = humantime::parse_duration)]
+    sql_over_http_circuit_breaker_window: tokio::time::Duration,
+
+    /// How long to fail fast once the breaker has opened, and how often to probe the compute after that.
+    #[clap(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    sql_over_http_circuit_breaker_cooldown: tokio::time::Duration,
+
     /// How long pooled connections should remain idle for before closing
     #[clap(long, default_value = "5m", value_parser = humantime::parse_duration)]
     sql_over_http_idle_timeout: tokio::time::Duration,
@@ -261,6 +470,21 @@ struct SqlOverHttpArgs {
     #[clap(long, default_value_t = 128)]
     sql_over_http_pool_shards: usize,
 
+    /// Which pooled connection to reuse first. `lifo` keeps traffic on few
+    /// connections so idle ones can be reaped, `fifo` spreads load across all of them.
+    #[clap(value_enum, long, default_value_t = PoolReusePolicy::Lifo)]
+    sql_over_http_pool_reuse_policy: PoolReusePolicy,
+
+    /// Query that clears session state before a connection is returned to the pool.
+    /// Connections are not pooled if it fails. Set to an empty string to disable.
+    #[clap(long, default_value = "DISCARD ALL")]
+    sql_over_http_pool_reset_query: String,
+
+    /// How many prepared statements to cache on each pooled connection, keyed by query text.
+    /// Zero disables the cache. When enabled, a `DISCARD ALL` pool reset keeps prepared statements.
+    #[clap(long, default_value_t = 0)]
+    sql_over_http_statement_cache_size: usize,
+
     #[clap(long, default_value_t = 10000)]
     sql_over_http_client_conn_threshold: u64,
 
@@ -272,6 +496,91 @@ struct SqlOverHttpArgs {
 
     #[clap(long, default_value_t = 10 * 1024 * 1024)] // 10 MiB
     sql_over_http_max_response_size_bytes: usize,
+
+    /// `statement_timeout` to set on new SQL over http connections to compute.
+    /// Requests can override it with the `Neon-Statement-Timeout` header.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    sql_over_http_statement_timeout: Option<tokio::time::Duration>,
+
+    /// How long a SQL over HTTP request may run before the proxy cancels it and closes its
+    /// connection, independently of `statement_timeout`. Unbounded if unset.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    sql_over_http_max_query_duration: Option<tokio::time::Duration>,
+
+    /// Compress SQL over HTTP bodies exchanged with local-proxy (auth-broker only).
+    #[clap(value_enum, long)]
+    sql_over_http_local_proxy_compression: Option<ContentEncoding>,
+
+    /// How many SQL over HTTP sticky sessions may be open at once. 0 disables sticky sessions.
+    ///
+    /// A request with a `Neon-Session-Id` header gets a dedicated compute connection, which is
+    /// kept for the next request of the same session instead of being reset and pooled.
+    /// Each session holds a compute connection open until it idles out.
+    #[clap(long, default_value_t = 0)]
+    sql_over_http_sticky_session_max: usize,
+
+    /// How long a sticky session is kept without requests before its connection is closed.
+    #[clap(long, default_value = "5m", value_parser = humantime::parse_duration)]
+    sql_over_http_sticky_session_idle_timeout: tokio::time::Duration,
+
+    /// How many SQL over HTTP listen requests may be open at once. Each holds a compute
+    /// connection until the client goes away. 0 disables listening for notifications.
+    #[clap(long, default_value_t = 0)]
+    sql_over_http_listen_max: usize,
+
+    /// How many SQL over HTTP listen requests may be open at once for a single endpoint.
+    #[clap(long, default_value_t = 10)]
+    sql_over_http_listen_max_per_endpoint: usize,
+
+    /// Stream the results of queries sent with `Neon-Stream-Rows: true` in chunks of at most
+    /// this many bytes of JSON, instead of buffering them whole. 0 disables streaming.
+    #[clap(long, default_value_t = 0)]
+    sql_over_http_stream_chunk_bytes: usize,
+
+    /// Flush a streamed chunk once it holds this many rows, even if it is below the byte size.
+    #[clap(long, default_value_t = 1000)]
+    sql_over_http_stream_chunk_rows: usize,
+
+    /// Comma-separated SQL commands, such as `SELECT` or `INSERT`, that SQL over HTTP queries
+    /// may run. Queries starting with any other command are refused before reaching compute.
+    /// This is a best-effort check that does not apply to websocket connections.
+    #[clap(
+        long,
+        value_delimiter = ',',
+        conflicts_with = "sql_over_http_denied_commands"
+    )]
+    sql_over_http_allowed_commands: Option<Vec<String>>,
+
+    /// Comma-separated SQL commands, such as `CREATE` or `DROP`, that SQL over HTTP queries
+    /// may not run. Queries starting with one of them are refused before reaching compute,
+    /// as are `DO` blocks. This is a best-effort check that does not apply to websocket
+    /// connections.
+    #[clap(long, value_delimiter = ',')]
+    sql_over_http_denied_commands: Option<Vec<String>>,
+
+    /// Comma-separated connection pools, as `name=max_conns`, that SQL over HTTP clients can
+    /// pick with `-c neon.pool=<name>`. A named pool is kept apart from the default pool of the
+    /// endpoint and holds at most `max_conns` connections per endpoint.
+    #[clap(long, value_delimiter = ',', value_parser = named_pool_from_str)]
+    sql_over_http_named_pools: Vec<(String, usize)>,
+
+    /// How many recently opened SQL over HTTP connections to compute the admin API can look up
+    /// by `conn_id`, with the parameters they were opened with. Disabled (0) by default, as
+    /// every connection to compute then takes a lock to record itself.
+    #[clap(long, default_value_t = 0)]
+    sql_over_http_recent_connections: usize,
+
+    /// Regular expression matching database names whose SQL over HTTP connections share a
+    /// pool, such as `^tenant_[0-9]+$`. Can be given several times, each pattern makes its own
+    /// pool. A pooled connection to any of the matching databases is reused for requests to
+    /// the others, so this is only safe when the databases are interchangeable.
+    #[clap(long)]
+    sql_over_http_shared_pool_dbname: Vec<regex::Regex>,
+
+    /// Return the PID of the compute backend that served a SQL over HTTP request in the
+    /// `Neon-Backend-Pid` response header, to correlate requests with compute logs.
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    sql_over_http_expose_backend_pid: bool,
 }
 
 #[derive(clap::Args, Clone, Debug)]
@@ -294,7 +603,8 @@ struct PgSniRouterArgs {
 }
 
 pub async fn run() -> anyhow::Result<()> {
-    let _logging_guard = crate::logging::init().await?;
+    let args = ProxyCliArgs::parse();
+    let _logging_guard = crate::logging::init(args.otel_endpoint.clone()).await?;
     let _panic_hook_guard = utils::logging::replace_panic_hook_with_tracing_panic_hook();
     let _sentry_guard = init_sentry(Some(GIT_VERSION.into()), &[]);
 
@@ -314,14 +624,14 @@ pub async fn run() -> anyhow::Result<()> {
         }
     };
 
-    let args = ProxyCliArgs::parse();
     let config = build_config(&args)?;
     let auth_backend = build_auth_backend(&args)?;
 
-    match auth_backend {
-        Either::Left(auth_backend) => info!("Authentication backend: {auth_backend}"),
-        Either::Right(auth_backend) => info!("Authentication backend: {auth_backend:?}"),
-    }
+    let auth_backend_name = match auth_backend {
+        Either::Left(auth_backend) => auth_backend.to_string(),
+        Either::Right(auth_backend) => format!("{auth_backend:?}"),
+    };
+    info!("Authentication backend: {auth_backend_name}");
     info!("Using region: {}", args.aws_region);
     let redis_client = configure_redis(&args).await?;
 
@@ -329,8 +639,43 @@ pub async fn run() -> anyhow::Result<()> {
     info!("Starting http on {}", args.http);
     let http_listener = TcpListener::bind(args.http).await?.into_std()?;
 
-    info!("Starting mgmt on {}", args.mgmt);
+    let mgmt_tls_config = match (
+        &args.mgmt_tls_key,
+        &args.mgmt_tls_cert,
+        &args.mgmt_tls_client_ca,
+    ) {
+        (Some(key_path), Some(cert_path), Some(client_ca_path)) => Some(
+            crate::tls::server_config::configure_mgmt_tls(key_path, cert_path, client_ca_path)?,
+        ),
+        (None, None, None) => {
+            for addr in std::iter::once(args.mgmt).chain(args.mgmt_http) {
+                if !addr.ip().is_loopback() {
+                    warn!(
+                        "mgmt listens on {addr} without TLS, configure mgmt-tls-key, mgmt-tls-cert and mgmt-tls-client-ca to require client certificates"
+                    );
+                }
+            }
+            None
+        }
+        _ => bail!(
+            "either all or none of mgmt-tls-key, mgmt-tls-cert and mgmt-tls-client-ca must be specified"
+        ),
+    };
+    info!(
+        tls = mgmt_tls_config.is_some(),
+        "Starting mgmt on {}", args.mgmt
+    );
     let mgmt_listener = TcpListener::bind(args.mgmt).await?;
+    let mgmt_http_listener = match args.mgmt_http {
+        Some(addr) => {
+            info!(
+                tls = mgmt_tls_config.is_some(),
+                "Starting mgmt http on {addr}"
+            );
+            Some(TcpListener::bind(addr).await?.into_std()?)
+        }
+        None => None,
+    };
 
     let proxy_listener = if args.is_auth_broker {
         None
@@ -376,6 +721,16 @@ pub async fn run() -> anyhow::Result<()> {
         None
     };
 
+    if let Some(metrics_config) = config
+        .metric_collection
+        .as_ref()
+        .filter(|_| args.require_metric_collection)
+    {
+        usage_metrics::check_endpoint(metrics_config)
+            .await
+            .context("metric collection is required")?;
+    }
+
     let cancellation_token = CancellationToken::new();
 
     let cancellation_handler = Arc::new(CancellationHandler::new(&config.connect_to_compute));
@@ -386,6 +741,8 @@ pub async fn run() -> anyhow::Result<()> {
         64,
     ));
 
+    let conn_pools = serverless::ConnPools::new(&config.http_config);
+
     // client facing tasks. these will exit on error or on cancellation
     // cancellation returns Ok(())
     let mut client_tasks = JoinSet::new();
@@ -410,6 +767,7 @@ pub async fn run() -> anyhow::Result<()> {
                     cancellation_token.clone(),
                     cancellation_handler.clone(),
                     endpoint_rate_limiter.clone(),
+                    conn_pools.clone(),
                 ));
             }
         }
@@ -460,6 +818,15 @@ pub async fn run() -> anyhow::Result<()> {
         args.region,
     ));
 
+    // computes are woken through the control plane, which console redirect does not use.
+    let compute_waker = match auth_backend {
+        Either::Left(backend) => Some(ComputeWaker {
+            backend,
+            retry_config: config.wake_compute_retry_config,
+        }),
+        Either::Right(_) => None,
+    };
+
     // maintenance tasks. these never return unless there's an error
     let mut maintenance_tasks = JoinSet::new();
     maintenance_tasks.spawn(crate::signals::handle(cancellation_token.clone(), || {}));
@@ -470,17 +837,34 @@ pub async fn run() -> anyhow::Result<()> {
             neon_metrics,
             proxy: crate::metrics::Metrics::get(),
         },
+        VersionInfo {
+            git_version: GIT_VERSION,
+            auth_backend: auth_backend_name,
+            wss: args.wss.is_some(),
+            metric_collection: config.metric_collection.is_some(),
+        },
+    ));
+    if let Some(listener) = mgmt_http_listener {
+        maintenance_tasks.spawn(http::admin_server::task_main(
+            listener,
+            mgmt_tls_config.clone(),
+            &config.authentication_config.jwks_cache,
+            conn_pools,
+            compute_waker,
+        ));
+    }
+    maintenance_tasks.spawn(control_plane::mgmt::task_main(
+        mgmt_listener,
+        mgmt_tls_config,
     ));
-    maintenance_tasks.spawn(control_plane::mgmt::task_main(mgmt_listener));
 
     if let Some(metrics_config) = &config.metric_collection {
         // TODO: Add gc regardles of the metric collection being enabled.
         maintenance_tasks.spawn(usage_metrics::task_main(metrics_config));
     }
 
-    #[cfg_attr(not(any(test, feature = "testing")), expect(irrefutable_let_patterns))]
     if let Either::Left(auth::Backend::ControlPlane(api, ())) = &auth_backend {
-        if let crate::control_plane::client::ControlPlaneClient::ProxyV1(api) = &**api {
+        if let Some(api) = api.proxy_v1() {
             if let Some(client) = redis_client {
                 // project info cache and invalidation of that cache.
                 let cache = api.caches.project_info.clone();
@@ -581,7 +965,9 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
         (Some(endpoint), Some(interval)) => Some(config::MetricCollectionConfig {
             endpoint: endpoint.parse()?,
             interval: humantime::parse_duration(interval)?,
+            interval_jitter: f64::from(args.metric_collection_interval_jitter_percent) / 100.0,
             backup_metric_collection_config,
+            unsent_buffer_size: args.metric_collection_unsent_buffer_size,
         }),
         (None, None) => None,
         _ => bail!(
@@ -589,6 +975,9 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
              and metric-collection-interval must be specified"
         ),
     };
+    if args.require_metric_collection && metric_collection.is_none() {
+        bail!("require-metric-collection needs metric-collection-endpoint to be specified");
+    }
 
     let config::ConcurrencyLockOptions {
         shards,
@@ -611,23 +1000,99 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
         &Metrics::get().proxy.connect_compute_lock,
     );
 
+    let config::ConcurrencyLockOptions {
+        shards,
+        limiter,
+        epoch,
+        timeout,
+    } = args.endpoint_connect_compute_lock.parse()?;
+    info!(
+        ?limiter,
+        shards,
+        ?epoch,
+        "Using EndpointLocks (connect_compute)"
+    );
+    let endpoint_connect_compute_locks = control_plane::locks::ApiLocks::new(
+        "endpoint_connect_compute_lock",
+        limiter,
+        shards,
+        timeout,
+        epoch,
+        &Metrics::get().proxy.endpoint_connect_compute_lock,
+    );
+
+    let statement_filter = match (
+        &args.sql_over_http.sql_over_http_allowed_commands,
+        &args.sql_over_http.sql_over_http_denied_commands,
+    ) {
+        (Some(allowed), _) => Some(StatementFilter::allow(allowed)),
+        (None, Some(denied)) => Some(StatementFilter::deny(denied)),
+        (None, None) => None,
+    };
+
+    ensure!(
+        args.sql_over_http.sql_over_http_pool_min_conns_per_endpoint
+            <= args.sql_over_http.sql_over_http_pool_max_conns_per_endpoint,
+        "sql-over-http-pool-min-conns-per-endpoint is larger than sql-over-http-pool-max-conns-per-endpoint"
+    );
     let http_config = HttpConfig {
         accept_websockets: !args.is_auth_broker,
         pool_options: GlobalConnPoolOptions {
             max_conns_per_endpoint: args.sql_over_http.sql_over_http_pool_max_conns_per_endpoint,
+            min_conns_per_endpoint: args.sql_over_http.sql_over_http_pool_min_conns_per_endpoint,
             gc_epoch: args.sql_over_http.sql_over_http_pool_gc_epoch,
             pool_shards: args.sql_over_http.sql_over_http_pool_shards,
             idle_timeout: args.sql_over_http.sql_over_http_idle_timeout,
             opt_in: args.sql_over_http.sql_over_http_pool_opt_in,
             max_total_conns: args.sql_over_http.sql_over_http_pool_max_total_conns,
+            reuse_policy: args.sql_over_http.sql_over_http_pool_reuse_policy,
+            statement_cache_size: args.sql_over_http.sql_over_http_statement_cache_size,
         },
         cancel_set: CancelSet::new(args.sql_over_http.sql_over_http_cancel_set_shards),
         client_conn_threshold: args.sql_over_http.sql_over_http_client_conn_threshold,
         max_request_size_bytes: args.sql_over_http.sql_over_http_max_request_size_bytes,
         max_response_size_bytes: args.sql_over_http.sql_over_http_max_response_size_bytes,
+        pool_reset_queries: pool_reset_queries(&args.sql_over_http),
+        pool_budget: PoolBudget::new(args.sql_over_http.sql_over_http_pool_max_conns_all_pools),
+        compute_circuit_breaker: ComputeCircuitBreaker::new(
+            (args.sql_over_http.sql_over_http_circuit_breaker_threshold > 0).then_some(
+                CircuitBreakerConfig {
+                    failure_threshold: args.sql_over_http.sql_over_http_circuit_breaker_threshold,
+                    window: args.sql_over_http.sql_over_http_circuit_breaker_window,
+                    cooldown: args.sql_over_http.sql_over_http_circuit_breaker_cooldown,
+                },
+            ),
+        ),
+        local_proxy_compression: args.sql_over_http.sql_over_http_local_proxy_compression,
+        sticky_sessions: (args.sql_over_http.sql_over_http_sticky_session_max > 0).then_some(
+            StickySessionConfig {
+                max_sessions: args.sql_over_http.sql_over_http_sticky_session_max,
+                idle_timeout: args.sql_over_http.sql_over_http_sticky_session_idle_timeout,
+            },
+        ),
+        listen: (args.sql_over_http.sql_over_http_listen_max > 0).then_some(ListenConfig {
+            max_listeners: args.sql_over_http.sql_over_http_listen_max,
+            max_listeners_per_endpoint: args.sql_over_http.sql_over_http_listen_max_per_endpoint,
+        }),
+        row_streaming: (args.sql_over_http.sql_over_http_stream_chunk_bytes > 0).then_some(
+            RowStreamingConfig {
+                chunk_rows: args.sql_over_http.sql_over_http_stream_chunk_rows,
+                chunk_bytes: args.sql_over_http.sql_over_http_stream_chunk_bytes,
+            },
+        ),
+        statement_filter,
+        named_pools: args
+            .sql_over_http
+            .sql_over_http_named_pools
+            .iter()
+            .cloned()
+            .collect(),
+        recent_connections: args.sql_over_http.sql_over_http_recent_connections,
+        shared_pool_dbnames: args.sql_over_http.sql_over_http_shared_pool_dbname.clone(),
+        expose_backend_pid: args.sql_over_http.sql_over_http_expose_backend_pid,
     };
     let authentication_config = AuthenticationConfig {
-        jwks_cache: JwkCache::default(),
+        jwks_cache: JwkCache::new(args.jwks_negative_cache_ttl, args.jwks_stale_ttl),
         thread_pool,
         scram_protocol_timeout: args.scram_protocol_timeout,
         ip_allowlist_check_enabled: !args.is_private_access_proxy,
@@ -635,12 +1100,33 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
         is_auth_broker: args.is_auth_broker,
         accept_jwts: args.is_auth_broker,
         console_redirect_confirmation_timeout: args.webauth_confirmation_timeout,
+        audit_log: args.audit_log,
+        cleartext_password_requires_tls: args.cleartext_password_requires_tls,
+    };
+
+    let compute_client_cert = match (&args.compute_tls_client_key, &args.compute_tls_client_cert) {
+        (Some(key_path), Some(cert_path)) => Some(ComputeClientCert {
+            key_path: key_path.clone(),
+            cert_path: cert_path.clone(),
+        }),
+        (None, None) => None,
+        _ => bail!(
+            "either both or neither compute-tls-client-key and compute-tls-client-cert must be specified"
+        ),
     };
 
     let compute_config = ComputeConfig {
         retry: config::RetryConfig::parse(&args.connect_to_compute_retry)?,
-        tls: Arc::new(compute_client_config_with_root_certs()?),
+        tls: Arc::new(compute_client_config_with_root_certs(
+            compute_client_cert.as_ref(),
+        )?),
         timeout: Duration::from_secs(2),
+        startup_timeout: args.connect_to_compute_startup_timeout,
+        slow_connect_threshold: args.connect_to_compute_slow_log_threshold,
+        statement_timeout: args.sql_over_http.sql_over_http_statement_timeout,
+        max_query_duration: args.sql_over_http.sql_over_http_max_query_duration,
+        read_endpoint_policy: args.read_endpoint_policy,
+        recent_wakes: RecentWakes::new(args.wake_compute_freshness_window),
     };
 
     let config = ProxyConfig {
@@ -649,15 +1135,36 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
         http_config,
         authentication_config,
         proxy_protocol_v2: args.proxy_protocol_v2,
+        client_tcp_keepalive: args.client_tcp_keepalive.then_some(TcpKeepaliveConfig {
+            idle: args.client_tcp_keepalive_idle,
+            interval: args.client_tcp_keepalive_interval,
+            count: args.client_tcp_keepalive_count,
+        }),
         handshake_timeout: args.handshake_timeout,
+        client_idle_timeout: args.client_idle_timeout,
+        close_idle_sessions_on_shutdown: args.close_idle_sessions_on_shutdown,
+        startup_params_limits: StartupParamsLimits {
+            max_count: args.max_startup_params,
+            max_size: args.max_startup_params_size,
+        },
+        client_connection_limit: args.max_client_connections.map(|max_connections| {
+            ClientConnectionLimit::new(max_connections, args.client_connection_queue_timeout)
+        }),
+        client_error_verbosity: args.client_error_verbosity,
         wake_compute_retry_config: config::RetryConfig::parse(&args.wake_compute_retry)?,
         connect_compute_locks,
+        endpoint_connect_compute_locks,
         connect_to_compute: compute_config,
     };
 
     let config = Box::leak(Box::new(config));
 
     tokio::spawn(config.connect_compute_locks.garbage_collect_worker());
+    tokio::spawn(
+        config
+            .endpoint_connect_compute_locks
+            .garbage_collect_worker(),
+    );
 
     Ok(config)
 }
@@ -666,6 +1173,21 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
 fn build_auth_backend(
     args: &ProxyCliArgs,
 ) -> anyhow::Result<Either<&'static auth::Backend<'static, ()>, &'static ConsoleRedirectBackend>> {
+    ensure!(
+        args.fallback_auth_endpoint.is_empty()
+            || matches!(args.auth_backend, AuthBackendType::ControlPlane),
+        "fallback-auth-endpoint is only supported by the control-plane auth backend"
+    );
+    ensure!(
+        args.balanced_auth_endpoint.is_empty()
+            || matches!(args.auth_backend, AuthBackendType::ControlPlane),
+        "balanced-auth-endpoint is only supported by the control-plane auth backend"
+    );
+    ensure!(
+        args.auth_endpoint_weight.len() <= 1 + args.balanced_auth_endpoint.len(),
+        "auth-endpoint-weight has more weights than there are auth endpoints"
+    );
+
     match &args.auth_backend {
         AuthBackendType::ControlPlane => {
             let wake_compute_cache_config: CacheOptions = args.wake_compute_cache.parse()?;
@@ -702,24 +1224,62 @@ fn build_auth_backend(
             )));
             tokio::spawn(locks.garbage_collect_worker());
 
-            let url: crate::url::ApiUrl = args.auth_endpoint.parse()?;
-
-            let endpoint = http::Endpoint::new(url, http::new_client());
-
             let mut wake_compute_rps_limit = args.wake_compute_limit.clone();
             RateBucketInfo::validate(&mut wake_compute_rps_limit)?;
             let wake_compute_endpoint_rate_limiter =
                 Arc::new(WakeComputeRateLimiter::new(wake_compute_rps_limit));
 
-            let api = control_plane::client::cplane_proxy_v1::NeonControlPlaneClient::new(
-                endpoint,
-                args.control_plane_token.clone(),
-                caches,
-                locks,
-                wake_compute_endpoint_rate_limiter,
+            let balanced = std::iter::once(&args.auth_endpoint)
+                .chain(&args.balanced_auth_endpoint)
+                .enumerate()
+                .map(|(i, url)| {
+                    let url: ApiUrl = url.parse()?;
+                    let weight = args.auth_endpoint_weight.get(i).copied().unwrap_or(1);
+                    Ok((url, weight))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            // a control plane that cannot be reached is failed over from on the first transport
+            // error, only the last one retries its requests.
+            let retry_duration = |is_last: bool| {
+                if is_last {
+                    args.control_plane_retry_duration
+                } else {
+                    Duration::ZERO
+                }
+            };
+            let fallback_count = args.fallback_auth_endpoint.len();
+            let primary = http::Endpoint::balanced(
+                balanced,
+                control_plane_http_client(args, retry_duration(fallback_count == 0)),
             );
-
-            let api = control_plane::client::ControlPlaneClient::ProxyV1(api);
+            let fallbacks = args
+                .fallback_auth_endpoint
+                .iter()
+                .enumerate()
+                .map(|(i, url)| {
+                    Ok(http::Endpoint::new(
+                        url.parse()?,
+                        control_plane_http_client(args, retry_duration(i + 1 == fallback_count)),
+                    ))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            // the fallbacks share the caches, locks and rate limits of the primary endpoint.
+            let apis = std::iter::once(primary)
+                .chain(fallbacks)
+                .map(|endpoint| {
+                    let api = control_plane::client::cplane_proxy_v1::NeonControlPlaneClient::new(
+                        endpoint,
+                        args.control_plane_token.clone(),
+                        caches,
+                        locks,
+                        wake_compute_endpoint_rate_limiter.clone(),
+                    );
+                    control_plane::client::FailoverMember::ProxyV1(api)
+                })
+                .collect::<Vec<_>>();
+
+            let api = control_plane::client::ControlPlaneClient::failover(apis);
             let auth_backend = auth::Backend::ControlPlane(MaybeOwned::Owned(api), ());
             let config = Box::leak(Box::new(auth_backend));
 
@@ -784,7 +1344,10 @@ fn build_auth_backend(
 
             let url = args.uri.clone().parse()?;
             let ep_url: crate::url::ApiUrl = args.auth_endpoint.parse()?;
-            let endpoint = http::Endpoint::new(ep_url, http::new_client());
+            let endpoint = http::Endpoint::new(
+                ep_url,
+                control_plane_http_client(args, args.control_plane_retry_duration),
+            );
             let mut wake_compute_rps_limit = args.wake_compute_limit.clone();
             RateBucketInfo::validate(&mut wake_compute_rps_limit)?;
             let wake_compute_endpoint_rate_limiter =
@@ -809,6 +1372,17 @@ fn build_auth_backend(
     }
 }
 
+fn control_plane_http_client(
+    args: &ProxyCliArgs,
+    retry_duration: Duration,
+) -> http::ClientWithMiddleware {
+    http::new_control_plane_client(
+        args.control_plane_connect_timeout,
+        args.control_plane_request_timeout,
+        retry_duration,
+    )
+}
+
 async fn configure_redis(
     args: &ProxyCliArgs,
 ) -> anyhow::Result<Option<ConnectionWithCredentialsProvider>> {
@@ -880,4 +1454,18 @@ fn parse_endpoint_rps_limit() {
             ]
         );
     }
+
+    #[test]
+    fn pool_reset_restores_statement_timeout() {
+        let config =
+            super::ProxyCliArgs::parse_from(["proxy", "--sql-over-http-statement-timeout", "5s"]);
+        assert_eq!(
+            super::pool_reset_queries(&config.sql_over_http),
+            ["DISCARD ALL", "SET statement_timeout = 5000"]
+        );
+
+        let config =
+            super::ProxyCliArgs::parse_from(["proxy", "--sql-over-http-pool-reset-query", ""]);
+        assert!(super::pool_reset_queries(&config.sql_over_http).is_empty());
+    }
 }
@@ -399,6 +399,7 @@ async fn test_project_info_cache_settings() {
             EndpointAccessControl {
                 allowed_ips: allowed_ips.clone(),
                 allowed_vpce: Arc::new(vec![]),
+                allowed_databases: Arc::new(vec![]),
                 flags: AccessBlockerFlags::default(),
                 rate_limits: EndpointRateLimitConfig::default(),
             },
@@ -415,6 +416,7 @@ async fn test_project_info_cache_settings() {
             EndpointAccessControl {
                 allowed_ips: allowed_ips.clone(),
                 allowed_vpce: Arc::new(vec![]),
+                allowed_databases: Arc::new(vec![]),
                 flags: AccessBlockerFlags::default(),
                 rate_limits: EndpointRateLimitConfig::default(),
             },
@@ -441,6 +443,7 @@ async fn test_project_info_cache_settings() {
             EndpointAccessControl {
                 allowed_ips: allowed_ips.clone(),
                 allowed_vpce: Arc::new(vec![]),
+                allowed_databases: Arc::new(vec![]),
                 flags: AccessBlockerFlags::default(),
                 rate_limits: EndpointRateLimitConfig::default(),
             },
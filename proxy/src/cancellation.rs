@@ -225,10 +225,7 @@ pub(crate) fn get_key(self: Arc<Self>) -> Session {
     }
 
     /// This is not cancel safe
-    async fn get_cancel_key(
-        &self,
-        key: CancelKeyData,
-    ) -> Result<Option<CancelClosure>, CancelError> {
+    async fn get_cancel_key(&self, key: CancelKeyData) -> Result<CancelClosure, CancelError> {
         let guard = Metrics::get()
             .proxy
             .cancel_channel_size
@@ -257,18 +254,7 @@ async fn get_cancel_key(
             CancelError::InternalError
         })?;
 
-        let cancel_state_str = String::from_owned_redis_value(result).map_err(|e| {
-            tracing::warn!("failed to receive GetCancelData response: {e}");
-            CancelError::InternalError
-        })?;
-
-        let cancel_closure: CancelClosure =
-            serde_json::from_str(&cancel_state_str).map_err(|e| {
-                tracing::warn!("failed to deserialize cancel state: {e}");
-                CancelError::InternalError
-            })?;
-
-        Ok(Some(cancel_closure))
+        parse_cancel_data(result)
     }
 
     /// Try to cancel a running query for the corresponding connection.
@@ -309,20 +295,22 @@ pub(crate) async fn cancel_session<T: ControlPlaneApi>(
             return Err(CancelError::RateLimit);
         }
 
-        let cancel_state = self.get_cancel_key(key).await.map_err(|e| {
-            tracing::warn!("failed to receive RedisOp response: {e}");
-            CancelError::InternalError
-        })?;
-
-        let Some(cancel_closure) = cancel_state else {
-            tracing::warn!("query cancellation key not found: {key}");
-            Metrics::get()
-                .proxy
-                .cancellation_requests_total
-                .inc(CancellationRequest {
-                    kind: crate::metrics::CancellationOutcome::NotFound,
-                });
-            return Err(CancelError::NotFound);
+        let cancel_closure = match self.get_cancel_key(key).await {
+            Ok(cancel_closure) => cancel_closure,
+            Err(CancelError::NotFound) => {
+                tracing::warn!("query cancellation key not found: {key}");
+                Metrics::get()
+                    .proxy
+                    .cancellation_requests_total
+                    .inc(CancellationRequest {
+                        kind: crate::metrics::CancellationOutcome::NotFound,
+                    });
+                return Err(CancelError::NotFound);
+            }
+            Err(e) => {
+                tracing::warn!("failed to receive RedisOp response: {e}");
+                return Err(CancelError::InternalError);
+            }
         };
 
         let info = &cancel_closure.user_info;
@@ -344,6 +332,28 @@ pub(crate) async fn cancel_session<T: ControlPlaneApi>(
     }
 }
 
+/// Decodes the stored [`CancelClosure`] for a cancel key.
+///
+/// Keys we never handed out, e.g. forged by the client, or that have expired,
+/// have no data stored and are reported as [`CancelError::NotFound`].
+fn parse_cancel_data(result: Value) -> Result<CancelClosure, CancelError> {
+    if matches!(result, Value::Nil) {
+        return Err(CancelError::NotFound);
+    }
+
+    let cancel_state_str = String::from_owned_redis_value(result).map_err(|e| {
+        tracing::warn!("failed to receive GetCancelData response: {e}");
+        CancelError::InternalError
+    })?;
+
+    let cancel_closure: CancelClosure = serde_json::from_str(&cancel_state_str).map_err(|e| {
+        tracing::warn!("failed to deserialize cancel state: {e}");
+        CancelError::InternalError
+    })?;
+
+    Ok(cancel_closure)
+}
+
 /// This should've been a [`std::future::Future`], but
 /// it's impossible to name a type of an unboxed future
 /// (we'd need something like `#![feature(type_alias_impl_trait)]`).
@@ -472,3 +482,113 @@ pub(crate) async fn maintain_cancel_key(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use postgres_client::config::SslMode;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::config::RetryConfig;
+    use crate::control_plane::read_endpoints::ReadEndpointPolicy;
+    use crate::proxy::wake_compute::RecentWakes;
+    use crate::tls::client_config::compute_client_config_with_certs;
+
+    fn compute_config() -> ComputeConfig {
+        ComputeConfig {
+            retry: RetryConfig {
+                base_delay: Duration::from_secs(1),
+                max_retries: 5,
+                backoff_factor: 2.0,
+            },
+            tls: Arc::new(compute_client_config_with_certs(std::iter::empty())),
+            timeout: Duration::from_secs(2),
+            startup_timeout: None,
+            slow_connect_threshold: None,
+            statement_timeout: None,
+            max_query_duration: None,
+            read_endpoint_policy: ReadEndpointPolicy::default(),
+            recent_wakes: RecentWakes::new(None),
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_is_forwarded_to_owning_compute() {
+        let compute = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closure = CancelClosure::new(
+            compute.local_addr().unwrap(),
+            RawCancelToken {
+                ssl_mode: SslMode::Disable,
+                process_id: 42,
+                secret_key: 1234,
+            },
+            "localhost".to_owned(),
+            ComputeUserInfo::default(),
+        );
+
+        // the closure is stored in redis as json under the key we handed to the client.
+        let stored = Value::BulkString(serde_json::to_vec(&closure).unwrap());
+        let closure = parse_cancel_data(stored).expect("key should be found");
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = compute.accept().await.unwrap();
+            let mut buf = [0u8; 16];
+            stream.read_exact(&mut buf).await.unwrap();
+            buf
+        });
+        closure.try_cancel_query(&compute_config()).await.unwrap();
+
+        let buf = server.await.unwrap();
+        let field = |i: usize| i32::from_be_bytes(buf[i * 4..(i + 1) * 4].try_into().unwrap());
+        assert_eq!(field(0), 16, "message length");
+        assert_eq!(field(1), 80877102, "cancel request code");
+        assert_eq!(field(2), 42, "backend pid");
+        assert_eq!(field(3), 1234, "backend secret");
+    }
+
+    #[test]
+    fn forged_cancel_key_is_not_found() {
+        let closure = CancelClosure::new(
+            "127.0.0.1:5432".parse().unwrap(),
+            RawCancelToken {
+                ssl_mode: SslMode::Disable,
+                process_id: 42,
+                secret_key: 1234,
+            },
+            "localhost".to_owned(),
+            ComputeUserInfo::default(),
+        );
+
+        // what `StoreCancelKey` leaves in redis for the key handed to the client,
+        // and what `GetCancelData` reads back for a key: nil if nothing is stored.
+        let issued: CancelKeyData = rand::random();
+        let stored = HashMap::from([(
+            KeyPrefix::Cancel(issued).build_redis_key(),
+            serde_json::to_vec(&closure).unwrap(),
+        )]);
+        let get_cancel_data = |key: CancelKeyData| {
+            let value = stored.get(&KeyPrefix::Cancel(key).build_redis_key());
+            parse_cancel_data(value.cloned().map_or(Value::Nil, Value::BulkString))
+        };
+
+        let found = get_cancel_data(issued).expect("the issued key should resolve");
+        assert_eq!(found.cancel_token.process_id, 42);
+        assert_eq!(found.cancel_token.secret_key, 1234);
+
+        // a forged key, even one bit away from an issued one, has nothing stored for it.
+        let forged = crate::pqproto::id_to_cancel_key(issued.0.get() ^ 1);
+        assert!(matches!(
+            get_cancel_data(forged),
+            Err(CancelError::NotFound)
+        ));
+
+        // which is told apart from data we fail to decode.
+        assert!(matches!(
+            parse_cancel_data(Value::BulkString(b"not a closure".to_vec())),
+            Err(CancelError::InternalError)
+        ));
+    }
+}
@@ -4,6 +4,7 @@
 use std::io;
 use std::net::{IpAddr, SocketAddr};
 
+use camino::Utf8PathBuf;
 use futures::{FutureExt, TryFutureExt};
 use itertools::Itertools;
 use postgres_client::config::{AuthKeys, ChannelBinding, SslMode};
@@ -142,6 +143,12 @@ pub struct ConnectInfo {
     pub host: Host,
     pub port: u16,
     pub ssl_mode: SslMode,
+    /// Connect over this Unix domain socket instead of `host` and `port`, skipping DNS and TLS.
+    /// Only used by the serverless backends, for computes on the same host.
+    pub unix_socket: Option<Utf8PathBuf>,
+    /// Verify the compute's TLS certificate against this name instead of `host`, for when
+    /// `host` is an IP address or otherwise differs from the name on the certificate.
+    pub tls_server_name: Option<Host>,
 }
 
 /// Creation and initialization routines.
@@ -175,12 +182,26 @@ pub(crate) fn with_auth_keys(keys: ComputeCredentialKeys) -> Self {
 }
 
 impl ConnectInfo {
+    /// The name the compute's TLS certificate is verified against.
+    pub(crate) fn tls_server_name(&self) -> &str {
+        self.tls_server_name
+            .as_deref()
+            .unwrap_or(self.host.as_str())
+    }
+
     pub fn to_postgres_client_config(&self) -> postgres_client::Config {
+        if let Some(path) = &self.unix_socket {
+            return postgres_client::Config::new_unix(path.clone().into_std_path_buf());
+        }
+
         let mut config = postgres_client::Config::new(self.host.to_string(), self.port);
         config.ssl_mode(self.ssl_mode);
         if let Some(host_addr) = self.host_addr {
             config.set_host_addr(host_addr);
         }
+        if let Some(tls_server_name) = &self.tls_server_name {
+            config.tls_server_name(tls_server_name.to_string());
+        }
         config
     }
 }
@@ -330,7 +351,7 @@ async fn connect_raw(
         match connect_once(&*addrs).await {
             Ok((sockaddr, stream)) => Ok((
                 sockaddr,
-                tls::connect_tls(stream, self.ssl_mode, config, host).await?,
+                tls::connect_tls(stream, self.ssl_mode, config, self.tls_server_name()).await?,
             )),
             Err(err) => {
                 warn!("couldn't connect to compute node at {host}:{port}: {err}");
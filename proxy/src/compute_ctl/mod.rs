@@ -73,6 +73,20 @@ pub async fn grant_role(
         .await
     }
 
+    /// Installs the extension, then grants the role the privileges on its schema.
+    ///
+    /// Safe to retry after either step failed: compute_ctl reports an extension that an
+    /// earlier attempt already installed at this version as installed.
+    pub async fn install_extension_and_grant_role(
+        &self,
+        install: &ExtensionInstallRequest,
+        grant: &SetRoleGrantsRequest,
+    ) -> Result<(), ComputeCtlError> {
+        self.install_extension(install).await?;
+        self.grant_role(grant).await?;
+        Ok(())
+    }
+
     async fn generic_request<Req, Resp>(
         &self,
         req: &Req,
@@ -100,3 +114,95 @@ async fn generic_request<Req, Resp>(
         resp.json().await.map_err(ComputeCtlError::Response)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::future::IntoFuture;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::Response;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// compute_ctl that installs the extension, but fails the first grant.
+    async fn compute_ctl_server() -> SocketAddr {
+        let grants = Arc::new(AtomicUsize::new(0));
+        let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+            let (status, body) = if req.uri().path().ends_with("/extensions") {
+                (StatusCode::CREATED, "{}")
+            } else {
+                match grants.fetch_add(1, Ordering::Relaxed) {
+                    0 => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        r#"{"error": "failed to grant privileges"}"#,
+                    ),
+                    _ => (StatusCode::CREATED, "{}"),
+                }
+            };
+            async move {
+                Response::builder()
+                    .status(status)
+                    .body(Full::new(Bytes::from_static(body.as_bytes())))
+            }
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = hyper::server::conn::http1::Builder::new();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (s, _) = listener.accept().await.unwrap();
+                let serve = server.serve_connection(TokioIo::new(s), service.clone());
+                tokio::spawn(serve.into_future());
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn retry_after_failed_grant() {
+        let addr = compute_ctl_server().await;
+        let api = ComputeCtlApi {
+            api: crate::http::Endpoint::new(
+                format!("http://{addr}/").parse().unwrap(),
+                crate::http::new_client(),
+            ),
+        };
+
+        let install = ExtensionInstallRequest {
+            extension: "pg_session_jwt",
+            database: "neondb".into(),
+            version: "0.1.0",
+        };
+        let grant = SetRoleGrantsRequest {
+            database: "neondb".into(),
+            schema: "auth",
+            privileges: vec![Privilege::Usage],
+            role: "neondb_owner".into(),
+        };
+
+        let err = api
+            .install_extension_and_grant_role(&install, &grant)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ComputeCtlError::Request {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                ..
+            }
+        ));
+
+        api.install_extension_and_grant_role(&install, &grant)
+            .await
+            .unwrap();
+    }
+}
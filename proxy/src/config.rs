@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -9,10 +10,20 @@
 
 use crate::auth::backend::jwt::JwkCache;
 use crate::control_plane::locks::ApiLocks;
+use crate::control_plane::read_endpoints::ReadEndpointPolicy;
+use crate::error::{ClientErrorVerbosity, ErrorKind, ReportableError, UserFacingError};
+use crate::intern::EndpointIdInt;
+use crate::pqproto::StartupMessageParams;
+use crate::proxy::wake_compute::RecentWakes;
 use crate::rate_limiter::{RateBucketInfo, RateLimitAlgorithm, RateLimiterConfig};
 use crate::scram::threadpool::ThreadPool;
-use crate::serverless::GlobalConnPoolOptions;
 use crate::serverless::cancel_set::CancelSet;
+use crate::serverless::circuit_breaker::ComputeCircuitBreaker;
+use crate::serverless::compression::ContentEncoding;
+use crate::serverless::{
+    GlobalConnPoolOptions, ListenConfig, PoolBudget, RowStreamingConfig, StatementFilter,
+    StickySessionConfig,
+};
 pub use crate::tls::server_config::{TlsConfig, configure_tls};
 use crate::types::Host;
 
@@ -22,16 +33,161 @@ pub struct ProxyConfig {
     pub http_config: HttpConfig,
     pub authentication_config: AuthenticationConfig,
     pub proxy_protocol_v2: ProxyProtocolV2,
+    /// TCP keepalive applied to accepted client connections. `None` disables keepalive.
+    pub client_tcp_keepalive: Option<TcpKeepaliveConfig>,
     pub handshake_timeout: Duration,
+    /// Client connections that leave compute waiting for them for this long are closed.
+    /// `None` keeps them open.
+    pub client_idle_timeout: Option<Duration>,
+    /// Close client connections that compute is waiting on once the proxy starts shutting down.
+    pub close_idle_sessions_on_shutdown: bool,
+    /// Limits on the startup parameters of client connections.
+    pub startup_params_limits: StartupParamsLimits,
+    /// Limit on the client connections handled at once. `None` handles all of them.
+    pub client_connection_limit: Option<ClientConnectionLimit>,
+    /// How much error detail is sent to clients.
+    pub client_error_verbosity: ClientErrorVerbosity,
     pub wake_compute_retry_config: RetryConfig,
     pub connect_compute_locks: ApiLocks<Host>,
+    /// Limits concurrent serverless connects per endpoint, on top of `connect_compute_locks`.
+    pub endpoint_connect_compute_locks: ApiLocks<EndpointIdInt>,
     pub connect_to_compute: ComputeConfig,
 }
 
 pub struct ComputeConfig {
     pub retry: RetryConfig,
+    /// TLS for connections to compute, including the client certificate the proxy presents
+    /// to computes when one is configured.
     pub tls: Arc<rustls::ClientConfig>,
+    /// Timeout for establishing the TCP connection to compute.
     pub timeout: Duration,
+    /// Timeout for the TLS handshake and postgres startup that follow the TCP connection, for
+    /// serverless connections. Computes that accept connections but stall here fail with a
+    /// startup timeout, which is not retried but counts against the compute's circuit breaker.
+    pub startup_timeout: Option<Duration>,
+    /// Connects to compute that take longer than this, retries included, are logged as a
+    /// warning with their latency breakdown. `None` disables the warning.
+    pub slow_connect_threshold: Option<Duration>,
+    /// `statement_timeout` new serverless connections to compute start with.
+    pub statement_timeout: Option<Duration>,
+    /// How long the proxy lets a SQL over HTTP request run before cancelling it, whatever
+    /// `statement_timeout` the compute enforces. `None` leaves requests unbounded.
+    pub max_query_duration: Option<Duration>,
+    /// How to pick a read endpoint for new read-only serverless connections.
+    pub read_endpoint_policy: ReadEndpointPolicy,
+    /// Computes woken recently, whose node info is trusted after a failed connect.
+    pub recent_wakes: RecentWakes,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TcpKeepaliveConfig {
+    /// How long the connection must be idle before the first probe is sent.
+    pub idle: Duration,
+    /// Time between probes.
+    pub interval: Duration,
+    /// Number of unanswered probes after which the connection is dropped.
+    pub count: u32,
+}
+
+impl TcpKeepaliveConfig {
+    pub(crate) fn apply(&self, socket: &tokio::net::TcpStream) -> std::io::Result<()> {
+        let keepalive = socket2::TcpKeepalive::new()
+            .with_time(self.idle)
+            .with_interval(self.interval)
+            .with_retries(self.count);
+        socket2::SockRef::from(socket).set_tcp_keepalive(&keepalive)
+    }
+}
+
+/// Limits on the parameters clients connect with, either in the startup message or in the
+/// connection string of SQL over HTTP requests. Everything in them, `options` included, is
+/// carried along with the connection and passed on to compute, so they are checked before
+/// authentication.
+#[derive(Clone, Copy, Debug)]
+pub struct StartupParamsLimits {
+    /// Most parameters, `user` and `database` included.
+    pub max_count: usize,
+    /// Most bytes of parameter names and values, in total.
+    pub max_size: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum StartupParamsError {
+    #[error("too many startup parameters: {count}, the limit is {limit}")]
+    TooMany { count: usize, limit: usize },
+    #[error("startup parameters are too large: {size} bytes, the limit is {limit} bytes")]
+    TooLarge { size: usize, limit: usize },
+}
+
+impl ReportableError for StartupParamsError {
+    fn get_error_kind(&self) -> ErrorKind {
+        ErrorKind::User
+    }
+}
+
+impl UserFacingError for StartupParamsError {
+    fn to_string_client(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl StartupParamsLimits {
+    /// Postgres clients send a handful of parameters. The size matches the largest startup
+    /// message accepted.
+    pub const DEFAULT: Self = Self {
+        max_count: 64,
+        max_size: 10_000,
+    };
+
+    pub(crate) fn check(&self, params: &StartupMessageParams) -> Result<(), StartupParamsError> {
+        let count = params.iter().count();
+        if count > self.max_count {
+            return Err(StartupParamsError::TooMany {
+                count,
+                limit: self.max_count,
+            });
+        }
+        let size = params.iter().map(|(k, v)| k.len() + v.len()).sum();
+        if size > self.max_size {
+            return Err(StartupParamsError::TooLarge {
+                size,
+                limit: self.max_size,
+            });
+        }
+        // `Ok` is anyhow's in this module.
+        Result::Ok(())
+    }
+}
+
+/// Limit on the client connections the proxy handles at once, from their accept until they
+/// close. Connections above the limit wait for a slot for up to `queue_timeout`, and are then
+/// closed with a "too many connections" error, before anything is read from them.
+pub struct ClientConnectionLimit {
+    permits: tokio::sync::Semaphore,
+    queue_timeout: Duration,
+}
+
+impl ClientConnectionLimit {
+    pub fn new(max_connections: usize, queue_timeout: Duration) -> Self {
+        Self {
+            permits: tokio::sync::Semaphore::new(max_connections),
+            queue_timeout,
+        }
+    }
+
+    /// A slot for a new connection, held until it closes. `None` if none freed up in time.
+    pub(crate) async fn acquire(&self) -> Option<tokio::sync::SemaphorePermit<'_>> {
+        if let Result::Ok(permit) = self.permits.try_acquire() {
+            return Some(permit);
+        }
+        if self.queue_timeout.is_zero() {
+            return None;
+        }
+        tokio::time::timeout(self.queue_timeout, self.permits.acquire())
+            .await
+            .ok()?
+            .ok()
+    }
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum, PartialEq)]
@@ -46,7 +202,12 @@ pub enum ProxyProtocolV2 {
 pub struct MetricCollectionConfig {
     pub endpoint: reqwest::Url,
     pub interval: Duration,
+    /// Each interval is randomly shortened or lengthened by up to this fraction of it, and the
+    /// first push is delayed by a random part of an interval.
+    pub interval_jitter: f64,
     pub backup_metric_collection_config: MetricBackupCollectionConfig,
+    /// How many collection intervals worth of events that failed to push are kept to be resent.
+    pub unsent_buffer_size: usize,
 }
 
 pub struct HttpConfig {
@@ -56,6 +217,38 @@ pub struct HttpConfig {
     pub client_conn_threshold: u64,
     pub max_request_size_bytes: usize,
     pub max_response_size_bytes: usize,
+    /// Statements run on a connection before it is returned to the pool, to clear session state.
+    /// Each one is sent as its own query. If one fails, the connection is closed instead of
+    /// being pooled. Empty disables the reset.
+    pub pool_reset_queries: Vec<String>,
+    /// Limit on pooled connections across all of the connection pools.
+    pub pool_budget: PoolBudget,
+    pub compute_circuit_breaker: ComputeCircuitBreaker,
+    /// Compress SQL over HTTP bodies sent to local-proxy, and ask for compressed responses.
+    pub local_proxy_compression: Option<ContentEncoding>,
+    /// Dedicated compute connections kept between requests of a session. `None` disables them.
+    pub sticky_sessions: Option<StickySessionConfig>,
+    /// Limits on open `LISTEN` requests, which each hold a compute connection. `None` disables them.
+    pub listen: Option<ListenConfig>,
+    /// Chunking of results streamed with `Neon-Stream-Rows`. `None` disables streaming.
+    pub row_streaming: Option<RowStreamingConfig>,
+    /// Which SQL statements may run over HTTP, on a best-effort basis. Websocket connections are
+    /// not filtered. `None` allows all of them.
+    pub statement_filter: Option<StatementFilter>,
+    /// Connection pools clients can pick with `-c neon.pool=<name>`, with their maximum number
+    /// of connections per endpoint. Each is kept apart from the default pool of the endpoint.
+    pub named_pools: HashMap<String, usize>,
+    /// How many recently opened connections to compute the admin API can look up by `conn_id`.
+    /// Zero disables it.
+    pub recent_connections: usize,
+    /// Databases whose names match the same pattern share connection pools: a connection
+    /// pooled for one of them is reused for requests to any other. New connections are still
+    /// made to the requested database. This is only safe when the databases are
+    /// interchangeable for the clients of the endpoint, as a request may run in any of them.
+    pub shared_pool_dbnames: Vec<regex::Regex>,
+    /// Tell clients the PID of the compute backend that served their request, in the
+    /// `Neon-Backend-Pid` response header.
+    pub expose_backend_pid: bool,
 }
 
 pub struct AuthenticationConfig {
@@ -67,6 +260,10 @@ pub struct AuthenticationConfig {
     pub is_auth_broker: bool,
     pub accept_jwts: bool,
     pub console_redirect_confirmation_timeout: tokio::time::Duration,
+    /// Emit a structured audit event for every authentication attempt.
+    pub audit_log: bool,
+    /// Refuse cleartext password authentication on connections that are not encrypted.
+    pub cleartext_password_requires_tls: bool,
 }
 
 #[derive(Debug)]
@@ -152,6 +349,20 @@ pub fn remote_storage_from_toml(s: &str) -> anyhow::Result<RemoteStorageConfig>
     RemoteStorageConfig::from_toml(&s.parse()?)
 }
 
+/// Parses a named connection pool, `name=max_conns`.
+pub fn named_pool_from_str(s: &str) -> anyhow::Result<(String, usize)> {
+    let Some((name, max_conns)) = s.split_once('=') else {
+        bail!("expected <name>=<max_conns>, got {s:?}");
+    };
+    let name = name.trim();
+    ensure!(!name.is_empty(), "pool name must not be empty");
+    let max_conns = max_conns
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid max_conns for pool {name}"))?;
+    Ok((name.to_owned(), max_conns))
+}
+
 /// Helper for cmdline cache options parsing.
 #[derive(Debug)]
 pub struct CacheOptions {
@@ -342,6 +553,9 @@ impl ConcurrencyLockOptions {
     /// Default options for [`crate::control_plane::client::ApiLocks`].
     pub const DEFAULT_OPTIONS_CONNECT_COMPUTE_LOCK: &'static str =
         "shards=64,permits=100,epoch=10m,timeout=10ms";
+    /// Default options for the per-endpoint limit on concurrent compute connections.
+    /// Disabled (`permits=0`) by default.
+    pub const DEFAULT_OPTIONS_ENDPOINT_CONNECT_COMPUTE_LOCK: &'static str = "permits=0";
 
     // pub const DEFAULT_OPTIONS_WAKE_COMPUTE_LOCK: &'static str = "shards=32,permits=4,epoch=10m,timeout=1s";
 
@@ -504,4 +718,53 @@ fn test_parse_json_lock_options() -> anyhow::Result<()> {
 
         Ok(())
     }
+
+    #[test]
+    fn test_startup_params_limits() {
+        let limits = StartupParamsLimits {
+            max_count: 3,
+            max_size: 32,
+        };
+        let params = StartupMessageParams::new([("user", "alice"), ("database", "db")]);
+        limits.check(&params).unwrap();
+
+        let mut too_many = params.clone();
+        too_many.insert("a", "b");
+        too_many.insert("c", "d");
+        assert!(matches!(
+            limits.check(&too_many),
+            Err(StartupParamsError::TooMany { count: 4, limit: 3 })
+        ));
+
+        let mut too_large = params;
+        too_large.insert("options", "-c search_path=public");
+        assert!(matches!(
+            limits.check(&too_large),
+            Err(StartupParamsError::TooLarge {
+                size: 47,
+                limit: 32
+            })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_client_connection_limit() {
+        let limit = ClientConnectionLimit::new(1, Duration::from_secs(1));
+        let permit = limit.acquire().await.unwrap();
+
+        // waits for a slot, up to the queue timeout.
+        let start = tokio::time::Instant::now();
+        assert!(limit.acquire().await.is_none());
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        // gets the slot of a connection that closes in the meantime.
+        let (waiting, ()) = tokio::join!(limit.acquire(), async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            drop(permit);
+        });
+        assert!(waiting.is_some());
+
+        let limit = ClientConnectionLimit::new(0, Duration::ZERO);
+        assert!(limit.acquire().await.is_none());
+    }
 }
@@ -32,7 +32,7 @@ pub async fn task_main(
 
     // When set for the server socket, the keepalive setting
     // will be inherited by all accepted client sockets.
-    socket2::SockRef::from(&listener).set_keepalive(true)?;
+    socket2::SockRef::from(&listener).set_keepalive(config.client_tcp_keepalive.is_some())?;
 
     let connections = tokio_util::task::task_tracker::TaskTracker::new();
     let cancellations = tokio_util::task::task_tracker::TaskTracker::new();
@@ -50,6 +50,7 @@ pub async fn task_main(
         let session_id = uuid::Uuid::new_v4();
         let cancellation_handler = Arc::clone(&cancellation_handler);
         let cancellations = cancellations.clone();
+        let shutdown = cancellation_token.clone();
 
         debug!(protocol = "tcp", %session_id, "accepted new TCP connection");
 
@@ -80,7 +81,13 @@ pub async fn task_main(
                 ),
             };
 
-            match socket.set_nodelay(true) {
+            let res = socket
+                .set_nodelay(true)
+                .and_then(|()| match &config.client_tcp_keepalive {
+                    Some(keepalive) => keepalive.apply(&socket),
+                    None => Ok(()),
+                });
+            match res {
                 Ok(()) => {}
                 Err(e) => {
                     error!(
@@ -116,7 +123,7 @@ pub async fn task_main(
                 Ok(Some(p)) => {
                     ctx.set_success();
                     let _disconnect = ctx.log_connect();
-                    match p.proxy_pass().await {
+                    match p.proxy_pass(shutdown).await {
                         Ok(()) => {}
                         Err(ErrorSource::Client(e)) => {
                             error!(
@@ -203,6 +210,12 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
     };
     drop(pause);
 
+    if let Err(e) = config.startup_params_limits.check(&params) {
+        Err(stream
+            .throw_error(e, Some(ctx), config.client_error_verbosity)
+            .await)?;
+    }
+
     ctx.set_db_options(params.clone());
 
     let (node_info, mut auth_info, user_info) = match backend
@@ -210,7 +223,9 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
         .await
     {
         Ok(auth_result) => auth_result,
-        Err(e) => Err(stream.throw_error(e, Some(ctx)).await)?,
+        Err(e) => Err(stream
+            .throw_error(e, Some(ctx), config.client_error_verbosity)
+            .await)?,
     };
     auth_info.set_startup_params(&params, true);
 
@@ -223,12 +238,20 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
         config.wake_compute_retry_config,
         &config.connect_to_compute,
     )
-    .or_else(|e| async { Err(stream.throw_error(e, Some(ctx)).await) })
+    .or_else(|e| async {
+        Err(stream
+            .throw_error(e, Some(ctx), config.client_error_verbosity)
+            .await)
+    })
     .await?;
 
     let pg_settings = auth_info
         .authenticate(ctx, &mut node, &user_info)
-        .or_else(|e| async { Err(stream.throw_error(e, Some(ctx)).await) })
+        .or_else(|e| async {
+            Err(stream
+                .throw_error(e, Some(ctx), config.client_error_verbosity)
+                .await)
+        })
         .await?;
 
     let session = cancellation_handler.get_key();
@@ -255,6 +278,8 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
 
         aux: node.aux,
         private_link_id: None,
+        idle_timeout: config.client_idle_timeout,
+        close_on_shutdown: config.close_idle_sessions_on_shutdown,
 
         _cancel_on_shutdown: cancel_on_shutdown,
 
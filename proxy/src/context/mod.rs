@@ -131,6 +131,8 @@ pub fn new(session_id: Uuid, conn_info: ConnectionInfo, protocol: Protocol) -> S
             %conn_info,
             ep = tracing::field::Empty,
             role = tracing::field::Empty,
+            compute_id = tracing::field::Empty,
+            pid = tracing::field::Empty,
         );
 
         let inner = RequestContextInner {
@@ -243,6 +245,21 @@ pub(crate) fn set_endpoint_id(&self, endpoint_id: EndpointId) {
             .set_endpoint_id(endpoint_id);
     }
 
+    /// Endpoint the client asked for: either already resolved, or passed via
+    /// the `endpoint=` (or `project=`) startup option.
+    pub(crate) fn requested_endpoint(&self) -> Option<EndpointId> {
+        let this = self.0.try_lock().expect("should not deadlock");
+        if let Some(endpoint_id) = &this.endpoint_id {
+            return Some(endpoint_id.clone());
+        }
+        this.pg_options
+            .as_ref()?
+            .get("options")?
+            .split_whitespace()
+            .find_map(crate::auth::parse_endpoint_param)
+            .map(EndpointId::from)
+    }
+
     pub(crate) fn set_dbname(&self, dbname: DbName) {
         self.0
             .try_lock()
@@ -257,6 +274,13 @@ pub(crate) fn set_user(&self, user: RoleName) {
             .set_user(user);
     }
 
+    pub(crate) fn set_application(&self, app: Option<SmolStr>) {
+        self.0
+            .try_lock()
+            .expect("should not deadlock")
+            .set_application(app);
+    }
+
     pub(crate) fn set_user_agent(&self, user_agent: Option<SmolStr>) {
         self.0
             .try_lock()
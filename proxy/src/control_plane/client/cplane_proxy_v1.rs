@@ -14,7 +14,9 @@
 use tokio::time::Instant;
 use tracing::{Instrument, debug, info, info_span, warn};
 
-use super::super::messages::{ControlPlaneErrorMessage, GetEndpointAccessControl, WakeCompute};
+use super::super::messages::{
+    ControlPlaneErrorMessage, GetEndpointAccessControl, ReadAddress, WakeCompute,
+};
 use crate::auth::backend::ComputeUserInfo;
 use crate::auth::backend::jwt::AuthRule;
 use crate::context::RequestContext;
@@ -24,13 +26,14 @@
 };
 use crate::control_plane::locks::ApiLocks;
 use crate::control_plane::messages::{ColdStartInfo, EndpointJwksResponse, Reason};
+use crate::control_plane::read_endpoints::ReadEndpoints;
 use crate::control_plane::{
     AccessBlockerFlags, AuthInfo, AuthSecret, CachedNodeInfo, EndpointAccessControl, NodeInfo,
     RoleAccessControl,
 };
 use crate::metrics::Metrics;
 use crate::rate_limiter::WakeComputeRateLimiter;
-use crate::types::{EndpointCacheKey, EndpointId, RoleName};
+use crate::types::{EndpointCacheKey, EndpointId, Host, RoleName};
 use crate::{compute, http, scram};
 
 pub(crate) const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
@@ -63,8 +66,8 @@ pub fn new(
         }
     }
 
-    pub(crate) fn url(&self) -> &str {
-        self.endpoint.url().as_str()
+    pub(crate) fn urls(&self) -> impl Iterator<Item = &str> {
+        self.endpoint.urls().map(|url| url.as_str())
     }
 
     async fn do_get_auth_req(
@@ -134,12 +137,14 @@ async fn do_get_auth_req(
                 .proxy
                 .allowed_vpc_endpoint_ids
                 .observe(allowed_vpc_endpoint_ids.len() as f64);
+            let allowed_databases = body.allowed_databases.unwrap_or_default();
             let block_public_connections = body.block_public_connections.unwrap_or_default();
             let block_vpc_connections = body.block_vpc_connections.unwrap_or_default();
             Ok(AuthInfo {
                 secret,
                 allowed_ips,
                 allowed_vpc_endpoint_ids,
+                allowed_databases,
                 project_id: body.project_id,
                 account_id: body.account_id,
                 access_blocker_flags: AccessBlockerFlags {
@@ -262,6 +267,14 @@ async fn do_wake_compute(
                 Some(_) => SslMode::Require,
                 None => SslMode::Disable,
             };
+            let read_endpoints = body
+                .read_addresses
+                .into_iter()
+                .map(|read_address| {
+                    read_endpoint_conn_info(read_address, ssl_mode, body.server_name.as_deref())
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
             let host = match body.server_name {
                 Some(host) => host.into(),
                 None => host.into(),
@@ -273,7 +286,10 @@ async fn do_wake_compute(
                     host,
                     port,
                     ssl_mode,
+                    unix_socket: None,
+                    tls_server_name: None,
                 },
+                read_endpoints: ReadEndpoints::new(read_endpoints),
                 aux: body.aux,
             };
 
@@ -312,6 +328,7 @@ async fn get_role_access_control(
         let control = EndpointAccessControl {
             allowed_ips: Arc::new(auth_info.allowed_ips),
             allowed_vpce: Arc::new(auth_info.allowed_vpc_endpoint_ids),
+            allowed_databases: Arc::new(auth_info.allowed_databases),
             flags: auth_info.access_blocker_flags,
             rate_limits: auth_info.rate_limits,
         };
@@ -358,6 +375,7 @@ async fn get_endpoint_access_control(
         let control = EndpointAccessControl {
             allowed_ips: Arc::new(auth_info.allowed_ips),
             allowed_vpce: Arc::new(auth_info.allowed_vpc_endpoint_ids),
+            allowed_databases: Arc::new(auth_info.allowed_databases),
             flags: auth_info.access_blocker_flags,
             rate_limits: auth_info.rate_limits,
         };
@@ -530,10 +548,76 @@ fn parse_host_port(input: &str) -> Option<(&str, u16)> {
     Some((host.trim_matches(ipv6_brackets), port.parse().ok()?))
 }
 
+/// Connection info of a read endpoint, which is dialed by its own address. Its certificate is
+/// verified against its own server name if the control plane reports one, and otherwise
+/// against the primary's, on the assumption that the computes of an endpoint share a
+/// certificate.
+fn read_endpoint_conn_info(
+    read_address: ReadAddress,
+    ssl_mode: SslMode,
+    primary_server_name: Option<&str>,
+) -> Result<compute::ConnectInfo, WakeComputeError> {
+    let (address, server_name) = match read_address {
+        ReadAddress::Address(address) => (address, None),
+        ReadAddress::Endpoint {
+            address,
+            server_name,
+        } => (address, server_name),
+    };
+    let Some((host, port)) = parse_host_port(&address) else {
+        return Err(WakeComputeError::BadComputeAddress(address));
+    };
+    Ok(compute::ConnectInfo {
+        host_addr: IpAddr::from_str(host).ok(),
+        host: host.into(),
+        port,
+        ssl_mode,
+        unix_socket: None,
+        tls_server_name: server_name
+            .as_deref()
+            .or(primary_server_name)
+            .map(Host::from),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_read_endpoint_tls_server_name() {
+        let primary = Some("ep-primary.compute.local");
+
+        let conn_info = read_endpoint_conn_info(
+            ReadAddress::Endpoint {
+                address: "10.0.0.2:5432".into(),
+                server_name: Some("ep-read.compute.local".to_owned()),
+            },
+            SslMode::Require,
+            primary,
+        )
+        .unwrap();
+        assert_eq!(conn_info.host, "10.0.0.2");
+        assert_eq!(conn_info.tls_server_name(), "ep-read.compute.local");
+
+        // without a name of its own, the read endpoint shares the primary's certificate
+        let conn_info = read_endpoint_conn_info(
+            ReadAddress::Address("10.0.0.3:5432".into()),
+            SslMode::Require,
+            primary,
+        )
+        .unwrap();
+        assert_eq!(conn_info.tls_server_name(), "ep-primary.compute.local");
+
+        let conn_info = read_endpoint_conn_info(
+            ReadAddress::Address("10.0.0.3:5432".into()),
+            SslMode::Disable,
+            None,
+        )
+        .unwrap();
+        assert_eq!(conn_info.tls_server_name(), "10.0.0.3");
+    }
+
     #[test]
     fn test_parse_host_port_v4() {
         let (host, port) = parse_host_port("127.0.0.1:5432").expect("failed to parse");
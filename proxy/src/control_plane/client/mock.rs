@@ -127,6 +127,7 @@ async fn do_get_auth_info(
             secret,
             allowed_ips,
             allowed_vpc_endpoint_ids: vec![],
+            allowed_databases: vec![],
             project_id: None,
             account_id: None,
             access_blocker_flags: AccessBlockerFlags::default(),
@@ -178,17 +179,22 @@ async fn do_wake_compute(&self) -> Result<NodeInfo, WakeComputeError> {
                 host: "localhost".into(),
                 port,
                 ssl_mode: SslMode::Disable,
+                unix_socket: None,
+                tls_server_name: None,
             },
             Some(host) => ConnectInfo {
                 host_addr: IpAddr::from_str(host).ok(),
                 host: host.into(),
                 port,
                 ssl_mode: SslMode::Disable,
+                unix_socket: None,
+                tls_server_name: None,
             },
         };
 
         let node = NodeInfo {
             conn_info,
+            read_endpoints: None,
             aux: MetricsAuxInfo {
                 endpoint_id: (&EndpointId::from("endpoint")).into(),
                 project_id: (&ProjectId::from("project")).into(),
@@ -233,6 +239,7 @@ async fn get_endpoint_access_control(
         Ok(EndpointAccessControl {
             allowed_ips: Arc::new(info.allowed_ips),
             allowed_vpce: Arc::new(info.allowed_vpc_endpoint_ids),
+            allowed_databases: Arc::new(info.allowed_databases),
             flags: info.access_blocker_flags,
             rate_limits: info.rate_limits,
         })
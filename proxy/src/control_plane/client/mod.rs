@@ -8,7 +8,7 @@
 
 use clashmap::ClashMap;
 use tokio::time::Instant;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use super::{EndpointAccessControl, RoleAccessControl};
 use crate::auth::backend::ComputeUserInfo;
@@ -17,6 +17,7 @@
 use crate::cache::project_info::ProjectInfoCacheImpl;
 use crate::config::{CacheOptions, EndpointCacheConfig, ProjectInfoCacheOptions};
 use crate::context::RequestContext;
+use crate::control_plane::errors::ShouldFailover;
 use crate::control_plane::{CachedNodeInfo, ControlPlaneApi, NodeInfoCache, errors};
 use crate::error::ReportableError;
 use crate::metrics::ApiLockMetrics;
@@ -31,12 +32,139 @@ pub enum ControlPlaneClient {
     /// Local mock control plane.
     #[cfg(any(test, feature = "testing"))]
     PostgresMock(mock::MockControlPlane),
+    /// Control planes asked in order: the next one is only asked when the ones before it are
+    /// unreachable, never when one of them gave a definitive answer.
+    Failover(Arc<[FailoverMember]>),
     /// Internal testing
     #[cfg(test)]
     #[allow(private_interfaces)]
     Test(Box<dyn TestControlPlaneClient>),
 }
 
+/// A control plane in a [failover list](ControlPlaneClient::Failover). Unlike
+/// [`ControlPlaneClient`], it cannot be a failover list itself.
+#[derive(Clone)]
+pub enum FailoverMember {
+    /// Proxy V1 control plane API
+    ProxyV1(cplane_proxy_v1::NeonControlPlaneClient),
+    /// Local mock control plane.
+    #[cfg(any(test, feature = "testing"))]
+    PostgresMock(mock::MockControlPlane),
+}
+
+impl From<FailoverMember> for ControlPlaneClient {
+    fn from(member: FailoverMember) -> Self {
+        match member {
+            FailoverMember::ProxyV1(api) => Self::ProxyV1(api),
+            #[cfg(any(test, feature = "testing"))]
+            FailoverMember::PostgresMock(api) => Self::PostgresMock(api),
+        }
+    }
+}
+
+impl ControlPlaneClient {
+    /// Asks `members` in order, falling back to the next one while they are unreachable.
+    pub fn failover(members: impl IntoIterator<Item = FailoverMember>) -> Self {
+        let mut members: Vec<_> = members.into_iter().collect();
+        if members.len() == 1 {
+            members.pop().expect("checked the length").into()
+        } else {
+            Self::Failover(members.into())
+        }
+    }
+
+    /// The first Proxy V1 client, whose caches the proxy maintains. All the Proxy V1 clients of
+    /// a failover list share the same caches.
+    pub(crate) fn proxy_v1(&self) -> Option<&cplane_proxy_v1::NeonControlPlaneClient> {
+        match self {
+            Self::ProxyV1(api) => Some(api),
+            Self::Failover(members) => members.iter().find_map(|member| match member {
+                FailoverMember::ProxyV1(api) => Some(api),
+                #[cfg(any(test, feature = "testing"))]
+                FailoverMember::PostgresMock(_) => None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Calls `f` on each member in turn, until one of them is reachable or there are none left.
+async fn failover<'a, T, E, Fut>(
+    members: &'a [FailoverMember],
+    mut f: impl FnMut(&'a FailoverMember) -> Fut,
+) -> Result<T, E>
+where
+    Fut: Future<Output = Result<T, E>>,
+    E: ShouldFailover + std::fmt::Debug,
+{
+    let (last, members) = members
+        .split_last()
+        .expect("there is at least one control plane client");
+    for (i, member) in members.iter().enumerate() {
+        match f(member).await {
+            // the control plane did not answer, the next one might.
+            Err(e) if e.should_failover() => {
+                warn!(error = ?e, client = i, "control plane is unreachable, failing over");
+            }
+            // an answer, even a rejection, is final.
+            res => return res,
+        }
+    }
+    f(last).await
+}
+
+impl ControlPlaneApi for FailoverMember {
+    async fn get_role_access_control(
+        &self,
+        ctx: &RequestContext,
+        endpoint: &EndpointId,
+        role: &crate::types::RoleName,
+    ) -> Result<RoleAccessControl, errors::GetAuthInfoError> {
+        match self {
+            Self::ProxyV1(api) => api.get_role_access_control(ctx, endpoint, role).await,
+            #[cfg(any(test, feature = "testing"))]
+            Self::PostgresMock(api) => api.get_role_access_control(ctx, endpoint, role).await,
+        }
+    }
+
+    async fn get_endpoint_access_control(
+        &self,
+        ctx: &RequestContext,
+        endpoint: &EndpointId,
+        role: &crate::types::RoleName,
+    ) -> Result<EndpointAccessControl, errors::GetAuthInfoError> {
+        match self {
+            Self::ProxyV1(api) => api.get_endpoint_access_control(ctx, endpoint, role).await,
+            #[cfg(any(test, feature = "testing"))]
+            Self::PostgresMock(api) => api.get_endpoint_access_control(ctx, endpoint, role).await,
+        }
+    }
+
+    async fn get_endpoint_jwks(
+        &self,
+        ctx: &RequestContext,
+        endpoint: &EndpointId,
+    ) -> Result<Vec<AuthRule>, errors::GetEndpointJwksError> {
+        match self {
+            Self::ProxyV1(api) => api.get_endpoint_jwks(ctx, endpoint).await,
+            #[cfg(any(test, feature = "testing"))]
+            Self::PostgresMock(api) => api.get_endpoint_jwks(ctx, endpoint).await,
+        }
+    }
+
+    async fn wake_compute(
+        &self,
+        ctx: &RequestContext,
+        user_info: &ComputeUserInfo,
+    ) -> Result<CachedNodeInfo, errors::WakeComputeError> {
+        match self {
+            Self::ProxyV1(api) => api.wake_compute(ctx, user_info).await,
+            #[cfg(any(test, feature = "testing"))]
+            Self::PostgresMock(api) => api.wake_compute(ctx, user_info).await,
+        }
+    }
+}
+
 impl ControlPlaneApi for ControlPlaneClient {
     async fn get_role_access_control(
         &self,
@@ -48,6 +176,12 @@ async fn get_role_access_control(
             Self::ProxyV1(api) => api.get_role_access_control(ctx, endpoint, role).await,
             #[cfg(any(test, feature = "testing"))]
             Self::PostgresMock(api) => api.get_role_access_control(ctx, endpoint, role).await,
+            Self::Failover(members) => {
+                failover(members, |member| {
+                    member.get_role_access_control(ctx, endpoint, role)
+                })
+                .await
+            }
             #[cfg(test)]
             Self::Test(_api) => {
                 unreachable!("this function should never be called in the test backend")
@@ -65,6 +199,12 @@ async fn get_endpoint_access_control(
             Self::ProxyV1(api) => api.get_endpoint_access_control(ctx, endpoint, role).await,
             #[cfg(any(test, feature = "testing"))]
             Self::PostgresMock(api) => api.get_endpoint_access_control(ctx, endpoint, role).await,
+            Self::Failover(members) => {
+                failover(members, |member| {
+                    member.get_endpoint_access_control(ctx, endpoint, role)
+                })
+                .await
+            }
             #[cfg(test)]
             Self::Test(api) => api.get_access_control(),
         }
@@ -79,6 +219,9 @@ async fn get_endpoint_jwks(
             Self::ProxyV1(api) => api.get_endpoint_jwks(ctx, endpoint).await,
             #[cfg(any(test, feature = "testing"))]
             Self::PostgresMock(api) => api.get_endpoint_jwks(ctx, endpoint).await,
+            Self::Failover(members) => {
+                failover(members, |member| member.get_endpoint_jwks(ctx, endpoint)).await
+            }
             #[cfg(test)]
             Self::Test(_api) => Ok(vec![]),
         }
@@ -93,6 +236,9 @@ async fn wake_compute(
             Self::ProxyV1(api) => api.wake_compute(ctx, user_info).await,
             #[cfg(any(test, feature = "testing"))]
             Self::PostgresMock(api) => api.wake_compute(ctx, user_info).await,
+            Self::Failover(members) => {
+                failover(members, |member| member.wake_compute(ctx, user_info)).await
+            }
             #[cfg(test)]
             Self::Test(api) => api.wake_compute(),
         }
@@ -10,6 +10,16 @@
 /// A go-to error message which doesn't leak any detail.
 pub(crate) const REQUEST_FAILED: &str = "Control plane request failed";
 
+/// Whether a failed control plane request should be sent to the next control plane of a
+/// [failover list](crate::control_plane::client::ControlPlaneClient::Failover).
+///
+/// Only errors that say nothing about the request itself fail over: the control plane could
+/// not be reached, or failed without giving a reason. A definitive answer, like an endpoint
+/// that does not exist or a quota that is exceeded, would only be repeated by the next one.
+pub(crate) trait ShouldFailover {
+    fn should_failover(&self) -> bool;
+}
+
 /// Common console API error.
 #[derive(Debug, Error)]
 pub(crate) enum ControlPlaneError {
@@ -79,6 +89,18 @@ fn could_retry(&self) -> bool {
     }
 }
 
+impl ShouldFailover for ControlPlaneError {
+    fn should_failover(&self) -> bool {
+        match self {
+            Self::Transport(_) => true,
+            // the control plane answered, but failed without saying why.
+            Self::Message(e) => {
+                e.http_status_code.is_server_error() && matches!(e.get_reason(), Reason::Unknown)
+            }
+        }
+    }
+}
+
 impl From<reqwest::Error> for ControlPlaneError {
     fn from(e: reqwest::Error) -> Self {
         io::Error::other(e).into()
@@ -136,6 +158,16 @@ fn get_error_kind(&self) -> crate::error::ErrorKind {
     }
 }
 
+impl ShouldFailover for GetAuthInfoError {
+    fn should_failover(&self) -> bool {
+        match self {
+            Self::BadSecret => false,
+            Self::ApiError(e) => e.should_failover(),
+            Self::UnknownEndpoint => false,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub(crate) enum WakeComputeError {
     #[error("Console responded with a malformed compute address: {0}")]
@@ -198,6 +230,18 @@ fn could_retry(&self) -> bool {
     }
 }
 
+impl ShouldFailover for WakeComputeError {
+    fn should_failover(&self) -> bool {
+        match self {
+            Self::BadComputeAddress(_) => false,
+            Self::ControlPlane(e) => e.should_failover(),
+            // local limits, the next control plane would share them.
+            Self::TooManyConnections => false,
+            Self::TooManyConnectionAttempts(_) => false,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum GetEndpointJwksError {
     #[error("endpoint not found")]
@@ -224,3 +268,63 @@ pub enum GetEndpointJwksError {
     #[error(transparent)]
     TaskJoin(#[from] tokio::task::JoinError),
 }
+
+impl ShouldFailover for GetEndpointJwksError {
+    fn should_failover(&self) -> bool {
+        match self {
+            Self::EndpointNotFound => false,
+            Self::RequestBuild(_) => false,
+            Self::RequestExecute(_) => true,
+            Self::ControlPlane(e) => e.should_failover(),
+            #[cfg(any(test, feature = "testing"))]
+            Self::TokioPostgres(e) => e.as_db_error().is_none(),
+            #[cfg(any(test, feature = "testing"))]
+            Self::ParseUrl(_) => false,
+            #[cfg(any(test, feature = "testing"))]
+            Self::TaskJoin(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_plane::messages::{Details, ErrorInfo, Status};
+
+    fn message(http_status_code: http::StatusCode, reason: Option<Reason>) -> ControlPlaneError {
+        ControlPlaneError::Message(Box::new(ControlPlaneErrorMessage {
+            error: "error".into(),
+            http_status_code,
+            status: reason.map(|reason| Status {
+                code: "code".into(),
+                message: "message".into(),
+                details: Details {
+                    error_info: Some(ErrorInfo { reason }),
+                    retry_info: None,
+                    user_facing_message: None,
+                },
+            }),
+        }))
+    }
+
+    #[test]
+    fn failover_only_when_unreachable() {
+        let unreachable = ControlPlaneError::Transport(io::ErrorKind::ConnectionRefused.into());
+        assert!(WakeComputeError::from(unreachable).should_failover());
+
+        let unexplained = message(http::StatusCode::BAD_GATEWAY, None);
+        assert!(GetAuthInfoError::from(unexplained).should_failover());
+
+        let not_found = message(http::StatusCode::NOT_FOUND, Some(Reason::EndpointNotFound));
+        assert!(!WakeComputeError::from(not_found).should_failover());
+
+        let quota = message(
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            Some(Reason::ComputeTimeQuotaExceeded),
+        );
+        assert!(!GetAuthInfoError::from(quota).should_failover());
+
+        assert!(!GetAuthInfoError::BadSecret.should_failover());
+        assert!(!WakeComputeError::TooManyConnections.should_failover());
+    }
+}
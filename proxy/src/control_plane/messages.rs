@@ -7,6 +7,7 @@
 use crate::auth::IpPattern;
 use crate::intern::{AccountIdInt, BranchIdInt, EndpointIdInt, ProjectIdInt, RoleNameInt};
 use crate::proxy::retry::CouldRetry;
+use crate::types::DbName;
 
 /// Generic error response with human-readable description.
 /// Note that we can't always present it to user as is.
@@ -233,6 +234,7 @@ pub(crate) struct GetEndpointAccessControl {
 
     pub(crate) allowed_ips: Option<Vec<IpPattern>>,
     pub(crate) allowed_vpc_endpoint_ids: Option<Vec<String>>,
+    pub(crate) allowed_databases: Option<Vec<DbName>>,
     pub(crate) block_public_connections: Option<bool>,
     pub(crate) block_vpc_connections: Option<bool>,
 
@@ -264,9 +266,24 @@ pub struct LeakyBucketSetting {
 pub(crate) struct WakeCompute {
     pub(crate) address: Box<str>,
     pub(crate) server_name: Option<String>,
+    /// Additional computes that can serve read-only sessions.
+    #[serde(default)]
+    pub(crate) read_addresses: Vec<ReadAddress>,
     pub(crate) aux: MetricsAuxInfo,
 }
 
+/// A compute that can serve read-only sessions: either just its address, or its address and
+/// the name its TLS certificate is issued for.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ReadAddress {
+    Address(Box<str>),
+    Endpoint {
+        address: Box<str>,
+        server_name: Option<String>,
+    },
+}
+
 /// Async response which concludes the console redirect auth flow.
 /// Also known as `kickResponse` in the console.
 #[derive(Debug, Deserialize)]
@@ -490,6 +507,27 @@ fn parse_wake_compute() -> anyhow::Result<()> {
             "aux": dummy_aux(),
         });
         serde_json::from_str::<WakeCompute>(&json.to_string())?;
+
+        let json = json!({
+            "address": "0.0.0.0",
+            "read_addresses": [
+                "0.0.0.1:5432",
+                {"address": "0.0.0.2:5432", "server_name": "ro-2.compute.local"},
+            ],
+            "aux": dummy_aux(),
+        });
+        let wake_compute = serde_json::from_str::<WakeCompute>(&json.to_string())?;
+        assert!(matches!(
+            &wake_compute.read_addresses[..],
+            [
+                ReadAddress::Address(_),
+                ReadAddress::Endpoint {
+                    server_name: Some(_),
+                    ..
+                }
+            ]
+        ));
+
         Ok(())
     }
 
@@ -523,6 +561,15 @@ fn parse_get_role_secret() -> anyhow::Result<()> {
             "project_id": "project",
         });
         serde_json::from_str::<GetEndpointAccessControl>(&json.to_string())?;
+        let json = json!({
+            "role_secret": "secret",
+            "allowed_databases": ["neondb", "analytics"],
+        });
+        let control = serde_json::from_str::<GetEndpointAccessControl>(&json.to_string())?;
+        assert_eq!(
+            control.allowed_databases,
+            Some(vec![DbName::from("neondb"), DbName::from("analytics")])
+        );
 
         Ok(())
     }
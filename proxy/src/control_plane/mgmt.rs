@@ -1,4 +1,5 @@
 use std::convert::Infallible;
+use std::sync::Arc;
 
 use anyhow::Context;
 use once_cell::sync::Lazy;
@@ -26,7 +27,11 @@ pub(crate) fn notify(psql_session_id: &str, msg: ComputeReady) -> Result<(), wai
 
 /// Management API listener task.
 /// It spawns management response handlers needed for the console redirect auth flow.
-pub async fn task_main(listener: TcpListener) -> anyhow::Result<Infallible> {
+/// With `tls_config`, connections must use TLS, and the client certificate is verified by it.
+pub async fn task_main(
+    listener: TcpListener,
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+) -> anyhow::Result<Infallible> {
     scopeguard::defer! {
         info!("mgmt has shut down");
     }
@@ -40,6 +45,7 @@ pub async fn task_main(listener: TcpListener) -> anyhow::Result<Infallible> {
             .context("failed to set client socket option")?;
 
         let span = info_span!("mgmt", peer = %peer_addr);
+        let tls_config = tls_config.clone();
 
         tokio::task::spawn(
             async move {
@@ -52,7 +58,7 @@ pub async fn task_main(listener: TcpListener) -> anyhow::Result<Infallible> {
                     info!("management API task cancelled");
                 });
 
-                if let Err(e) = handle_connection(socket).await {
+                if let Err(e) = handle_connection(socket, tls_config).await {
                     error!("serving failed with an error: {e}");
                 } else {
                     info!("serving completed");
@@ -66,8 +72,12 @@ pub async fn task_main(listener: TcpListener) -> anyhow::Result<Infallible> {
     }
 }
 
-async fn handle_connection(socket: TcpStream) -> Result<(), QueryError> {
-    let pgbackend = PostgresBackend::new(socket, AuthType::Trust, None)?;
+async fn handle_connection(
+    socket: TcpStream,
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+) -> Result<(), QueryError> {
+    // with TLS configured, the backend refuses to start plaintext sessions.
+    let pgbackend = PostgresBackend::new(socket, AuthType::Trust, tls_config)?;
     pgbackend
         .run(&mut MgmtHandler, &CancellationToken::new())
         .await
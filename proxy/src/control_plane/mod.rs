@@ -9,6 +9,9 @@
 
 pub(crate) mod errors;
 
+/// Additional computes for read-only sessions.
+pub(crate) mod read_endpoints;
+
 use std::sync::Arc;
 
 use messages::EndpointRateLimitConfig;
@@ -20,10 +23,11 @@
 use crate::config::ComputeConfig;
 use crate::context::RequestContext;
 use crate::control_plane::messages::{ControlPlaneErrorMessage, MetricsAuxInfo};
+use crate::control_plane::read_endpoints::{ReadEndpointGuard, ReadEndpointPolicy, ReadEndpoints};
 use crate::intern::{AccountIdInt, EndpointIdInt, ProjectIdInt};
 use crate::protocol2::ConnectionInfoExtra;
 use crate::rate_limiter::{EndpointRateLimiter, LeakyBucketConfig};
-use crate::types::{EndpointCacheKey, EndpointId, RoleName};
+use crate::types::{DbName, EndpointCacheKey, EndpointId, RoleName};
 use crate::{compute, scram};
 
 /// Various cache-related types.
@@ -53,6 +57,8 @@ pub(crate) struct AuthInfo {
     pub(crate) allowed_ips: Vec<IpPattern>,
     /// List of VPC endpoints allowed for the autorization.
     pub(crate) allowed_vpc_endpoint_ids: Vec<String>,
+    /// List of databases that can be connected to. Empty means all databases are allowed.
+    pub(crate) allowed_databases: Vec<DbName>,
     /// Project ID. This is used for cache invalidation.
     pub(crate) project_id: Option<ProjectIdInt>,
     /// Account ID. This is used for cache invalidation.
@@ -68,6 +74,9 @@ pub(crate) struct AuthInfo {
 pub(crate) struct NodeInfo {
     pub(crate) conn_info: compute::ConnectInfo,
 
+    /// Additional computes that can serve read-only sessions, if any.
+    pub(crate) read_endpoints: Option<Arc<ReadEndpoints>>,
+
     /// Labels for proxy's metrics.
     pub(crate) aux: MetricsAuxInfo,
 }
@@ -80,6 +89,22 @@ pub(crate) async fn connect(
     ) -> Result<compute::ComputeConnection, compute::ConnectionError> {
         self.conn_info.connect(ctx, &self.aux, config).await
     }
+
+    /// Pick the compute to open a new connection to. Read-only sessions go to one of
+    /// the read endpoints if there are any, everything else goes to the primary.
+    pub(crate) fn select_compute(
+        &self,
+        read_only: bool,
+        policy: ReadEndpointPolicy,
+    ) -> (&compute::ConnectInfo, Option<ReadEndpointGuard>) {
+        match &self.read_endpoints {
+            Some(read_endpoints) if read_only => {
+                let (conn_info, guard) = read_endpoints.select(policy);
+                (conn_info, Some(guard))
+            }
+            _ => (&self.conn_info, None),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Default)]
@@ -101,6 +126,7 @@ pub struct RoleAccessControl {
 pub struct EndpointAccessControl {
     pub allowed_ips: Arc<Vec<IpPattern>>,
     pub allowed_vpce: Arc<Vec<String>>,
+    pub allowed_databases: Arc<Vec<DbName>>,
     pub flags: AccessBlockerFlags,
 
     pub rate_limits: EndpointRateLimitConfig,
@@ -143,6 +169,14 @@ pub fn check(
         Ok(())
     }
 
+    pub fn check_database(&self, dbname: &DbName) -> Result<(), AuthError> {
+        // An empty list means all databases are allowed.
+        if !self.allowed_databases.is_empty() && !self.allowed_databases.contains(dbname) {
+            return Err(AuthError::DatabaseNotAllowed(dbname.clone()));
+        }
+        Ok(())
+    }
+
     pub fn connection_attempt_rate_limit(
         &self,
         ctx: &RequestContext,
@@ -0,0 +1,127 @@
+//! Additional computes that can serve read-only sessions of an endpoint.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::compute;
+
+/// How to pick among the read endpoints for a new read-only connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReadEndpointPolicy {
+    /// Cycle through the read endpoints in order.
+    #[default]
+    RoundRobin,
+    /// Pick the read endpoint with the fewest open connections from this proxy.
+    LeastConnections,
+}
+
+pub(crate) struct ReadEndpoints {
+    endpoints: Vec<ReadEndpoint>,
+    next: AtomicUsize,
+}
+
+struct ReadEndpoint {
+    conn_info: compute::ConnectInfo,
+    connections: Arc<AtomicUsize>,
+}
+
+/// Counts a connection towards its read endpoint for as long as it is held.
+pub(crate) struct ReadEndpointGuard(Arc<AtomicUsize>);
+
+impl Drop for ReadEndpointGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ReadEndpoints {
+    /// Returns `None` if there are no read endpoints to choose from.
+    pub(crate) fn new(conn_infos: Vec<compute::ConnectInfo>) -> Option<Arc<Self>> {
+        if conn_infos.is_empty() {
+            return None;
+        }
+
+        let endpoints = conn_infos
+            .into_iter()
+            .map(|conn_info| ReadEndpoint {
+                conn_info,
+                connections: Arc::new(AtomicUsize::new(0)),
+            })
+            .collect();
+
+        Some(Arc::new(Self {
+            endpoints,
+            next: AtomicUsize::new(0),
+        }))
+    }
+
+    pub(crate) fn select(
+        &self,
+        policy: ReadEndpointPolicy,
+    ) -> (&compute::ConnectInfo, ReadEndpointGuard) {
+        let endpoint = match policy {
+            ReadEndpointPolicy::RoundRobin => {
+                let next = self.next.fetch_add(1, Ordering::Relaxed);
+                &self.endpoints[next % self.endpoints.len()]
+            }
+            ReadEndpointPolicy::LeastConnections => self
+                .endpoints
+                .iter()
+                .min_by_key(|endpoint| endpoint.connections.load(Ordering::Relaxed))
+                .expect("read endpoints should not be empty"),
+        };
+
+        endpoint.connections.fetch_add(1, Ordering::Relaxed);
+        (
+            &endpoint.conn_info,
+            ReadEndpointGuard(endpoint.connections.clone()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use postgres_client::config::SslMode;
+
+    use super::*;
+
+    fn conn_info(host: &str) -> compute::ConnectInfo {
+        compute::ConnectInfo {
+            host_addr: None,
+            host: host.into(),
+            port: 5432,
+            ssl_mode: SslMode::Disable,
+            unix_socket: None,
+            tls_server_name: None,
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        let endpoints = ReadEndpoints::new(vec![conn_info("a"), conn_info("b")]).unwrap();
+
+        for expected in ["a", "b", "a", "b"] {
+            let (host, _guard) = endpoints.select(ReadEndpointPolicy::RoundRobin);
+            assert_eq!(host.host, expected);
+        }
+    }
+
+    #[test]
+    fn least_connections() {
+        let endpoints = ReadEndpoints::new(vec![conn_info("a"), conn_info("b")]).unwrap();
+
+        let (host, guard_a) = endpoints.select(ReadEndpointPolicy::LeastConnections);
+        assert_eq!(host.host, "a");
+        let (host, _guard_b) = endpoints.select(ReadEndpointPolicy::LeastConnections);
+        assert_eq!(host.host, "b");
+
+        drop(guard_a);
+        let (host, _guard_a) = endpoints.select(ReadEndpointPolicy::LeastConnections);
+        assert_eq!(host.host, "a");
+    }
+
+    #[test]
+    fn empty() {
+        assert!(ReadEndpoints::new(vec![]).is_none());
+    }
+}
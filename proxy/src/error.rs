@@ -20,6 +20,45 @@ pub(crate) trait UserFacingError: ReportableError {
     fn to_string_client(&self) -> String {
         self.to_string()
     }
+
+    /// Format the error for client at the requested level of detail.
+    ///
+    /// [`ClientErrorVerbosity::Terse`] only keeps the message for errors the
+    /// client can act upon, everything else is replaced with a generic
+    /// message for its [`ErrorKind`].
+    fn to_string_client_with(&self, verbosity: ClientErrorVerbosity) -> String {
+        let kind = self.get_error_kind();
+        match verbosity {
+            ClientErrorVerbosity::Verbose => self.to_string(),
+            _ if verbosity.hides(kind) => kind.terse_message().to_owned(),
+            _ => self.to_string_client(),
+        }
+    }
+}
+
+/// How much error detail is sent to clients.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum ClientErrorVerbosity {
+    /// Only describe errors caused by the client, report everything else generically.
+    Terse,
+    /// Send what [`UserFacingError::to_string_client`] allows.
+    #[default]
+    Default,
+    /// Send the full error message, including internal details.
+    Verbose,
+}
+
+impl ClientErrorVerbosity {
+    /// Whether errors of this kind are replaced with a generic message.
+    pub(crate) fn hides(self, kind: ErrorKind) -> bool {
+        match self {
+            ClientErrorVerbosity::Terse => !matches!(
+                kind,
+                ErrorKind::User | ErrorKind::RateLimit | ErrorKind::Quota | ErrorKind::Postgres
+            ),
+            ClientErrorVerbosity::Default | ClientErrorVerbosity::Verbose => false,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, FixedCardinalityLabel)]
@@ -72,6 +111,14 @@ pub(crate) fn to_metric_label(self) -> &'static str {
             ErrorKind::Compute => "compute",
         }
     }
+
+    fn terse_message(self) -> &'static str {
+        match self {
+            ErrorKind::ServiceRateLimit => "Too many requests, please try again later",
+            ErrorKind::Compute => "Could not connect to the database, please try again later",
+            _ => "Internal error",
+        }
+    }
 }
 
 pub(crate) trait ReportableError: fmt::Display + Send + 'static {
@@ -82,3 +129,57 @@ pub(crate) trait ReportableError: fmt::Display + Send + 'static {
 pub fn flatten_err<T>(r: Result<anyhow::Result<T>, JoinError>) -> anyhow::Result<T> {
     r.context("join error").and_then(|x| x)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("{kind:?} error at 10.0.0.1")]
+    struct TestError {
+        kind: ErrorKind,
+    }
+
+    impl ReportableError for TestError {
+        fn get_error_kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl UserFacingError for TestError {
+        fn to_string_client(&self) -> String {
+            format!("{:?} error", self.kind)
+        }
+    }
+
+    #[test]
+    fn client_error_verbosity() {
+        let user = TestError {
+            kind: ErrorKind::User,
+        };
+        let compute = TestError {
+            kind: ErrorKind::Compute,
+        };
+        let service = TestError {
+            kind: ErrorKind::Service,
+        };
+
+        let terse = ClientErrorVerbosity::Terse;
+        assert_eq!(user.to_string_client_with(terse), "User error");
+        assert_eq!(
+            compute.to_string_client_with(terse),
+            "Could not connect to the database, please try again later"
+        );
+        assert_eq!(service.to_string_client_with(terse), "Internal error");
+
+        let default = ClientErrorVerbosity::Default;
+        assert_eq!(user.to_string_client_with(default), "User error");
+        assert_eq!(service.to_string_client_with(default), "Service error");
+
+        let verbose = ClientErrorVerbosity::Verbose;
+        assert_eq!(
+            service.to_string_client_with(verbose),
+            "Service error at 10.0.0.1"
+        );
+    }
+}
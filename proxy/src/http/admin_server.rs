@@ -0,0 +1,201 @@
+//! Admin API of the proxy, served on its own listener next to `mgmt`.
+//! With the mgmt TLS settings, clients must present a trusted certificate.
+
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use http_utils::endpoint::{self, request_span};
+use http_utils::error::ApiError;
+use http_utils::json::json_response;
+use http_utils::request::get_query_param;
+use http_utils::server::Server;
+use http_utils::{RequestServiceBuilder, RouterBuilder};
+use hyper0::{Body, Request, Response, StatusCode};
+use serde::Serialize;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::auth;
+use crate::auth::backend::ComputeUserInfo;
+use crate::auth::backend::jwt::JwkCache;
+use crate::config::RetryConfig;
+use crate::context::RequestContext;
+use crate::error::UserFacingError;
+use crate::metrics::Protocol;
+use crate::protocol2::ConnectionInfo;
+use crate::proxy::NeonOptions;
+use crate::proxy::wake_compute::wake_compute_only;
+use crate::serverless::ConnPools;
+use crate::types::{EndpointId, RoleName};
+
+#[derive(Serialize)]
+struct JwksInvalidateResponse {
+    invalidated: usize,
+}
+
+/// `POST /admin/jwks/invalidate?endpoint=<id>` drops the cached JWKS for one endpoint,
+/// `POST /admin/jwks/invalidate?all=true` drops the whole cache.
+async fn jwks_invalidate_handler(
+    req: Request<Body>,
+    jwks_cache: &'static JwkCache,
+) -> Result<Response<Body>, ApiError> {
+    let invalidated = if let Some(endpoint) = get_query_param(&req, "endpoint")? {
+        let endpoint = EndpointId::from(&*endpoint);
+        let invalidated = jwks_cache.invalidate_endpoint(&endpoint);
+        info!(%endpoint, invalidated, "invalidated jwks cache for endpoint");
+        invalidated
+    } else if get_query_param(&req, "all")?.as_deref() == Some("true") {
+        let invalidated = jwks_cache.invalidate_all();
+        info!(invalidated, "invalidated whole jwks cache");
+        invalidated
+    } else {
+        return Err(ApiError::BadRequest(anyhow!(
+            "either endpoint or all=true must be specified"
+        )));
+    };
+
+    json_response(StatusCode::OK, JwksInvalidateResponse { invalidated })
+}
+
+#[derive(Serialize)]
+struct PoolCloseResponse {
+    closed: usize,
+}
+
+/// `POST /admin/pool/close?endpoint=<id>` closes the pooled serverless connections of an
+/// endpoint, e.g. when its compute is replaced, so that the next requests connect to the new one.
+async fn pool_close_handler(
+    req: Request<Body>,
+    conn_pools: Arc<ConnPools>,
+) -> Result<Response<Body>, ApiError> {
+    let Some(endpoint) = get_query_param(&req, "endpoint")? else {
+        return Err(ApiError::BadRequest(anyhow!("endpoint must be specified")));
+    };
+    let closed = conn_pools.close_endpoint(&endpoint);
+    info!(%endpoint, closed, "closed pooled connections for endpoint");
+
+    json_response(StatusCode::OK, PoolCloseResponse { closed })
+}
+
+/// What `POST /admin/compute/wake` needs to wake computes.
+#[derive(Clone, Copy)]
+pub struct ComputeWaker {
+    pub backend: &'static auth::Backend<'static, ()>,
+    pub retry_config: RetryConfig,
+}
+
+#[derive(Serialize)]
+struct ComputeWakeResponse {
+    woken: bool,
+}
+
+/// `POST /admin/compute/wake?endpoint=<id>` wakes the compute of an endpoint without connecting
+/// to it, e.g. when a user opens the dashboard, so that it is warm by the first query.
+async fn compute_wake_handler(
+    req: Request<Body>,
+    waker: Option<ComputeWaker>,
+) -> Result<Response<Body>, ApiError> {
+    let Some(waker) = waker else {
+        return Err(ApiError::BadRequest(anyhow!(
+            "this proxy does not wake computes"
+        )));
+    };
+    let Some(endpoint) = get_query_param(&req, "endpoint")? else {
+        return Err(ApiError::BadRequest(anyhow!("endpoint must be specified")));
+    };
+
+    // not a client connection, so there is no peer address to report.
+    let conn_info = ConnectionInfo {
+        addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        extra: None,
+    };
+    let ctx = RequestContext::new(Uuid::now_v7(), conn_info, Protocol::Http);
+    let user_info = ComputeUserInfo {
+        endpoint: EndpointId::from(&*endpoint),
+        user: RoleName::default(),
+        options: NeonOptions::default(),
+    };
+    let backend = waker.backend.as_ref().map(|()| user_info);
+
+    match wake_compute_only(&ctx, &backend, waker.retry_config).await {
+        Ok(()) => {
+            ctx.set_success();
+            info!(%endpoint, "woke compute");
+            json_response(StatusCode::OK, ComputeWakeResponse { woken: true })
+        }
+        Err(e) => {
+            warn!(%endpoint, error = ?e, "could not wake compute");
+            Err(ApiError::ResourceUnavailable(
+                format!("could not wake compute: {}", e.to_string_client()).into(),
+            ))
+        }
+    }
+}
+
+/// `GET /admin/connections?conn_id=<uuid>` returns the parameters a recent serverless connection
+/// to compute was opened with: which compute it went to, its TLS mode and its timeouts.
+async fn connection_info_handler(
+    req: Request<Body>,
+    conn_pools: Arc<ConnPools>,
+) -> Result<Response<Body>, ApiError> {
+    let Some(conn_id) = get_query_param(&req, "conn_id")? else {
+        return Err(ApiError::BadRequest(anyhow!("conn_id must be specified")));
+    };
+    let conn_id = Uuid::parse_str(&conn_id)
+        .map_err(|e| ApiError::BadRequest(anyhow!("invalid conn_id: {e}")))?;
+    match conn_pools.recent_connection(conn_id) {
+        Some(record) => json_response(StatusCode::OK, record),
+        None => Err(ApiError::NotFound(
+            anyhow!("connection {conn_id} is not among the recent connections").into(),
+        )),
+    }
+}
+
+fn make_router(
+    jwks_cache: &'static JwkCache,
+    conn_pools: Arc<ConnPools>,
+    compute_waker: Option<ComputeWaker>,
+) -> RouterBuilder<hyper0::Body, ApiError> {
+    endpoint::make_router()
+        .post("/admin/jwks/invalidate", move |r| {
+            request_span(r, move |b| jwks_invalidate_handler(b, jwks_cache))
+        })
+        .post("/admin/pool/close", move |r| {
+            let conn_pools = conn_pools.clone();
+            request_span(r, move |b| pool_close_handler(b, conn_pools))
+        })
+        .get("/admin/connections", move |r| {
+            let conn_pools = conn_pools.clone();
+            request_span(r, move |b| connection_info_handler(b, conn_pools))
+        })
+        .post("/admin/compute/wake", move |r| {
+            request_span(r, move |b| compute_wake_handler(b, compute_waker))
+        })
+}
+
+pub async fn task_main(
+    listener: TcpListener,
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+    jwks_cache: &'static JwkCache,
+    conn_pools: Arc<ConnPools>,
+    compute_waker: Option<ComputeWaker>,
+) -> anyhow::Result<Infallible> {
+    scopeguard::defer! {
+        info!("admin http has shut down");
+    }
+
+    let router = make_router(jwks_cache, conn_pools, compute_waker)
+        .build()
+        .map_err(|e| anyhow!(e))?;
+    let service = Arc::new(RequestServiceBuilder::new(router).map_err(|e| anyhow!(e))?);
+
+    Server::new(service, listener, tls_config.map(TlsAcceptor::from))?
+        .serve(CancellationToken::new())
+        .await?;
+
+    bail!("admin http server without shutdown handling cannot shutdown successfully");
+}
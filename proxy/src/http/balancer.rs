@@ -0,0 +1,226 @@
+//! Spreads the requests to an API across several base URLs serving it.
+//!
+//! URLs are picked by smooth weighted round-robin, so that a URL of weight 3 gets three requests
+//! for each request to a URL of weight 1, interleaved with them. The health of URLs is tracked
+//! passively from the outcome of the requests sent to them: a URL that failed at least half of
+//! its recent requests is skipped until the failures age out of its window, after which it gets
+//! requests again. If every URL is unhealthy, requests are spread across all of them.
+
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::url::ApiUrl;
+
+/// How long the outcome of a request counts towards the health of its URL, at most twice this.
+const HEALTH_WINDOW: Duration = Duration::from_secs(10);
+
+/// A URL with fewer recent requests than this is healthy whatever their outcome.
+const MIN_REQUESTS: u32 = 5;
+
+#[derive(Debug)]
+pub(crate) struct Balancer {
+    urls: Box<[(ApiUrl, u32)]>,
+    state: Mutex<Box<[UrlState]>>,
+}
+
+#[derive(Debug)]
+struct UrlState {
+    current_weight: i64,
+    health: Health,
+}
+
+/// Request and error counts of the current and previous health windows.
+#[derive(Debug)]
+struct Health {
+    window_start: Instant,
+    requests: [u32; 2],
+    errors: [u32; 2],
+}
+
+impl Balancer {
+    /// URLs with their weights. URLs of weight zero only get requests when no other URL is
+    /// healthy.
+    pub(crate) fn new(urls: impl IntoIterator<Item = (ApiUrl, u32)>) -> Self {
+        let urls: Box<[_]> = urls.into_iter().collect();
+        assert!(!urls.is_empty(), "no URL to balance across");
+        let now = Instant::now();
+        let state = urls
+            .iter()
+            .map(|_| UrlState {
+                current_weight: 0,
+                health: Health::new(now),
+            })
+            .collect();
+        Self {
+            urls,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// The URL listed first.
+    pub(crate) fn primary(&self) -> &ApiUrl {
+        &self.urls[0].0
+    }
+
+    pub(crate) fn urls(&self) -> impl Iterator<Item = &ApiUrl> {
+        self.urls.iter().map(|(url, _)| url)
+    }
+
+    /// Picks the URL for the next request.
+    pub(crate) fn pick(&self) -> &ApiUrl {
+        if self.urls.len() == 1 {
+            return self.primary();
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock();
+        let healthy: Vec<usize> = (0..self.urls.len())
+            .filter(|&i| state[i].health.is_healthy(now))
+            .collect();
+        let weighted: Vec<usize> = healthy
+            .iter()
+            .copied()
+            .filter(|&i| self.urls[i].1 > 0)
+            .collect();
+        let candidates = if !weighted.is_empty() {
+            weighted
+        } else if !healthy.is_empty() {
+            healthy
+        } else {
+            (0..self.urls.len()).collect()
+        };
+
+        // all weights are zero when only weightless URLs are left: treat them as equal.
+        let weight = |i: usize| i64::from(self.urls[i].1.max(1));
+        let total: i64 = candidates.iter().map(|&i| weight(i)).sum();
+        let mut best = candidates[0];
+        for &i in &candidates {
+            state[i].current_weight += weight(i);
+            if state[i].current_weight > state[best].current_weight {
+                best = i;
+            }
+        }
+        state[best].current_weight -= total;
+
+        &self.urls[best].0
+    }
+
+    /// Records the outcome of a request to `url`, which must have been built on a URL of this
+    /// balancer. Requests to other URLs are ignored.
+    pub(crate) fn record(&self, url: &url::Url, success: bool) {
+        if self.urls.len() == 1 {
+            return;
+        }
+        // the request URL extends the base URL it was built on with path segments and a query.
+        let Some(i) = (0..self.urls.len())
+            .filter(|&i| url.as_str().starts_with(self.urls[i].0.as_str()))
+            .max_by_key(|&i| self.urls[i].0.as_str().len())
+        else {
+            return;
+        };
+
+        let now = Instant::now();
+        let mut state = self.state.lock();
+        let health = &mut state[i].health;
+        let was_healthy = health.is_healthy(now);
+        health.requests[1] += 1;
+        if !success {
+            health.errors[1] += 1;
+        }
+        if was_healthy && !health.is_healthy(now) {
+            warn!(url = %self.urls[i].0, "too many failed requests, skipping the URL for a while");
+        }
+    }
+}
+
+impl Health {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            requests: [0; 2],
+            errors: [0; 2],
+        }
+    }
+
+    fn is_healthy(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= 2 * HEALTH_WINDOW {
+            *self = Self::new(now);
+        } else if elapsed >= HEALTH_WINDOW {
+            self.window_start += HEALTH_WINDOW;
+            self.requests = [self.requests[1], 0];
+            self.errors = [self.errors[1], 0];
+        }
+
+        let requests = self.requests[0] + self.requests[1];
+        let errors = self.errors[0] + self.errors[1];
+        requests < MIN_REQUESTS || errors * 2 < requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balancer(weights: &[u32]) -> Balancer {
+        Balancer::new(weights.iter().enumerate().map(|(i, &weight)| {
+            let url = format!("http://cplane-{i}.example.com/api/")
+                .parse()
+                .unwrap();
+            (url, weight)
+        }))
+    }
+
+    fn picks(balancer: &Balancer, n: usize) -> String {
+        (0..n)
+            .map(|_| balancer.pick().host_str().unwrap()[7..8].to_owned())
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn weighted_round_robin() {
+        let balancer = balancer(&[3, 1, 0]);
+        assert_eq!(picks(&balancer, 8), "00100010");
+
+        let balancer = balancer(&[1, 1]);
+        assert_eq!(picks(&balancer, 4), "0101");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_failing_urls() {
+        let balancer = balancer(&[1, 1]);
+        let failing: url::Url = "http://cplane-0.example.com/api/wake_compute?endpointish=ep"
+            .parse()
+            .unwrap();
+        let unknown: url::Url = "http://other.example.com/api/".parse().unwrap();
+        for _ in 0..MIN_REQUESTS {
+            balancer.record(&failing, false);
+            balancer.record(&unknown, false);
+        }
+        assert_eq!(picks(&balancer, 4), "1111");
+
+        // the failures age out.
+        tokio::time::advance(2 * HEALTH_WINDOW).await;
+        assert_eq!(picks(&balancer, 4), "0101");
+
+        // a URL recovering from a few failures stays in use.
+        for _ in 0..MIN_REQUESTS {
+            balancer.record(&failing, true);
+        }
+        for _ in 0..MIN_REQUESTS - 1 {
+            balancer.record(&failing, false);
+        }
+        assert_eq!(picks(&balancer, 2), "01");
+
+        // with every URL failing, all of them are used.
+        let other: url::Url = "http://cplane-1.example.com/api/".parse().unwrap();
+        for _ in 0..MIN_REQUESTS {
+            balancer.record(&failing, false);
+            balancer.record(&other, false);
+        }
+        assert_eq!(picks(&balancer, 2), "01");
+    }
+}
@@ -12,6 +12,7 @@
 use measured::MetricGroup;
 use measured::text::BufferedTextEncoder;
 use metrics::NeonMetrics;
+use serde::Serialize;
 use tracing::{info, info_span};
 
 use crate::ext::{LockExt, TaskExt};
@@ -21,11 +22,31 @@ async fn status_handler(_: Request<Body>) -> Result<Response<Body>, ApiError> {
     json_response(StatusCode::OK, "")
 }
 
-fn make_router(metrics: AppMetrics) -> RouterBuilder<hyper0::Body, ApiError> {
+/// What is deployed, as returned by `GET /version`.
+#[derive(Serialize)]
+pub struct VersionInfo {
+    pub git_version: &'static str,
+    pub auth_backend: String,
+    pub wss: bool,
+    pub metric_collection: bool,
+}
+
+async fn version_handler(
+    _: Request<Body>,
+    version_info: Arc<VersionInfo>,
+) -> Result<Response<Body>, ApiError> {
+    json_response(StatusCode::OK, &*version_info)
+}
+
+fn make_router(
+    metrics: AppMetrics,
+    version_info: VersionInfo,
+) -> RouterBuilder<hyper0::Body, ApiError> {
     let state = Arc::new(Mutex::new(PrometheusHandler {
         encoder: BufferedTextEncoder::new(),
         metrics,
     }));
+    let version_info = Arc::new(version_info);
 
     endpoint::make_router()
         .get("/metrics", move |r| {
@@ -33,6 +54,10 @@ fn make_router(metrics: AppMetrics) -> RouterBuilder<hyper0::Body, ApiError> {
             request_span(r, move |b| prometheus_metrics_handler(b, state))
         })
         .get("/v1/status", status_handler)
+        .get("/version", move |r| {
+            let version_info = version_info.clone();
+            request_span(r, move |b| version_handler(b, version_info))
+        })
         .get("/profile/cpu", move |r| {
             request_span(r, profile_cpu_handler)
         })
@@ -44,12 +69,13 @@ fn make_router(metrics: AppMetrics) -> RouterBuilder<hyper0::Body, ApiError> {
 pub async fn task_main(
     http_listener: TcpListener,
     metrics: AppMetrics,
+    version_info: VersionInfo,
 ) -> anyhow::Result<Infallible> {
     scopeguard::defer! {
         info!("http has shut down");
     }
 
-    let service = || RouterService::new(make_router(metrics).build()?);
+    let service = || RouterService::new(make_router(metrics, version_info).build()?);
 
     hyper0::Server::from_tcp(http_listener)?
         .serve(service().map_err(|e| anyhow!(e))?)
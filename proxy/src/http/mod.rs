@@ -2,8 +2,11 @@
 //! Other modules should use stuff from this module instead of
 //! directly relying on deps like `reqwest` (think loose coupling).
 
+pub mod admin_server;
+mod balancer;
 pub mod health_server;
 
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use bytes::Bytes;
@@ -18,6 +21,7 @@
 pub(crate) use reqwest_retry::policies::ExponentialBackoff;
 use thiserror::Error;
 
+use self::balancer::Balancer;
 use crate::metrics::{ConsoleRequest, Metrics};
 use crate::url::ApiUrl;
 
@@ -59,11 +63,37 @@ pub(crate) fn new_client_with_timeout(
         .build()
 }
 
+/// Client for the control plane API. Requests that fail to connect, time out or get a
+/// transient error status are retried with exponential backoff, until `total_retry_duration`
+/// has passed since the first attempt. Callers like wake_compute retry on their own, so both
+/// timeouts should be short.
+///
+/// Only suitable for idempotent requests: the control plane API is all `GET`s.
+pub(crate) fn new_control_plane_client(
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    total_retry_duration: Duration,
+) -> ClientWithMiddleware {
+    let client = reqwest::ClientBuilder::new()
+        .connect_timeout(connect_timeout)
+        .timeout(request_timeout)
+        .build()
+        .expect("Failed to create http client with timeout");
+
+    let retry_policy =
+        ExponentialBackoff::builder().build_with_total_retry_duration(total_retry_duration);
+
+    reqwest_middleware::ClientBuilder::new(client)
+        .with(reqwest_tracing::TracingMiddleware::default())
+        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        .build()
+}
+
 /// Thin convenience wrapper for an API provided by an http endpoint.
 #[derive(Debug, Clone)]
 pub struct Endpoint {
-    /// API's base URL.
-    endpoint: ApiUrl,
+    /// API's base URLs.
+    endpoint: Arc<Balancer>,
     /// Connection manager with built-in pooling.
     client: ClientWithMiddleware,
 }
@@ -72,15 +102,23 @@ impl Endpoint {
     /// Construct a new HTTP endpoint wrapper.
     /// Http client is not constructed under the hood so that it can be shared.
     pub fn new(endpoint: ApiUrl, client: impl Into<ClientWithMiddleware>) -> Self {
+        Self::balanced([(endpoint, 1)], client)
+    }
+
+    /// Construct an HTTP endpoint wrapper for an API served at several base URLs.
+    /// Requests are spread across the healthy URLs according to their weights.
+    pub fn balanced(
+        endpoints: impl IntoIterator<Item = (ApiUrl, u32)>,
+        client: impl Into<ClientWithMiddleware>,
+    ) -> Self {
         Self {
-            endpoint,
+            endpoint: Arc::new(Balancer::new(endpoints)),
             client: client.into(),
         }
     }
 
-    #[inline(always)]
-    pub(crate) fn url(&self) -> &ApiUrl {
-        &self.endpoint
+    pub(crate) fn urls(&self) -> impl Iterator<Item = &ApiUrl> {
+        self.endpoint.urls()
     }
 
     /// Return a [builder](RequestBuilder) for a `GET` request,
@@ -104,7 +142,7 @@ pub(crate) fn request_with_url(
         method: Method,
         f: impl for<'a> FnOnce(&'a mut ApiUrl),
     ) -> RequestBuilder {
-        let mut url = self.endpoint.clone();
+        let mut url = self.endpoint.pick().clone();
         f(&mut url);
         self.client.request(method, url.into_inner())
     }
@@ -121,7 +159,9 @@ pub(crate) fn execute(
                 request: request.url().path(),
             });
 
+        let url = request.url().clone();
         let req = self.client.execute(request).boxed();
+        let balancer = Arc::clone(&self.endpoint);
 
         async move {
             let start = Instant::now();
@@ -133,7 +173,12 @@ pub(crate) fn execute(
                     .observe_duration_since(start);
             });
 
-            req.await
+            let res = req.await;
+            let success = res
+                .as_ref()
+                .is_ok_and(|response| !response.status().is_server_error());
+            balancer.record(&url, success);
+            res
         }
     }
 }
@@ -26,7 +26,10 @@
 /// configuration from environment variables. For example, to change the
 /// destination, set `OTEL_EXPORTER_OTLP_ENDPOINT=http://jaeger:4318`.
 /// See <https://opentelemetry.io/docs/reference/specification/sdk-environment-variables>
-pub async fn init() -> anyhow::Result<LoggingGuard> {
+///
+/// If `otel_endpoint` is set, it takes precedence over the environment and
+/// is used as the full URL of the OTLP/HTTP traces endpoint.
+pub async fn init(otel_endpoint: Option<String>) -> anyhow::Result<LoggingGuard> {
     let logfmt = LogFormat::from_env()?;
 
     let env_filter = EnvFilter::builder()
@@ -43,8 +46,11 @@ pub async fn init() -> anyhow::Result<LoggingGuard> {
                 .expect("this should be a valid filter directive"),
         );
 
-    let otlp_layer =
-        tracing_utils::init_tracing("proxy", tracing_utils::ExportConfig::default()).await;
+    let export_config = tracing_utils::ExportConfig {
+        endpoint: otel_endpoint,
+        ..Default::default()
+    };
+    let otlp_layer = tracing_utils::init_tracing("proxy", export_config).await;
 
     let json_log_layer = if logfmt == LogFormat::Json {
         Some(JsonLoggingLayer::new(
@@ -38,6 +38,15 @@ pub fn install(thread_pool: Arc<ThreadPoolMetrics>) {
         metrics.proxy.retries_metric.init_all_dense();
         metrics.proxy.invalid_endpoints_total.init_all_dense();
         metrics.proxy.connection_failures_total.init_all_dense();
+        metrics
+            .proxy
+            .wake_compute_after_failure_total
+            .init_all_dense();
+        metrics.proxy.http_pool_connections_total.init_all_dense();
+        metrics
+            .proxy
+            .compute_circuit_breaker_transitions_total
+            .init_all_dense();
 
         SELF.set(metrics)
             .ok()
@@ -91,6 +100,9 @@ pub struct ProxyMetrics {
     #[metric(metadata = Thresholds::exponential_buckets(16.0, 4.0))]
     pub http_conn_content_length_bytes: HistogramVec<StaticLabelSet<HttpDirection>, 12>,
 
+    /// Number of bytes saved by compressing SQL over HTTP bodies between auth-broker and local-proxy.
+    pub http_local_proxy_compression_saved_bytes: CounterVec<StaticLabelSet<HttpDirection>>,
+
     /// Time it takes to reclaim unused connection pools.
     #[metric(metadata = Thresholds::exponential_buckets(1e-6, 2.0))]
     pub http_pool_reclaimation_lag_seconds: Histogram<16>,
@@ -98,6 +110,24 @@ pub struct ProxyMetrics {
     /// Number of opened connections to a database.
     pub http_pool_opened_connections: Gauge,
 
+    /// Number of connections across all connection pools, as of the last pool budget check.
+    pub http_pool_budget_connections: Gauge,
+
+    /// Number of open SQL over HTTP sticky sessions.
+    pub http_sticky_sessions: Gauge,
+
+    /// Number of open SQL over HTTP listen requests.
+    pub http_listen_requests: Gauge,
+
+    /// Number of SQL over HTTP listen requests rejected for being over the limit.
+    pub http_listen_rejected_total: Counter,
+
+    /// Number of serverless connections taken from or opened for a connection pool (per pool, per outcome).
+    pub http_pool_connections_total: CounterVec<HttpPoolOutcomeSet>,
+
+    /// Number of prepared statement cache hits/misses for SQL over HTTP queries.
+    pub http_pool_statement_cache_stats: CounterVec<StaticLabelSet<CacheOutcome>>,
+
     /// Number of cache hits/misses for allowed ips.
     pub allowed_ips_cache_misses: CounterVec<StaticLabelSet<CacheOutcome>>,
 
@@ -121,6 +151,16 @@ pub struct ProxyMetrics {
     /// Number of connection failures (per kind).
     pub connection_failures_total: CounterVec<StaticLabelSet<ConnectionFailureKind>>,
 
+    /// Number of wake-ups that bypassed cached compute node info on request.
+    pub forced_wakes_total: Counter,
+
+    /// Number of wake-ups after a failed connect to compute, performed or skipped because the
+    /// compute was woken recently.
+    pub wake_compute_after_failure_total: CounterVec<StaticLabelSet<WakeComputeDecision>>,
+
+    /// Number of compute circuit breaker state transitions (per new state).
+    pub compute_circuit_breaker_transitions_total: CounterVec<StaticLabelSet<CircuitBreakerState>>,
+
     /// Number of wake-up failures (per kind).
     pub connection_failures_breakdown: CounterVec<ConnectionFailuresBreakdownSet>,
 
@@ -142,6 +182,19 @@ pub struct ProxyMetrics {
     /// Number of connection requests affected by authentication rate limits
     pub requests_auth_rate_limits_total: Counter,
 
+    /// Number of client connections closed right after accept, as too many were being handled.
+    pub client_connections_rejected_total: Counter,
+
+    /// Number of JWKS fetches from identity providers.
+    pub jwks_refetches_total: Counter,
+
+    /// Number of JWTs checked against JWKS that could not be refetched when due.
+    pub jwks_stale_served_total: Counter,
+
+    /// Number of usage events dropped because the buffer of unsent events was full, or because
+    /// the collection endpoint refused them for good.
+    pub usage_metrics_dropped_total: Counter,
+
     /// HLL approximate cardinality of endpoints that are connecting
     pub connecting_endpoints: HyperLogLogVec<StaticLabelSet<Protocol>, 32>,
 
@@ -164,6 +217,9 @@ pub struct ProxyMetrics {
     #[metric(namespace = "connect_compute_lock")]
     pub connect_compute_lock: ApiLockMetrics,
 
+    #[metric(namespace = "endpoint_connect_compute_lock")]
+    pub endpoint_connect_compute_lock: ApiLockMetrics,
+
     #[metric(namespace = "scram_pool")]
     #[metric(init = thread_pool)]
     pub scram_pool: Arc<ThreadPoolMetrics>,
@@ -366,6 +422,21 @@ pub enum ConnectionFailureKind {
     ComputeUncached,
 }
 
+#[derive(FixedCardinalityLabel, Copy, Clone)]
+#[label(singleton = "decision")]
+pub enum WakeComputeDecision {
+    Performed,
+    Suppressed,
+}
+
+#[derive(FixedCardinalityLabel, Copy, Clone)]
+#[label(singleton = "state")]
+pub enum CircuitBreakerState {
+    Open,
+    HalfOpen,
+    Closed,
+}
+
 #[derive(LabelGroup)]
 #[label(set = ConnectionFailuresBreakdownSet)]
 pub struct ConnectionFailuresBreakdownGroup {
@@ -373,6 +444,33 @@ pub struct ConnectionFailuresBreakdownGroup {
     pub retry: Bool,
 }
 
+#[derive(LabelGroup)]
+#[label(set = HttpPoolOutcomeSet)]
+pub struct HttpPoolOutcomeGroup {
+    pub pool: HttpPoolKind,
+    pub outcome: HttpPoolOutcome,
+}
+
+#[derive(FixedCardinalityLabel, Copy, Clone)]
+pub enum HttpPoolKind {
+    /// Postgres connections to computes, for SQL over HTTP.
+    Postgres,
+    /// HTTP/2 connections to local_proxy.
+    Http2,
+    /// Postgres connections from local_proxy to its local postgres.
+    Local,
+}
+
+#[derive(FixedCardinalityLabel, Copy, Clone)]
+pub enum HttpPoolOutcome {
+    /// An idle connection was taken from the pool.
+    Hit,
+    /// No idle connection was available, so a new one was opened.
+    Miss,
+    /// The pool was bypassed on request, so a new connection was opened.
+    ForcedNew,
+}
+
 #[derive(LabelGroup, Copy, Clone)]
 #[label(set = RedisErrorsSet)]
 pub struct RedisErrors<'a> {
@@ -688,4 +786,12 @@ pub struct ThreadPoolMetrics {
     pub worker_task_turns_total: CounterVec<ThreadPoolWorkers>,
     #[metric(init = CounterVec::with_label_set(ThreadPoolWorkers(workers)))]
     pub worker_task_skips_total: CounterVec<ThreadPoolWorkers>,
+
+    /// Number of password hashing jobs waiting for a worker to start them.
+    pub queued_jobs: Gauge,
+
+    /// Time password hashing jobs waited for a worker to start them.
+    // largest bucket = 2^16 * 0.1ms = 6.5s
+    #[metric(metadata = Thresholds::exponential_buckets(0.0001, 2.0))]
+    pub job_wait_seconds: Histogram<16>,
 }
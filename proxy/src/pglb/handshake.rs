@@ -6,7 +6,7 @@
 use crate::auth::endpoint_sni;
 use crate::config::TlsConfig;
 use crate::context::RequestContext;
-use crate::error::ReportableError;
+use crate::error::{ClientErrorVerbosity, ReportableError};
 use crate::metrics::Metrics;
 use crate::pglb::TlsRequired;
 use crate::pqproto::{
@@ -178,7 +178,9 @@ pub(crate) async fn handshake<S: AsyncRead + AsyncWrite + Unpin + Send>(
                 // Check that the config has been consumed during upgrade
                 // OR we didn't provide it at all (for dev purposes).
                 if tls.is_some() {
-                    Err(stream.throw_error(TlsRequired, None).await)?;
+                    Err(stream
+                        .throw_error(TlsRequired, None, ClientErrorVerbosity::Default)
+                        .await)?;
                 }
 
                 // This log highlights the start of the connection.
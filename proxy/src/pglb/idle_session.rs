@@ -0,0 +1,291 @@
+//! Closes client connections that leave compute waiting on them for too long, or, if enabled,
+//! that are waiting on the client when the proxy shuts down.
+//!
+//! A connection is idle while compute has answered everything the client asked, that is, it
+//! has sent a `ReadyForQuery` for each `Query`, `Sync` and `FunctionCall` the client sent, and
+//! the client sent nothing since. A query that runs for long, or a result the client is slow to
+//! read, keeps the connection busy however long it takes. Only the message framing of the
+//! stream is looked at, the messages themselves are not parsed.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+use crate::pqproto::{ErrorCode, SQLSTATE_ADMIN_SHUTDOWN, SQLSTATE_IDLE_SESSION_TIMEOUT};
+
+/// Why the proxy closed a client connection that compute was waiting on.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SessionClosed {
+    #[error("client was idle for {0:?}")]
+    IdleTimeout(Duration),
+    #[error("proxy is shutting down")]
+    Shutdown,
+}
+
+impl SessionClosed {
+    /// The error sent to the client before its connection is closed. Clients can tell the
+    /// reasons apart by their SQLSTATE.
+    pub(crate) fn client_error(&self) -> (&'static str, ErrorCode) {
+        match self {
+            SessionClosed::IdleTimeout(_) => (
+                "terminating connection due to idle-session timeout",
+                SQLSTATE_IDLE_SESSION_TIMEOUT,
+            ),
+            SessionClosed::Shutdown => (
+                "terminating connection because the proxy is shutting down, please reconnect",
+                SQLSTATE_ADMIN_SHUTDOWN,
+            ),
+        }
+    }
+}
+
+pin_project! {
+    /// The compute side of a passthrough connection, which fails reads with [`SessionClosed`]
+    /// once the client has been idle for too long, or, if enabled, once the proxy shuts down
+    /// while compute waits on the client.
+    pub(crate) struct IdleSessionCloser<S> {
+        #[pin]
+        compute: S,
+        timeout: Option<Duration>,
+        #[pin]
+        deadline: Sleep,
+        #[pin]
+        shutdown: WaitForCancellationFutureOwned,
+        /// Messages of the client that compute has not answered with `ReadyForQuery` yet.
+        pending: u64,
+        client_messages: MessageFraming,
+        compute_messages: MessageFraming,
+    }
+}
+
+impl<S> IdleSessionCloser<S> {
+    /// Wraps a compute connection that is waiting for the client's first query.
+    /// A `timeout` of `None` never times out, a `shutdown` of `None` keeps the connection open
+    /// when the proxy shuts down.
+    pub(crate) fn new(
+        compute: S,
+        timeout: Option<Duration>,
+        shutdown: Option<CancellationToken>,
+    ) -> Self {
+        Self {
+            compute,
+            timeout,
+            deadline: tokio::time::sleep(timeout.unwrap_or_default()),
+            shutdown: shutdown.unwrap_or_default().cancelled_owned(),
+            pending: 0,
+            client_messages: MessageFraming::default(),
+            compute_messages: MessageFraming::default(),
+        }
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for IdleSessionCloser<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        let filled = buf.filled().len();
+        match this.compute.poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                let ready_for_query = this
+                    .compute_messages
+                    .feed(&buf.filled()[filled..])
+                    .filter(|&tag| tag == b'Z')
+                    .count() as u64;
+                if ready_for_query > 0 {
+                    *this.pending = this.pending.saturating_sub(ready_for_query);
+                    if let Some(timeout) = *this.timeout {
+                        this.deadline.as_mut().reset(Instant::now() + timeout);
+                    }
+                }
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => {
+                // reads from compute are only polled once everything read before was written to
+                // the client, so a connection waiting here is not slowed down by the client.
+                if *this.pending > 0 {
+                    return Poll::Pending;
+                }
+                if this.shutdown.poll(cx).is_ready() {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        SessionClosed::Shutdown,
+                    )));
+                }
+                if let Some(timeout) = *this.timeout {
+                    ready!(this.deadline.poll(cx));
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        SessionClosed::IdleTimeout(timeout),
+                    )));
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for IdleSessionCloser<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let n = ready!(this.compute.poll_write(cx, buf))?;
+        *this.pending += this
+            .client_messages
+            .feed(&buf[..n])
+            .filter(|tag| matches!(tag, b'Q' | b'S' | b'F'))
+            .count() as u64;
+        if let Some(timeout) = *this.timeout {
+            this.deadline.reset(Instant::now() + timeout);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().compute.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().compute.poll_shutdown(cx)
+    }
+}
+
+/// Tracks where messages start and end in a stream of postgres protocol messages.
+#[derive(Default)]
+struct MessageFraming {
+    /// The tag and length of the current message, while they are incomplete.
+    header: [u8; 5],
+    header_len: usize,
+    /// What is left of the body of the current message.
+    body_remaining: usize,
+}
+
+impl MessageFraming {
+    /// Feeds the next bytes of the stream, returning the tags of the messages they complete.
+    fn feed<'a>(&'a mut self, mut data: &'a [u8]) -> impl Iterator<Item = u8> + 'a {
+        std::iter::from_fn(move || {
+            while !data.is_empty() {
+                if self.header_len < self.header.len() {
+                    let n = usize::min(self.header.len() - self.header_len, data.len());
+                    self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+                    self.header_len += n;
+                    data = &data[n..];
+                    if self.header_len < self.header.len() {
+                        return None;
+                    }
+                    let len = u32::from_be_bytes(self.header[1..].try_into().expect("4 bytes"));
+                    // the length includes itself.
+                    self.body_remaining = (len as usize).saturating_sub(4);
+                } else {
+                    let n = usize::min(self.body_remaining, data.len());
+                    self.body_remaining -= n;
+                    data = &data[n..];
+                }
+
+                if self.body_remaining == 0 {
+                    self.header_len = 0;
+                    return Some(self.header[0]);
+                }
+            }
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    fn message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut msg = vec![tag];
+        msg.extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        msg.extend_from_slice(body);
+        msg
+    }
+
+    #[test]
+    fn message_framing() {
+        let mut stream = message(b'T', b"row description");
+        stream.extend(message(b'C', b"SELECT 1\0"));
+        stream.extend(message(b'Z', b"I"));
+
+        let mut framing = MessageFraming::default();
+        let tags: Vec<u8> = stream
+            .chunks(3)
+            .flat_map(|chunk| framing.feed(chunk).collect::<Vec<_>>())
+            .collect();
+        assert_eq!(tags, b"TCZ");
+
+        let tags: Vec<u8> = framing.feed(&stream).collect();
+        assert_eq!(tags, b"TCZ");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn times_out_when_compute_waits_on_client() {
+        let timeout = Duration::from_secs(60);
+        let (proxy, mut compute) = tokio::io::duplex(1024);
+        let mut proxy = Box::pin(IdleSessionCloser::new(proxy, Some(timeout), None));
+        let mut buf = [0; 64];
+
+        // a long running query is not idle.
+        proxy
+            .write_all(&message(b'Q', b"SELECT pg_sleep(120)\0"))
+            .await
+            .unwrap();
+        let read = tokio::time::timeout(2 * timeout, proxy.read(&mut buf)).await;
+        assert!(read.is_err(), "the query is still running");
+
+        compute.write_all(&message(b'Z', b"I")).await.unwrap();
+        proxy.read(&mut buf).await.unwrap();
+
+        let start = Instant::now();
+        let err = proxy.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(SessionClosed::IdleTimeout(_))
+        ));
+        assert_eq!(start.elapsed(), timeout);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closes_on_shutdown_between_queries() {
+        let shutdown = CancellationToken::new();
+        let (proxy, mut compute) = tokio::io::duplex(1024);
+        let mut proxy = Box::pin(IdleSessionCloser::new(proxy, None, Some(shutdown.clone())));
+        let mut buf = [0; 64];
+
+        // a running query is not interrupted.
+        proxy
+            .write_all(&message(b'Q', b"SELECT pg_sleep(120)\0"))
+            .await
+            .unwrap();
+        shutdown.cancel();
+        let read = tokio::time::timeout(Duration::from_secs(60), proxy.read(&mut buf)).await;
+        assert!(read.is_err(), "the query is still running");
+
+        compute.write_all(&message(b'Z', b"I")).await.unwrap();
+        proxy.read(&mut buf).await.unwrap();
+
+        let err = proxy.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(SessionClosed::Shutdown)
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod copy_bidirectional;
 pub mod handshake;
+mod idle_session;
 pub mod inprocess;
 pub mod passthrough;
 
@@ -8,7 +9,7 @@
 use futures::FutureExt;
 use smol_str::ToSmolStr;
 use thiserror::Error;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio_util::sync::CancellationToken;
 use tracing::{Instrument, debug, error, info, warn};
 
@@ -21,6 +22,7 @@
 pub use crate::pglb::copy_bidirectional::ErrorSource;
 use crate::pglb::handshake::{HandshakeData, HandshakeError, handshake};
 use crate::pglb::passthrough::ProxyPassthrough;
+use crate::pqproto::{SQLSTATE_TOO_MANY_CONNECTIONS, WriteBuf};
 use crate::protocol2::{ConnectHeader, ConnectionInfo, ConnectionInfoExtra, read_proxy_protocol};
 use crate::proxy::handle_client;
 use crate::rate_limiter::EndpointRateLimiter;
@@ -55,7 +57,7 @@ pub async fn task_main(
 
     // When set for the server socket, the keepalive setting
     // will be inherited by all accepted client sockets.
-    socket2::SockRef::from(&listener).set_keepalive(true)?;
+    socket2::SockRef::from(&listener).set_keepalive(config.client_tcp_keepalive.is_some())?;
 
     let connections = tokio_util::task::task_tracker::TaskTracker::new();
     let cancellations = tokio_util::task::task_tracker::TaskTracker::new();
@@ -73,6 +75,7 @@ pub async fn task_main(
         let session_id = uuid::Uuid::new_v4();
         let cancellation_handler = Arc::clone(&cancellation_handler);
         let cancellations = cancellations.clone();
+        let shutdown = cancellation_token.clone();
 
         debug!(protocol = "tcp", %session_id, "accepted new TCP connection");
         let endpoint_rate_limiter2 = endpoint_rate_limiter.clone();
@@ -104,7 +107,26 @@ pub async fn task_main(
                 ),
             };
 
-            match socket.set_nodelay(true) {
+            let _permit = match &config.client_connection_limit {
+                Some(limit) => match limit.acquire().await {
+                    Some(permit) => Some(permit),
+                    None => {
+                        Metrics::get().proxy.client_connections_rejected_total.inc();
+                        warn!(?session_id, peer_addr = %conn_info.addr, "too many client connections, rejecting connection");
+                        reject_busy(socket).await;
+                        return;
+                    }
+                },
+                None => None,
+            };
+
+            let res = socket
+                .set_nodelay(true)
+                .and_then(|()| match &config.client_tcp_keepalive {
+                    Some(keepalive) => keepalive.apply(&socket),
+                    None => Ok(()),
+                });
+            match res {
                 Ok(()) => {}
                 Err(e) => {
                     error!(
@@ -142,7 +164,7 @@ pub async fn task_main(
                 Ok(Some(p)) => {
                     ctx.set_success();
                     let _disconnect = ctx.log_connect();
-                    match p.proxy_pass().await {
+                    match p.proxy_pass(shutdown).await {
                         Ok(()) => {}
                         Err(ErrorSource::Client(e)) => {
                             warn!(
@@ -173,6 +195,20 @@ pub async fn task_main(
     Ok(())
 }
 
+/// Closes a connection the proxy has no room for. The error is sent before reading anything,
+/// which clients accept in reply to their startup or SSL request alike.
+async fn reject_busy(mut socket: impl AsyncWrite + Unpin) {
+    let mut buf = WriteBuf::new();
+    buf.write_error(
+        "proxy is handling too many connections, please retry later",
+        SQLSTATE_TOO_MANY_CONNECTIONS,
+    );
+    if let Err(e) = socket.write_all_buf(&mut buf).await {
+        debug!("failed to send the too many connections error: {e}");
+    }
+    let _ = socket.shutdown().await;
+}
+
 pub(crate) enum ClientMode {
     Tcp,
     Websockets { hostname: Option<String> },
@@ -292,6 +328,12 @@ pub(crate) async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin + Send>(
     };
     drop(pause);
 
+    if let Err(e) = config.startup_params_limits.check(&params) {
+        Err(client
+            .throw_error(e, Some(ctx), config.client_error_verbosity)
+            .await)?;
+    }
+
     ctx.set_db_options(params.clone());
 
     let common_names = tls.map(|tls| &tls.common_names);
@@ -323,6 +365,8 @@ pub(crate) async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin + Send>(
 
         aux: node.aux,
         private_link_id,
+        idle_timeout: config.client_idle_timeout,
+        close_on_shutdown: config.close_idle_sessions_on_shutdown,
 
         _cancel_on_shutdown: cancel_on_shutdown,
 
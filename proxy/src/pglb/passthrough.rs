@@ -1,17 +1,21 @@
 use std::convert::Infallible;
+use std::time::Duration;
 
 use smol_str::SmolStr;
-use tokio::io::{AsyncRead, AsyncWrite};
-use tracing::debug;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
 use utils::measured_stream::MeasuredStream;
 
 use super::copy_bidirectional::ErrorSource;
+use super::idle_session::{IdleSessionCloser, SessionClosed};
 use crate::compute::MaybeRustlsStream;
 use crate::control_plane::messages::MetricsAuxInfo;
 use crate::metrics::{
     Direction, Metrics, NumClientConnectionsGuard, NumConnectionRequestsGuard,
     NumDbConnectionsGuard,
 };
+use crate::pqproto::WriteBuf;
 use crate::stream::Stream;
 use crate::usage_metrics::{Ids, MetricCounterRecorder, USAGE_METRICS};
 
@@ -22,6 +26,8 @@ pub(crate) async fn proxy_pass(
     compute: impl AsyncRead + AsyncWrite + Unpin,
     aux: MetricsAuxInfo,
     private_link_id: Option<SmolStr>,
+    idle_timeout: Option<Duration>,
+    shutdown: Option<CancellationToken>,
 ) -> Result<(), ErrorSource> {
     // we will report ingress at a later date
     let usage_tx = USAGE_METRICS.register(Ids {
@@ -44,7 +50,7 @@ pub(crate) async fn proxy_pass(
 
     let m_recv = metrics.with_labels(Direction::Rx);
     let mut compute = MeasuredStream::new(
-        compute,
+        Box::pin(IdleSessionCloser::new(compute, idle_timeout, shutdown)),
         |_| {},
         |cnt| {
             // Number of bytes the client sent to the compute node (inbound).
@@ -55,13 +61,38 @@ pub(crate) async fn proxy_pass(
 
     // Starting from here we only proxy the client's traffic.
     debug!("performing the proxy pass...");
-    let _ = crate::pglb::copy_bidirectional::copy_bidirectional_client_compute(
+    let res = crate::pglb::copy_bidirectional::copy_bidirectional_client_compute(
         &mut client,
         &mut compute,
     )
-    .await?;
+    .await;
 
-    Ok(())
+    match res {
+        Ok(_) => Ok(()),
+        Err(ErrorSource::Compute(e)) => {
+            let closed = e
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<SessionClosed>())
+                .map(SessionClosed::client_error);
+            let Some((message, code)) = closed else {
+                return Err(ErrorSource::Compute(e));
+            };
+
+            info!("closing client connection: {e}");
+            // compute is waiting for the next query, so the client can take an error message.
+            let mut buf = WriteBuf::new();
+            buf.write_error(message, code);
+            client
+                .write_all_buf(&mut buf)
+                .await
+                .unwrap_or_else(|e| debug!("failed to send the error closing the connection: {e}"));
+            let _ = client.shutdown().await;
+            // Terminate
+            let _ = compute.write_all(b"X\0\0\0\x04").await;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
 }
 
 pub(crate) struct ProxyPassthrough<S> {
@@ -70,6 +101,8 @@ pub(crate) struct ProxyPassthrough<S> {
 
     pub(crate) aux: MetricsAuxInfo,
     pub(crate) private_link_id: Option<SmolStr>,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) close_on_shutdown: bool,
 
     pub(crate) _cancel_on_shutdown: tokio::sync::oneshot::Sender<Infallible>,
 
@@ -79,7 +112,17 @@ pub(crate) struct ProxyPassthrough<S> {
 }
 
 impl<S: AsyncRead + AsyncWrite + Unpin> ProxyPassthrough<S> {
-    pub(crate) async fn proxy_pass(self) -> Result<(), ErrorSource> {
-        proxy_pass(self.client, self.compute, self.aux, self.private_link_id).await
+    /// If `close_on_shutdown` is set, the connection is closed once `shutdown` is cancelled, as
+    /// soon as compute waits on the client.
+    pub(crate) async fn proxy_pass(self, shutdown: CancellationToken) -> Result<(), ErrorSource> {
+        proxy_pass(
+            self.client,
+            self.compute,
+            self.aux,
+            self.private_link_id,
+            self.idle_timeout,
+            self.close_on_shutdown.then_some(shutdown),
+        )
+        .await
     }
 }
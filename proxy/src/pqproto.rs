@@ -16,6 +16,9 @@
 pub const FE_PASSWORD_MESSAGE: u8 = b'p';
 
 pub const SQLSTATE_INTERNAL_ERROR: [u8; 5] = *b"XX000";
+pub const SQLSTATE_IDLE_SESSION_TIMEOUT: [u8; 5] = *b"57P05";
+pub const SQLSTATE_ADMIN_SHUTDOWN: [u8; 5] = *b"57P01";
+pub const SQLSTATE_TOO_MANY_CONNECTIONS: [u8; 5] = *b"53300";
 
 /// The protocol version number.
 ///
@@ -11,6 +11,7 @@
 use crate::error::ReportableError;
 use crate::metrics::{
     ConnectOutcome, ConnectionFailureKind, Metrics, RetriesMetricGroup, RetryType,
+    WakeComputeDecision,
 };
 use crate::proxy::retry::{CouldRetry, ShouldRetryWakeCompute, retry_after, should_retry};
 use crate::proxy::wake_compute::{WakeComputeBackend, wake_compute};
@@ -87,9 +88,11 @@ pub(crate) async fn connect_to_compute<M: ConnectMechanism, B: WakeComputeBacken
     M::ConnectError: CouldRetry + ShouldRetryWakeCompute + std::fmt::Debug,
     M::Error: From<WakeComputeError>,
 {
+    let started = time::Instant::now();
     let mut num_retries = 0;
     let node_info =
         wake_compute(&mut num_retries, ctx, user_info, wake_compute_retry_config).await?;
+    compute.recent_wakes.record(&node_info);
 
     // try once
     let err = match mechanism.connect_once(ctx, &node_info, compute).await {
@@ -102,6 +105,7 @@ pub(crate) async fn connect_to_compute<M: ConnectMechanism, B: WakeComputeBacken
                 },
                 num_retries.into(),
             );
+            log_slow_connect(ctx, compute, started, &node_info, num_retries);
             return Ok(res);
         }
         Err(e) => e,
@@ -109,7 +113,10 @@ pub(crate) async fn connect_to_compute<M: ConnectMechanism, B: WakeComputeBacken
 
     debug!(error = ?err, COULD_NOT_CONNECT);
 
-    let node_info = if !node_info.cached() || !err.should_retry_wake_compute() {
+    let node_info = if !node_info.cached()
+        || !err.should_retry_wake_compute()
+        || skip_wake(compute, &node_info)
+    {
         // If we just recieved this from cplane and didn't get it from cache, we shouldn't retry.
         // Do not need to retrieve a new node_info, just return the old one.
         if !should_retry(&err, num_retries, compute.retry) {
@@ -128,7 +135,10 @@ pub(crate) async fn connect_to_compute<M: ConnectMechanism, B: WakeComputeBacken
         debug!("compute node's state has likely changed; requesting a wake-up");
         invalidate_cache(node_info);
         // TODO: increment num_retries?
-        wake_compute(&mut num_retries, ctx, user_info, wake_compute_retry_config).await?
+        let node_info =
+            wake_compute(&mut num_retries, ctx, user_info, wake_compute_retry_config).await?;
+        compute.recent_wakes.record(&node_info);
+        node_info
     };
 
     // now that we have a new node, try connect to it repeatedly.
@@ -149,6 +159,7 @@ pub(crate) async fn connect_to_compute<M: ConnectMechanism, B: WakeComputeBacken
                 );
                 // TODO: is this necessary? We have a metric.
                 info!(?num_retries, "connected to compute node after");
+                log_slow_connect(ctx, compute, started, &node_info, num_retries);
                 return Ok(res);
             }
             Err(e) => {
@@ -176,3 +187,43 @@ pub(crate) async fn connect_to_compute<M: ConnectMechanism, B: WakeComputeBacken
         drop(pause);
     }
 }
+
+/// Whether to keep the cached node info after a failed connect instead of waking the compute
+/// again, because the compute was woken recently.
+fn skip_wake(compute: &ComputeConfig, node_info: &control_plane::CachedNodeInfo) -> bool {
+    let skip = compute.recent_wakes.skip_wake(node_info);
+    let decision = if skip {
+        debug!("compute node was woken recently; retrying without a wake-up");
+        WakeComputeDecision::Suppressed
+    } else {
+        WakeComputeDecision::Performed
+    };
+    Metrics::get()
+        .proxy
+        .wake_compute_after_failure_total
+        .inc(decision);
+    skip
+}
+
+/// Warns about a connect to compute that exceeded the configured slow connect threshold.
+fn log_slow_connect(
+    ctx: &RequestContext,
+    compute: &ComputeConfig,
+    started: time::Instant,
+    node_info: &control_plane::CachedNodeInfo,
+    num_retries: u32,
+) {
+    let Some(threshold) = compute.slow_connect_threshold else {
+        return;
+    };
+    let elapsed = started.elapsed();
+    if elapsed > threshold {
+        warn!(
+            elapsed = ?elapsed,
+            num_retries,
+            compute_id = %node_info.aux.compute_id,
+            latency_us = %ctx.get_proxy_latency(),
+            "slow connect to compute"
+        );
+    }
+}
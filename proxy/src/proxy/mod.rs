@@ -23,7 +23,6 @@
 use crate::compute::ComputeConnection;
 use crate::config::ProxyConfig;
 use crate::context::RequestContext;
-use crate::control_plane::client::ControlPlaneClient;
 pub use crate::pglb::copy_bidirectional::{ErrorSource, copy_bidirectional_client_compute};
 use crate::pglb::{ClientMode, ClientRequestError};
 use crate::pqproto::{BeMessage, CancelKeyData, StartupMessageParams};
@@ -31,7 +30,7 @@
 use crate::proxy::retry::ShouldRetryWakeCompute;
 use crate::rate_limiter::EndpointRateLimiter;
 use crate::stream::{PqStream, Stream};
-use crate::types::EndpointCacheKey;
+use crate::types::{DbName, EndpointCacheKey};
 use crate::{auth, compute};
 
 #[allow(clippy::too_many_arguments)]
@@ -55,13 +54,18 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
 
     let user_info = match result {
         Ok(user_info) => user_info,
-        Err(e) => Err(client.throw_error(e, Some(ctx)).await)?,
+        Err(e) => Err(client
+            .throw_error(e, Some(ctx), config.client_error_verbosity)
+            .await)?,
     };
 
     let user = user_info.get_user().to_owned();
+    // postgres connects to the database named after the user if none is given.
+    let dbname = DbName::from(params.get("database").unwrap_or(&user));
     let user_info = match user_info
         .authenticate(
             ctx,
+            &dbname,
             client,
             mode.allow_cleartext(),
             &config.authentication_config,
@@ -76,7 +80,7 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
             let params_span = tracing::info_span!("", ?user, ?db, ?app);
 
             return Err(client
-                .throw_error(e, Some(ctx))
+                .throw_error(e, Some(ctx), config.client_error_verbosity)
                 .instrument(params_span)
                 .await)?;
         }
@@ -120,7 +124,11 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
 
         match res {
             Ok(n) => node = n,
-            Err(e) => return Err(client.throw_error(e, Some(ctx)).await)?,
+            Err(e) => {
+                return Err(client
+                    .throw_error(e, Some(ctx), config.client_error_verbosity)
+                    .await)?;
+            }
         }
 
         let auth::Backend::ControlPlane(cplane, user_info) = &backend else {
@@ -133,13 +141,14 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin + Send>(
             Err(e) if attempt < 2 && e.should_retry_wake_compute() => {
                 tracing::warn!(error = ?e, "retrying wake compute");
 
-                #[allow(irrefutable_let_patterns)]
-                if let ControlPlaneClient::ProxyV1(cplane_proxy_v1) = &**cplane {
+                if let Some(cplane_proxy_v1) = cplane.proxy_v1() {
                     let key = user_info.endpoint_cache_key();
                     cplane_proxy_v1.caches.node_info.invalidate(&key);
                 }
             }
-            Err(e) => Err(client.throw_error(e, Some(ctx)).await)?,
+            Err(e) => Err(client
+                .throw_error(e, Some(ctx), config.client_error_verbosity)
+                .await)?,
         }
     };
 
@@ -263,6 +272,104 @@ pub(crate) fn to_deep_object(&self) -> Vec<(SmolStr, SmolStr)> {
     }
 }
 
+/// Postgres settings passed as `-c key=value` or `--key=value` in the startup `options`,
+/// in the order they were given. Later settings take precedence, as they do in postgres.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct PgSettings(Vec<(SmolStr, SmolStr)>);
+
+impl PgSettings {
+    pub(crate) fn parse_options_raw(options: &str) -> Self {
+        let mut settings = vec![];
+        let mut opts = StartupMessageParams::parse_options_raw(options);
+        while let Some(opt) = opts.next() {
+            let setting = match opt.strip_prefix("-c") {
+                Some("") => opts.next(),
+                Some(setting) => Some(setting),
+                None => opt.strip_prefix("--"),
+            };
+            let Some((key, value)) = setting.and_then(|s| s.split_once('=')) else {
+                continue;
+            };
+            // See `postgres: ParseLongOption`.
+            let key = unescape_option(key).replace('-', "_");
+            settings.push((key.into(), unescape_option(value).into()));
+        }
+        Self(settings)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .rev()
+            .find_map(|(k, v)| (k == key).then_some(&**v))
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, value: &str) {
+        self.0.push((key.into(), value.into()));
+    }
+
+    /// Removes every occurrence of `key`, returning the value that would have taken effect.
+    pub(crate) fn remove(&mut self, key: &str) -> Option<SmolStr> {
+        let value = self.get(key).map(SmolStr::from);
+        self.0.retain(|(k, _)| k != key);
+        value
+    }
+
+    /// Removes every setting whose name starts with `prefix`, returning how many were removed.
+    pub(crate) fn remove_prefixed(&mut self, prefix: &str) -> usize {
+        let before = self.0.len();
+        self.0.retain(|(k, _)| !k.starts_with(prefix));
+        before - self.0.len()
+    }
+
+    /// Encodes the settings as a startup `options` value that postgres splits back into
+    /// the same settings.
+    pub(crate) fn to_options_raw(&self) -> String {
+        self.0
+            .iter()
+            .map(|(k, v)| format!("-c {}={}", escape_option(k), escape_option(v)))
+            .join(" ")
+    }
+}
+
+/// Parses a boolean setting the way postgres does, e.g. `on`, `true`, `yes` or `1`.
+/// See `postgres: parse_bool`.
+pub(crate) fn parse_pg_bool(value: &str) -> Option<bool> {
+    match &*value.to_ascii_lowercase() {
+        "on" | "true" | "yes" | "1" => Some(true),
+        "off" | "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// See `postgres: pg_split_opts`.
+fn unescape_option(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_option(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || c.is_ascii_whitespace() {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
 pub(crate) fn neon_option(bytes: &str) -> Option<(&str, &str)> {
     static RE: OnceCell<Regex> = OnceCell::new();
     let re = RE.get_or_init(|| Regex::new(r"^neon_(\w+):(.+)").expect("regex should be correct"));
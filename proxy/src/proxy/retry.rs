@@ -75,7 +75,11 @@ fn should_retry_wake_compute(&self) -> bool {
 
 impl CouldRetry for postgres_client::Error {
     fn could_retry(&self) -> bool {
-        if let Some(io_err) = self.source().and_then(|x| x.downcast_ref()) {
+        if self.is_startup_timeout() {
+            // the compute accepted the connection but stalled, another attempt would likely
+            // just stall again.
+            false
+        } else if let Some(io_err) = self.source().and_then(|x| x.downcast_ref()) {
             io::Error::could_retry(io_err)
         } else if let Some(db_err) = self.source().and_then(|x| x.downcast_ref()) {
             postgres_client::error::DbError::could_retry(db_err)
@@ -22,15 +22,19 @@
 use crate::config::{ComputeConfig, RetryConfig, TlsConfig};
 use crate::context::RequestContext;
 use crate::control_plane::client::{ControlPlaneClient, TestControlPlaneClient};
-use crate::control_plane::messages::{ControlPlaneErrorMessage, Details, MetricsAuxInfo, Status};
+use crate::control_plane::messages::{
+    ColdStartInfo, ControlPlaneErrorMessage, Details, MetricsAuxInfo, Status,
+};
+use crate::control_plane::read_endpoints::ReadEndpointPolicy;
 use crate::control_plane::{self, CachedNodeInfo, NodeInfo, NodeInfoCache};
 use crate::error::{ErrorKind, ReportableError};
 use crate::pglb::ERR_INSECURE_CONNECTION;
 use crate::pglb::handshake::{HandshakeData, handshake};
 use crate::pqproto::BeMessage;
-use crate::proxy::NeonOptions;
 use crate::proxy::connect_compute::{ConnectMechanism, connect_to_compute};
 use crate::proxy::retry::{ShouldRetryWakeCompute, retry_after};
+use crate::proxy::wake_compute::{ForceWake, RecentWakes};
+use crate::proxy::{NeonOptions, PgSettings, parse_pg_bool};
 use crate::stream::{PqStream, Stream};
 use crate::tls::client_config::compute_client_config_with_certs;
 use crate::tls::server_config::CertResolver;
@@ -380,6 +384,8 @@ fn connect_compute_total_wait() {
 #[derive(Clone, Copy, Debug)]
 enum ConnectAction {
     Wake,
+    // wake_compute -> node info found in the cache
+    WakeCached,
     WakeCold,
     WakeFail,
     WakeRetry,
@@ -511,6 +517,11 @@ fn wake_compute(&self) -> Result<CachedNodeInfo, control_plane::errors::WakeComp
         *counter += 1;
         match action {
             ConnectAction::Wake => Ok(helper_create_cached_node_info(self.cache)),
+            ConnectAction::WakeCached => {
+                let mut node_info = helper_create_cached_node_info(self.cache);
+                node_info.aux.cold_start_info = ColdStartInfo::WarmCached;
+                Ok(node_info)
+            }
             ConnectAction::WakeCold => Ok(CachedNodeInfo::new_uncached(
                 helper_create_uncached_node_info(),
             )),
@@ -568,7 +579,10 @@ fn helper_create_uncached_node_info() -> NodeInfo {
             port: 5432,
             ssl_mode: SslMode::Disable,
             host_addr: None,
+            unix_socket: None,
+            tls_server_name: None,
         },
+        read_endpoints: None,
         aux: MetricsAuxInfo {
             endpoint_id: (&EndpointId::from("endpoint")).into(),
             project_id: (&ProjectId::from("project")).into(),
@@ -609,6 +623,12 @@ fn config() -> ComputeConfig {
         retry,
         tls: Arc::new(compute_client_config_with_certs(std::iter::empty())),
         timeout: Duration::from_secs(2),
+        startup_timeout: None,
+        slow_connect_threshold: None,
+        statement_timeout: None,
+        max_query_duration: None,
+        read_endpoint_policy: ReadEndpointPolicy::default(),
+        recent_wakes: RecentWakes::new(None),
     }
 }
 
@@ -640,6 +660,55 @@ async fn connect_to_compute_retry() {
     mechanism.verify();
 }
 
+/// A compute woken moments ago is not woken again after a failed connect.
+#[tokio::test]
+async fn connect_to_compute_retry_fresh_wake() {
+    let _ = env_logger::try_init();
+    use ConnectAction::*;
+    let ctx = RequestContext::test();
+    let mechanism = TestConnectMechanism::new(vec![Wake, Retry, Connect]);
+    let user_info = helper_create_connect_info(&mechanism);
+    let config = ComputeConfig {
+        recent_wakes: RecentWakes::new(Some(Duration::from_secs(60))),
+        ..config()
+    };
+    connect_to_compute(&ctx, &mechanism, &user_info, config.retry, &config)
+        .await
+        .unwrap();
+    mechanism.verify();
+}
+
+/// A forced wake-up replaces cached node info before connecting.
+#[tokio::test]
+async fn connect_to_compute_force_wake() {
+    let _ = env_logger::try_init();
+    use ConnectAction::*;
+    let ctx = RequestContext::test();
+    let config = config();
+
+    let mechanism = TestConnectMechanism::new(vec![WakeCached, Wake, Connect]);
+    let user_info = helper_create_connect_info(&mechanism);
+    let force_wake = ForceWake {
+        backend: &user_info,
+        force: true,
+    };
+    connect_to_compute(&ctx, &mechanism, &force_wake, config.retry, &config)
+        .await
+        .unwrap();
+    mechanism.verify();
+
+    let mechanism = TestConnectMechanism::new(vec![WakeCached, Connect]);
+    let user_info = helper_create_connect_info(&mechanism);
+    let no_force_wake = ForceWake {
+        backend: &user_info,
+        force: false,
+    };
+    connect_to_compute(&ctx, &mechanism, &no_force_wake, config.retry, &config)
+        .await
+        .unwrap();
+    mechanism.verify();
+}
+
 /// Test that we don't retry if the error is not retryable.
 #[tokio::test]
 async fn connect_to_compute_non_retry_1() {
@@ -857,3 +926,69 @@ async fn retry_cold_wake_skips_invalidation() {
         .unwrap();
     mechanism.verify();
 }
+
+#[test]
+fn pg_settings_from_options() {
+    let settings = PgSettings::parse_options_raw(
+        r"neon_lsn:0/2 -c search_path=app,public -cstatement_timeout=5s endpoint=ep --lock-timeout=1s -c application_name=my\ app",
+    );
+    assert_eq!(settings.get("search_path"), Some("app,public"));
+    assert_eq!(settings.get("statement_timeout"), Some("5s"));
+    assert_eq!(settings.get("lock_timeout"), Some("1s"));
+    assert_eq!(settings.get("application_name"), Some("my app"));
+    assert_eq!(settings.get("lsn"), None);
+    assert_eq!(settings.get("endpoint"), None);
+
+    // later settings win
+    let settings = PgSettings::parse_options_raw("-c work_mem=1MB -c work_mem=64MB");
+    assert_eq!(settings.get("work_mem"), Some("64MB"));
+
+    assert!(PgSettings::parse_options_raw("neon_lsn:0/2 -c").is_empty());
+}
+
+#[test]
+fn pg_settings_round_trip() {
+    let options = r"-c search_path=app,public -c application_name=my\ app -c foo.bar=a\\b";
+    let settings = PgSettings::parse_options_raw(options);
+    assert_eq!(settings.get("application_name"), Some("my app"));
+    assert_eq!(settings.get("foo.bar"), Some(r"a\b"));
+    assert_eq!(settings.to_options_raw(), options);
+    assert_eq!(
+        PgSettings::parse_options_raw(&settings.to_options_raw()),
+        settings
+    );
+}
+
+#[test]
+fn pg_settings_session_jwk_cannot_be_overridden() {
+    let mut settings = PgSettings::parse_options_raw(
+        "-c pg_session_jwt.jwk=evil -c search_path=app -c pg_session_jwt.audit_log=off",
+    );
+    assert_eq!(settings.remove_prefixed("pg_session_jwt."), 2);
+    settings.insert("pg_session_jwt.jwk", r#"{"kty":"OKP"}"#);
+
+    let settings = PgSettings::parse_options_raw(&settings.to_options_raw());
+    assert_eq!(settings.get("search_path"), Some("app"));
+    assert_eq!(settings.get("pg_session_jwt.jwk"), Some(r#"{"kty":"OKP"}"#));
+    assert_eq!(settings.get("pg_session_jwt.audit_log"), None);
+}
+
+#[test]
+fn pg_settings_read_replica() {
+    let mut settings = PgSettings::parse_options_raw(
+        "-c neon.read_replica=off -c search_path=app -c neon.read_replica=TRUE",
+    );
+    assert_eq!(
+        settings
+            .remove("neon.read_replica")
+            .as_deref()
+            .and_then(parse_pg_bool),
+        Some(true)
+    );
+    assert_eq!(settings.get("neon.read_replica"), None);
+    assert_eq!(settings.to_options_raw(), "-c search_path=app");
+
+    assert_eq!(parse_pg_bool("on"), Some(true));
+    assert_eq!(parse_pg_bool("0"), Some(false));
+    assert_eq!(parse_pg_bool("replica"), None);
+}
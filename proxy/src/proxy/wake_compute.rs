@@ -1,10 +1,16 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
+use clashmap::ClashMap;
+use smol_str::SmolStr;
+use tokio::time::Instant;
 use tracing::{error, info};
 
 use crate::config::RetryConfig;
 use crate::context::RequestContext;
-use crate::control_plane::CachedNodeInfo;
 use crate::control_plane::errors::{ControlPlaneError, WakeComputeError};
+use crate::control_plane::messages::ColdStartInfo;
+use crate::control_plane::{CachedNodeInfo, NodeInfo};
 use crate::error::ReportableError;
 use crate::metrics::{
     ConnectOutcome, ConnectionFailuresBreakdownGroup, Metrics, RetriesMetricGroup, RetryType,
@@ -75,6 +81,92 @@ pub(crate) async fn wake_compute<B: WakeComputeBackend>(
     }
 }
 
+/// Wakes the compute through the control plane even if its node info is cached, replacing the
+/// cached node info, when `force` is set. For when the cached node info is known to be stale,
+/// e.g. after the compute moved, to reach the compute without waiting for the cache to expire.
+pub(crate) struct ForceWake<'a, B> {
+    pub(crate) backend: &'a B,
+    pub(crate) force: bool,
+}
+
+#[async_trait]
+impl<B: WakeComputeBackend + Sync> WakeComputeBackend for ForceWake<'_, B> {
+    async fn wake_compute(&self, ctx: &RequestContext) -> Result<CachedNodeInfo, WakeComputeError> {
+        let node_info = self.backend.wake_compute(ctx).await?;
+        if !self.force || !matches!(node_info.aux.cold_start_info, ColdStartInfo::WarmCached) {
+            return Ok(node_info);
+        }
+        info!("bypassing cached compute node info for a forced wake-up");
+        Metrics::get().proxy.forced_wakes_total.inc();
+        node_info.invalidate();
+        self.backend.wake_compute(ctx).await
+    }
+}
+
+/// Wakes the compute without connecting to it, e.g. to warm it up ahead of the first query.
+/// The node info is cached as usual, so the connections that follow skip the wake up.
+pub(crate) async fn wake_compute_only<B: WakeComputeBackend>(
+    ctx: &RequestContext,
+    api: &B,
+    config: RetryConfig,
+) -> Result<(), WakeComputeError> {
+    let mut num_retries = 0;
+    wake_compute(&mut num_retries, ctx, api, config).await?;
+    Ok(())
+}
+
+/// Drop stale entries once the map grows beyond this many computes.
+const GC_THRESHOLD: usize = 4096;
+
+/// When computes were last woken through the control plane.
+///
+/// A connect failure on cached node info normally invalidates it and wakes the compute again.
+/// If the control plane woke that compute less than `freshness` ago, the node info is almost
+/// certainly still valid and the failure is more likely a hiccup of the compute itself, so the
+/// wake-up is skipped once and the connect retried on the same node info instead.
+pub struct RecentWakes {
+    freshness: Option<Duration>,
+    computes: ClashMap<SmolStr, Instant>,
+}
+
+impl RecentWakes {
+    /// `None` never skips a wake-up.
+    pub fn new(freshness: Option<Duration>) -> Self {
+        Self {
+            freshness,
+            computes: ClashMap::default(),
+        }
+    }
+
+    /// Records the node info returned by [`wake_compute`], unless it came from the cache.
+    pub(crate) fn record(&self, node_info: &NodeInfo) {
+        let Some(freshness) = self.freshness else {
+            return;
+        };
+        if matches!(node_info.aux.cold_start_info, ColdStartInfo::WarmCached) {
+            return;
+        }
+
+        let now = Instant::now();
+        if self.computes.len() >= GC_THRESHOLD {
+            self.computes
+                .retain(|_, woken_at| now < *woken_at + freshness);
+        }
+        self.computes.insert(node_info.aux.compute_id.clone(), now);
+    }
+
+    /// Whether the wake-up after a failed connect to this compute can be skipped. Only one
+    /// wake-up is skipped per wake, the next failure wakes the compute again.
+    pub(crate) fn skip_wake(&self, node_info: &NodeInfo) -> bool {
+        let Some(freshness) = self.freshness else {
+            return false;
+        };
+        self.computes
+            .remove(&node_info.aux.compute_id)
+            .is_some_and(|(_, woken_at)| Instant::now() < woken_at + freshness)
+    }
+}
+
 fn report_error(e: &WakeComputeError, retry: bool) {
     let kind = e.get_error_kind();
 
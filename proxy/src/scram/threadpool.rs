@@ -10,6 +10,7 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
 use std::task::{Context, Poll};
+use std::time::Instant;
 
 use futures::FutureExt;
 use rand::rngs::SmallRng;
@@ -73,11 +74,16 @@ pub fn new(mut n_workers: u8) -> Arc<Self> {
     }
 
     pub(crate) fn spawn_job(&self, endpoint: EndpointIdInt, pbkdf2: Pbkdf2) -> JobHandle {
+        let queued = QueuedJob::new(self.metrics.clone());
         JobHandle(
             self.runtime
                 .as_ref()
                 .expect("runtime is always set")
-                .spawn(JobSpec { pbkdf2, endpoint }),
+                .spawn(JobSpec {
+                    pbkdf2,
+                    endpoint,
+                    queued: Some(queued),
+                }),
         )
     }
 }
@@ -127,12 +133,46 @@ fn should_run(&mut self, job: &JobSpec) -> bool {
 struct JobSpec {
     pbkdf2: Pbkdf2,
     endpoint: EndpointIdInt,
+    /// Set until a worker first polls the job.
+    queued: Option<QueuedJob>,
+}
+
+/// Counts a job in the queue depth of the pool until dropped.
+struct QueuedJob {
+    metrics: Arc<ThreadPoolMetrics>,
+    since: Instant,
+}
+
+impl QueuedJob {
+    fn new(metrics: Arc<ThreadPoolMetrics>) -> Self {
+        metrics.queued_jobs.get_metric().inc();
+        Self {
+            metrics,
+            since: Instant::now(),
+        }
+    }
+
+    fn started(self) {
+        self.metrics
+            .job_wait_seconds
+            .observe(self.since.elapsed().as_secs_f64());
+    }
+}
+
+impl Drop for QueuedJob {
+    fn drop(&mut self) {
+        self.metrics.queued_jobs.get_metric().dec();
+    }
 }
 
 impl Future for JobSpec {
     type Output = [u8; 32];
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(queued) = self.queued.take() {
+            queued.started();
+        }
+
         STATE.with_borrow_mut(|state| {
             let state = state.as_mut().expect("should be set on thread startup");
 
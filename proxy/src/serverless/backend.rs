@@ -1,49 +1,64 @@
 use std::io;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use async_trait::async_trait;
+use camino::Utf8Path;
 use ed25519_dalek::SigningKey;
 use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
 use jose_jwk::jose_b64;
 use postgres_client::config::SslMode;
 use rand::rngs::OsRng;
-use rustls::pki_types::{DnsName, ServerName};
-use tokio::net::{TcpStream, lookup_host};
+use rustls::pki_types::ServerName;
+use smol_str::SmolStr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream, lookup_host};
 use tokio_rustls::TlsConnector;
 use tracing::field::display;
-use tracing::{debug, info};
+use tracing::{Instrument, debug, info, info_span, warn};
 
 use super::AsyncRW;
+use super::circuit_breaker::ComputeCircuitBreaker;
 use super::conn_pool::poll_client;
 use super::conn_pool_lib::{Client, ConnInfo, EndpointConnPool, GlobalConnPool};
 use super::http_conn_pool::{self, HttpConnPool, Send, poll_http2_client};
+use super::listen::Listeners;
 use super::local_conn_pool::{self, EXT_NAME, EXT_SCHEMA, EXT_VERSION, LocalConnPool};
+use super::recent_connections::{ConnectionRecord, RecentConnections};
+use super::sticky_session::{StickySession, StickySessions};
 use crate::auth::backend::local::StaticAuthRules;
 use crate::auth::backend::{ComputeCredentialKeys, ComputeCredentials, ComputeUserInfo};
 use crate::auth::{self, AuthError};
+use crate::compute::ConnectInfo;
 use crate::compute_ctl::{
     ComputeCtlError, ExtensionInstallRequest, Privilege, SetRoleGrantsRequest,
 };
 use crate::config::{ComputeConfig, ProxyConfig};
 use crate::context::RequestContext;
-use crate::control_plane::CachedNodeInfo;
-use crate::control_plane::client::ApiLockError;
+use crate::control_plane::client::{ApiLockError, WakeComputePermit};
 use crate::control_plane::errors::{GetAuthInfoError, WakeComputeError};
 use crate::control_plane::locks::ApiLocks;
+use crate::control_plane::read_endpoints::{ReadEndpointGuard, ReadEndpointPolicy};
+use crate::control_plane::{CachedNodeInfo, ControlPlaneApi};
 use crate::error::{ErrorKind, ReportableError, UserFacingError};
 use crate::intern::EndpointIdInt;
+use crate::metrics::{HttpPoolKind, HttpPoolOutcome, HttpPoolOutcomeGroup, Metrics};
+use crate::proxy::PgSettings;
 use crate::proxy::connect_compute::ConnectMechanism;
 use crate::proxy::retry::{CouldRetry, ShouldRetryWakeCompute};
+use crate::proxy::wake_compute::ForceWake;
 use crate::rate_limiter::EndpointRateLimiter;
-use crate::types::{EndpointId, Host, LOCAL_PROXY_SUFFIX};
+use crate::types::{DbName, EndpointId, Host, LOCAL_PROXY_SUFFIX};
 
 pub(crate) struct PoolingBackend {
     pub(crate) http_conn_pool: Arc<GlobalConnPool<Send, HttpConnPool<Send>>>,
     pub(crate) local_pool: Arc<LocalConnPool<postgres_client::Client>>,
     pub(crate) pool:
         Arc<GlobalConnPool<postgres_client::Client, EndpointConnPool<postgres_client::Client>>>,
+    pub(crate) sticky_sessions: Arc<StickySessions<Client<postgres_client::Client>>>,
+    pub(crate) listeners: Arc<Listeners>,
+    pub(crate) recent_connections: Arc<RecentConnections>,
 
     pub(crate) config: &'static ProxyConfig,
     pub(crate) auth_backend: &'static crate::auth::Backend<'static, ()>,
@@ -55,10 +70,42 @@ pub(crate) async fn authenticate_with_password(
         &self,
         ctx: &RequestContext,
         user_info: &ComputeUserInfo,
+        dbname: &DbName,
         password: &[u8],
     ) -> Result<ComputeCredentials, AuthError> {
         ctx.set_auth_method(crate::context::AuthMethod::Cleartext);
 
+        let res = self
+            .authenticate_with_password_inner(ctx, user_info, dbname, password)
+            .await;
+        if self.config.authentication_config.audit_log {
+            auth::audit::auth_attempt(
+                ctx,
+                user_info,
+                crate::context::AuthMethod::Cleartext,
+                res.as_ref().map(|_| ()),
+            );
+        }
+        res
+    }
+
+    async fn authenticate_with_password_inner(
+        &self,
+        ctx: &RequestContext,
+        user_info: &ComputeUserInfo,
+        dbname: &DbName,
+        password: &[u8],
+    ) -> Result<ComputeCredentials, AuthError> {
+        // Without a TLS config, the HTTP connection carrying the password is not encrypted.
+        if self
+            .config
+            .authentication_config
+            .cleartext_password_requires_tls
+            && self.config.tls_config.load().is_none()
+        {
+            return Err(AuthError::CleartextPasswordRequiresTls);
+        }
+
         let user_info = user_info.clone();
         let backend = self.auth_backend.as_ref().map(|()| user_info.clone());
         let access_control = backend.get_endpoint_access_control(ctx).await?;
@@ -67,6 +114,7 @@ pub(crate) async fn authenticate_with_password(
             self.config.authentication_config.ip_allowlist_check_enabled,
             self.config.authentication_config.is_vpc_acccess_proxy,
         )?;
+        access_control.check_database(dbname)?;
 
         access_control.connection_attempt_rate_limit(
             ctx,
@@ -109,12 +157,18 @@ pub(crate) async fn authenticate_with_jwt(
         &self,
         ctx: &RequestContext,
         user_info: &ComputeUserInfo,
+        dbname: &DbName,
         jwt: String,
     ) -> Result<ComputeCredentials, AuthError> {
         ctx.set_auth_method(crate::context::AuthMethod::Jwt);
 
         match &self.auth_backend {
             crate::auth::Backend::ControlPlane(console, ()) => {
+                let access_control = console
+                    .get_endpoint_access_control(ctx, &user_info.endpoint, &user_info.user)
+                    .await?;
+                access_control.check_database(dbname)?;
+
                 self.config
                     .authentication_config
                     .jwks_cache
@@ -165,39 +219,71 @@ pub(crate) async fn authenticate_with_jwt(
     pub(crate) async fn connect_to_compute(
         &self,
         ctx: &RequestContext,
-        conn_info: ConnInfo,
+        mut conn_info: ConnInfo,
         keys: ComputeCredentials,
         force_new: bool,
+        force_wake: bool,
+        read_only: bool,
+        mut sticky_session: Option<&mut StickySession<Client<postgres_client::Client>>>,
     ) -> Result<Client<postgres_client::Client>, HttpConnError> {
-        let maybe_client = if force_new {
+        let named_pools = &self.config.http_config.named_pools;
+        if let Some(pool_name) = (conn_info.pool_name.as_ref())
+            .filter(|pool_name| !named_pools.contains_key(pool_name.as_str()))
+        {
+            return Err(HttpConnError::UnknownPool(pool_name.clone()));
+        }
+
+        if let Some(client) = sticky_session
+            .as_deref_mut()
+            .and_then(StickySession::take_client)
+        {
+            debug!("sticky session: reusing the session's connection");
+            return Ok(client);
+        }
+
+        // read-only connections go to the read endpoints and are pooled apart from the primary's.
+        conn_info.read_only = read_only || conn_info.read_replica;
+        let outcome = if force_new || sticky_session.is_some() {
             debug!("pool: pool is disabled");
-            None
+            HttpPoolOutcome::ForcedNew
         } else {
             debug!("pool: looking for an existing connection");
-            self.pool.get(ctx, &conn_info)?
+            if let Some(client) = self.pool.get(ctx, &conn_info)? {
+                record_pool_outcome(HttpPoolKind::Postgres, HttpPoolOutcome::Hit);
+                return Ok(client);
+            }
+            HttpPoolOutcome::Miss
         };
+        record_pool_outcome(HttpPoolKind::Postgres, outcome);
 
-        if let Some(client) = maybe_client {
-            return Ok(client);
-        }
         let conn_id = uuid::Uuid::new_v4();
         tracing::Span::current().record("conn_id", display(conn_id));
         info!(%conn_id, "pool: opening a new connection '{conn_info}'");
         let backend = self.auth_backend.as_ref().map(|()| keys.info);
-        crate::proxy::connect_compute::connect_to_compute(
+        let mut client = crate::proxy::connect_compute::connect_to_compute(
             ctx,
             &TokioMechanism {
                 conn_id,
                 conn_info,
                 pool: self.pool.clone(),
-                locks: &self.config.connect_compute_locks,
                 keys: keys.keys,
+                limits: self.connect_limits(),
+                recent_connections: Arc::clone(&self.recent_connections),
+            },
+            &ForceWake {
+                backend: &backend,
+                force: force_wake,
             },
-            &backend,
             self.config.wake_compute_retry_config,
             &self.config.connect_to_compute,
         )
-        .await
+        .await?;
+
+        if sticky_session.is_some() {
+            // the session owns the connection now, so it is never reset and pooled.
+            client.inner().1.detach();
+        }
+        Ok(client)
     }
 
     // Wake up the destination if needed
@@ -208,12 +294,17 @@ pub(crate) async fn connect_to_compute(
     pub(crate) async fn connect_to_local_proxy(
         &self,
         ctx: &RequestContext,
-        conn_info: ConnInfo,
+        mut conn_info: ConnInfo,
+        read_only: bool,
     ) -> Result<http_conn_pool::Client<Send>, HttpConnError> {
+        // read-only connections go to the read endpoints and are pooled apart from the primary's.
+        conn_info.read_only = read_only || conn_info.read_replica;
         debug!("pool: looking for an existing connection");
         if let Ok(Some(client)) = self.http_conn_pool.get(ctx, &conn_info) {
+            record_pool_outcome(HttpPoolKind::Http2, HttpPoolOutcome::Hit);
             return Ok(client);
         }
+        record_pool_outcome(HttpPoolKind::Http2, HttpPoolOutcome::Miss);
 
         let conn_id = uuid::Uuid::new_v4();
         tracing::Span::current().record("conn_id", display(conn_id));
@@ -232,7 +323,7 @@ pub(crate) async fn connect_to_local_proxy(
                 conn_id,
                 conn_info,
                 pool: self.http_conn_pool.clone(),
-                locks: &self.config.connect_compute_locks,
+                limits: self.connect_limits(),
             },
             &backend,
             self.config.wake_compute_retry_config,
@@ -257,8 +348,16 @@ pub(crate) async fn connect_to_local_postgres(
         ctx: &RequestContext,
         conn_info: ConnInfo,
     ) -> Result<Client<postgres_client::Client>, HttpConnError> {
-        if let Some(client) = self.local_pool.get(ctx, &conn_info)? {
+        // pooled connections are keyed by database and user only, so connections with
+        // custom settings are neither taken from nor returned to the pool.
+        let pooled = conn_info.pg_settings.is_empty();
+        if !pooled {
+            record_pool_outcome(HttpPoolKind::Local, HttpPoolOutcome::ForcedNew);
+        } else if let Some(client) = self.local_pool.get(ctx, &conn_info)? {
+            record_pool_outcome(HttpPoolKind::Local, HttpPoolOutcome::Hit);
             return Ok(client);
+        } else {
+            record_pool_outcome(HttpPoolKind::Local, HttpPoolOutcome::Miss);
         }
 
         let local_backend = match &self.auth_backend {
@@ -268,7 +367,12 @@ pub(crate) async fn connect_to_local_postgres(
             auth::Backend::Local(local) => local,
         };
 
-        if !self.local_pool.initialized(&conn_info) {
+        if local_backend.skip_extension_install {
+            // the extension is already installed, there is nothing to initialize.
+            if !self.local_pool.initialized(&conn_info) {
+                self.local_pool.set_initialized(&conn_info);
+            }
+        } else if !self.local_pool.initialized(&conn_info) {
             // only install and grant usage one at a time.
             let _permit = local_backend
                 .initialize
@@ -278,23 +382,22 @@ pub(crate) async fn connect_to_local_postgres(
 
             // check again for race
             if !self.local_pool.initialized(&conn_info) {
+                // if this fails, the pool stays uninitialized and the next request retries.
                 local_backend
                     .compute_ctl
-                    .install_extension(&ExtensionInstallRequest {
-                        extension: EXT_NAME,
-                        database: conn_info.dbname.clone(),
-                        version: EXT_VERSION,
-                    })
-                    .await?;
-
-                local_backend
-                    .compute_ctl
-                    .grant_role(&SetRoleGrantsRequest {
-                        schema: EXT_SCHEMA,
-                        privileges: vec![Privilege::Usage],
-                        database: conn_info.dbname.clone(),
-                        role: conn_info.user_info.user.clone(),
-                    })
+                    .install_extension_and_grant_role(
+                        &ExtensionInstallRequest {
+                            extension: EXT_NAME,
+                            database: conn_info.dbname.clone(),
+                            version: EXT_VERSION,
+                        },
+                        &SetRoleGrantsRequest {
+                            schema: EXT_SCHEMA,
+                            privileges: vec![Privilege::Usage],
+                            database: conn_info.dbname.clone(),
+                            role: conn_info.user_info.user.clone(),
+                        },
+                    )
                     .await?;
 
                 self.local_pool.set_initialized(&conn_info);
@@ -307,6 +410,16 @@ pub(crate) async fn connect_to_local_postgres(
 
         let (key, jwk) = create_random_jwk();
 
+        // the session jwk must be the one we generated, the client doesn't get to override it.
+        let mut settings = conn_info.pg_settings.clone();
+        if settings.remove_prefixed("pg_session_jwt.") > 0 {
+            warn!("ignoring client provided pg_session_jwt settings");
+        }
+        settings.insert(
+            "pg_session_jwt.jwk",
+            &serde_json::to_string(&jwk).expect("serializing jwk to json should not fail"),
+        );
+
         let mut config = local_backend
             .node_info
             .conn_info
@@ -314,31 +427,40 @@ pub(crate) async fn connect_to_local_postgres(
         config
             .user(&conn_info.user_info.user)
             .dbname(&conn_info.dbname)
-            .set_param(
-                "options",
-                &format!(
-                    "-c pg_session_jwt.jwk={}",
-                    serde_json::to_string(&jwk).expect("serializing jwk to json should not fail")
-                ),
-            );
+            .set_param("options", &settings.to_options_raw());
 
         let pause = ctx.latency_timer_pause(crate::metrics::Waiting::Compute);
-        let (client, connection) = config.connect(&postgres_client::NoTls).await?;
-        drop(pause);
-
-        let pid = client.get_process_id();
-        tracing::Span::current().record("pid", pid);
+        let mut handle = if local_backend.node_info.conn_info.unix_socket.is_some() {
+            let (client, connection) = config.connect_unix().await?;
+            drop(pause);
+            tracing::Span::current().record("pid", client.get_process_id());
+
+            local_conn_pool::poll_client(
+                self.local_pool.clone(),
+                ctx,
+                conn_info,
+                client,
+                connection,
+                key,
+                conn_id,
+                local_backend.node_info.aux.clone(),
+            )
+        } else {
+            let (client, connection) = config.connect(&postgres_client::NoTls).await?;
+            drop(pause);
+            tracing::Span::current().record("pid", client.get_process_id());
 
-        let mut handle = local_conn_pool::poll_client(
-            self.local_pool.clone(),
-            ctx,
-            conn_info,
-            client,
-            connection,
-            key,
-            conn_id,
-            local_backend.node_info.aux.clone(),
-        );
+            local_conn_pool::poll_client(
+                self.local_pool.clone(),
+                ctx,
+                conn_info,
+                client,
+                connection,
+                key,
+                conn_id,
+                local_backend.node_info.aux.clone(),
+            )
+        };
 
         {
             let (client, mut discard) = handle.inner();
@@ -350,11 +472,37 @@ pub(crate) async fn connect_to_local_postgres(
                 return Err(e.into());
             }
 
+            for statement in &local_backend.session_setup {
+                if let Err(e) = client.batch_execute(statement).await {
+                    discard.discard();
+                    return Err(e.into());
+                }
+            }
+
             info!("backend session state initialized");
+
+            if !pooled {
+                discard.detach();
+            }
         }
 
         Ok(handle)
     }
+
+    fn connect_limits(&self) -> ComputeConnectLimits {
+        ComputeConnectLimits {
+            locks: &self.config.connect_compute_locks,
+            endpoint_locks: &self.config.endpoint_connect_compute_locks,
+            circuit_breaker: &self.config.http_config.compute_circuit_breaker,
+        }
+    }
+}
+
+fn record_pool_outcome(pool: HttpPoolKind, outcome: HttpPoolOutcome) {
+    Metrics::get()
+        .proxy
+        .http_pool_connections_total
+        .inc(HttpPoolOutcomeGroup { pool, outcome });
 }
 
 fn create_random_jwk() -> (SigningKey, jose_jwk::Key) {
@@ -390,6 +538,12 @@ pub(crate) enum HttpConnError {
     WakeCompute(#[from] WakeComputeError),
     #[error("error acquiring resource permit: {0}")]
     TooManyConnectionAttempts(#[from] ApiLockError),
+    #[error("error acquiring endpoint resource permit: {0}")]
+    TooManyEndpointConnectionAttempts(ApiLockError),
+    #[error("compute {0} is failing repeatedly, not connecting to it for now")]
+    CircuitBreakerOpen(SmolStr),
+    #[error("connection pool {0} is not configured")]
+    UnknownPool(SmolStr),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -420,6 +574,9 @@ fn get_error_kind(&self) -> ErrorKind {
             HttpConnError::AuthError(a) => a.get_error_kind(),
             HttpConnError::WakeCompute(w) => w.get_error_kind(),
             HttpConnError::TooManyConnectionAttempts(w) => w.get_error_kind(),
+            HttpConnError::TooManyEndpointConnectionAttempts(w) => w.get_error_kind(),
+            HttpConnError::CircuitBreakerOpen(_) => ErrorKind::Compute,
+            HttpConnError::UnknownPool(_) => ErrorKind::User,
         }
     }
 }
@@ -438,6 +595,13 @@ fn to_string_client(&self) -> String {
             HttpConnError::TooManyConnectionAttempts(_) => {
                 "Failed to acquire permit to connect to the database. Too many database connection attempts are currently ongoing.".to_owned()
             }
+            HttpConnError::TooManyEndpointConnectionAttempts(_) => {
+                "Failed to acquire permit to connect to the database. Too many connection attempts to this endpoint are currently ongoing.".to_owned()
+            }
+            HttpConnError::CircuitBreakerOpen(_) => {
+                "The database is temporarily unavailable after repeated connection failures.".to_owned()
+            }
+            HttpConnError::UnknownPool(_) => self.to_string(),
         }
     }
 }
@@ -454,6 +618,9 @@ fn could_retry(&self) -> bool {
             HttpConnError::AuthError(_) => false,
             HttpConnError::WakeCompute(_) => false,
             HttpConnError::TooManyConnectionAttempts(_) => false,
+            HttpConnError::TooManyEndpointConnectionAttempts(_) => false,
+            HttpConnError::CircuitBreakerOpen(_) => false,
+            HttpConnError::UnknownPool(_) => false,
         }
     }
 }
@@ -462,7 +629,11 @@ fn should_retry_wake_compute(&self) -> bool {
         match self {
             HttpConnError::PostgresConnectionError(e) => e.should_retry_wake_compute(),
             // we never checked cache validity
-            HttpConnError::TooManyConnectionAttempts(_) => false,
+            HttpConnError::TooManyConnectionAttempts(_)
+            | HttpConnError::TooManyEndpointConnectionAttempts(_) => false,
+            // the breaker is only opened by repeated failures, a new wake up is not going to help
+            HttpConnError::CircuitBreakerOpen(_) => false,
+            HttpConnError::UnknownPool(_) => false,
             _ => true,
         }
     }
@@ -500,14 +671,79 @@ fn should_retry_wake_compute(&self) -> bool {
     }
 }
 
+/// Limits applied to every new compute connection opened by the serverless backend.
+struct ComputeConnectLimits {
+    /// connect_to_compute concurrency lock
+    locks: &'static ApiLocks<Host>,
+    /// connect_to_compute concurrency lock, per endpoint
+    endpoint_locks: &'static ApiLocks<EndpointIdInt>,
+
+    /// Fast-fails connections to computes that keep failing.
+    circuit_breaker: &'static ComputeCircuitBreaker,
+}
+
+/// The compute picked for a connection attempt.
+struct ComputeAttempt<'a> {
+    compute: &'a ConnectInfo,
+    read_endpoint: Option<ReadEndpointGuard>,
+    /// Held until the attempt finishes.
+    permits: ConnectPermits,
+}
+
+struct ConnectPermits {
+    endpoint: WakeComputePermit,
+    host: WakeComputePermit,
+}
+
+impl ConnectPermits {
+    fn release_result<T, E>(self, res: Result<T, E>) -> Result<T, E> {
+        self.host.release_result(self.endpoint.release_result(res))
+    }
+}
+
+impl ComputeConnectLimits {
+    /// Picks the compute for a new connection, fast-fails it if its circuit breaker is open,
+    /// and waits for the per-endpoint and per-host connect permits.
+    async fn acquire<'a>(
+        &self,
+        conn_info: &ConnInfo,
+        node_info: &'a CachedNodeInfo,
+        policy: ReadEndpointPolicy,
+    ) -> Result<ComputeAttempt<'a>, HttpConnError> {
+        if conn_info.read_replica && node_info.read_endpoints.is_none() {
+            warn!("no read replica available, falling back to the primary");
+        }
+        let (compute, read_endpoint) = node_info.select_compute(conn_info.read_only, policy);
+        tracing::Span::current().record("host", tracing::field::display(&compute.host));
+        let compute_id = &node_info.aux.compute_id;
+        if !self.circuit_breaker.check(compute_id) {
+            return Err(HttpConnError::CircuitBreakerOpen(compute_id.clone()));
+        }
+        let endpoint = self
+            .endpoint_locks
+            .get_permit(&node_info.aux.endpoint_id)
+            .await
+            .map_err(HttpConnError::TooManyEndpointConnectionAttempts)?;
+        // the wait is recorded in the `connect_compute_lock` semaphore_acquire_seconds metric.
+        let host = self.locks.get_permit(&compute.host).await?;
+
+        Ok(ComputeAttempt {
+            compute,
+            read_endpoint,
+            permits: ConnectPermits { endpoint, host },
+        })
+    }
+}
+
 struct TokioMechanism {
     pool: Arc<GlobalConnPool<postgres_client::Client, EndpointConnPool<postgres_client::Client>>>,
     conn_info: ConnInfo,
     conn_id: uuid::Uuid,
     keys: ComputeCredentialKeys,
+    limits: ComputeConnectLimits,
 
-    /// connect_to_compute concurrency lock
-    locks: &'static ApiLocks<Host>,
+    /// Remembers the parameters of the connections, for the admin API.
+    recent_connections: Arc<RecentConnections>,
 }
 
 #[async_trait]
@@ -516,31 +752,134 @@ impl ConnectMechanism for TokioMechanism {
     type ConnectError = HttpConnError;
     type Error = HttpConnError;
 
+    #[tracing::instrument(skip_all, fields(
+        host = tracing::field::Empty,
+        application_name = tracing::field::Empty,
+    ))]
     async fn connect_once(
         &self,
         ctx: &RequestContext,
         node_info: &CachedNodeInfo,
         compute_config: &ComputeConfig,
     ) -> Result<Self::Connection, Self::ConnectError> {
-        let permit = self.locks.get_permit(&node_info.conn_info.host).await?;
-
-        let mut config = node_info.conn_info.to_postgres_client_config();
+        let ComputeAttempt {
+            compute,
+            read_endpoint,
+            permits,
+        } = self
+            .limits
+            .acquire(
+                &self.conn_info,
+                node_info,
+                compute_config.read_endpoint_policy,
+            )
+            .await?;
+        let compute_id = &node_info.aux.compute_id;
+
+        let mut config = compute.to_postgres_client_config();
         let config = config
             .user(&self.conn_info.user_info.user)
             .dbname(&self.conn_info.dbname)
             .connect_timeout(compute_config.timeout);
+        if let Some(startup_timeout) = compute_config.startup_timeout {
+            config.startup_timeout(startup_timeout);
+        }
+        // lets compute logs and pg_stat_activity tell apart the client applications.
+        if let Some(application_name) = &self.conn_info.application_name {
+            tracing::Span::current().record("application_name", &**application_name);
+            config.set_param("application_name", application_name);
+        }
+        // set at startup, so that `RESET statement_timeout` returns to it.
+        if let Some(timeout) = compute_config.statement_timeout {
+            let mut settings = PgSettings::default();
+            settings.insert("statement_timeout", &timeout.as_millis().to_string());
+            config.set_param("options", &settings.to_options_raw());
+        }
 
         if let ComputeCredentialKeys::AuthKeys(auth_keys) = self.keys {
             config.auth_keys(auth_keys);
         }
 
+        let is_read_endpoint = read_endpoint.is_some();
         let pause = ctx.latency_timer_pause(crate::metrics::Waiting::Compute);
-        let res = config.connect(compute_config).await;
-        drop(pause);
-        let (client, connection) = permit.release_result(res)?;
+        let client = if compute.unix_socket.is_some() {
+            let res = config.connect_unix().await;
+            drop(pause);
+            self.record_connect_result(compute_id, &res);
+            let (client, connection) = permits.release_result(res)?;
+            self.poll_new_client(ctx, node_info, client, connection, read_endpoint)
+        } else {
+            let res = config.connect(compute_config).await;
+            drop(pause);
+            self.record_connect_result(compute_id, &res);
+            let (client, connection) = permits.release_result(res)?;
+            self.poll_new_client(ctx, node_info, client, connection, read_endpoint)
+        };
 
-        tracing::Span::current().record("pid", tracing::field::display(client.get_process_id()));
-        tracing::Span::current().record(
+        if self.recent_connections.is_enabled() {
+            let conn_info = &self.conn_info;
+            self.recent_connections.record(ConnectionRecord {
+                conn_id: self.conn_id,
+                opened_at: SystemTime::now(),
+                endpoint: conn_info.user_info.endpoint.clone(),
+                user: conn_info.user_info.user.clone(),
+                dbname: conn_info.dbname.clone(),
+                options: conn_info
+                    .user_info
+                    .options
+                    .get_cache_key("")
+                    .as_str()
+                    .into(),
+                pool_name: conn_info.pool_name.clone(),
+                application_name: self.conn_info.application_name.clone(),
+                compute_id: compute_id.clone(),
+                host: compute.host.clone(),
+                port: compute.port,
+                unix_socket: compute.unix_socket.is_some(),
+                ssl_mode: format!("{:?}", compute.ssl_mode).to_lowercase(),
+                tls_server_name: compute.tls_server_name.clone(),
+                read_endpoint: is_read_endpoint,
+                connect_timeout: compute_config.timeout,
+                startup_timeout: compute_config.startup_timeout,
+                statement_timeout: compute_config.statement_timeout,
+                max_query_duration: compute_config.max_query_duration,
+            });
+        }
+
+        Ok(client)
+    }
+}
+
+impl TokioMechanism {
+    fn record_connect_result<T>(&self, compute_id: &str, res: &Result<T, postgres_client::Error>) {
+        match res {
+            Ok(_) => self.limits.circuit_breaker.record_success(compute_id),
+            // postgres itself rejected the connection, so the compute is reachable.
+            Err(e) if e.as_db_error().is_some() => {
+                self.limits.circuit_breaker.record_success(compute_id);
+            }
+            Err(_) => self.limits.circuit_breaker.record_failure(compute_id),
+        }
+    }
+
+    fn poll_new_client<S, T>(
+        &self,
+        ctx: &RequestContext,
+        node_info: &CachedNodeInfo,
+        mut client: postgres_client::Client,
+        connection: postgres_client::Connection<S, T>,
+        read_endpoint: Option<ReadEndpointGuard>,
+    ) -> Client<postgres_client::Client>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + std::marker::Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + std::marker::Send + 'static,
+    {
+        client.set_statement_cache_size(self.pool.get_statement_cache_size());
+
+        // on the request span rather than the span of this attempt, which ends with it.
+        let span = ctx.span();
+        span.record("pid", tracing::field::display(client.get_process_id()));
+        span.record(
             "compute_id",
             tracing::field::display(&node_info.aux.compute_id),
         );
@@ -549,7 +888,7 @@ async fn connect_once(
             info!("latency={}, query_id={}", ctx.get_proxy_latency(), query_id);
         }
 
-        Ok(poll_client(
+        poll_client(
             self.pool.clone(),
             ctx,
             self.conn_info.clone(),
@@ -557,7 +896,8 @@ async fn connect_once(
             connection,
             self.conn_id,
             node_info.aux.clone(),
-        ))
+            read_endpoint,
+        )
     }
 }
 
@@ -565,9 +905,7 @@ struct HyperMechanism {
     pool: Arc<GlobalConnPool<Send, HttpConnPool<Send>>>,
     conn_info: ConnInfo,
     conn_id: uuid::Uuid,
-
-    /// connect_to_compute concurrency lock
-    locks: &'static ApiLocks<Host>,
+    limits: ComputeConnectLimits,
 }
 
 #[async_trait]
@@ -576,30 +914,56 @@ impl ConnectMechanism for HyperMechanism {
     type ConnectError = HttpConnError;
     type Error = HttpConnError;
 
+    #[tracing::instrument(skip_all, fields(host = tracing::field::Empty))]
     async fn connect_once(
         &self,
         ctx: &RequestContext,
         node_info: &CachedNodeInfo,
         config: &ComputeConfig,
     ) -> Result<Self::Connection, Self::ConnectError> {
-        let host_addr = node_info.conn_info.host_addr;
-        let host = &node_info.conn_info.host;
-        let permit = self.locks.get_permit(host).await?;
+        let ComputeAttempt {
+            compute,
+            read_endpoint,
+            permits,
+        } = self
+            .limits
+            .acquire(&self.conn_info, node_info, config.read_endpoint_policy)
+            .await?;
+        let host_addr = compute.host_addr;
+        let host = &compute.host;
+        let compute_id = &node_info.aux.compute_id;
 
         let pause = ctx.latency_timer_pause(crate::metrics::Waiting::Compute);
 
-        let tls = if node_info.conn_info.ssl_mode == SslMode::Disable {
+        let tls = if compute.ssl_mode == SslMode::Disable {
             None
         } else {
             Some(&config.tls)
         };
 
-        let port = node_info.conn_info.port;
-        let res = connect_http2(host_addr, host, port, config.timeout, tls).await;
+        let port = compute.port;
+        let res = match &compute.unix_socket {
+            Some(path) => connect_http2_unix(path, config.timeout).await,
+            None => {
+                connect_http2(
+                    host_addr,
+                    host,
+                    port,
+                    config.timeout,
+                    tls,
+                    compute.tls_server_name(),
+                )
+                .await
+            }
+        };
         drop(pause);
-        let (client, connection) = permit.release_result(res)?;
+        match &res {
+            Ok(_) => self.limits.circuit_breaker.record_success(compute_id),
+            Err(_) => self.limits.circuit_breaker.record_failure(compute_id),
+        }
+        let (client, connection) = permits.release_result(res)?;
 
-        tracing::Span::current().record(
+        ctx.span().record(
             "compute_id",
             tracing::field::display(&node_info.aux.compute_id),
         );
@@ -616,20 +980,24 @@ async fn connect_once(
             connection,
             self.conn_id,
             node_info.aux.clone(),
+            read_endpoint,
         ))
     }
 }
 
+// Each phase of the connection gets its own span, so that slow connects can be broken down.
 async fn connect_http2(
     host_addr: Option<IpAddr>,
     host: &str,
     port: u16,
     timeout: Duration,
     tls: Option<&Arc<rustls::ClientConfig>>,
+    tls_server_name: &str,
 ) -> Result<(http_conn_pool::Send, http_conn_pool::Connect), LocalProxyConnError> {
     let addrs = match host_addr {
         Some(addr) => vec![SocketAddr::new(addr, port)],
         None => lookup_host((host, port))
+            .instrument(info_span!("resolve_host"))
             .await
             .map_err(LocalProxyConnError::Io)?
             .collect(),
@@ -647,9 +1015,16 @@ async fn connect_http2(
             }));
         };
 
-        match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        match tokio::time::timeout(timeout, TcpStream::connect(addr))
+            .instrument(info_span!("tcp_connect", %addr))
+            .await
+        {
             Ok(Ok(stream)) => {
                 stream.set_nodelay(true).map_err(LocalProxyConnError::Io)?;
+                // This prevents load balancer from severing the connection.
+                socket2::SockRef::from(&stream)
+                    .set_keepalive(true)
+                    .map_err(LocalProxyConnError::Io)?;
                 break stream;
             }
             Ok(Err(e)) => {
@@ -665,12 +1040,14 @@ async fn connect_http2(
     };
 
     let stream = if let Some(tls) = tls {
-        let host = DnsName::try_from(host)
+        // accepts IP addresses too, which certificates can list as SANs.
+        let server_name = ServerName::try_from(tls_server_name)
             .map_err(io::Error::other)
             .map_err(LocalProxyConnError::Io)?
             .to_owned();
         let stream = TlsConnector::from(tls.clone())
-            .connect(ServerName::DnsName(host), stream)
+            .connect(server_name, stream)
+            .instrument(info_span!("tls_handshake"))
             .await
             .map_err(LocalProxyConnError::Io)?;
         Box::pin(stream) as AsyncRW
@@ -678,12 +1055,41 @@ async fn connect_http2(
         Box::pin(stream) as AsyncRW
     };
 
+    http2_handshake(stream).await
+}
+
+/// Like [`connect_http2`], but for a compute on the same host, so without DNS or TLS.
+async fn connect_http2_unix(
+    path: &Utf8Path,
+    timeout: Duration,
+) -> Result<(http_conn_pool::Send, http_conn_pool::Connect), LocalProxyConnError> {
+    let stream = match tokio::time::timeout(timeout, UnixStream::connect(path))
+        .instrument(info_span!("unix_connect", %path))
+        .await
+    {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => return Err(LocalProxyConnError::Io(e)),
+        Err(e) => {
+            return Err(LocalProxyConnError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                e,
+            )));
+        }
+    };
+
+    http2_handshake(Box::pin(stream)).await
+}
+
+async fn http2_handshake(
+    stream: AsyncRW,
+) -> Result<(http_conn_pool::Send, http_conn_pool::Connect), LocalProxyConnError> {
     let (client, connection) = hyper::client::conn::http2::Builder::new(TokioExecutor::new())
         .timer(TokioTimer::new())
         .keep_alive_interval(Duration::from_secs(20))
         .keep_alive_while_idle(true)
         .keep_alive_timeout(Duration::from_secs(5))
         .handshake(TokioIo::new(stream))
+        .instrument(info_span!("http2_handshake"))
         .await?;
 
     Ok((client, connection))
@@ -0,0 +1,236 @@
+//! Stops connecting to computes that keep failing to accept connections.
+//!
+//! After `failure_threshold` consecutive connect failures within `window`, the breaker for that
+//! compute opens and new attempts fail fast for `cooldown`. After that, a single probe attempt
+//! is let through per `cooldown` until one succeeds and closes the breaker again.
+
+use std::time::Duration;
+
+use clashmap::ClashMap;
+use smol_str::SmolStr;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::metrics::{CircuitBreakerState, Metrics};
+
+/// Drop stale entries once the map grows beyond this many computes.
+const GC_THRESHOLD: usize = 4096;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub window: Duration,
+    pub cooldown: Duration,
+}
+
+pub struct ComputeCircuitBreaker {
+    config: Option<CircuitBreakerConfig>,
+    computes: ClashMap<SmolStr, BreakerState>,
+}
+
+#[derive(Clone, Copy)]
+enum BreakerState {
+    Closed {
+        failures: u32,
+        window_start: Instant,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probe_started: Instant,
+    },
+}
+
+impl BreakerState {
+    fn is_stale(&self, config: &CircuitBreakerConfig, now: Instant) -> bool {
+        match *self {
+            BreakerState::Closed { window_start, .. } => now >= window_start + config.window,
+            BreakerState::Open { until } => now >= until,
+            BreakerState::HalfOpen { probe_started } => now >= probe_started + config.cooldown,
+        }
+    }
+}
+
+impl ComputeCircuitBreaker {
+    /// `None` disables the breaker, so that every attempt is allowed.
+    pub fn new(config: Option<CircuitBreakerConfig>) -> Self {
+        Self {
+            config,
+            computes: ClashMap::default(),
+        }
+    }
+
+    /// Whether a connection attempt to the compute may go ahead.
+    pub(crate) fn check(&self, compute_id: &str) -> bool {
+        let Some(config) = &self.config else {
+            return true;
+        };
+        let Some(mut state) = self.computes.get_mut(compute_id) else {
+            return true;
+        };
+
+        let now = Instant::now();
+        let current = *state;
+        match current {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now < until => false,
+            BreakerState::Open { .. } => {
+                info!(compute_id, "circuit breaker half-open, probing compute");
+                Metrics::get()
+                    .proxy
+                    .compute_circuit_breaker_transitions_total
+                    .inc(CircuitBreakerState::HalfOpen);
+                *state = BreakerState::HalfOpen { probe_started: now };
+                true
+            }
+            // only one probe at a time, unless the last one never reported back.
+            BreakerState::HalfOpen { probe_started } if now < probe_started + config.cooldown => {
+                false
+            }
+            BreakerState::HalfOpen { .. } => {
+                *state = BreakerState::HalfOpen { probe_started: now };
+                true
+            }
+        }
+    }
+
+    pub(crate) fn record_success(&self, compute_id: &str) {
+        if let Some((_, BreakerState::Open { .. } | BreakerState::HalfOpen { .. })) =
+            self.computes.remove(compute_id)
+        {
+            info!(compute_id, "circuit breaker closed");
+            Metrics::get()
+                .proxy
+                .compute_circuit_breaker_transitions_total
+                .inc(CircuitBreakerState::Closed);
+        }
+    }
+
+    pub(crate) fn record_failure(&self, compute_id: &str) {
+        let Some(config) = &self.config else {
+            return;
+        };
+
+        let now = Instant::now();
+        if self.computes.len() >= GC_THRESHOLD {
+            self.computes
+                .retain(|_, state| !state.is_stale(config, now));
+        }
+
+        let mut state =
+            self.computes
+                .entry(SmolStr::from(compute_id))
+                .or_insert(BreakerState::Closed {
+                    failures: 0,
+                    window_start: now,
+                });
+
+        let current = *state;
+        let failures = match current {
+            BreakerState::Closed {
+                failures,
+                window_start,
+            } if now < window_start + config.window => failures + 1,
+            BreakerState::Closed { .. } => {
+                *state = BreakerState::Closed {
+                    failures: 1,
+                    window_start: now,
+                };
+                1
+            }
+            BreakerState::HalfOpen { .. } => config.failure_threshold,
+            // attempts that were already in flight when the breaker opened.
+            BreakerState::Open { .. } => return,
+        };
+
+        if failures >= config.failure_threshold {
+            warn!(compute_id, failures, "circuit breaker opened");
+            Metrics::get()
+                .proxy
+                .compute_circuit_breaker_transitions_total
+                .inc(CircuitBreakerState::Open);
+            *state = BreakerState::Open {
+                until: now + config.cooldown,
+            };
+        } else if let BreakerState::Closed { window_start, .. } = *state {
+            *state = BreakerState::Closed {
+                failures,
+                window_start,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> ComputeCircuitBreaker {
+        ComputeCircuitBreaker::new(Some(CircuitBreakerConfig {
+            failure_threshold: 3,
+            window: Duration::from_secs(10),
+            cooldown: Duration::from_secs(5),
+        }))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn opens_after_threshold() {
+        let breaker = breaker();
+
+        for _ in 0..2 {
+            breaker.record_failure("compute");
+        }
+        assert!(breaker.check("compute"));
+
+        breaker.record_failure("compute");
+        assert!(!breaker.check("compute"));
+        assert!(breaker.check("other"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failures_outside_window_are_forgotten() {
+        let breaker = breaker();
+
+        for _ in 0..2 {
+            breaker.record_failure("compute");
+        }
+        tokio::time::advance(Duration::from_secs(11)).await;
+        breaker.record_failure("compute");
+        assert!(breaker.check("compute"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn half_open_probe() {
+        let breaker = breaker();
+
+        for _ in 0..3 {
+            breaker.record_failure("compute");
+        }
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        // one probe is let through, the rest still fail fast.
+        assert!(breaker.check("compute"));
+        assert!(!breaker.check("compute"));
+
+        // a failed probe opens the breaker again.
+        breaker.record_failure("compute");
+        assert!(!breaker.check("compute"));
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(breaker.check("compute"));
+        breaker.record_success("compute");
+        assert!(breaker.check("compute"));
+        assert!(breaker.check("compute"));
+    }
+
+    #[test]
+    fn disabled() {
+        let breaker = ComputeCircuitBreaker::new(None);
+
+        for _ in 0..100 {
+            breaker.record_failure("compute");
+        }
+        assert!(breaker.check("compute"));
+    }
+}
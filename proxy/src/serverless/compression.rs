@@ -0,0 +1,192 @@
+//! Content encoding of the sql-over-http bodies exchanged between auth-broker and local_proxy.
+
+use std::io;
+
+use async_compression::tokio::bufread::{GzipDecoder, GzipEncoder, ZstdDecoder, ZstdEncoder};
+use futures::executor::block_on;
+use hyper::HeaderMap;
+use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+use hyper::http::HeaderValue;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::ext::TaskExt;
+use crate::http::ReadBodyError;
+use crate::metrics::{HttpDirection, Metrics};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ContentEncoding {
+    Gzip,
+    Zstd,
+}
+
+impl ContentEncoding {
+    pub(crate) fn header_value(self) -> HeaderValue {
+        match self {
+            ContentEncoding::Gzip => HeaderValue::from_static("gzip"),
+            ContentEncoding::Zstd => HeaderValue::from_static("zstd"),
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("gzip") {
+            Some(ContentEncoding::Gzip)
+        } else if s.eq_ignore_ascii_case("zstd") {
+            Some(ContentEncoding::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// The encoding of a body, according to its `Content-Encoding` header.
+    pub(crate) fn from_content_encoding(headers: &HeaderMap) -> io::Result<Option<Self>> {
+        let Some(value) = headers.get(CONTENT_ENCODING) else {
+            return Ok(None);
+        };
+        match value.to_str() {
+            Ok(s) if s.trim().eq_ignore_ascii_case("identity") => Ok(None),
+            Ok(s) => Self::parse(s).map(Some).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "unsupported content encoding")
+            }),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid content encoding",
+            )),
+        }
+    }
+
+    /// The first supported encoding listed in the `Accept-Encoding` header. Quality values are ignored.
+    pub(crate) fn from_accept_encoding(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|coding| Self::parse(coding.split(';').next().unwrap_or_default()))
+    }
+
+    pub(crate) async fn compress(self, data: Vec<u8>) -> Vec<u8> {
+        run_codec(data.len(), move || {
+            let data = &data[..];
+            let mut out = Vec::new();
+            // reading from memory never waits, so the futures complete on the first poll.
+            let res = match self {
+                ContentEncoding::Gzip => block_on(GzipEncoder::new(data).read_to_end(&mut out)),
+                ContentEncoding::Zstd => block_on(ZstdEncoder::new(data).read_to_end(&mut out)),
+            };
+            res.expect("compressing an in-memory buffer should not fail");
+            out
+        })
+        .await
+    }
+
+    /// Decompresses `data`, failing if the decompressed body exceeds `limit` bytes.
+    pub(crate) async fn decompress(
+        self,
+        data: Vec<u8>,
+        limit: usize,
+    ) -> Result<Vec<u8>, ReadBodyError<io::Error>> {
+        run_codec(data.len(), move || {
+            let data = &data[..];
+            match self {
+                ContentEncoding::Gzip => block_on(read_with_limit(GzipDecoder::new(data), limit)),
+                ContentEncoding::Zstd => block_on(read_with_limit(ZstdDecoder::new(data), limit)),
+            }
+        })
+        .await
+    }
+}
+
+/// Bodies up to this size are (de)compressed in place. Larger ones are handed to the blocking
+/// thread pool, so that they don't hold up the other tasks of the runtime worker.
+const INLINE_CODEC_LIMIT: usize = 64 * 1024;
+
+async fn run_codec<T: Send + 'static>(len: usize, f: impl FnOnce() -> T + Send + 'static) -> T {
+    if len <= INLINE_CODEC_LIMIT {
+        f()
+    } else {
+        tokio::task::spawn_blocking(f).await.propagate_task_panic()
+    }
+}
+
+async fn read_with_limit(
+    reader: impl AsyncRead + Unpin,
+    limit: usize,
+) -> Result<Vec<u8>, ReadBodyError<io::Error>> {
+    let mut out = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut out).await?;
+    if out.len() > limit {
+        return Err(ReadBodyError::BodyTooLarge { limit });
+    }
+    Ok(out)
+}
+
+/// Records how many bytes compression saved on the wire.
+pub(crate) fn record_saved_bytes(direction: HttpDirection, decompressed: usize, compressed: usize) {
+    Metrics::get()
+        .proxy
+        .http_local_proxy_compression_saved_bytes
+        .inc_by(direction, decompressed.saturating_sub(compressed) as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn round_trip() {
+        // small enough to be handled inline, and large enough for the blocking pool.
+        for repeat in [100, 10_000] {
+            let data = br#"{"rows":[{"id":1},{"id":2},{"id":3},{"id":4}]}"#.repeat(repeat);
+            for encoding in [ContentEncoding::Gzip, ContentEncoding::Zstd] {
+                let compressed = encoding.compress(data.clone()).await;
+                assert!(compressed.len() < data.len());
+
+                let decompressed = encoding
+                    .decompress(compressed.clone(), data.len())
+                    .await
+                    .unwrap();
+                assert_eq!(decompressed, data);
+
+                let err = encoding
+                    .decompress(compressed, data.len() - 1)
+                    .await
+                    .unwrap_err();
+                assert!(matches!(err, ReadBodyError::BodyTooLarge { .. }));
+
+                let err = encoding
+                    .decompress(data.clone(), data.len())
+                    .await
+                    .unwrap_err();
+                assert!(matches!(err, ReadBodyError::Read(_)));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ContentEncoding::from_accept_encoding(&headers), None);
+        assert_eq!(
+            ContentEncoding::from_content_encoding(&headers).unwrap(),
+            None
+        );
+
+        headers.insert(
+            ACCEPT_ENCODING,
+            HeaderValue::from_static("br;q=1.0, ZSTD;q=0.9, gzip"),
+        );
+        assert_eq!(
+            ContentEncoding::from_accept_encoding(&headers),
+            Some(ContentEncoding::Zstd)
+        );
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        assert_eq!(
+            ContentEncoding::from_content_encoding(&headers).unwrap(),
+            Some(ContentEncoding::Gzip)
+        );
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("br"));
+        ContentEncoding::from_content_encoding(&headers).unwrap_err();
+    }
+}
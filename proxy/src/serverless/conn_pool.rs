@@ -2,34 +2,33 @@
 use std::pin::pin;
 use std::sync::{Arc, Weak};
 use std::task::{Poll, ready};
+use std::time::Duration;
 
 use futures::Future;
 use futures::future::poll_fn;
-use postgres_client::AsyncMessage;
-use postgres_client::tls::MakeTlsConnect;
+use parking_lot::Mutex;
+use postgres_client::{AsyncMessage, Notification, ReadyForQueryStatus};
 use smallvec::SmallVec;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
 use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 use tracing::{Instrument, error, info, info_span, warn};
 #[cfg(test)]
 use {
-    super::conn_pool_lib::GlobalConnPoolOptions,
-    crate::auth::backend::ComputeUserInfo,
-    std::{sync::atomic, time::Duration},
+    super::conn_pool_lib::GlobalConnPoolOptions, crate::auth::backend::ComputeUserInfo,
+    std::sync::atomic,
 };
 
 use super::conn_pool_lib::{
     Client, ClientDataEnum, ClientInnerCommon, ClientInnerExt, ConnInfo, EndpointConnPool,
     GlobalConnPool,
 };
-use crate::config::ComputeConfig;
 use crate::context::RequestContext;
 use crate::control_plane::messages::MetricsAuxInfo;
+use crate::control_plane::read_endpoints::ReadEndpointGuard;
 use crate::metrics::Metrics;
 
-type TlsStream = <ComputeConfig as MakeTlsConnect<TcpStream>>::Stream;
-
 #[derive(Debug, Clone)]
 pub(crate) struct ConnInfoWithAuth {
     pub(crate) conn_info: ConnInfo,
@@ -56,15 +55,23 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     }
 }
 
-pub(crate) fn poll_client<C: ClientInnerExt>(
+pub(crate) fn poll_client<C, S, T>(
     global_pool: Arc<GlobalConnPool<C, EndpointConnPool<C>>>,
     ctx: &RequestContext,
     conn_info: ConnInfo,
     client: C,
-    mut connection: postgres_client::Connection<TcpStream, TlsStream>,
+    mut connection: postgres_client::Connection<S, T>,
     conn_id: uuid::Uuid,
     aux: MetricsAuxInfo,
-) -> Client<C> {
+    read_endpoint: Option<ReadEndpointGuard>,
+) -> Client<C>
+where
+    C: ClientInnerExt,
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    global_pool.config.pool_budget.make_room();
+
     let conn_gauge = Metrics::get().proxy.db_connections.guard(ctx.protocol());
     let mut session_id = ctx.session_id();
     let (tx, mut rx) = tokio::sync::watch::channel(session_id);
@@ -74,8 +81,8 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
     span.in_scope(|| {
         info!(cold_start_info = cold_start_info.as_str(), %conn_info, %session_id, "new connection");
     });
-    let pool = match conn_info.endpoint_cache_key() {
-        Some(endpoint) => Arc::downgrade(&global_pool.get_or_create_endpoint_pool(&endpoint)),
+    let pool = match global_pool.get_or_create_pool(&conn_info) {
+        Some(pool) => Arc::downgrade(&pool),
         None => Weak::new(),
     };
     let pool_clone = pool.clone();
@@ -84,13 +91,20 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
     let idle = global_pool.get_idle_timeout();
     let cancel = CancellationToken::new();
     let cancelled = cancel.clone().cancelled_owned();
+    let notifications = Arc::new(Mutex::new(None));
+    let notifications_clone = Arc::clone(&notifications);
 
     tokio::spawn(
     async move {
         let _conn_gauge = conn_gauge;
+        let _read_endpoint = read_endpoint;
         let mut idle_timeout = pin!(tokio::time::sleep(idle));
         let mut cancelled = pin!(cancelled);
 
+        // closes the subscriber's channel once the connection is gone.
+        let _notifications = scopeguard::guard(notifications_clone.clone(), |n| {
+            n.lock().take();
+        });
         poll_fn(move |cx| {
             if cancelled.as_mut().poll(cx).is_ready() {
                 info!("connection dropped");
@@ -117,7 +131,7 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
                 if let Some(pool) = pool.clone().upgrade() {
                     // remove client from pool - should close the connection if it's idle.
                     // does nothing if the client is currently checked-out and in-use
-                    if pool.write().remove_client(db_user.clone(), conn_id) {
+                    if pool.write().remove_idle_client(db_user.clone(), conn_id) {
                         info!("idle connection removed");
                     }
                 }
@@ -131,7 +145,17 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
                         info!(%session_id, "notice: {}", notice);
                     }
                     Some(Ok(AsyncMessage::Notification(notif))) => {
-                        warn!(%session_id, pid = notif.process_id(), channel = notif.channel(), "notification received");
+                        let subscriber = notifications_clone.lock().clone();
+                        match subscriber {
+                            Some(subscriber) => {
+                                if let Err(e) = subscriber.try_send(notif) {
+                                    warn!(%session_id, "notification dropped: {e}");
+                                }
+                            }
+                            None => {
+                                warn!(%session_id, pid = notif.process_id(), channel = notif.channel(), "notification received");
+                            }
+                        }
                     }
                     Some(Ok(_)) => {
                         warn!(%session_id, "unknown message");
@@ -166,6 +190,7 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
         data: ClientDataEnum::Remote(ClientDataRemote {
             session: tx,
             cancel,
+            notifications,
         }),
     };
 
@@ -176,6 +201,7 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
 pub(crate) struct ClientDataRemote {
     session: tokio::sync::watch::Sender<uuid::Uuid>,
     cancel: CancellationToken,
+    notifications: Arc<Mutex<Option<mpsc::Sender<Notification>>>>,
 }
 
 impl ClientDataRemote {
@@ -186,21 +212,45 @@ pub fn session(&mut self) -> &mut tokio::sync::watch::Sender<uuid::Uuid> {
     pub fn cancel(&mut self) {
         self.cancel.cancel();
     }
+
+    /// Forwards the `NOTIFY`s received on this connection to the returned channel instead of
+    /// logging them. The channel closes when the connection does.
+    pub fn subscribe_notifications(&mut self, buffer: usize) -> mpsc::Receiver<Notification> {
+        let (tx, rx) = mpsc::channel(buffer);
+        *self.notifications.lock() = Some(tx);
+        rx
+    }
+}
+
+/// Sets `statement_timeout` for the session, or resets it to the server default if `None`.
+pub(crate) async fn set_statement_timeout(
+    client: &mut postgres_client::Client,
+    timeout: Option<Duration>,
+) -> Result<ReadyForQueryStatus, postgres_client::Error> {
+    let query = match timeout {
+        Some(timeout) => format!("SET statement_timeout = {}", timeout.as_millis()),
+        None => "RESET statement_timeout".to_owned(),
+    };
+    client.batch_execute(&query).await
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::atomic::AtomicBool;
 
     use super::*;
-    use crate::proxy::NeonOptions;
+    use crate::proxy::{NeonOptions, PgSettings};
     use crate::serverless::cancel_set::CancelSet;
+    use crate::serverless::circuit_breaker::ComputeCircuitBreaker;
+    use crate::serverless::conn_pool_lib::shared_pool_dbname;
+    use crate::serverless::{PoolBudget, PoolReusePolicy};
     use crate::types::{BranchId, EndpointId, ProjectId};
 
-    struct MockClient(Arc<AtomicBool>);
+    struct MockClient(Arc<AtomicBool>, Arc<Mutex<Vec<&'static str>>>);
     impl MockClient {
         fn new(is_closed: bool) -> Self {
-            MockClient(Arc::new(is_closed.into()))
+            MockClient(Arc::new(is_closed.into()), Arc::default())
         }
     }
     impl ClientInnerExt for MockClient {
@@ -210,6 +260,13 @@ fn is_closed(&self) -> bool {
         fn get_process_id(&self) -> i32 {
             0
         }
+        async fn reset_session(
+            &mut self,
+            query: &'static str,
+        ) -> Result<(), postgres_client::Error> {
+            self.1.lock().push(query);
+            Ok(())
+        }
     }
 
     fn create_inner() -> ClientInnerCommon<MockClient> {
@@ -230,37 +287,74 @@ fn create_inner_with(client: MockClient) -> ClientInnerCommon<MockClient> {
             data: ClientDataEnum::Remote(ClientDataRemote {
                 session: tokio::sync::watch::Sender::new(uuid::Uuid::new_v4()),
                 cancel: CancellationToken::new(),
+                notifications: Arc::default(),
             }),
         }
     }
 
-    #[tokio::test]
-    async fn test_pool() {
-        let _ = env_logger::try_init();
-        let config = Box::leak(Box::new(crate::config::HttpConfig {
+    fn test_http_config() -> crate::config::HttpConfig {
+        crate::config::HttpConfig {
             accept_websockets: false,
             pool_options: GlobalConnPoolOptions {
                 max_conns_per_endpoint: 2,
+                min_conns_per_endpoint: 0,
                 gc_epoch: Duration::from_secs(1),
                 pool_shards: 2,
                 idle_timeout: Duration::from_secs(1),
                 opt_in: false,
-                max_total_conns: 3,
+                max_total_conns: 2,
+                reuse_policy: PoolReusePolicy::Lifo,
+                statement_cache_size: 0,
             },
             cancel_set: CancelSet::new(0),
             client_conn_threshold: u64::MAX,
             max_request_size_bytes: usize::MAX,
             max_response_size_bytes: usize::MAX,
-        }));
-        let pool = GlobalConnPool::new(config);
-        let conn_info = ConnInfo {
+            pool_reset_queries: Vec::new(),
+            pool_budget: PoolBudget::new(None),
+            compute_circuit_breaker: ComputeCircuitBreaker::new(None),
+            local_proxy_compression: None,
+            sticky_sessions: None,
+            listen: None,
+            row_streaming: None,
+            statement_filter: None,
+            named_pools: HashMap::new(),
+            recent_connections: 0,
+            shared_pool_dbnames: Vec::new(),
+            expose_backend_pid: false,
+        }
+    }
+
+    /// A connection to `endpoint` with the default settings, for tests to tweak.
+    fn test_conn_info(endpoint: &str) -> ConnInfo {
+        ConnInfo {
             user_info: ComputeUserInfo {
                 user: "user".into(),
-                endpoint: "endpoint".into(),
+                endpoint: endpoint.into(),
                 options: NeonOptions::default(),
             },
             dbname: "dbname".into(),
-        };
+            pg_settings: PgSettings::default(),
+            read_replica: false,
+            read_only: false,
+            pool_name: None,
+            pool_dbname: None,
+            application_name: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool() {
+        let _ = env_logger::try_init();
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_options: GlobalConnPoolOptions {
+                max_total_conns: 3,
+                ..test_http_config().pool_options
+            },
+            ..test_http_config()
+        }));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = test_conn_info("endpoint");
         let ep_pool = Arc::downgrade(
             &pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap()),
         );
@@ -288,7 +382,7 @@ async fn test_pool() {
         let is_closed: Arc<AtomicBool> = Arc::new(false.into());
         {
             let client = Client::new(
-                create_inner_with(MockClient(is_closed.clone())),
+                create_inner_with(MockClient(is_closed.clone(), Arc::default())),
                 conn_info.clone(),
                 ep_pool.clone(),
             );
@@ -304,14 +398,7 @@ async fn test_pool() {
             assert_eq!(2, pool.get_global_connections_count());
         }
 
-        let conn_info = ConnInfo {
-            user_info: ComputeUserInfo {
-                user: "user".into(),
-                endpoint: "endpoint-2".into(),
-                options: NeonOptions::default(),
-            },
-            dbname: "dbname".into(),
-        };
+        let conn_info = test_conn_info("endpoint-2");
         let ep_pool = Arc::downgrade(
             &pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap()),
         );
@@ -335,4 +422,463 @@ async fn test_pool() {
         // Closed client should be removed from the pool.
         assert_eq!(2, pool.get_global_connections_count());
     }
+
+    #[tokio::test]
+    async fn test_pool_statement_timeout_override() {
+        let config = Box::leak(Box::new(test_http_config()));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = test_conn_info("endpoint");
+        let ep_pool = Arc::downgrade(
+            &pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap()),
+        );
+        {
+            let mut client = Client::new(create_inner(), conn_info.clone(), ep_pool.clone());
+            client.set_statement_timeout_overridden(true);
+            drop(client);
+            // The override was never reset, so the connection must not be reused.
+            assert_eq!(0, pool.get_global_connections_count());
+        }
+        {
+            let mut client = Client::new(create_inner(), conn_info.clone(), ep_pool.clone());
+            client.set_statement_timeout_overridden(true);
+            client.set_statement_timeout_overridden(false);
+            drop(client);
+            // Once reset, the connection goes back to the pool.
+            assert_eq!(1, pool.get_global_connections_count());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_min_conns_per_endpoint() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_options: GlobalConnPoolOptions {
+                min_conns_per_endpoint: 1,
+                ..test_http_config().pool_options
+            },
+            ..test_http_config()
+        }));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = test_conn_info("endpoint");
+        let ep_pool = pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap());
+        let conn_ids: Vec<_> = (0..2)
+            .map(|_| {
+                let inner = create_inner();
+                let conn_id = inner.get_conn_id();
+                drop(Client::new(
+                    inner,
+                    conn_info.clone(),
+                    Arc::downgrade(&ep_pool),
+                ));
+                conn_id
+            })
+            .collect();
+        assert_eq!(2, pool.get_global_connections_count());
+
+        // the idle timeout closes connections down to the minimum.
+        for conn_id in conn_ids.iter().rev() {
+            ep_pool
+                .write()
+                .remove_idle_client(conn_info.db_and_user(), *conn_id);
+        }
+        assert_eq!(1, pool.get_global_connections_count());
+
+        // closed connections are still removed.
+        assert!(
+            ep_pool
+                .write()
+                .remove_client(conn_info.db_and_user(), conn_ids[0])
+        );
+        assert_eq!(0, pool.get_global_connections_count());
+    }
+
+    #[tokio::test]
+    async fn test_pool_reset_before_reuse() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_reset_queries: vec![
+                "DISCARD ALL".to_owned(),
+                "SET statement_timeout = 5000".to_owned(),
+            ],
+            ..test_http_config()
+        }));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = test_conn_info("endpoint");
+        let ep_pool = Arc::downgrade(
+            &pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap()),
+        );
+
+        let client = MockClient::new(false);
+        let resets = client.1.clone();
+        drop(Client::new(
+            create_inner_with(client),
+            conn_info.clone(),
+            ep_pool,
+        ));
+        // The connection only becomes reusable once the reset has completed.
+        assert_eq!(0, pool.get_global_connections_count());
+        tokio::task::yield_now().await;
+        assert_eq!(1, pool.get_global_connections_count());
+        // DISCARD ALL cannot run in the implicit transaction of a multi-statement query.
+        assert_eq!(
+            *resets.lock(),
+            ["DISCARD ALL", "SET statement_timeout = 5000"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_options_not_shared() {
+        let config = Box::leak(Box::new(test_http_config()));
+        let pool = GlobalConnPool::new(config);
+        let mut conn_info = test_conn_info("endpoint");
+        conn_info.user_info.options =
+            NeonOptions::parse_options_raw("neon_proxy_params_compat:true");
+        let mut other_conn_info = conn_info.clone();
+        other_conn_info.user_info.options = NeonOptions::default();
+
+        let key = conn_info.endpoint_cache_key().unwrap();
+        let other_key = other_conn_info.endpoint_cache_key().unwrap();
+        let ep_pool = Arc::downgrade(&pool.get_or_create_endpoint_pool(&key));
+        drop(Client::new(create_inner(), conn_info.clone(), ep_pool));
+        assert_eq!(1, pool.get_global_connections_count());
+
+        // same endpoint, database and user, but different options: no connection to reuse.
+        assert!(
+            pool.get_or_create_endpoint_pool(&other_key)
+                .write()
+                .get_conn_entry(other_conn_info.db_and_user())
+                .is_none()
+        );
+        assert!(
+            pool.get_or_create_endpoint_pool(&key)
+                .write()
+                .get_conn_entry(conn_info.db_and_user())
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_read_only_not_shared() {
+        let config = Box::leak(Box::new(test_http_config()));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = ConnInfo {
+            read_only: true,
+            ..test_conn_info("endpoint")
+        };
+        let mut primary_conn_info = conn_info.clone();
+        primary_conn_info.read_only = false;
+
+        let ep_pool = Arc::downgrade(&pool.get_or_create_pool(&conn_info).unwrap());
+        drop(Client::new(create_inner(), conn_info.clone(), ep_pool));
+        assert_eq!(1, pool.get_global_connections_count());
+
+        // a connection to a read endpoint is never handed to a read-write request.
+        let primary_pool = pool.get_or_create_pool(&primary_conn_info).unwrap();
+        assert!(
+            primary_pool
+                .write()
+                .get_conn_entry(primary_conn_info.db_and_user())
+                .is_none()
+        );
+        let read_pool = pool.get_or_create_pool(&conn_info).unwrap();
+        assert!(
+            read_pool
+                .write()
+                .get_conn_entry(conn_info.db_and_user())
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_named_pools() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_options: GlobalConnPoolOptions {
+                max_total_conns: 10,
+                ..test_http_config().pool_options
+            },
+            named_pools: HashMap::from([("reporting".to_owned(), 1)]),
+            ..test_http_config()
+        }));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = test_conn_info("endpoint");
+        let mut reporting = conn_info.clone();
+        reporting.pool_name = Some("reporting".into());
+
+        let ep_pool = Arc::downgrade(&pool.get_or_create_pool(&conn_info).unwrap());
+        let reporting_pool = Arc::downgrade(&pool.get_or_create_pool(&reporting).unwrap());
+        drop(Client::new(
+            create_inner(),
+            reporting.clone(),
+            reporting_pool.clone(),
+        ));
+        // the named pool has its own limit
+        drop(Client::new(
+            create_inner(),
+            reporting.clone(),
+            reporting_pool,
+        ));
+        assert_eq!(1, pool.get_global_connections_count());
+
+        // the default pool does not hand out connections of the named pool, nor the other way round.
+        assert!(
+            pool.get_or_create_pool(&conn_info)
+                .unwrap()
+                .write()
+                .get_conn_entry(conn_info.db_and_user())
+                .is_none()
+        );
+        drop(Client::new(create_inner(), conn_info.clone(), ep_pool));
+        assert!(
+            pool.get_or_create_pool(&reporting)
+                .unwrap()
+                .write()
+                .get_conn_entry(reporting.db_and_user())
+                .is_some()
+        );
+        assert!(
+            pool.get_or_create_pool(&reporting)
+                .unwrap()
+                .write()
+                .get_conn_entry(reporting.db_and_user())
+                .is_none()
+        );
+
+        // closing the endpoint closes its named pools too.
+        assert_eq!(pool.close_endpoint("endpoint"), 1);
+        assert!(pool.global_pool.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shared_pool_dbnames() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_options: GlobalConnPoolOptions {
+                max_total_conns: 10,
+                ..test_http_config().pool_options
+            },
+            shared_pool_dbnames: vec![regex::Regex::new("^tenant_[0-9]+$").unwrap()],
+            ..test_http_config()
+        }));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = |dbname: &str| ConnInfo {
+            dbname: dbname.into(),
+            pool_dbname: shared_pool_dbname(&config.shared_pool_dbnames, dbname),
+            ..test_conn_info("endpoint")
+        };
+        let tenant_1 = conn_info("tenant_1");
+        let tenant_2 = conn_info("tenant_2");
+        let other = conn_info("other");
+        assert!(other.pool_dbname.is_none());
+
+        let ep_pool = Arc::downgrade(&pool.get_or_create_pool(&tenant_1).unwrap());
+        drop(Client::new(create_inner(), tenant_1.clone(), ep_pool));
+
+        // a database that matches no pattern keeps its own pool.
+        assert!(
+            pool.get_or_create_pool(&other)
+                .unwrap()
+                .write()
+                .get_conn_entry(other.db_and_user())
+                .is_none()
+        );
+        assert!(
+            pool.get_or_create_pool(&tenant_2)
+                .unwrap()
+                .write()
+                .get_conn_entry(tenant_2.db_and_user())
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_by_application_name() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_options: GlobalConnPoolOptions {
+                max_total_conns: 10,
+                ..test_http_config().pool_options
+            },
+            ..test_http_config()
+        }));
+        let pool = GlobalConnPool::new(config);
+        let conn_info = |application_name: Option<&str>| ConnInfo {
+            application_name: application_name.map(Into::into),
+            ..test_conn_info("endpoint")
+        };
+        let app_a = conn_info(Some("app_a"));
+        let app_b = conn_info(Some("app_b"));
+        let no_app = conn_info(None);
+
+        let ep_pool = Arc::downgrade(&pool.get_or_create_pool(&app_a).unwrap());
+        drop(Client::new(create_inner(), app_a.clone(), ep_pool));
+
+        // a connection opened with one application_name is not reused by another.
+        for other in [&app_b, &no_app] {
+            assert!(
+                pool.get_or_create_pool(other)
+                    .unwrap()
+                    .write()
+                    .get_conn_entry(other.db_and_user())
+                    .is_none()
+            );
+        }
+        assert!(
+            pool.get_or_create_pool(&app_a)
+                .unwrap()
+                .write()
+                .get_conn_entry(app_a.db_and_user())
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_budget() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_budget: PoolBudget::new(Some(2)),
+            ..test_http_config()
+        }));
+        let pool_a = GlobalConnPool::new(config);
+        let pool_b = GlobalConnPool::new(config);
+        let conn_info = test_conn_info("endpoint");
+        let endpoint = conn_info.endpoint_cache_key().unwrap();
+        let ep_pool_a = Arc::downgrade(&pool_a.get_or_create_endpoint_pool(&endpoint));
+        let ep_pool_b = Arc::downgrade(&pool_b.get_or_create_endpoint_pool(&endpoint));
+
+        drop(Client::new(create_inner(), conn_info.clone(), ep_pool_a));
+        drop(Client::new(
+            create_inner(),
+            conn_info.clone(),
+            ep_pool_b.clone(),
+        ));
+        assert_eq!(1, pool_a.get_global_connections_count());
+        assert_eq!(1, pool_b.get_global_connections_count());
+
+        // The budget is used up, so the least recently used connection, in the other pool,
+        // makes room for this one.
+        drop(Client::new(create_inner(), conn_info, ep_pool_b));
+        assert_eq!(0, pool_a.get_global_connections_count());
+        assert_eq!(2, pool_b.get_global_connections_count());
+    }
+
+    #[tokio::test]
+    async fn test_pool_budget_keeps_min_conns() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_budget: PoolBudget::new(Some(2)),
+            pool_options: GlobalConnPoolOptions {
+                min_conns_per_endpoint: 1,
+                ..test_http_config().pool_options
+            },
+            ..test_http_config()
+        }));
+        let pool_a = GlobalConnPool::new(config);
+        let pool_b = GlobalConnPool::new(config);
+        let conn_info = test_conn_info("endpoint");
+        let endpoint = conn_info.endpoint_cache_key().unwrap();
+        let ep_pool_a = Arc::downgrade(&pool_a.get_or_create_endpoint_pool(&endpoint));
+        let ep_pool_b = Arc::downgrade(&pool_b.get_or_create_endpoint_pool(&endpoint));
+
+        drop(Client::new(create_inner(), conn_info.clone(), ep_pool_a));
+        for _ in 0..3 {
+            drop(Client::new(
+                create_inner(),
+                conn_info.clone(),
+                ep_pool_b.clone(),
+            ));
+        }
+
+        // Only the connections above the minimum can make room, and those are all in pool b.
+        assert_eq!(1, pool_a.get_global_connections_count());
+        assert_eq!(2, pool_b.get_global_connections_count());
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuse_policy() {
+        for (policy, expect_first) in [(PoolReusePolicy::Lifo, 1), (PoolReusePolicy::Fifo, 0)] {
+            let config = Box::leak(Box::new(crate::config::HttpConfig {
+                pool_options: GlobalConnPoolOptions {
+                    reuse_policy: policy,
+                    ..test_http_config().pool_options
+                },
+                ..test_http_config()
+            }));
+            let pool = GlobalConnPool::new(config);
+            let conn_info = test_conn_info("endpoint");
+            let ep_pool =
+                pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap());
+
+            // return two connections to the pool, in order
+            let mut conn_ids = vec![];
+            for _ in 0..2 {
+                let inner = create_inner();
+                conn_ids.push(inner.get_conn_id());
+                drop(Client::new(
+                    inner,
+                    conn_info.clone(),
+                    Arc::downgrade(&ep_pool),
+                ));
+            }
+            assert_eq!(2, pool.get_global_connections_count());
+
+            let first = ep_pool
+                .write()
+                .get_conn_entry(conn_info.db_and_user())
+                .unwrap();
+            assert_eq!(
+                conn_ids[expect_first],
+                first.conn.get_conn_id(),
+                "{policy:?}"
+            );
+            let second = ep_pool
+                .write()
+                .get_conn_entry(conn_info.db_and_user())
+                .unwrap();
+            assert_eq!(
+                conn_ids[1 - expect_first],
+                second.conn.get_conn_id(),
+                "{policy:?}"
+            );
+            assert!(
+                ep_pool
+                    .write()
+                    .get_conn_entry(conn_info.db_and_user())
+                    .is_none()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_close_endpoint() {
+        let config = Box::leak(Box::new(crate::config::HttpConfig {
+            pool_options: GlobalConnPoolOptions {
+                max_total_conns: 4,
+                ..test_http_config().pool_options
+            },
+            ..test_http_config()
+        }));
+        let pool = GlobalConnPool::new(config);
+        // "endpoint2" shares a prefix with "endpoint" but is a different endpoint
+        for endpoint in ["endpoint", "endpoint2"] {
+            let conn_info = test_conn_info(endpoint);
+            let ep_pool =
+                pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap());
+            for _ in 0..2 {
+                drop(Client::new(
+                    create_inner(),
+                    conn_info.clone(),
+                    Arc::downgrade(&ep_pool),
+                ));
+            }
+        }
+        assert_eq!(4, pool.get_global_connections_count());
+
+        assert_eq!(2, pool.close_endpoint("endpoint"));
+        assert_eq!(2, pool.get_global_connections_count());
+        assert_eq!(0, pool.close_endpoint("endpoint"));
+
+        // a connection in use when its pool is closed is not returned to the pool
+        let conn_info = test_conn_info("endpoint2");
+        let ep_pool = Arc::downgrade(
+            &pool.get_or_create_endpoint_pool(&conn_info.endpoint_cache_key().unwrap()),
+        );
+        let client = Client::new(create_inner(), conn_info, ep_pool);
+        assert_eq!(2, pool.close_endpoint("endpoint2"));
+        drop(client);
+        assert_eq!(0, pool.get_global_connections_count());
+    }
 }
@@ -3,14 +3,14 @@
 use std::ops::Deref;
 use std::sync::atomic::{self, AtomicUsize};
 use std::sync::{Arc, Weak};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use clashmap::ClashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use postgres_client::ReadyForQueryStatus;
 use rand::Rng;
-use smol_str::ToSmolStr;
-use tracing::{Span, debug, info};
+use smol_str::{SmolStr, ToSmolStr, format_smolstr};
+use tracing::{Instrument, Span, debug, info};
 
 use super::backend::HttpConnError;
 use super::conn_pool::ClientDataRemote;
@@ -21,6 +21,7 @@
 use crate::control_plane::messages::{ColdStartInfo, MetricsAuxInfo};
 use crate::metrics::{HttpEndpointPoolsGuard, Metrics};
 use crate::protocol2::ConnectionInfoExtra;
+use crate::proxy::PgSettings;
 use crate::types::{DbName, EndpointCacheKey, RoleName};
 use crate::usage_metrics::{Ids, MetricCounter, USAGE_METRICS};
 
@@ -28,20 +29,65 @@
 pub(crate) struct ConnInfo {
     pub(crate) user_info: ComputeUserInfo,
     pub(crate) dbname: DbName,
+    /// Postgres settings from the startup `options`. Only applied by local_proxy.
+    pub(crate) pg_settings: PgSettings,
+    /// The client asked for a read replica with `-c neon.read_replica=true`.
+    pub(crate) read_replica: bool,
+    /// The connection goes to a read endpoint of the compute, if it has any. Such connections
+    /// are pooled apart from connections to the primary.
+    pub(crate) read_only: bool,
+    /// Named connection pool picked with `-c neon.pool=<name>`, kept apart from the default
+    /// pool of the endpoint.
+    pub(crate) pool_name: Option<SmolStr>,
+    /// Pools the connections of `dbname` with those of other databases, see
+    /// [`shared_pool_dbname`].
+    pub(crate) pool_dbname: Option<DbName>,
+    /// The `application_name` the compute connection is opened with. Connections are only
+    /// reused by requests with the same one.
+    pub(crate) application_name: Option<SmolStr>,
+}
+
+/// The name connections to `dbname` are pooled under, if it matches one of `patterns`, shared
+/// with the other databases matching the same pattern.
+pub(crate) fn shared_pool_dbname(patterns: &[regex::Regex], dbname: &str) -> Option<DbName> {
+    let pattern = patterns.iter().find(|pattern| pattern.is_match(dbname))?;
+    // database names cannot contain a NUL, so this never is the name of an actual database.
+    Some(format_smolstr!("\0{}", pattern.as_str()).into())
 }
 
 impl ConnInfo {
     // hm, change to hasher to avoid cloning?
     pub(crate) fn db_and_user(&self) -> (DbName, RoleName) {
-        (self.dbname.clone(), self.user_info.user.clone())
+        let dbname = self.pool_dbname.as_ref().unwrap_or(&self.dbname);
+        let dbname = match &self.application_name {
+            // database names cannot contain a NUL, so this never is the name of another database.
+            Some(application_name) => format_smolstr!("{dbname}\0{application_name}").into(),
+            None => dbname.clone(),
+        };
+        (dbname, self.user_info.user.clone())
     }
 
+    /// Key of the endpoint pool. It includes the startup options, so connections
+    /// are only reused by requests with the same options, and the read/write target.
     pub(crate) fn endpoint_cache_key(&self) -> Option<EndpointCacheKey> {
         // We don't want to cache http connections for ephemeral endpoints.
         if self.user_info.options.is_ephemeral() {
-            None
+            return None;
+        }
+        let key = self.user_info.endpoint_cache_key();
+        if self.read_only {
+            Some(format_smolstr!("{key} read").into())
         } else {
-            Some(self.user_info.endpoint_cache_key())
+            Some(key)
+        }
+    }
+
+    /// Key of the compute connection pool. Named pools of an endpoint get their own key.
+    pub(crate) fn pool_key(&self) -> Option<EndpointCacheKey> {
+        let key = self.endpoint_cache_key()?;
+        match &self.pool_name {
+            Some(pool_name) => Some(format_smolstr!("{key} pool:{pool_name}").into()),
+            None => Some(key),
         }
     }
 }
@@ -88,7 +134,7 @@ pub(crate) fn get_data(&mut self) -> &mut ClientDataEnum {
 
 pub(crate) struct ConnPoolEntry<C: ClientInnerExt> {
     pub(crate) conn: ClientInnerCommon<C>,
-    pub(crate) _last_access: std::time::Instant,
+    pub(crate) last_access: Instant,
 }
 
 // Per-endpoint connection pool, (dbname, username) -> DbUserConnPool
@@ -98,10 +144,15 @@ pub(crate) struct EndpointConnPool<C: ClientInnerExt> {
     total_conns: usize,
     /// max # connections per endpoint
     max_conns: usize,
+    /// # connections the idle timeout does not close below
+    min_conns: usize,
     _guard: HttpEndpointPoolsGuard<'static>,
     global_connections_count: Arc<AtomicUsize>,
     global_pool_size_max_conns: usize,
     pool_name: String,
+    reuse_policy: PoolReusePolicy,
+    reset_queries: &'static [String],
+    pool_budget: &'static PoolBudget,
 }
 
 impl<C: ClientInnerExt> EndpointConnPool<C> {
@@ -109,18 +160,26 @@ pub(crate) fn new(
         hmap: HashMap<(DbName, RoleName), DbUserConnPool<C>>,
         tconns: usize,
         max_conns_per_endpoint: usize,
+        min_conns_per_endpoint: usize,
         global_connections_count: Arc<AtomicUsize>,
         max_total_conns: usize,
         pname: String,
+        reuse_policy: PoolReusePolicy,
+        reset_queries: &'static [String],
+        pool_budget: &'static PoolBudget,
     ) -> Self {
         Self {
             pools: hmap,
             total_conns: tconns,
             max_conns: max_conns_per_endpoint,
+            min_conns: min_conns_per_endpoint.min(max_conns_per_endpoint),
             _guard: Metrics::get().proxy.http_endpoint_pools.guard(),
             global_connections_count,
             global_pool_size_max_conns: max_total_conns,
             pool_name: pname,
+            reuse_policy,
+            reset_queries,
+            pool_budget,
         }
     }
 
@@ -132,10 +191,11 @@ pub(crate) fn get_conn_entry(
             pools,
             total_conns,
             global_connections_count,
+            reuse_policy,
             ..
         } = self;
         pools.get_mut(&db_user).and_then(|pool_entries| {
-            let (entry, removed) = pool_entries.get_conn_entry(total_conns);
+            let (entry, removed) = pool_entries.get_conn_entry(total_conns, *reuse_policy);
             global_connections_count.fetch_sub(removed, atomic::Ordering::Relaxed);
             entry
         })
@@ -173,6 +233,46 @@ pub(crate) fn remove_client(
         }
     }
 
+    /// Removes a connection that has been idle for the idle timeout, unless the pool holds no
+    /// more than its minimum of connections.
+    pub(crate) fn remove_idle_client(
+        &mut self,
+        db_user: (DbName, RoleName),
+        conn_id: uuid::Uuid,
+    ) -> bool {
+        if self.total_conns <= self.min_conns {
+            return false;
+        }
+        self.remove_client(db_user, conn_id)
+    }
+
+    /// Removes the pooled connections of `endpoint`. Returns how many were removed.
+    pub(crate) fn remove_endpoint_conns(&mut self, endpoint: &str) -> usize {
+        let mut removed = 0;
+        for db_pool in self.pools.values_mut() {
+            let before = db_pool.conns.len();
+            db_pool
+                .conns
+                .retain(|entry| entry.conn.aux.endpoint_id.as_str() != endpoint);
+            removed += before - db_pool.conns.len();
+        }
+        if removed > 0 {
+            self.total_conns -= removed;
+            self.global_connections_count
+                .fetch_sub(removed, atomic::Ordering::Relaxed);
+            Metrics::get()
+                .proxy
+                .http_pool_opened_connections
+                .get_metric()
+                .dec_by(removed as i64);
+            info!(
+                "{}: closed {removed} connections of endpoint {endpoint}",
+                self.pool_name
+            );
+        }
+        removed
+    }
+
     pub(crate) fn get_name(&self) -> &str {
         &self.pool_name
     }
@@ -190,14 +290,9 @@ pub(crate) fn get_pool_mut(
 
     pub(crate) fn put(pool: &RwLock<Self>, conn_info: &ConnInfo, client: ClientInnerCommon<C>) {
         let conn_id = client.get_conn_id();
-        let (max_conn, conn_count, pool_name) = {
+        let (pool_budget, pool_name) = {
             let pool = pool.read();
-            (
-                pool.global_pool_size_max_conns,
-                pool.global_connections_count
-                    .load(atomic::Ordering::Relaxed),
-                pool.get_name().to_string(),
-            )
+            (pool.pool_budget, pool.get_name().to_string())
         };
 
         if client.inner.is_closed() {
@@ -205,6 +300,17 @@ pub(crate) fn put(pool: &RwLock<Self>, conn_info: &ConnInfo, client: ClientInner
             return;
         }
 
+        // evict before checking the pool's own limit, as that may free up a slot in it too
+        pool_budget.make_room();
+        let (max_conn, conn_count) = {
+            let pool = pool.read();
+            (
+                pool.global_pool_size_max_conns,
+                pool.global_connections_count
+                    .load(atomic::Ordering::Relaxed),
+            )
+        };
+
         if conn_count >= max_conn {
             info!(%conn_id, "{}: throwing away connection '{conn_info}' because pool is full", pool_name);
             return;
@@ -220,7 +326,7 @@ pub(crate) fn put(pool: &RwLock<Self>, conn_info: &ConnInfo, client: ClientInner
                 let pool_entries = pool.pools.entry(conn_info.db_and_user()).or_default();
                 pool_entries.get_conns().push(ConnPoolEntry {
                     conn: client,
-                    _last_access: std::time::Instant::now(),
+                    last_access: Instant::now(),
                 });
 
                 returned = true;
@@ -280,7 +386,11 @@ pub(crate) trait DbUserConn<C: ClientInnerExt>: Default {
     fn set_initialized(&mut self);
     fn is_initialized(&self) -> bool;
     fn clear_closed_clients(&mut self, conns: &mut usize) -> usize;
-    fn get_conn_entry(&mut self, conns: &mut usize) -> (Option<ConnPoolEntry<C>>, usize);
+    fn get_conn_entry(
+        &mut self,
+        conns: &mut usize,
+        policy: PoolReusePolicy,
+    ) -> (Option<ConnPoolEntry<C>>, usize);
     fn get_conns(&mut self) -> &mut Vec<ConnPoolEntry<C>>;
 }
 
@@ -304,9 +414,18 @@ fn clear_closed_clients(&mut self, conns: &mut usize) -> usize {
         removed
     }
 
-    fn get_conn_entry(&mut self, conns: &mut usize) -> (Option<ConnPoolEntry<C>>, usize) {
+    fn get_conn_entry(
+        &mut self,
+        conns: &mut usize,
+        policy: PoolReusePolicy,
+    ) -> (Option<ConnPoolEntry<C>>, usize) {
         let mut removed = self.clear_closed_clients(conns);
-        let conn = self.conns.pop();
+        // connections are always returned to the back of the queue
+        let conn = match policy {
+            PoolReusePolicy::Lifo => self.conns.pop(),
+            PoolReusePolicy::Fifo if self.conns.is_empty() => None,
+            PoolReusePolicy::Fifo => Some(self.conns.remove(0)),
+        };
         if conn.is_some() {
             *conns -= 1;
             removed += 1;
@@ -326,9 +445,13 @@ fn get_conns(&mut self) -> &mut Vec<ConnPoolEntry<C>> {
     }
 }
 
-pub(crate) trait EndpointConnPoolExt<C: ClientInnerExt> {
+pub(crate) trait EndpointConnPoolExt<C: ClientInnerExt>: Send + Sync + 'static {
     fn clear_closed(&mut self) -> usize;
     fn total_conns(&self) -> usize;
+    /// Last access of the least recently used connection the pool can give up, if any.
+    fn oldest_idle(&self) -> Option<Instant>;
+    /// Removes the least recently used connection. Returns false if there was none to give up.
+    fn evict_oldest_idle(&mut self) -> bool;
 }
 
 impl<C: ClientInnerExt> EndpointConnPoolExt<C> for EndpointConnPool<C> {
@@ -343,6 +466,123 @@ fn clear_closed(&mut self) -> usize {
     fn total_conns(&self) -> usize {
         self.total_conns
     }
+
+    fn oldest_idle(&self) -> Option<Instant> {
+        // like the idle timeout, the budget does not close connections below the minimum.
+        if self.total_conns <= self.min_conns {
+            return None;
+        }
+        self.pools
+            .values()
+            .flat_map(|db_pool| &db_pool.conns)
+            .map(|entry| entry.last_access)
+            .min()
+    }
+
+    fn evict_oldest_idle(&mut self) -> bool {
+        if self.total_conns <= self.min_conns {
+            return false;
+        }
+        let oldest = self
+            .pools
+            .values_mut()
+            .filter_map(|db_pool| {
+                let (idx, entry) = db_pool
+                    .conns
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, entry)| entry.last_access)?;
+                Some((entry.last_access, idx, db_pool))
+            })
+            .min_by_key(|(last_access, _, _)| *last_access);
+        let Some((_, idx, db_pool)) = oldest else {
+            return false;
+        };
+
+        let entry = db_pool.conns.remove(idx);
+        self.total_conns -= 1;
+        self.global_connections_count
+            .fetch_sub(1, atomic::Ordering::Relaxed);
+        Metrics::get()
+            .proxy
+            .http_pool_opened_connections
+            .get_metric()
+            .dec();
+        info!(conn_id = %entry.conn.get_conn_id(), "{}: evicting idle connection to stay within the pool budget", self.pool_name);
+        true
+    }
+}
+
+/// A connection pool whose idle connections count towards, and can be evicted to
+/// stay within, the [`PoolBudget`].
+pub(crate) trait IdleConnPool: Send + Sync {
+    /// Number of connections currently held by the pool.
+    fn pooled_conns(&self) -> usize;
+    /// Last access of the least recently used connection the pool can give up, if any.
+    fn oldest_idle(&self) -> Option<Instant>;
+    /// Removes the least recently used connection. Returns false if there was none to give up.
+    fn evict_oldest_idle(&self) -> bool;
+}
+
+/// Limit on the number of pooled connections across all connection pools of the process.
+///
+/// The per-endpoint (`max_conns_per_endpoint`) and per-pool (`max_total_conns`) limits
+/// are still enforced when a connection is returned to its pool. The budget is checked
+/// whenever a new connection is opened: if it is used up, the least recently used idle
+/// connection of any endpoint in any pool is evicted to make room. A busy endpoint can
+/// thus push out the idle connections of other endpoints, but it never grows past its
+/// own per-endpoint limit, and never pushes another endpoint below `min_conns_per_endpoint`.
+pub struct PoolBudget {
+    max_conns: Option<usize>,
+    pools: Mutex<Vec<Weak<dyn IdleConnPool>>>,
+}
+
+impl PoolBudget {
+    /// `None` disables the budget, but the total is still reported.
+    pub fn new(max_conns: Option<usize>) -> Self {
+        Self {
+            max_conns,
+            pools: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn register(&self, pool: Weak<dyn IdleConnPool>) {
+        let mut pools = self.pools.lock();
+        pools.retain(|pool| pool.strong_count() > 0);
+        pools.push(pool);
+    }
+
+    /// Evicts idle connections until there is room in the budget for one more.
+    pub(crate) fn make_room(&self) {
+        let pools = self.pools.lock();
+        loop {
+            let total: usize = pools
+                .iter()
+                .filter_map(Weak::upgrade)
+                .map(|pool| pool.pooled_conns())
+                .sum();
+            Metrics::get()
+                .proxy
+                .http_pool_budget_connections
+                .get_metric()
+                .set(total as i64);
+
+            if self.max_conns.is_none_or(|max_conns| total < max_conns) {
+                return;
+            }
+
+            let oldest = pools
+                .iter()
+                .filter_map(Weak::upgrade)
+                .filter_map(|pool| Some((pool.oldest_idle()?, pool)))
+                .min_by_key(|(last_access, _)| *last_access);
+            match oldest {
+                Some((_, pool)) if pool.evict_oldest_idle() => {}
+                // nothing left to evict, everything in the pools is in use
+                _ => return,
+            }
+        }
+    }
 }
 
 pub(crate) struct GlobalConnPool<C, P>
@@ -379,6 +619,10 @@ pub struct GlobalConnPoolOptions {
     // falls back to opening a new connection for each request.
     pub max_conns_per_endpoint: usize,
 
+    // Number of pooled connections per endpoint that are kept open when idle.
+    // At most `max_conns_per_endpoint`.
+    pub min_conns_per_endpoint: usize,
+
     pub gc_epoch: Duration,
 
     pub pool_shards: usize,
@@ -389,6 +633,24 @@ pub struct GlobalConnPoolOptions {
 
     // Total number of connections in the pool.
     pub max_total_conns: usize,
+
+    // Order in which idle connections are handed out for reuse.
+    pub reuse_policy: PoolReusePolicy,
+
+    // Number of prepared statements cached per connection. Zero disables the cache.
+    pub statement_cache_size: usize,
+}
+
+/// Order in which idle pooled connections are reused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PoolReusePolicy {
+    /// Reuse the most recently returned connection first. This concentrates
+    /// traffic on few connections, so that the idle ones can be reaped.
+    #[default]
+    Lifo,
+    /// Reuse the least recently returned connection first. This spreads
+    /// the load evenly across all pooled connections.
+    Fifo,
 }
 
 impl<C, P> GlobalConnPool<C, P>
@@ -398,13 +660,17 @@ impl<C, P> GlobalConnPool<C, P>
 {
     pub(crate) fn new(config: &'static crate::config::HttpConfig) -> Arc<Self> {
         let shards = config.pool_options.pool_shards;
-        Arc::new(Self {
+        let pool = Arc::new(Self {
             global_pool: ClashMap::with_shard_amount(shards),
             global_pool_size: AtomicUsize::new(0),
             config,
             global_connections_count: Arc::new(AtomicUsize::new(0)),
             _marker: PhantomData,
-        })
+        });
+        config
+            .pool_budget
+            .register(Arc::downgrade(&pool) as Weak<dyn IdleConnPool>);
+        pool
     }
 
     #[cfg(test)]
@@ -417,6 +683,33 @@ pub(crate) fn get_idle_timeout(&self) -> Duration {
         self.config.pool_options.idle_timeout
     }
 
+    pub(crate) fn get_statement_cache_size(&self) -> usize {
+        self.config.pool_options.statement_cache_size
+    }
+
+    /// Removes the pools of `endpoint`, whatever the startup options in their key. Returns the
+    /// number of connections they held.
+    pub(crate) fn close_endpoint(&self, endpoint: &str) -> usize {
+        let mut closed = 0;
+        let mut removed = 0;
+        self.global_pool.retain(|key, pool| {
+            let matches = key
+                .strip_prefix(endpoint)
+                .is_some_and(|options| options.is_empty() || options.starts_with(' '));
+            if matches {
+                closed += pool.read().total_conns();
+                removed += 1;
+            }
+            !matches
+        });
+        if removed > 0 {
+            self.global_pool_size
+                .fetch_sub(removed, atomic::Ordering::Relaxed);
+            info!("pool: closed {closed} connections of endpoint {endpoint}");
+        }
+        closed
+    }
+
     pub(crate) fn shutdown(&self) {
         // drops all strong references to endpoint-pools
         self.global_pool.clear();
@@ -492,6 +785,37 @@ pub(crate) fn gc(&self, shard: usize) {
     }
 }
 
+impl<C, P> IdleConnPool for GlobalConnPool<C, P>
+where
+    C: ClientInnerExt,
+    P: EndpointConnPoolExt<C>,
+{
+    fn pooled_conns(&self) -> usize {
+        self.global_connections_count
+            .load(atomic::Ordering::Relaxed)
+    }
+
+    fn oldest_idle(&self) -> Option<Instant> {
+        self.global_pool
+            .iter()
+            .filter_map(|entry| entry.value().read().oldest_idle())
+            .min()
+    }
+
+    fn evict_oldest_idle(&self) -> bool {
+        let oldest = self
+            .global_pool
+            .iter()
+            .filter_map(|entry| {
+                let pool = entry.value().clone();
+                let last_access = pool.read().oldest_idle()?;
+                Some((last_access, pool))
+            })
+            .min_by_key(|(last_access, _)| *last_access);
+        oldest.is_some_and(|(_, pool)| pool.write().evict_oldest_idle())
+    }
+}
+
 impl<C: ClientInnerExt> GlobalConnPool<C, EndpointConnPool<C>> {
     pub(crate) fn get(
         self: &Arc<Self>,
@@ -499,11 +823,10 @@ pub(crate) fn get(
         conn_info: &ConnInfo,
     ) -> Result<Option<Client<C>>, HttpConnError> {
         let mut client: Option<ClientInnerCommon<C>> = None;
-        let Some(endpoint) = conn_info.endpoint_cache_key() else {
+        let Some(endpoint_pool) = self.get_or_create_pool(conn_info) else {
             return Ok(None);
         };
 
-        let endpoint_pool = self.get_or_create_endpoint_pool(&endpoint);
         if let Some(entry) = endpoint_pool
             .write()
             .get_conn_entry(conn_info.db_and_user())
@@ -547,9 +870,36 @@ pub(crate) fn get(
         Ok(None)
     }
 
+    /// The pool for the connections of `conn_info`, either the default pool of its endpoint or
+    /// the named pool it asked for. `None` if its connections are not pooled.
+    pub(crate) fn get_or_create_pool(
+        self: &Arc<Self>,
+        conn_info: &ConnInfo,
+    ) -> Option<Arc<RwLock<EndpointConnPool<C>>>> {
+        let key = conn_info.pool_key()?;
+        let max_conns = conn_info
+            .pool_name
+            .as_ref()
+            .and_then(|pool_name| self.config.named_pools.get(pool_name.as_str()))
+            .copied()
+            .unwrap_or(self.config.pool_options.max_conns_per_endpoint);
+        Some(self.get_or_create_pool_with_limit(&key, max_conns))
+    }
+
     pub(crate) fn get_or_create_endpoint_pool(
         self: &Arc<Self>,
         endpoint: &EndpointCacheKey,
+    ) -> Arc<RwLock<EndpointConnPool<C>>> {
+        self.get_or_create_pool_with_limit(
+            endpoint,
+            self.config.pool_options.max_conns_per_endpoint,
+        )
+    }
+
+    fn get_or_create_pool_with_limit(
+        self: &Arc<Self>,
+        endpoint: &EndpointCacheKey,
+        max_conns: usize,
     ) -> Arc<RwLock<EndpointConnPool<C>>> {
         // fast path
         if let Some(pool) = self.global_pool.get(endpoint) {
@@ -560,11 +910,19 @@ pub(crate) fn get_or_create_endpoint_pool(
         let new_pool = Arc::new(RwLock::new(EndpointConnPool {
             pools: HashMap::new(),
             total_conns: 0,
-            max_conns: self.config.pool_options.max_conns_per_endpoint,
+            max_conns,
+            min_conns: self
+                .config
+                .pool_options
+                .min_conns_per_endpoint
+                .min(max_conns),
             _guard: Metrics::get().proxy.http_endpoint_pools.guard(),
             global_connections_count: self.global_connections_count.clone(),
             global_pool_size_max_conns: self.config.pool_options.max_total_conns,
             pool_name: String::from("remote"),
+            reuse_policy: self.config.pool_options.reuse_policy,
+            reset_queries: &self.config.pool_reset_queries,
+            pool_budget: &self.config.pool_budget,
         }));
 
         // find or create a pool for this endpoint
@@ -597,6 +955,8 @@ pub(crate) struct Client<C: ClientInnerExt> {
     inner: Option<ClientInnerCommon<C>>,
     conn_info: ConnInfo,
     pool: Weak<RwLock<EndpointConnPool<C>>>,
+    /// Set while the session carries a per-request `statement_timeout` that has not been reset.
+    statement_timeout_overridden: bool,
 }
 
 pub(crate) struct Discard<'a, C: ClientInnerExt> {
@@ -615,15 +975,23 @@ pub(crate) fn new(
             span: Span::current(),
             conn_info,
             pool,
+            statement_timeout_overridden: false,
         }
     }
 
+    /// Marks whether the session currently has a per-request `statement_timeout`.
+    /// A connection that is still marked when dropped is not returned to the pool.
+    pub(crate) fn set_statement_timeout_overridden(&mut self, overridden: bool) {
+        self.statement_timeout_overridden = overridden;
+    }
+
     pub(crate) fn client_inner(&mut self) -> (&mut ClientInnerCommon<C>, Discard<'_, C>) {
         let Self {
             inner,
             pool,
             conn_info,
             span: _,
+            statement_timeout_overridden: _,
         } = self;
         let inner_m = inner.as_mut().expect("client inner should not be removed");
         (inner_m, Discard { conn_info, pool })
@@ -635,6 +1003,7 @@ pub(crate) fn inner(&mut self) -> (&mut C, Discard<'_, C>) {
             pool,
             conn_info,
             span: _,
+            statement_timeout_overridden: _,
         } = self;
         let inner = inner.as_mut().expect("client inner should not be removed");
         (&mut inner.inner, Discard { conn_info, pool })
@@ -664,14 +1033,43 @@ pub(crate) fn metrics(&self, ctx: &RequestContext) -> Arc<MetricCounter> {
 impl<C: ClientInnerExt> Drop for Client<C> {
     fn drop(&mut self) {
         let conn_info = self.conn_info.clone();
-        let client = self
+        let mut client = self
             .inner
             .take()
             .expect("client inner should not be removed");
         if let Some(conn_pool) = std::mem::take(&mut self.pool).upgrade() {
             let _current_span = self.span.enter();
-            // return connection to the pool
-            EndpointConnPool::put(&conn_pool, &conn_info, client);
+            if self.statement_timeout_overridden {
+                info!(
+                    "pool: throwing away connection '{conn_info}' because statement_timeout override was not reset"
+                );
+                return;
+            }
+
+            let reset_queries = conn_pool.read().reset_queries;
+            if reset_queries.is_empty() {
+                // return connection to the pool
+                EndpointConnPool::put(&conn_pool, &conn_info, client);
+                return;
+            }
+
+            // clear session state left by this request before anyone else can borrow the connection
+            tokio::spawn(
+                async move {
+                    // one query per statement: a multi-statement query runs in an implicit
+                    // transaction, and DISCARD ALL refuses to run inside one.
+                    for query in reset_queries {
+                        if let Err(e) = client.inner.reset_session(query).await {
+                            info!(
+                                "pool: throwing away connection '{conn_info}' because session reset failed: {e}"
+                            );
+                            return;
+                        }
+                    }
+                    EndpointConnPool::put(&conn_pool, &conn_info, client);
+                }
+                .instrument(self.span.clone()),
+            );
         }
     }
 }
@@ -691,6 +1089,14 @@ fn deref(&self) -> &Self::Target {
 pub(crate) trait ClientInnerExt: Sync + Send + 'static {
     fn is_closed(&self) -> bool;
     fn get_process_id(&self) -> i32;
+
+    /// Runs `query` to clear session state before the connection is reused.
+    fn reset_session(
+        &mut self,
+        _query: &'static str,
+    ) -> impl Future<Output = Result<(), postgres_client::Error>> + Send {
+        std::future::ready(Ok(()))
+    }
 }
 
 impl ClientInnerExt for postgres_client::Client {
@@ -701,6 +1107,10 @@ fn is_closed(&self) -> bool {
     fn get_process_id(&self) -> i32 {
         self.get_process_id()
     }
+
+    async fn reset_session(&mut self, query: &'static str) -> Result<(), postgres_client::Error> {
+        self.batch_execute(query).await.map(|_| ())
+    }
 }
 
 impl<C: ClientInnerExt> Discard<'_, C> {
@@ -718,4 +1128,9 @@ pub(crate) fn discard(&mut self) {
             );
         }
     }
+
+    /// Keeps a healthy connection from being returned to the pool.
+    pub(crate) fn detach(&mut self) {
+        drop(std::mem::take(self.pool));
+    }
 }
@@ -1,7 +1,10 @@
 use std::collections::VecDeque;
 use std::sync::atomic::{self, AtomicUsize};
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
 use hyper::client::conn::http2;
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use parking_lot::RwLock;
@@ -16,13 +19,15 @@
 };
 use crate::context::RequestContext;
 use crate::control_plane::messages::{ColdStartInfo, MetricsAuxInfo};
+use crate::control_plane::read_endpoints::ReadEndpointGuard;
 use crate::metrics::{HttpEndpointPoolsGuard, Metrics};
 use crate::protocol2::ConnectionInfoExtra;
 use crate::types::EndpointCacheKey;
 use crate::usage_metrics::{Ids, MetricCounter, USAGE_METRICS};
 
-pub(crate) type Send = http2::SendRequest<hyper::body::Incoming>;
-pub(crate) type Connect = http2::Connection<TokioIo<AsyncRW>, hyper::body::Incoming, TokioExecutor>;
+pub(crate) type Send = http2::SendRequest<BoxBody<Bytes, hyper::Error>>;
+pub(crate) type Connect =
+    http2::Connection<TokioIo<AsyncRW>, BoxBody<Bytes, hyper::Error>, TokioExecutor>;
 
 #[derive(Clone)]
 pub(crate) struct ClientDataHttp();
@@ -53,7 +58,7 @@ fn get_conn_entry(&mut self) -> Option<ConnPoolEntry<C>> {
             if !conn.conn.inner.is_closed() {
                 let new_conn = ConnPoolEntry {
                     conn: conn.conn.clone(),
-                    _last_access: std::time::Instant::now(),
+                    last_access: Instant::now(),
                 };
 
                 conns.push_back(new_conn);
@@ -98,6 +103,27 @@ fn clear_closed(&mut self) -> usize {
     fn total_conns(&self) -> usize {
         self.conns.len()
     }
+
+    fn oldest_idle(&self) -> Option<Instant> {
+        // connections are moved to the back whenever they are used
+        self.conns.front().map(|entry| entry.last_access)
+    }
+
+    fn evict_oldest_idle(&mut self) -> bool {
+        let Some(entry) = self.conns.pop_front() else {
+            return false;
+        };
+
+        self.global_connections_count
+            .fetch_sub(1, atomic::Ordering::Relaxed);
+        Metrics::get()
+            .proxy
+            .http_pool_opened_connections
+            .get_metric()
+            .dec();
+        info!(conn_id = %entry.conn.conn_id, "pool: evicting idle connection to stay within the pool budget");
+        true
+    }
 }
 
 impl<C: ClientInnerExt + Clone> Drop for HttpConnPool<C> {
@@ -193,7 +219,10 @@ pub(crate) fn poll_http2_client(
     connection: Connect,
     conn_id: uuid::Uuid,
     aux: MetricsAuxInfo,
+    read_endpoint: Option<ReadEndpointGuard>,
 ) -> Client<Send> {
+    global_pool.config.pool_budget.make_room();
+
     let conn_gauge = Metrics::get().proxy.db_connections.guard(ctx.protocol());
     let session_id = ctx.session_id();
 
@@ -214,7 +243,7 @@ pub(crate) fn poll_http2_client(
             };
             pool.write().conns.push_back(ConnPoolEntry {
                 conn: client,
-                _last_access: std::time::Instant::now(),
+                last_access: Instant::now(),
             });
             Metrics::get()
                 .proxy
@@ -230,6 +259,7 @@ pub(crate) fn poll_http2_client(
     tokio::spawn(
         async move {
             let _conn_gauge = conn_gauge;
+            let _read_endpoint = read_endpoint;
             let res = connection.await;
             match res {
                 Ok(()) => info!("connection closed"),
@@ -0,0 +1,175 @@
+//! Limits on SQL over HTTP `LISTEN` requests.
+//!
+//! A `LISTEN` request holds a compute connection out of the pool for as long as the client keeps
+//! the response open, so each one costs a connection slot of the compute. `max_listeners` bounds
+//! how many of them can be open at once, and `max_listeners_per_endpoint` keeps a single endpoint
+//! from taking all of them.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clashmap::ClashMap;
+
+use crate::error::{ErrorKind, ReportableError, UserFacingError};
+use crate::intern::EndpointIdInt;
+use crate::metrics::Metrics;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ListenConfig {
+    pub max_listeners: usize,
+    pub max_listeners_per_endpoint: usize,
+}
+
+pub(crate) struct Listeners {
+    config: Option<ListenConfig>,
+    total: AtomicUsize,
+    endpoints: ClashMap<EndpointIdInt, usize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ListenError {
+    #[error("listening for notifications is not enabled")]
+    Disabled,
+    #[error("too many open listen requests (max is {0})")]
+    TooMany(usize),
+    #[error("too many open listen requests for this endpoint (max is {0})")]
+    TooManyForEndpoint(usize),
+}
+
+impl ReportableError for ListenError {
+    fn get_error_kind(&self) -> ErrorKind {
+        match self {
+            ListenError::Disabled => ErrorKind::User,
+            ListenError::TooMany(_) | ListenError::TooManyForEndpoint(_) => ErrorKind::RateLimit,
+        }
+    }
+}
+
+impl UserFacingError for ListenError {}
+
+/// An open listen request. Releases its slot when dropped.
+pub(crate) struct ListenPermit {
+    listeners: Arc<Listeners>,
+    endpoint: EndpointIdInt,
+}
+
+impl Listeners {
+    /// `None` disables listen requests.
+    pub(crate) fn new(config: Option<ListenConfig>) -> Self {
+        Self {
+            config,
+            total: AtomicUsize::new(0),
+            endpoints: ClashMap::default(),
+        }
+    }
+
+    /// Takes a slot for a listen request to the endpoint, if there is one left.
+    pub(crate) fn acquire(
+        self: &Arc<Self>,
+        endpoint: EndpointIdInt,
+    ) -> Result<ListenPermit, ListenError> {
+        let Some(config) = &self.config else {
+            return Err(ListenError::Disabled);
+        };
+
+        let metrics = &Metrics::get().proxy;
+        if self
+            .total
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+                (total < config.max_listeners).then_some(total + 1)
+            })
+            .is_err()
+        {
+            metrics.http_listen_rejected_total.inc();
+            return Err(ListenError::TooMany(config.max_listeners));
+        }
+
+        let mut count = self.endpoints.entry(endpoint).or_insert(0);
+        if *count >= config.max_listeners_per_endpoint {
+            drop(count);
+            self.release(endpoint, false);
+            metrics.http_listen_rejected_total.inc();
+            return Err(ListenError::TooManyForEndpoint(
+                config.max_listeners_per_endpoint,
+            ));
+        }
+        *count += 1;
+        drop(count);
+
+        metrics.http_listen_requests.get_metric().inc();
+        Ok(ListenPermit {
+            listeners: Arc::clone(self),
+            endpoint,
+        })
+    }
+
+    fn release(&self, endpoint: EndpointIdInt, held: bool) {
+        if held {
+            if let Some(mut count) = self.endpoints.get_mut(&endpoint) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        self.endpoints.remove_if(&endpoint, |_, count| *count == 0);
+        self.total.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Drop for ListenPermit {
+    fn drop(&mut self) {
+        Metrics::get().proxy.http_listen_requests.get_metric().dec();
+        self.listeners.release(self.endpoint, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EndpointId;
+
+    fn endpoint(id: &str) -> EndpointIdInt {
+        EndpointIdInt::from(&EndpointId::from(id))
+    }
+
+    fn listeners(max_listeners: usize, max_listeners_per_endpoint: usize) -> Arc<Listeners> {
+        Arc::new(Listeners::new(Some(ListenConfig {
+            max_listeners,
+            max_listeners_per_endpoint,
+        })))
+    }
+
+    #[test]
+    fn endpoint_limit() {
+        let listeners = listeners(10, 2);
+        let first = listeners.acquire(endpoint("ep-a")).unwrap();
+        let _second = listeners.acquire(endpoint("ep-a")).unwrap();
+
+        let err = listeners.acquire(endpoint("ep-a")).err().unwrap();
+        assert!(matches!(err, ListenError::TooManyForEndpoint(2)));
+        // other endpoints are not affected
+        let _other = listeners.acquire(endpoint("ep-b")).unwrap();
+        assert_eq!(listeners.total.load(Ordering::Acquire), 3);
+
+        drop(first);
+        let _third = listeners.acquire(endpoint("ep-a")).unwrap();
+    }
+
+    #[test]
+    fn total_limit() {
+        let listeners = listeners(2, 10);
+        let first = listeners.acquire(endpoint("ep-a")).unwrap();
+        let second = listeners.acquire(endpoint("ep-b")).unwrap();
+
+        let err = listeners.acquire(endpoint("ep-c")).err().unwrap();
+        assert!(matches!(err, ListenError::TooMany(2)));
+
+        drop(first);
+        drop(second);
+        assert_eq!(listeners.total.load(Ordering::Acquire), 0);
+        assert!(listeners.endpoints.is_empty());
+        let _third = listeners.acquire(endpoint("ep-c")).unwrap();
+
+        let disabled = Arc::new(Listeners::new(None));
+        let err = disabled.acquire(endpoint("ep-a")).err().unwrap();
+        assert!(matches!(err, ListenError::Disabled));
+    }
+}
@@ -11,8 +11,8 @@
 
 use std::collections::HashMap;
 use std::pin::pin;
-use std::sync::Arc;
 use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Weak};
 use std::task::{Poll, ready};
 use std::time::Duration;
 
@@ -27,7 +27,7 @@
 use postgres_client::AsyncMessage;
 use postgres_client::tls::NoTlsStream;
 use serde_json::value::RawValue;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 use tracing::{Instrument, debug, error, info, info_span, warn};
@@ -35,7 +35,7 @@
 use super::backend::HttpConnError;
 use super::conn_pool_lib::{
     Client, ClientDataEnum, ClientInnerCommon, ClientInnerExt, ConnInfo, DbUserConn,
-    EndpointConnPool,
+    EndpointConnPool, EndpointConnPoolExt, IdleConnPool,
 };
 use super::sql_over_http::SqlOverHttpError;
 use crate::context::RequestContext;
@@ -72,17 +72,25 @@ pub(crate) struct LocalConnPool<C: ClientInnerExt> {
 
 impl<C: ClientInnerExt> LocalConnPool<C> {
     pub(crate) fn new(config: &'static crate::config::HttpConfig) -> Arc<Self> {
-        Arc::new(Self {
+        let pool = Arc::new(Self {
             global_pool: Arc::new(RwLock::new(EndpointConnPool::new(
                 HashMap::new(),
                 0,
                 config.pool_options.max_conns_per_endpoint,
+                config.pool_options.min_conns_per_endpoint,
                 Arc::new(AtomicUsize::new(0)),
                 config.pool_options.max_total_conns,
                 String::from("local_pool"),
+                config.pool_options.reuse_policy,
+                &config.pool_reset_queries,
+                &config.pool_budget,
             ))),
             config,
-        })
+        });
+        config
+            .pool_budget
+            .register(Arc::downgrade(&pool) as Weak<dyn IdleConnPool>);
+        pool
     }
 
     pub(crate) fn get_idle_timeout(&self) -> Duration {
@@ -141,6 +149,10 @@ pub(crate) fn get(
         Ok(None)
     }
 
+    pub(crate) fn close_endpoint(&self, endpoint: &str) -> usize {
+        self.global_pool.write().remove_endpoint_conns(endpoint)
+    }
+
     pub(crate) fn initialized(self: &Arc<Self>, conn_info: &ConnInfo) -> bool {
         if let Some(pool) = self.global_pool.read().get_pool(conn_info.db_and_user()) {
             return pool.is_initialized();
@@ -159,17 +171,37 @@ pub(crate) fn set_initialized(self: &Arc<Self>, conn_info: &ConnInfo) {
     }
 }
 
+impl<C: ClientInnerExt> IdleConnPool for LocalConnPool<C> {
+    fn pooled_conns(&self) -> usize {
+        self.global_pool.read().total_conns()
+    }
+
+    fn oldest_idle(&self) -> Option<std::time::Instant> {
+        self.global_pool.read().oldest_idle()
+    }
+
+    fn evict_oldest_idle(&self) -> bool {
+        self.global_pool.write().evict_oldest_idle()
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn poll_client<C: ClientInnerExt>(
+pub(crate) fn poll_client<C, S>(
     global_pool: Arc<LocalConnPool<C>>,
     ctx: &RequestContext,
     conn_info: ConnInfo,
     client: C,
-    mut connection: postgres_client::Connection<TcpStream, NoTlsStream>,
+    mut connection: postgres_client::Connection<S, NoTlsStream>,
     key: SigningKey,
     conn_id: uuid::Uuid,
     aux: MetricsAuxInfo,
-) -> Client<C> {
+) -> Client<C>
+where
+    C: ClientInnerExt,
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    global_pool.config.pool_budget.make_room();
+
     let conn_gauge = Metrics::get().proxy.db_connections.guard(ctx.protocol());
     let mut session_id = ctx.session_id();
     let (tx, mut rx) = tokio::sync::watch::channel(session_id);
@@ -218,7 +250,7 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
                 if let Some(pool) = pool.clone().upgrade() {
                     // remove client from pool - should close the connection if it's idle.
                     // does nothing if the client is currently checked-out and in-use
-                    if pool.global_pool.write().remove_client(db_user.clone(), conn_id) {
+                    if pool.global_pool.write().remove_idle_client(db_user.clone(), conn_id) {
                         info!("idle connection removed");
                     }
                 }
@@ -4,14 +4,21 @@
 
 mod backend;
 pub mod cancel_set;
+pub mod circuit_breaker;
+pub mod compression;
 mod conn_pool;
 mod conn_pool_lib;
 mod error;
 mod http_conn_pool;
 mod http_util;
 mod json;
+mod listen;
 mod local_conn_pool;
+mod recent_connections;
+mod row_stream;
 mod sql_over_http;
+mod statement_filter;
+mod sticky_session;
 mod websocket;
 
 use std::net::{IpAddr, SocketAddr};
@@ -23,7 +30,7 @@
 use async_trait::async_trait;
 use atomic_take::AtomicTake;
 use bytes::Bytes;
-pub use conn_pool_lib::GlobalConnPoolOptions;
+pub use conn_pool_lib::{GlobalConnPoolOptions, PoolBudget, PoolReusePolicy};
 use futures::TryFutureExt;
 use futures::future::{Either, select};
 use http::{Method, Response, StatusCode};
@@ -33,9 +40,13 @@
 use hyper::body::Incoming;
 use hyper_util::rt::TokioExecutor;
 use hyper_util::server::conn::auto::Builder;
+pub use listen::ListenConfig;
 use rand::SeedableRng;
 use rand::rngs::StdRng;
+pub use row_stream::RowStreamingConfig;
 use sql_over_http::{NEON_REQUEST_ID, uuid_to_header_value};
+pub use statement_filter::StatementFilter;
+pub use sticky_session::StickySessionConfig;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::timeout;
@@ -43,21 +54,64 @@
 use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
 use tracing::{Instrument, info, warn};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::cancellation::CancellationHandler;
-use crate::config::{ProxyConfig, ProxyProtocolV2};
+use crate::config::{HttpConfig, ProxyConfig, ProxyProtocolV2};
 use crate::context::RequestContext;
 use crate::ext::TaskExt;
 use crate::metrics::Metrics;
 use crate::protocol2::{ConnectHeader, ConnectionInfo, read_proxy_protocol};
 use crate::rate_limiter::EndpointRateLimiter;
 use crate::serverless::backend::PoolingBackend;
-use crate::serverless::http_util::{api_error_into_response, json_response};
+use crate::serverless::conn_pool_lib::{EndpointConnPool, GlobalConnPool};
+use crate::serverless::http_conn_pool::{HttpConnPool, Send};
+use crate::serverless::http_util::{
+    api_error_into_response, extract_remote_context, json_response,
+};
+use crate::serverless::listen::Listeners;
+use crate::serverless::local_conn_pool::LocalConnPool;
+use crate::serverless::recent_connections::{ConnectionRecord, RecentConnections};
+use crate::serverless::sticky_session::StickySessions;
 use crate::util::run_until_cancelled;
 
 pub(crate) const SERVERLESS_DRIVER_SNI: &str = "api";
 pub(crate) const AUTH_BROKER_SNI: &str = "apiauth";
 
+/// The connection pools of the serverless backend, shared with the admin API.
+pub struct ConnPools {
+    conn_pool:
+        Arc<GlobalConnPool<postgres_client::Client, EndpointConnPool<postgres_client::Client>>>,
+    http_conn_pool: Arc<GlobalConnPool<Send, HttpConnPool<Send>>>,
+    local_pool: Arc<LocalConnPool<postgres_client::Client>>,
+    recent_connections: Arc<RecentConnections>,
+}
+
+impl ConnPools {
+    pub fn new(config: &'static HttpConfig) -> Arc<Self> {
+        Arc::new(Self {
+            conn_pool: GlobalConnPool::new(config),
+            http_conn_pool: GlobalConnPool::new(config),
+            local_pool: LocalConnPool::new(config),
+            recent_connections: Arc::new(RecentConnections::new(config.recent_connections)),
+        })
+    }
+
+    /// Closes the pooled postgres, http2 and local connections of `endpoint`, so that the next
+    /// requests connect to its current compute. Connections in use are closed instead of being
+    /// returned to the pool. Returns the number of pooled connections closed.
+    pub fn close_endpoint(&self, endpoint: &str) -> usize {
+        self.conn_pool.close_endpoint(endpoint)
+            + self.http_conn_pool.close_endpoint(endpoint)
+            + self.local_pool.close_endpoint(endpoint)
+    }
+
+    /// The parameters of a recently opened connection to compute, if it is still remembered.
+    pub(crate) fn recent_connection(&self, conn_id: uuid::Uuid) -> Option<ConnectionRecord> {
+        self.recent_connections.get(conn_id)
+    }
+}
+
 pub async fn task_main(
     config: &'static ProxyConfig,
     auth_backend: &'static crate::auth::Backend<'static, ()>,
@@ -65,13 +119,14 @@ pub async fn task_main(
     cancellation_token: CancellationToken,
     cancellation_handler: Arc<CancellationHandler>,
     endpoint_rate_limiter: Arc<EndpointRateLimiter>,
+    conn_pools: Arc<ConnPools>,
 ) -> anyhow::Result<()> {
     scopeguard::defer! {
         info!("websocket server has shut down");
     }
 
-    let local_pool = local_conn_pool::LocalConnPool::new(&config.http_config);
-    let conn_pool = conn_pool_lib::GlobalConnPool::new(&config.http_config);
+    let local_pool = Arc::clone(&conn_pools.local_pool);
+    let conn_pool = Arc::clone(&conn_pools.conn_pool);
     {
         let conn_pool = Arc::clone(&conn_pool);
         tokio::spawn(async move {
@@ -91,7 +146,7 @@ pub async fn task_main(
         }
     });
 
-    let http_conn_pool = conn_pool_lib::GlobalConnPool::new(&config.http_config);
+    let http_conn_pool = Arc::clone(&conn_pools.http_conn_pool);
     {
         let http_conn_pool = Arc::clone(&http_conn_pool);
         tokio::spawn(async move {
@@ -111,10 +166,21 @@ pub async fn task_main(
         }
     });
 
+    let sticky_sessions = Arc::new(StickySessions::new(config.http_config.sticky_sessions));
+    {
+        let sticky_sessions = Arc::clone(&sticky_sessions);
+        tokio::spawn(async move {
+            sticky_sessions.gc_worker().await;
+        });
+    }
+
     let backend = Arc::new(PoolingBackend {
         http_conn_pool: Arc::clone(&http_conn_pool),
         local_pool,
         pool: Arc::clone(&conn_pool),
+        sticky_sessions,
+        listeners: Arc::new(Listeners::new(config.http_config.listen)),
+        recent_connections: Arc::clone(&conn_pools.recent_connections),
         config,
         auth_backend,
         endpoint_rate_limiter: Arc::clone(&endpoint_rate_limiter),
@@ -148,6 +214,7 @@ pub async fn task_main(
         }
 
         let conn_token = cancellation_token.child_token();
+        let shutdown = cancellation_token.clone();
         let tls_acceptor = tls_acceptor.clone();
         let backend = backend.clone();
         let connections2 = connections.clone();
@@ -186,6 +253,7 @@ pub async fn task_main(
                     cancellation_handler,
                     endpoint_rate_limiter,
                     conn_token,
+                    shutdown,
                     conn,
                     conn_info,
                     session_id,
@@ -307,6 +375,8 @@ async fn connection_handler(
     cancellation_handler: Arc<CancellationHandler>,
     endpoint_rate_limiter: Arc<EndpointRateLimiter>,
     cancellation_token: CancellationToken,
+    // closes websocket sessions when the proxy shuts down, if enabled.
+    shutdown: CancellationToken,
     conn: AsyncRW,
     conn_info: ConnectionInfo,
     session_id: uuid::Uuid,
@@ -357,6 +427,7 @@ async fn connection_handler(
                     http_request_token,
                     endpoint_rate_limiter.clone(),
                     cancellations,
+                    shutdown.clone(),
                 )
                 .in_current_span()
                 .map_ok_or_else(api_error_into_response, |r| r),
@@ -405,6 +476,7 @@ async fn request_handler(
     http_cancellation_token: CancellationToken,
     endpoint_rate_limiter: Arc<EndpointRateLimiter>,
     cancellations: TaskTracker,
+    shutdown: CancellationToken,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, ApiError> {
     let host = request
         .headers()
@@ -428,11 +500,21 @@ async fn request_handler(
         );
 
         let span = ctx.span();
+        span.set_parent(extract_remote_context(request.headers()));
         info!(parent: &span, "performing websocket upgrade");
 
-        let (response, websocket) = framed_websockets::upgrade::upgrade(&mut request)
+        let subprotocol = websocket::negotiate_subprotocol(request.headers())
             .map_err(|e| ApiError::BadRequest(e.into()))?;
 
+        let (mut response, websocket) = framed_websockets::upgrade::upgrade(&mut request)
+            .map_err(|e| ApiError::BadRequest(e.into()))?;
+        if let Some(subprotocol) = subprotocol {
+            response.headers_mut().insert(
+                http::header::SEC_WEBSOCKET_PROTOCOL,
+                http::HeaderValue::from_static(subprotocol),
+            );
+        }
+
         let cancellations = cancellations.clone();
         ws_connections.spawn(
             async move {
@@ -445,6 +527,7 @@ async fn request_handler(
                     endpoint_rate_limiter,
                     host,
                     cancellations,
+                    shutdown,
                 )
                 .await
                 {
@@ -456,9 +539,12 @@ async fn request_handler(
 
         // Return the response so the spawned future can continue.
         Ok(response.map(|b| b.map_err(|x| match x {}).boxed()))
-    } else if request.uri().path() == "/sql" && *request.method() == Method::POST {
+    } else if matches!(request.uri().path(), "/sql" | sql_over_http::LISTEN_PATH)
+        && *request.method() == Method::POST
+    {
         let ctx = RequestContext::new(session_id, conn_info, crate::metrics::Protocol::Http);
         let span = ctx.span();
+        span.set_parent(extract_remote_context(request.headers()));
 
         let testodrome_id = request
             .headers()
@@ -480,7 +566,7 @@ async fn request_handler(
             .header("Access-Control-Allow-Origin", "*")
             .header(
                 "Access-Control-Allow-Headers",
-                "Authorization, Neon-Connection-String, Neon-Raw-Text-Output, Neon-Array-Mode, Neon-Pool-Opt-In, Neon-Batch-Read-Only, Neon-Batch-Isolation-Level",
+                "Authorization, Neon-Connection-String, Neon-Raw-Text-Output, Neon-Array-Mode, Neon-Pool-Opt-In, Neon-Batch-Read-Only, Neon-Batch-Isolation-Level, Traceparent, Tracestate",
             )
             .header("Access-Control-Max-Age", "86400" /* 24 hours */)
             .status(StatusCode::OK) // 204 is also valid, but see: https://developer.mozilla.org/en-US/docs/Web/HTTP/Methods/OPTIONS#status_code
@@ -0,0 +1,136 @@
+//! The parameters recently opened serverless connections to compute were opened with.
+//!
+//! The admin API looks connections up by `conn_id`, so that support can tell which compute,
+//! TLS mode and timeouts a connection used without reproducing the client's request. Only the
+//! last few connections are kept.
+
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use smol_str::SmolStr;
+use uuid::Uuid;
+
+use crate::types::{DbName, EndpointId, Host, RoleName};
+
+/// The effective parameters of a connection to compute.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct ConnectionRecord {
+    pub(crate) conn_id: Uuid,
+    #[serde(with = "humantime_serde")]
+    pub(crate) opened_at: SystemTime,
+    pub(crate) endpoint: EndpointId,
+    pub(crate) user: RoleName,
+    pub(crate) dbname: DbName,
+    /// The neon options of the connection string, as they appear in the pool key.
+    pub(crate) options: SmolStr,
+    pub(crate) pool_name: Option<SmolStr>,
+    pub(crate) application_name: Option<SmolStr>,
+    pub(crate) compute_id: SmolStr,
+    pub(crate) host: Host,
+    pub(crate) port: u16,
+    pub(crate) unix_socket: bool,
+    pub(crate) ssl_mode: String,
+    pub(crate) tls_server_name: Option<Host>,
+    pub(crate) read_endpoint: bool,
+    #[serde(with = "humantime_serde")]
+    pub(crate) connect_timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    pub(crate) startup_timeout: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    pub(crate) statement_timeout: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    pub(crate) max_query_duration: Option<Duration>,
+}
+
+pub(crate) struct RecentConnections {
+    capacity: usize,
+    records: Mutex<VecDeque<ConnectionRecord>>,
+}
+
+impl RecentConnections {
+    /// Keeps the last `capacity` connections. Zero keeps none.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Records a new connection, forgetting the oldest one if the buffer is full.
+    pub(crate) fn record(&self, record: ConnectionRecord) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut records = self.records.lock();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    pub(crate) fn get(&self, conn_id: Uuid) -> Option<ConnectionRecord> {
+        self.records
+            .lock()
+            .iter()
+            .rev()
+            .find(|record| record.conn_id == conn_id)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(conn_id: Uuid) -> ConnectionRecord {
+        ConnectionRecord {
+            conn_id,
+            opened_at: SystemTime::UNIX_EPOCH,
+            endpoint: "endpoint".into(),
+            user: "user".into(),
+            dbname: "dbname".into(),
+            options: SmolStr::default(),
+            pool_name: None,
+            application_name: None,
+            compute_id: "compute".into(),
+            host: "localhost".into(),
+            port: 5432,
+            unix_socket: false,
+            ssl_mode: "require".to_owned(),
+            tls_server_name: None,
+            read_endpoint: false,
+            connect_timeout: Duration::from_secs(2),
+            startup_timeout: None,
+            statement_timeout: Some(Duration::from_secs(30)),
+            max_query_duration: None,
+        }
+    }
+
+    #[test]
+    fn keeps_last_connections() {
+        let recent = RecentConnections::new(2);
+        let ids: Vec<_> = (0..3).map(|_| Uuid::new_v4()).collect();
+        for &id in &ids {
+            recent.record(record(id));
+        }
+
+        assert!(recent.get(ids[0]).is_none());
+        assert_eq!(recent.get(ids[1]).unwrap().conn_id, ids[1]);
+        assert_eq!(recent.get(ids[2]).unwrap().conn_id, ids[2]);
+
+        let json = serde_json::to_value(recent.get(ids[2]).unwrap()).unwrap();
+        assert_eq!(json["opened_at"], "1970-01-01T00:00:00Z");
+        assert_eq!(json["statement_timeout"], "30s");
+        assert_eq!(json["startup_timeout"], serde_json::Value::Null);
+
+        let disabled = RecentConnections::new(0);
+        disabled.record(record(ids[0]));
+        assert!(disabled.get(ids[0]).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//! Streaming of SQL over HTTP results.
+//!
+//! A single query sent with `Neon-Stream-Rows: true` gets its result as newline-delimited JSON,
+//! flushed to the client as rows arrive from compute instead of being buffered whole:
+//!
+//! ```text
+//! {"fields":[...],"rowAsArray":false}
+//! {"rows":[...]}
+//! {"rows":[...]}
+//! {"command":"SELECT","rowCount":12345}
+//! ```
+//!
+//! The stream always ends with a `command` line on success or an `error` line if the query
+//! failed after the response had started, so a stream that ends with neither was cut short.
+//! `max_response_size_bytes` does not apply to streamed results, since they are never held in
+//! memory at once.
+
+use bytes::Bytes;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RowStreamingConfig {
+    /// Flush a chunk once it holds this many rows.
+    pub chunk_rows: usize,
+    /// Flush a chunk once it holds this many bytes of JSON.
+    pub chunk_bytes: usize,
+}
+
+/// Collects rows into `{"rows":[...]}` lines of at most the configured chunk size.
+pub(crate) struct RowChunker {
+    config: RowStreamingConfig,
+    buf: Vec<u8>,
+    rows: usize,
+}
+
+impl RowChunker {
+    pub(crate) fn new(config: RowStreamingConfig) -> Self {
+        Self {
+            config,
+            buf: Vec::new(),
+            rows: 0,
+        }
+    }
+
+    /// Adds a row, returning the chunk if it is now full.
+    pub(crate) fn push(&mut self, row: &impl Serialize) -> Option<Bytes> {
+        if self.rows == 0 {
+            self.buf.extend_from_slice(br#"{"rows":["#);
+        } else {
+            self.buf.push(b',');
+        }
+        serde_json::to_writer(&mut self.buf, row).expect("json serialization should not fail");
+        self.rows += 1;
+
+        if self.rows >= self.config.chunk_rows || self.buf.len() >= self.config.chunk_bytes {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rows collected so far, if any.
+    pub(crate) fn flush(&mut self) -> Option<Bytes> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.buf.extend_from_slice(b"]}\n");
+        self.rows = 0;
+        Some(Bytes::from(std::mem::take(&mut self.buf)))
+    }
+}
+
+/// Encodes one line of the stream.
+pub(crate) fn json_line(value: &impl Serialize) -> Bytes {
+    let mut line = serde_json::to_vec(value).expect("json serialization should not fail");
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn parse(chunk: &Bytes) -> Value {
+        assert_eq!(chunk.last(), Some(&b'\n'));
+        serde_json::from_slice(chunk).unwrap()
+    }
+
+    #[test]
+    fn chunks_by_rows() {
+        let mut chunker = RowChunker::new(RowStreamingConfig {
+            chunk_rows: 2,
+            chunk_bytes: usize::MAX,
+        });
+        assert_eq!(chunker.flush(), None);
+
+        assert_eq!(chunker.push(&json!({"id": 1})), None);
+        let chunk = chunker.push(&json!({"id": 2})).unwrap();
+        assert_eq!(parse(&chunk), json!({"rows": [{"id": 1}, {"id": 2}]}));
+
+        assert_eq!(chunker.push(&json!({"id": 3})), None);
+        let chunk = chunker.flush().unwrap();
+        assert_eq!(parse(&chunk), json!({"rows": [{"id": 3}]}));
+        assert_eq!(chunker.flush(), None);
+    }
+
+    #[test]
+    fn chunks_by_bytes() {
+        let mut chunker = RowChunker::new(RowStreamingConfig {
+            chunk_rows: usize::MAX,
+            chunk_bytes: 32,
+        });
+        let row = json!(["0123456789abcdef"]);
+
+        assert_eq!(chunker.push(&row), None);
+        let chunk = chunker.push(&row).unwrap();
+        assert_eq!(parse(&chunk), json!({"rows": [row, row]}));
+    }
+}
@@ -1,5 +1,7 @@
+use std::convert::Infallible;
 use std::pin::pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
 use futures::future::{Either, select, try_join};
@@ -7,41 +9,54 @@
 use http::Method;
 use http::header::AUTHORIZATION;
 use http_body_util::combinators::BoxBody;
-use http_body_util::{BodyExt, Full};
+use http_body_util::{BodyExt, Full, StreamBody};
 use http_utils::error::ApiError;
-use hyper::body::Incoming;
+use hyper::body::{Body as _, Frame, Incoming};
 use hyper::http::{HeaderName, HeaderValue};
 use hyper::{HeaderMap, Request, Response, StatusCode, header};
 use indexmap::IndexMap;
 use postgres_client::error::{DbError, ErrorPosition, SqlState};
+use postgres_client::types::Type;
 use postgres_client::{
-    GenericClient, IsolationLevel, NoTls, ReadyForQueryStatus, RowStream, Transaction,
+    GenericClient, IsolationLevel, NoTls, ReadyForQueryStatus, RowStream, Statement, Transaction,
 };
+use postgres_protocol::escape::escape_identifier;
 use serde::Serialize;
 use serde_json::Value;
 use serde_json::value::RawValue;
+use smol_str::SmolStr;
+use tokio::sync::mpsc;
 use tokio::time::{self, Instant};
 use tokio_util::sync::CancellationToken;
-use tracing::{Level, debug, error, info};
+use tracing::{Instrument, Level, debug, error, info, warn};
 use typed_json::json;
 use url::Url;
 use uuid::Uuid;
 
 use super::backend::{LocalProxyConnError, PoolingBackend};
-use super::conn_pool::{AuthData, ConnInfoWithAuth};
-use super::conn_pool_lib::{self, ConnInfo};
+use super::compression::{self, ContentEncoding};
+use super::conn_pool::{AuthData, ConnInfoWithAuth, set_statement_timeout};
+use super::conn_pool_lib::{self, ClientDataEnum, ConnInfo};
 use super::error::HttpCodeError;
 use super::http_util::json_response;
 use super::json::{JsonConversionError, json_to_pg_text, pg_text_row_to_json};
+use super::listen::ListenError;
+use super::row_stream::{RowChunker, RowStreamingConfig, json_line};
+use super::statement_filter::StatementNotAllowed;
+use super::sticky_session::{MAX_SESSION_ID_LEN, StickySessionError};
 use crate::auth::backend::{ComputeCredentialKeys, ComputeUserInfo};
 use crate::auth::{ComputeUserInfoParseError, endpoint_sni};
-use crate::config::{AuthenticationConfig, HttpConfig, ProxyConfig, TlsConfig};
+use crate::config::{
+    AuthenticationConfig, HttpConfig, ProxyConfig, StartupParamsError, StartupParamsLimits,
+    TlsConfig,
+};
 use crate::context::RequestContext;
-use crate::error::{ErrorKind, ReportableError, UserFacingError};
+use crate::error::{ClientErrorVerbosity, ErrorKind, ReportableError, UserFacingError};
 use crate::http::{ReadBodyError, read_body_with_limit};
-use crate::metrics::{HttpDirection, Metrics, SniGroup, SniKind};
+use crate::intern::EndpointIdInt;
+use crate::metrics::{CacheOutcome, HttpDirection, Metrics, SniGroup, SniKind};
 use crate::pqproto::StartupMessageParams;
-use crate::proxy::NeonOptions;
+use crate::proxy::{NeonOptions, PgSettings, parse_pg_bool};
 use crate::serverless::backend::HttpConnError;
 use crate::types::{DbName, RoleName};
 use crate::usage_metrics::{MetricCounter, MetricCounterRecorder};
@@ -70,18 +85,50 @@ enum Payload {
     Batch(BatchQueryData),
 }
 
+impl Payload {
+    fn queries(&self) -> impl Iterator<Item = &str> {
+        let queries = match self {
+            Payload::Single(query) => std::slice::from_ref(query),
+            Payload::Batch(batch) => &batch.queries,
+        };
+        queries.iter().map(|query| query.query.as_str())
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ListenPayload {
+    channel: String,
+}
+
+/// Path of the endpoint that streams the notifications of a `LISTEN` back to the client.
+pub(super) const LISTEN_PATH: &str = "/sql/listen";
+
+/// How many notifications to buffer for a client that is slow to read them before dropping some.
+const NOTIFICATION_BUFFER: usize = 1024;
+
 pub(super) static NEON_REQUEST_ID: HeaderName = HeaderName::from_static("neon-request-id");
 
 static CONN_STRING: HeaderName = HeaderName::from_static("neon-connection-string");
 static RAW_TEXT_OUTPUT: HeaderName = HeaderName::from_static("neon-raw-text-output");
 static ARRAY_MODE: HeaderName = HeaderName::from_static("neon-array-mode");
 static ALLOW_POOL: HeaderName = HeaderName::from_static("neon-pool-opt-in");
+static FORCE_WAKE: HeaderName = HeaderName::from_static("neon-force-wake");
 static TXN_ISOLATION_LEVEL: HeaderName = HeaderName::from_static("neon-batch-isolation-level");
 static TXN_READ_ONLY: HeaderName = HeaderName::from_static("neon-batch-read-only");
 static TXN_DEFERRABLE: HeaderName = HeaderName::from_static("neon-batch-deferrable");
+static STATEMENT_TIMEOUT: HeaderName = HeaderName::from_static("neon-statement-timeout");
+static SESSION_ID: HeaderName = HeaderName::from_static("neon-session-id");
+static STREAM_ROWS: HeaderName = HeaderName::from_static("neon-stream-rows");
+static BACKEND_PID: HeaderName = HeaderName::from_static("neon-backend-pid");
 
 static HEADER_VALUE_TRUE: HeaderValue = HeaderValue::from_static("true");
 
+/// Startup option that routes the connection to a read replica, if the endpoint has one.
+const READ_REPLICA_SETTING: &str = "neon.read_replica";
+
+/// Startup option that picks a named connection pool, instead of the default pool of the endpoint.
+const POOL_SETTING: &str = "neon.pool";
+
 fn bytes_to_pg_text<'de, D>(deserializer: D) -> Result<Vec<Option<String>>, D::Error>
 where
     D: serde::de::Deserializer<'de>,
@@ -115,6 +162,10 @@ pub(crate) enum ConnInfoError {
     InvalidEndpoint(#[from] ComputeUserInfoParseError),
     #[error("malformed endpoint")]
     MalformedEndpoint,
+    #[error("invalid value for {READ_REPLICA_SETTING}: {0}")]
+    InvalidReadReplica(SmolStr),
+    #[error("{0}")]
+    StartupParams(#[from] StartupParamsError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -139,6 +190,8 @@ fn to_string_client(&self) -> String {
 
 fn get_conn_info(
     config: &'static AuthenticationConfig,
+    limits: &StartupParamsLimits,
+    shared_pool_dbnames: &[regex::Regex],
     ctx: &RequestContext,
     headers: &HeaderMap,
     tls: Option<&TlsConfig>,
@@ -219,6 +272,7 @@ fn get_conn_info(
     let pairs = connection_url.query_pairs();
 
     let mut options = Option::None;
+    let mut pg_settings = PgSettings::default();
 
     let mut params = StartupMessageParams::default();
     params.insert("user", &username);
@@ -227,8 +281,19 @@ fn get_conn_info(
         params.insert(&key, &value);
         if key == "options" {
             options = Some(NeonOptions::parse_options_raw(&value));
+            pg_settings = PgSettings::parse_options_raw(&value);
         }
     }
+    limits.check(&params)?;
+
+    // routing hint for proxy, not a postgres setting.
+    let read_replica = match pg_settings.remove(READ_REPLICA_SETTING) {
+        Some(value) => parse_pg_bool(&value).ok_or(ConnInfoError::InvalidReadReplica(value))?,
+        None => false,
+    };
+    let pool_name = pg_settings
+        .remove(POOL_SETTING)
+        .filter(|pool_name| !pool_name.is_empty());
 
     // check the URL that was used, for metrics
     {
@@ -256,6 +321,8 @@ fn get_conn_info(
             .inc(SniGroup { protocol, kind });
     }
 
+    let application_name: Option<SmolStr> = params.get("application_name").map(Into::into);
+    ctx.set_application(application_name.clone());
     ctx.set_user_agent(
         headers
             .get(hyper::header::USER_AGENT)
@@ -269,7 +336,17 @@ fn get_conn_info(
         options: options.unwrap_or_default(),
     };
 
-    let conn_info = ConnInfo { user_info, dbname };
+    let pool_dbname = conn_pool_lib::shared_pool_dbname(shared_pool_dbnames, &dbname);
+    let conn_info = ConnInfo {
+        user_info,
+        dbname,
+        pg_settings,
+        read_replica,
+        read_only: false,
+        pool_name,
+        pool_dbname,
+        application_name,
+    };
     Ok(ConnInfoWithAuth { conn_info, auth })
 }
 
@@ -353,42 +430,7 @@ pub(crate) async fn handle(
             let error_kind = e.get_error_kind();
             ctx.set_error_kind(error_kind);
 
-            let mut message = e.to_string_client();
-            let db_error = match &e {
-                SqlOverHttpError::ConnectCompute(HttpConnError::PostgresConnectionError(e))
-                | SqlOverHttpError::Postgres(e) => e.as_db_error(),
-                _ => None,
-            };
-            fn get<'a, T: Default>(db: Option<&'a DbError>, x: impl FnOnce(&'a DbError) -> T) -> T {
-                db.map(x).unwrap_or_default()
-            }
-
-            if let Some(db_error) = db_error {
-                db_error.message().clone_into(&mut message);
-            }
-
-            let position = db_error.and_then(|db| db.position());
-            let (position, internal_position, internal_query) = match position {
-                Some(ErrorPosition::Original(position)) => (Some(position.to_string()), None, None),
-                Some(ErrorPosition::Internal { position, query }) => {
-                    (None, Some(position.to_string()), Some(query.clone()))
-                }
-                None => (None, None, None),
-            };
-
-            let code = get(db_error, |db| db.code().code());
-            let severity = get(db_error, |db| db.severity());
-            let detail = get(db_error, |db| db.detail());
-            let hint = get(db_error, |db| db.hint());
-            let where_ = get(db_error, |db| db.where_());
-            let table = get(db_error, |db| db.table());
-            let column = get(db_error, |db| db.column());
-            let schema = get(db_error, |db| db.schema());
-            let datatype = get(db_error, |db| db.datatype());
-            let constraint = get(db_error, |db| db.constraint());
-            let file = get(db_error, |db| db.file());
-            let line = get(db_error, |db| db.line().map(|l| l.to_string()));
-            let routine = get(db_error, |db| db.routine());
+            let (message, db_error) = client_error(&e, config.client_error_verbosity);
 
             match &e {
                 SqlOverHttpError::Postgres(e)
@@ -420,28 +462,7 @@ fn get<'a, T: Default>(db: Option<&'a DbError>, x: impl FnOnce(&'a DbError) -> T
                 }
             }
 
-            json_response(
-                e.get_http_status_code(),
-                json!({
-                    "message": message,
-                    "code": code,
-                    "detail": detail,
-                    "hint": hint,
-                    "position": position,
-                    "internalPosition": internal_position,
-                    "internalQuery": internal_query,
-                    "severity": severity,
-                    "where": where_,
-                    "table": table,
-                    "column": column,
-                    "schema": schema,
-                    "dataType": datatype,
-                    "constraint": constraint,
-                    "file": file,
-                    "line": line,
-                    "routine": routine,
-                }),
-            )?
+            json_response(e.get_http_status_code(), error_body(message, db_error))?
         }
     };
 
@@ -451,6 +472,76 @@ fn get<'a, T: Default>(db: Option<&'a DbError>, x: impl FnOnce(&'a DbError) -> T
     Ok(response)
 }
 
+/// The message and the compute's error, if any, reported to the client for `e`.
+fn client_error(
+    e: &SqlOverHttpError,
+    verbosity: ClientErrorVerbosity,
+) -> (String, Option<&DbError>) {
+    let error_kind = e.get_error_kind();
+    let mut message = e.to_string_client_with(verbosity);
+    let db_error = match e {
+        SqlOverHttpError::ConnectCompute(HttpConnError::PostgresConnectionError(e))
+        | SqlOverHttpError::Postgres(e) => e.as_db_error(),
+        _ => None,
+    };
+    // don't let compute internals through the terse message.
+    let db_error = db_error.filter(|_| !verbosity.hides(error_kind));
+    if let Some(db_error) = db_error {
+        db_error.message().clone_into(&mut message);
+    }
+    (message, db_error)
+}
+
+/// The JSON body describing an error, in the format of node-postgres errors.
+fn error_body(message: String, db_error: Option<&DbError>) -> impl Serialize {
+    fn get<'a, T: Default>(db: Option<&'a DbError>, x: impl FnOnce(&'a DbError) -> T) -> T {
+        db.map(x).unwrap_or_default()
+    }
+
+    let position = db_error.and_then(|db| db.position());
+    let (position, internal_position, internal_query) = match position {
+        Some(ErrorPosition::Original(position)) => (Some(position.to_string()), None, None),
+        Some(ErrorPosition::Internal { position, query }) => {
+            (None, Some(position.to_string()), Some(query.clone()))
+        }
+        None => (None, None, None),
+    };
+
+    let code = get(db_error, |db| db.code().code());
+    let severity = get(db_error, |db| db.severity());
+    let detail = get(db_error, |db| db.detail());
+    let hint = get(db_error, |db| db.hint());
+    let where_ = get(db_error, |db| db.where_());
+    let table = get(db_error, |db| db.table());
+    let column = get(db_error, |db| db.column());
+    let schema = get(db_error, |db| db.schema());
+    let datatype = get(db_error, |db| db.datatype());
+    let constraint = get(db_error, |db| db.constraint());
+    let file = get(db_error, |db| db.file());
+    let line = get(db_error, |db| db.line().map(|l| l.to_string()));
+    let routine = get(db_error, |db| db.routine());
+
+    json!({
+        "message": message,
+        "code": code,
+        "detail": detail,
+        "hint": hint,
+        "position": position,
+        "internalPosition": internal_position,
+        "internalQuery": internal_query,
+        "severity": severity,
+        "where": where_,
+        "table": table,
+        "column": column,
+        "schema": schema,
+        "dataType": datatype,
+        "constraint": constraint,
+        "file": file,
+        "line": line,
+        "routine": routine,
+    })
+}
+
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum SqlOverHttpError {
     #[error("{0}")]
@@ -463,6 +554,20 @@ pub(crate) enum SqlOverHttpError {
     ResponseTooLarge(usize),
     #[error("invalid isolation level")]
     InvalidIsolationLevel,
+    #[error("invalid statement timeout, expected milliseconds")]
+    InvalidStatementTimeout,
+    #[error("invalid session id, expected at most {MAX_SESSION_ID_LEN} printable characters")]
+    InvalidSessionId,
+    #[error("{0}")]
+    StickySession(#[from] StickySessionError),
+    #[error("{0}")]
+    Listen(#[from] ListenError),
+    #[error("listening for notifications is not supported by this endpoint")]
+    ListenNotSupported,
+    #[error("query exceeded the maximum duration of {}ms", .0.as_millis())]
+    QueryTimeout(Duration),
+    #[error("{0}")]
+    StatementNotAllowed(#[from] StatementNotAllowed),
     /// for queries our customers choose to run
     #[error("{0}")]
     Postgres(#[source] postgres_client::Error),
@@ -483,6 +588,13 @@ fn get_error_kind(&self) -> ErrorKind {
             SqlOverHttpError::ConnInfo(e) => e.get_error_kind(),
             SqlOverHttpError::ResponseTooLarge(_) => ErrorKind::User,
             SqlOverHttpError::InvalidIsolationLevel => ErrorKind::User,
+            SqlOverHttpError::InvalidStatementTimeout => ErrorKind::User,
+            SqlOverHttpError::InvalidSessionId => ErrorKind::User,
+            SqlOverHttpError::StickySession(e) => e.get_error_kind(),
+            SqlOverHttpError::Listen(e) => e.get_error_kind(),
+            SqlOverHttpError::ListenNotSupported => ErrorKind::User,
+            SqlOverHttpError::QueryTimeout(_) => ErrorKind::User,
+            SqlOverHttpError::StatementNotAllowed(_) => ErrorKind::User,
             // customer initiated SQL errors.
             SqlOverHttpError::Postgres(p) => {
                 if p.as_db_error().is_some() {
@@ -514,6 +626,13 @@ fn to_string_client(&self) -> String {
             SqlOverHttpError::ConnInfo(c) => c.to_string_client(),
             SqlOverHttpError::ResponseTooLarge(_) => self.to_string(),
             SqlOverHttpError::InvalidIsolationLevel => self.to_string(),
+            SqlOverHttpError::InvalidStatementTimeout => self.to_string(),
+            SqlOverHttpError::InvalidSessionId => self.to_string(),
+            SqlOverHttpError::StickySession(e) => e.to_string_client(),
+            SqlOverHttpError::Listen(e) => e.to_string_client(),
+            SqlOverHttpError::ListenNotSupported => self.to_string(),
+            SqlOverHttpError::QueryTimeout(_) => self.to_string(),
+            SqlOverHttpError::StatementNotAllowed(_) => self.to_string(),
             SqlOverHttpError::Postgres(p) => p.to_string(),
             SqlOverHttpError::InternalPostgres(p) => p.to_string(),
             SqlOverHttpError::JsonConversion(_) => "could not parse postgres response".to_string(),
@@ -526,6 +645,9 @@ impl HttpCodeError for SqlOverHttpError {
     fn get_http_status_code(&self) -> StatusCode {
         match self {
             SqlOverHttpError::ReadPayload(e) => e.get_http_status_code(),
+            SqlOverHttpError::ConnectCompute(HttpConnError::CircuitBreakerOpen(_)) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
             SqlOverHttpError::ConnectCompute(h) => match h.get_error_kind() {
                 ErrorKind::User => StatusCode::BAD_REQUEST,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
@@ -533,6 +655,21 @@ fn get_http_status_code(&self) -> StatusCode {
             SqlOverHttpError::ConnInfo(_) => StatusCode::BAD_REQUEST,
             SqlOverHttpError::ResponseTooLarge(_) => StatusCode::INSUFFICIENT_STORAGE,
             SqlOverHttpError::InvalidIsolationLevel => StatusCode::BAD_REQUEST,
+            SqlOverHttpError::InvalidStatementTimeout => StatusCode::BAD_REQUEST,
+            SqlOverHttpError::InvalidSessionId => StatusCode::BAD_REQUEST,
+            SqlOverHttpError::StickySession(StickySessionError::Disabled) => {
+                StatusCode::BAD_REQUEST
+            }
+            SqlOverHttpError::StickySession(StickySessionError::TooMany(_)) => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            SqlOverHttpError::Listen(ListenError::Disabled) => StatusCode::BAD_REQUEST,
+            SqlOverHttpError::Listen(
+                ListenError::TooMany(_) | ListenError::TooManyForEndpoint(_),
+            ) => StatusCode::TOO_MANY_REQUESTS,
+            SqlOverHttpError::ListenNotSupported => StatusCode::BAD_REQUEST,
+            SqlOverHttpError::QueryTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            SqlOverHttpError::StatementNotAllowed(_) => StatusCode::FORBIDDEN,
             SqlOverHttpError::Postgres(_) => StatusCode::BAD_REQUEST,
             SqlOverHttpError::InternalPostgres(_) => StatusCode::INTERNAL_SERVER_ERROR,
             SqlOverHttpError::JsonConversion(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -549,6 +686,8 @@ pub(crate) enum ReadPayloadError {
     BodyTooLarge { limit: usize },
     #[error("could not parse the HTTP request body: {0}")]
     Parse(#[from] serde_json::Error),
+    #[error("could not decompress the HTTP request body: {0}")]
+    Decompress(#[source] std::io::Error),
 }
 
 impl From<ReadBodyError<hyper::Error>> for ReadPayloadError {
@@ -560,12 +699,22 @@ fn from(value: ReadBodyError<hyper::Error>) -> Self {
     }
 }
 
+impl From<ReadBodyError<std::io::Error>> for ReadPayloadError {
+    fn from(value: ReadBodyError<std::io::Error>) -> Self {
+        match value {
+            ReadBodyError::BodyTooLarge { limit } => Self::BodyTooLarge { limit },
+            ReadBodyError::Read(e) => Self::Decompress(e),
+        }
+    }
+}
+
 impl ReportableError for ReadPayloadError {
     fn get_error_kind(&self) -> ErrorKind {
         match self {
             ReadPayloadError::Read(_) => ErrorKind::ClientDisconnect,
             ReadPayloadError::BodyTooLarge { .. } => ErrorKind::User,
             ReadPayloadError::Parse(_) => ErrorKind::User,
+            ReadPayloadError::Decompress(_) => ErrorKind::User,
         }
     }
 }
@@ -576,6 +725,7 @@ fn get_http_status_code(&self) -> StatusCode {
             ReadPayloadError::Read(_) => StatusCode::BAD_REQUEST,
             ReadPayloadError::BodyTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
             ReadPayloadError::Parse(_) => StatusCode::BAD_REQUEST,
+            ReadPayloadError::Decompress(_) => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -604,6 +754,8 @@ struct HttpHeaders {
     txn_isolation_level: Option<IsolationLevel>,
     txn_read_only: bool,
     txn_deferrable: bool,
+    statement_timeout: Option<Duration>,
+    stream_rows: bool,
 }
 
 impl HttpHeaders {
@@ -624,16 +776,46 @@ fn try_parse(headers: &hyper::http::HeaderMap) -> Result<Self, SqlOverHttpError>
         let txn_read_only = headers.get(&TXN_READ_ONLY) == Some(&HEADER_VALUE_TRUE);
         let txn_deferrable = headers.get(&TXN_DEFERRABLE) == Some(&HEADER_VALUE_TRUE);
 
+        // per-request statement timeout, in milliseconds
+        let statement_timeout = match headers.get(&STATEMENT_TIMEOUT) {
+            Some(x) => Some(
+                x.to_str()
+                    .ok()
+                    .and_then(|x| x.parse().ok())
+                    .map(Duration::from_millis)
+                    .ok_or(SqlOverHttpError::InvalidStatementTimeout)?,
+            ),
+            None => None,
+        };
+
+        // stream the result of a single query, see `row_stream`
+        let stream_rows = headers.get(&STREAM_ROWS) == Some(&HEADER_VALUE_TRUE);
+
         Ok(Self {
             raw_output,
             default_array_mode,
             txn_isolation_level,
             txn_read_only,
             txn_deferrable,
+            statement_timeout,
+            stream_rows,
         })
     }
 }
 
+/// The `Neon-Session-Id` header, which opts the request into a sticky session.
+fn parse_session_id(headers: &hyper::http::HeaderMap) -> Result<Option<SmolStr>, SqlOverHttpError> {
+    let Some(session_id) = headers.get(&SESSION_ID) else {
+        return Ok(None);
+    };
+    match session_id.to_str() {
+        Ok(session_id) if !session_id.is_empty() && session_id.len() <= MAX_SESSION_ID_LEN => {
+            Ok(Some(session_id.into()))
+        }
+        _ => Err(SqlOverHttpError::InvalidSessionId),
+    }
+}
+
 fn map_header_to_isolation_level(level: &HeaderValue) -> Option<IsolationLevel> {
     match level.as_bytes() {
         b"Serializable" => Some(IsolationLevel::Serializable),
@@ -670,8 +852,21 @@ async fn handle_inner(
         "handling interactive connection from client"
     );
 
-    let conn_info = get_conn_info(
+    // Reject requests that announce an oversized body before doing any authentication
+    // work, so that unauthenticated clients cannot make us buffer large payloads.
+    // The body is still read with the same limit, in case the announced size is a lie.
+    let max_request_size = config.http_config.max_request_size_bytes;
+    if request.body().size_hint().lower() > max_request_size as u64 {
+        return Err(ReadPayloadError::BodyTooLarge {
+            limit: max_request_size,
+        }
+        .into());
+    }
+
+    let mut conn_info = get_conn_info(
         &config.authentication_config,
+        &config.startup_params_limits,
+        &config.http_config.shared_pool_dbnames,
         ctx,
         request.headers(),
         // todo: race condition?
@@ -682,10 +877,28 @@ async fn handle_inner(
         user = conn_info.conn_info.user_info.user.as_str(),
         "credentials"
     );
+    // local_proxy does not pass the application_name on to postgres, so its connections need
+    // not be pooled apart by it.
+    if backend.auth_backend.is_local_proxy() {
+        conn_info.conn_info.application_name = None;
+    }
+
+    if request.uri().path() == LISTEN_PATH {
+        return handle_listen_inner(
+            cancel,
+            config,
+            ctx,
+            request,
+            conn_info.conn_info,
+            conn_info.auth,
+            backend,
+        )
+        .await;
+    }
 
     match conn_info.auth {
         AuthData::Jwt(jwt) if config.authentication_config.is_auth_broker => {
-            handle_auth_broker_inner(ctx, request, conn_info.conn_info, jwt, backend).await
+            handle_auth_broker_inner(config, ctx, request, conn_info.conn_info, jwt, backend).await
         }
         auth => {
             handle_db_inner(
@@ -720,17 +933,31 @@ async fn handle_db_inner(
     // or if we have decided that http pool is no longer opt-in
     let allow_pool = !config.http_config.pool_options.opt_in
         || headers.get(&ALLOW_POOL) == Some(&HEADER_VALUE_TRUE);
+    // Ignore cached compute node info for new connections, e.g. because the compute moved.
+    let force_wake = headers.get(&FORCE_WAKE) == Some(&HEADER_VALUE_TRUE);
 
     let parsed_headers = HttpHeaders::try_parse(headers)?;
+    let session_id = parse_session_id(headers)?;
+
+    // auth-broker may compress the bodies it exchanges with local-proxy.
+    let (request_encoding, response_encoding) = if backend.auth_backend.is_local_proxy() {
+        (
+            ContentEncoding::from_content_encoding(headers)
+                .map_err(ReadPayloadError::Decompress)?,
+            ContentEncoding::from_accept_encoding(headers),
+        )
+    } else {
+        (None, None)
+    };
 
     let mut request_len = 0;
     let fetch_and_process_request = Box::pin(
         async {
-            let body = read_body_with_limit(
-                request.into_body(),
-                config.http_config.max_request_size_bytes,
-            )
-            .await?;
+            let max_request_size = config.http_config.max_request_size_bytes;
+            let mut body = read_body_with_limit(request.into_body(), max_request_size).await?;
+            if let Some(encoding) = request_encoding {
+                body = encoding.decompress(body, max_request_size).await?;
+            }
 
             request_len = body.len();
 
@@ -743,22 +970,39 @@ async fn handle_db_inner(
             let payload: Payload = serde_json::from_slice(&body)?;
             Ok::<Payload, ReadPayloadError>(payload) // Adjust error type accordingly
         }
-        .map_err(SqlOverHttpError::from),
+        .map_err(SqlOverHttpError::from)
+        .and_then(|payload| async move {
+            // refuse the request before any of it reaches compute.
+            if let Some(filter) = &config.http_config.statement_filter {
+                filter.check(payload.queries())?;
+            }
+            Ok(payload)
+        }),
     );
 
     let authenticate_and_connect = Box::pin(
         async {
             let keys = match auth {
                 AuthData::Password(pw) => backend
-                    .authenticate_with_password(ctx, &conn_info.user_info, &pw)
+                    .authenticate_with_password(ctx, &conn_info.user_info, &conn_info.dbname, &pw)
                     .await
                     .map_err(HttpConnError::AuthError)?,
                 AuthData::Jwt(jwt) => backend
-                    .authenticate_with_jwt(ctx, &conn_info.user_info, jwt)
+                    .authenticate_with_jwt(ctx, &conn_info.user_info, &conn_info.dbname, jwt)
                     .await
                     .map_err(HttpConnError::AuthError)?,
             };
 
+            let mut sticky_session = match &session_id {
+                Some(session_id) => Some(
+                    backend
+                        .sticky_sessions
+                        .acquire(&conn_info, session_id)
+                        .await?,
+                ),
+                None => None,
+            };
+
             let client = match keys.keys {
                 ComputeCredentialKeys::JwtPayload(payload)
                     if backend.auth_backend.is_local_proxy() =>
@@ -770,7 +1014,15 @@ async fn handle_db_inner(
                 }
                 _ => {
                     let client = backend
-                        .connect_to_compute(ctx, conn_info, keys, !allow_pool)
+                        .connect_to_compute(
+                            ctx,
+                            conn_info,
+                            keys,
+                            !allow_pool,
+                            force_wake,
+                            parsed_headers.txn_read_only,
+                            sticky_session.as_deref_mut(),
+                        )
                         .await?;
                     Client::Remote(client)
                 }
@@ -779,12 +1031,12 @@ async fn handle_db_inner(
             // not strictly necessary to mark success here,
             // but it's just insurance for if we forget it somewhere else
             ctx.success();
-            Ok::<_, SqlOverHttpError>(client)
+            Ok::<_, SqlOverHttpError>((client, sticky_session))
         }
         .map_err(SqlOverHttpError::from),
     );
 
-    let (payload, mut client) = match run_until_cancelled(
+    let (payload, (mut client, sticky_session)) = match run_until_cancelled(
         // Run both operations in parallel
         try_join(
             pin!(fetch_and_process_request),
@@ -802,11 +1054,58 @@ async fn handle_db_inner(
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "application/json");
 
+    let backend_pid = config
+        .http_config
+        .expose_backend_pid
+        .then(|| client.inner().0.get_process_id());
+    if let Some(pid) = backend_pid {
+        response = response.header(BACKEND_PID.clone(), pid);
+    }
+
+    if let Some(timeout) = parsed_headers.statement_timeout {
+        client.override_statement_timeout(timeout).await?;
+    }
+
+    let payload = match (payload, config.http_config.row_streaming) {
+        // sticky sessions keep their connection, which the stream would hold on to.
+        (Payload::Single(stmt), Some(streaming))
+            if parsed_headers.stream_rows && sticky_session.is_none() =>
+        {
+            let metrics = client.metrics(ctx);
+            metrics.record_ingress(request_len as u64);
+            let mut response = stream_query(
+                config,
+                streaming,
+                cancel,
+                client,
+                stmt,
+                parsed_headers,
+                metrics,
+            );
+            if let Some(pid) = backend_pid {
+                response
+                    .headers_mut()
+                    .insert(BACKEND_PID.clone(), HeaderValue::from(pid));
+            }
+            return Ok(response);
+        }
+        (payload, _) => payload,
+    };
+
+    // cancelled like a client disconnect, but also once the query has run for too long.
+    let query_cancel = cancel.child_token();
+    let max_query_duration = config.connect_to_compute.max_query_duration;
+
     // Now execute the query and return the result.
-    let json_output = match payload {
+    let result = match payload {
         Payload::Single(stmt) => {
-            stmt.process(&config.http_config, cancel, &mut client, parsed_headers)
-                .await?
+            let query = stmt.process(
+                &config.http_config,
+                query_cancel.clone(),
+                &mut client,
+                parsed_headers,
+            );
+            cancel_after(max_query_duration, &query_cancel, query).await
         }
         Payload::Batch(statements) => {
             if parsed_headers.txn_read_only {
@@ -822,21 +1121,56 @@ async fn handle_db_inner(
                 response = response.header(TXN_ISOLATION_LEVEL.clone(), txn_isolation_level);
             }
 
-            statements
-                .process(&config.http_config, cancel, &mut client, parsed_headers)
-                .await?
+            let query = statements.process(
+                &config.http_config,
+                query_cancel.clone(),
+                &mut client,
+                parsed_headers,
+            );
+            cancel_after(max_query_duration, &query_cancel, query).await
+        }
+    };
+
+    let timed_out = query_cancel.is_cancelled() && !cancel.is_cancelled();
+    let result = match max_query_duration {
+        Some(max_query_duration) if timed_out => {
+            warn!(
+                ?max_query_duration,
+                "query took too long, discarding the connection"
+            );
+            // the query was cancelled, but the session may still be in any state.
+            client.inner().1.discard();
+            Err(SqlOverHttpError::QueryTimeout(max_query_duration))
         }
+        _ => result,
     };
 
+    // never hand a connection with a per-request statement_timeout back to the pool
+    if parsed_headers.statement_timeout.is_some() && !timed_out {
+        client.reset_statement_timeout().await;
+    }
+
     let metrics = client.metrics(ctx);
 
+    // keep the connection for the next request of the session, unless it broke
+    if let (Some(mut sticky_session), Client::Remote(client)) = (sticky_session, client) {
+        if !client.is_closed() && !timed_out {
+            sticky_session.put_client(client);
+        }
+    }
+
+    let json_output = result?;
+
     let len = json_output.len();
+    let body = match response_encoding {
+        Some(encoding) => {
+            response = response.header(header::CONTENT_ENCODING, encoding.header_value());
+            encoding.compress(json_output.into_bytes()).await
+        }
+        None => json_output.into_bytes(),
+    };
     let response = response
-        .body(
-            Full::new(Bytes::from(json_output))
-                .map_err(|x| match x {})
-                .boxed(),
-        )
+        .body(Full::new(Bytes::from(body)).map_err(|x| match x {}).boxed())
         // only fails if invalid status code or invalid header/values are given.
         // these are not user configurable so it cannot fail dynamically
         .expect("building response payload should not fail");
@@ -854,6 +1188,345 @@ async fn handle_db_inner(
     Ok(response)
 }
 
+/// Runs a single query in the background, streaming its result to the client as rows arrive
+/// from compute. See [`super::row_stream`] for the format.
+fn stream_query(
+    config: &'static ProxyConfig,
+    streaming: RowStreamingConfig,
+    cancel: CancellationToken,
+    mut client: Client,
+    data: QueryData,
+    parsed_headers: HttpHeaders,
+    metrics: Arc<MetricCounter>,
+) -> Response<BoxBody<Bytes, hyper::Error>> {
+    // a single chunk in flight, so that a slow client slows down reading from compute.
+    let (tx, mut rx) = mpsc::channel::<Bytes>(1);
+    tokio::spawn(
+        async move {
+            let mut sender = RowSender {
+                tx,
+                metrics,
+                sent: 0,
+            };
+
+            // cancelled like a client disconnect, but also once the query has run for too long.
+            let query_cancel = cancel.child_token();
+            let max_query_duration = config.connect_to_compute.max_query_duration;
+            let query = stream_rows(
+                streaming,
+                query_cancel.clone(),
+                &mut client,
+                data,
+                parsed_headers,
+                &mut sender,
+            );
+            let result = cancel_after(max_query_duration, &query_cancel, query).await;
+
+            let timed_out = query_cancel.is_cancelled() && !cancel.is_cancelled();
+            let result = match max_query_duration {
+                Some(max_query_duration) if timed_out => {
+                    warn!(
+                        ?max_query_duration,
+                        "query took too long, discarding the connection"
+                    );
+                    Err(SqlOverHttpError::QueryTimeout(max_query_duration))
+                }
+                _ => result,
+            };
+
+            if let Err(e) = result {
+                info!(
+                    kind = e.get_error_kind().to_metric_label(),
+                    error = %e,
+                    "query failed while streaming its result"
+                );
+                let (message, db_error) = client_error(&e, config.client_error_verbosity);
+                let line = json_line(&json!({ "error": error_body(message, db_error) }));
+                // the client might be gone already.
+                let _ = sender.send(line).await;
+            }
+
+            // never hand a connection with a per-request statement_timeout back to the pool
+            if parsed_headers.statement_timeout.is_some() && !timed_out {
+                client.reset_statement_timeout().await;
+            }
+
+            Metrics::get()
+                .proxy
+                .http_conn_content_length_bytes
+                .observe(HttpDirection::Response, sender.sent as f64);
+        }
+        .in_current_span(),
+    );
+
+    let body = futures::stream::poll_fn(move |cx| {
+        rx.poll_recv(cx)
+            .map(|line| line.map(|line| Ok::<_, hyper::Error>(Frame::data(line))))
+    });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(StreamBody::new(body).boxed())
+        .expect("building response payload should not fail")
+}
+
+/// Sends the lines of a streamed result to the client.
+struct RowSender {
+    tx: mpsc::Sender<Bytes>,
+    metrics: Arc<MetricCounter>,
+    sent: usize,
+}
+
+impl RowSender {
+    async fn send(&mut self, line: Bytes) -> Result<(), SqlOverHttpError> {
+        let len = line.len();
+        if self.tx.send(line).await.is_err() {
+            // the client went away.
+            return Err(SqlOverHttpError::Cancelled(SqlOverHttpCancel::Postgres));
+        }
+        self.metrics.record_egress(len as u64);
+        self.sent += len;
+        Ok(())
+    }
+}
+
+async fn stream_rows(
+    streaming: RowStreamingConfig,
+    cancel: CancellationToken,
+    client: &mut Client,
+    data: QueryData,
+    parsed_headers: HttpHeaders,
+    sender: &mut RowSender,
+) -> Result<(), SqlOverHttpError> {
+    let (inner, mut discard) = client.inner();
+    let cancel_token = inner.cancel_token();
+
+    match select(
+        pin!(send_rows(
+            streaming,
+            &mut *inner,
+            data,
+            parsed_headers,
+            sender
+        )),
+        pin!(cancel.cancelled()),
+    )
+    .await
+    {
+        Either::Left((Ok(status), _)) => {
+            discard.check_idle(status);
+            Ok(())
+        }
+        Either::Left((Err(e), _)) => {
+            // the rest of the result might still be on the connection.
+            discard.discard();
+            Err(e)
+        }
+        Either::Right((_cancelled, _)) => {
+            tracing::info!("cancelling query");
+            if let Err(err) = cancel_token.cancel_query(NoTls).await {
+                tracing::warn!(?err, "could not cancel query");
+            }
+            discard.discard();
+            Err(SqlOverHttpError::Cancelled(SqlOverHttpCancel::Postgres))
+        }
+    }
+}
+
+async fn send_rows<T: GenericClient>(
+    streaming: RowStreamingConfig,
+    client: &mut T,
+    data: QueryData,
+    parsed_headers: HttpHeaders,
+    sender: &mut RowSender,
+) -> Result<ReadyForQueryStatus, SqlOverHttpError> {
+    let query_start = Instant::now();
+
+    let mut row_stream = client
+        .query_raw_txt(&data.query, data.params)
+        .await
+        .map_err(SqlOverHttpError::Postgres)?;
+    record_statement_cache_hit(row_stream.statement_cache_hit);
+
+    let (fields, types) = statement_fields(&row_stream.statement);
+    let raw_output = parsed_headers.raw_output;
+    let array_mode = data.array_mode.unwrap_or(parsed_headers.default_array_mode);
+    sender
+        .send(json_line(
+            &json!({ "fields": fields, "rowAsArray": array_mode }),
+        ))
+        .await?;
+
+    let mut chunker = RowChunker::new(streaming);
+    let mut rows = 0;
+    while let Some(row) = row_stream.next().await {
+        let row = row.map_err(SqlOverHttpError::Postgres)?;
+        let row = pg_text_row_to_json(&row, &types, raw_output, array_mode)?;
+        rows += 1;
+        if let Some(chunk) = chunker.push(&row) {
+            sender.send(chunk).await?;
+        }
+
+        // see `query_to_json`.
+        tokio::task::consume_budget().await;
+    }
+    if let Some(chunk) = chunker.flush() {
+        sender.send(chunk).await?;
+    }
+
+    let RowStream {
+        command_tag,
+        status: ready,
+        ..
+    } = row_stream;
+    let command_tag = command_tag.unwrap_or_default();
+    let (command_tag_name, command_tag_count) = parse_command_tag(&command_tag);
+
+    info!(
+        rows,
+        ?ready,
+        command_tag,
+        response = ?query_start.elapsed(),
+        "finished streaming query"
+    );
+
+    sender
+        .send(json_line(
+            &json!({ "command": command_tag_name, "rowCount": command_tag_count }),
+        ))
+        .await?;
+    Ok(ready)
+}
+
+/// Cancels `cancel` if `query` runs for longer than `max_duration`. The query is expected to
+/// react to that as to a client disconnect, by cancelling the query on compute and finishing.
+async fn cancel_after<T>(
+    max_duration: Option<Duration>,
+    cancel: &CancellationToken,
+    query: impl Future<Output = T>,
+) -> T {
+    let Some(max_duration) = max_duration else {
+        return query.await;
+    };
+    let timer = async {
+        time::sleep(max_duration).await;
+        cancel.cancel();
+        std::future::pending::<Infallible>().await
+    };
+    match select(pin!(query), pin!(timer)).await {
+        Either::Left((result, _)) => result,
+        Either::Right((never, _)) => match never {},
+    }
+}
+
+/// Subscribes to a channel with `LISTEN` on a dedicated compute connection, and streams the
+/// notifications back as newline-delimited JSON until the client disconnects.
+async fn handle_listen_inner(
+    cancel: CancellationToken,
+    config: &'static ProxyConfig,
+    ctx: &RequestContext,
+    request: Request<Incoming>,
+    conn_info: ConnInfo,
+    auth: AuthData,
+    backend: Arc<PoolingBackend>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, SqlOverHttpError> {
+    // only direct connections to compute can hold on to a session.
+    if config.authentication_config.is_auth_broker || backend.auth_backend.is_local_proxy() {
+        return Err(SqlOverHttpError::ListenNotSupported);
+    }
+
+    let body = read_body_with_limit(
+        request.into_body(),
+        config.http_config.max_request_size_bytes,
+    )
+    .await
+    .map_err(ReadPayloadError::from)?;
+    let ListenPayload { channel } =
+        serde_json::from_slice(&body).map_err(ReadPayloadError::from)?;
+
+    let keys = match auth {
+        AuthData::Password(pw) => backend
+            .authenticate_with_password(ctx, &conn_info.user_info, &conn_info.dbname, &pw)
+            .await
+            .map_err(HttpConnError::AuthError)?,
+        AuthData::Jwt(jwt) => backend
+            .authenticate_with_jwt(ctx, &conn_info.user_info, &conn_info.dbname, jwt)
+            .await
+            .map_err(HttpConnError::AuthError)?,
+    };
+
+    // released once the connection is closed.
+    let permit = backend
+        .listeners
+        .acquire(EndpointIdInt::from(&conn_info.user_info.endpoint))?;
+
+    // the connection is held out of the pool, and closed once the client goes away.
+    let mut client = backend
+        .connect_to_compute(ctx, conn_info, keys, true, false, false, None)
+        .await?;
+    let (inner, mut discard) = client.client_inner();
+    discard.detach();
+    let ClientDataEnum::Remote(data) = inner.get_data() else {
+        unreachable!("compute connections always have remote client data")
+    };
+    let notifications = data.subscribe_notifications(NOTIFICATION_BUFFER);
+
+    let (inner, _) = client.inner();
+    inner
+        .batch_execute(&format!("LISTEN {}", escape_identifier(&channel)))
+        .await
+        .map_err(SqlOverHttpError::Postgres)?;
+    info!(%channel, "listening for notifications");
+    ctx.success();
+
+    let metrics = client.metrics(ctx);
+    let (tx, mut rx) = mpsc::channel::<Bytes>(1);
+    tokio::spawn(
+        async move {
+            // keeps the connection open
+            let _client = client;
+            let _permit = permit;
+            let mut notifications = notifications;
+            loop {
+                let notification = tokio::select! {
+                    notification = notifications.recv() => notification,
+                    () = tx.closed() => None,
+                    () = cancel.cancelled() => None,
+                };
+                let Some(notification) = notification else {
+                    break;
+                };
+
+                let mut line = serde_json::to_vec(&json!({
+                    "channel": notification.channel(),
+                    "payload": notification.payload(),
+                    "pid": notification.process_id(),
+                }))
+                .expect("json serialization should not fail");
+                line.push(b'\n');
+                metrics.record_egress(line.len() as u64);
+
+                if tx.send(Bytes::from(line)).await.is_err() {
+                    break;
+                }
+            }
+            info!("stopped listening for notifications");
+        }
+        .in_current_span(),
+    );
+
+    let body = futures::stream::poll_fn(move |cx| {
+        rx.poll_recv(cx)
+            .map(|line| line.map(|line| Ok::<_, hyper::Error>(Frame::data(line))))
+    });
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(StreamBody::new(body).boxed())
+        .expect("building response payload should not fail");
+    Ok(response)
+}
+
 static HEADERS_TO_FORWARD: &[&HeaderName] = &[
     &AUTHORIZATION,
     &CONN_STRING,
@@ -862,6 +1535,7 @@ async fn handle_db_inner(
     &TXN_ISOLATION_LEVEL,
     &TXN_READ_ONLY,
     &TXN_DEFERRABLE,
+    &STATEMENT_TIMEOUT,
 ];
 
 pub(crate) fn uuid_to_header_value(id: Uuid) -> HeaderValue {
@@ -871,6 +1545,7 @@ pub(crate) fn uuid_to_header_value(id: Uuid) -> HeaderValue {
 }
 
 async fn handle_auth_broker_inner(
+    config: &'static ProxyConfig,
     ctx: &RequestContext,
     request: Request<Incoming>,
     conn_info: ConnInfo,
@@ -878,11 +1553,14 @@ async fn handle_auth_broker_inner(
     backend: Arc<PoolingBackend>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, SqlOverHttpError> {
     backend
-        .authenticate_with_jwt(ctx, &conn_info.user_info, jwt)
+        .authenticate_with_jwt(ctx, &conn_info.user_info, &conn_info.dbname, jwt)
         .await
         .map_err(HttpConnError::from)?;
 
-    let mut client = backend.connect_to_local_proxy(ctx, conn_info).await?;
+    let read_only = request.headers().get(&TXN_READ_ONLY) == Some(&HEADER_VALUE_TRUE);
+    let mut client = backend
+        .connect_to_local_proxy(ctx, conn_info, read_only)
+        .await?;
 
     let local_proxy_uri = ::http::Uri::from_static("http://proxy.local/sql");
 
@@ -898,6 +1576,25 @@ async fn handle_auth_broker_inner(
     }
     req = req.header(&NEON_REQUEST_ID, uuid_to_header_value(ctx.session_id()));
 
+    let body = match config.http_config.local_proxy_compression {
+        Some(encoding) => {
+            let body = read_body_with_limit(body, config.http_config.max_request_size_bytes)
+                .await
+                .map_err(ReadPayloadError::from)?;
+            let len = body.len();
+            let compressed = encoding.compress(body).await;
+            compression::record_saved_bytes(HttpDirection::Request, len, compressed.len());
+
+            req = req
+                .header(header::CONTENT_ENCODING, encoding.header_value())
+                .header(header::ACCEPT_ENCODING, encoding.header_value());
+            Full::new(Bytes::from(compressed))
+                .map_err(|x| match x {})
+                .boxed()
+        }
+        None => body.boxed(),
+    };
+
     let req = req
         .body(body)
         .expect("all headers and params received via hyper should be valid for request");
@@ -905,14 +1602,46 @@ async fn handle_auth_broker_inner(
     // todo: map body to count egress
     let _metrics = client.metrics(ctx);
 
-    Ok(client
+    let response = client
         .inner
         .inner
         .send_request(req)
         .await
         .map_err(LocalProxyConnError::from)
-        .map_err(HttpConnError::from)?
-        .map(|b| b.boxed()))
+        .map_err(HttpConnError::from)?;
+
+    let encoding = ContentEncoding::from_content_encoding(response.headers())
+        .map_err(LocalProxyConnError::Io)
+        .map_err(HttpConnError::from)?;
+    let Some(encoding) = encoding else {
+        return Ok(response.map(|b| b.boxed()));
+    };
+
+    // the client did not ask for compression, so undo it.
+    let (mut parts, body) = response.into_parts();
+    let limit = config.http_config.max_response_size_bytes;
+    let compressed = read_body_with_limit(body, limit)
+        .await
+        .map_err(|e| match e {
+            ReadBodyError::BodyTooLarge { limit } => SqlOverHttpError::ResponseTooLarge(limit),
+            ReadBodyError::Read(e) => HttpConnError::from(LocalProxyConnError::H2(e)).into(),
+        })?;
+    let compressed_len = compressed.len();
+    let body = encoding
+        .decompress(compressed, limit)
+        .await
+        .map_err(|e| match e {
+            ReadBodyError::BodyTooLarge { limit } => SqlOverHttpError::ResponseTooLarge(limit),
+            ReadBodyError::Read(e) => HttpConnError::from(LocalProxyConnError::Io(e)).into(),
+        })?;
+    compression::record_saved_bytes(HttpDirection::Response, body.len(), compressed_len);
+
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Ok(Response::from_parts(
+        parts,
+        Full::new(Bytes::from(body)).map_err(|x| match x {}).boxed(),
+    ))
 }
 
 impl QueryData {
@@ -1133,23 +1862,9 @@ async fn query_to_json<T: GenericClient>(
         .map_err(SqlOverHttpError::Postgres)?;
     let query_acknowledged = Instant::now();
 
-    let columns_len = row_stream.statement.columns().len();
-    let mut fields = Vec::with_capacity(columns_len);
-    let mut types = Vec::with_capacity(columns_len);
-
-    for c in row_stream.statement.columns() {
-        fields.push(json!({
-            "name": c.name().to_owned(),
-            "dataTypeID": c.type_().oid(),
-            "tableID": c.table_oid(),
-            "columnID": c.column_id(),
-            "dataTypeSize": c.type_size(),
-            "dataTypeModifier": c.type_modifier(),
-            "format": "text",
-        }));
+    record_statement_cache_hit(row_stream.statement_cache_hit);
 
-        types.push(c.type_().clone());
-    }
+    let (fields, types) = statement_fields(&row_stream.statement);
 
     let raw_output = parsed_headers.raw_output;
     let array_mode = data.array_mode.unwrap_or(parsed_headers.default_array_mode);
@@ -1187,18 +1902,8 @@ async fn query_to_json<T: GenericClient>(
         ..
     } = row_stream;
 
-    // grab the command tag and number of rows affected
     let command_tag = command_tag.unwrap_or_default();
-    let mut command_tag_split = command_tag.split(' ');
-    let command_tag_name = command_tag_split.next().unwrap_or_default();
-    let command_tag_count = if command_tag_name == "INSERT" {
-        // INSERT returns OID first and then number of rows
-        command_tag_split.nth(1)
-    } else {
-        // other commands return number of rows (if any)
-        command_tag_split.next()
-    }
-    .and_then(|s| s.parse::<i64>().ok());
+    let (command_tag_name, command_tag_count) = parse_command_tag(&command_tag);
 
     info!(
         rows = rows.len(),
@@ -1221,6 +1926,58 @@ async fn query_to_json<T: GenericClient>(
     Ok((ready, results))
 }
 
+fn record_statement_cache_hit(hit: Option<bool>) {
+    if let Some(hit) = hit {
+        let outcome = if hit {
+            CacheOutcome::Hit
+        } else {
+            CacheOutcome::Miss
+        };
+        Metrics::get()
+            .proxy
+            .http_pool_statement_cache_stats
+            .inc(outcome);
+    }
+}
+
+/// The `fields` of a result, and the types to convert its rows with.
+fn statement_fields(statement: &Statement) -> (Vec<impl Serialize + use<>>, Vec<Type>) {
+    let columns_len = statement.columns().len();
+    let mut fields = Vec::with_capacity(columns_len);
+    let mut types = Vec::with_capacity(columns_len);
+
+    for c in statement.columns() {
+        fields.push(json!({
+            "name": c.name().to_owned(),
+            "dataTypeID": c.type_().oid(),
+            "tableID": c.table_oid(),
+            "columnID": c.column_id(),
+            "dataTypeSize": c.type_size(),
+            "dataTypeModifier": c.type_modifier(),
+            "format": "text",
+        }));
+
+        types.push(c.type_().clone());
+    }
+
+    (fields, types)
+}
+
+/// Splits a command tag into the command name and the number of rows affected, if any.
+fn parse_command_tag(command_tag: &str) -> (&str, Option<i64>) {
+    let mut command_tag_split = command_tag.split(' ');
+    let command_tag_name = command_tag_split.next().unwrap_or_default();
+    let command_tag_count = if command_tag_name == "INSERT" {
+        // INSERT returns OID first and then number of rows
+        command_tag_split.nth(1)
+    } else {
+        // other commands return number of rows (if any)
+        command_tag_split.next()
+    }
+    .and_then(|s| s.parse::<i64>().ok());
+    (command_tag_name, command_tag_count)
+}
+
 enum Client {
     Remote(conn_pool_lib::Client<postgres_client::Client>),
     Local(conn_pool_lib::Client<postgres_client::Client>),
@@ -1251,6 +2008,45 @@ fn inner(&mut self) -> (&mut postgres_client::Client, Discard<'_>) {
             }
         }
     }
+
+    fn set_statement_timeout_overridden(&mut self, overridden: bool) {
+        match self {
+            Client::Remote(client) => client.set_statement_timeout_overridden(overridden),
+            Client::Local(local_client) => {
+                local_client.set_statement_timeout_overridden(overridden);
+            }
+        }
+    }
+
+    /// Applies a per-request `statement_timeout` to the session.
+    async fn override_statement_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<(), SqlOverHttpError> {
+        // mark first, so the connection is not pooled if anything below fails
+        self.set_statement_timeout_overridden(true);
+        let (inner, mut discard) = self.inner();
+        if let Err(e) = set_statement_timeout(inner, Some(timeout)).await {
+            discard.discard();
+            return Err(SqlOverHttpError::Postgres(e));
+        }
+        Ok(())
+    }
+
+    /// Restores the connection's own `statement_timeout` after a per-request override,
+    /// so the override does not leak to the next user of a pooled connection.
+    async fn reset_statement_timeout(&mut self) {
+        let (inner, mut discard) = self.inner();
+        match set_statement_timeout(inner, None).await {
+            Ok(status) => discard.check_idle(status),
+            Err(e) => {
+                warn!(?e, "could not reset statement_timeout");
+                discard.discard();
+                return;
+            }
+        }
+        self.set_statement_timeout_overridden(false);
+    }
 }
 
 impl Discard<'_> {
@@ -1326,4 +2122,20 @@ fn test_payload() {
             Payload::Batch(_) => panic!("deserialization failed: case with only one query"),
         }
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_after() {
+        let cancel = CancellationToken::new();
+        let query = async {
+            cancel.cancelled().await;
+            "cancelled"
+        };
+        let result = cancel_after(Some(Duration::from_secs(1)), &cancel, query).await;
+        assert_eq!(result, "cancelled");
+
+        let cancel = CancellationToken::new();
+        let result = cancel_after(Some(Duration::from_secs(1)), &cancel, async { "done" }).await;
+        assert_eq!(result, "done");
+        assert!(!cancel.is_cancelled());
+    }
 }
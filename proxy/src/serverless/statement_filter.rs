@@ -0,0 +1,281 @@
+//! Restricts which SQL statements can be run over HTTP.
+//!
+//! Statements are told apart by their command, the first keyword of the query such as `CREATE`,
+//! `DROP` or `COPY`. This is a best-effort check, not a security boundary:
+//!
+//! * `EXPLAIN` counts as the statement it explains, as `EXPLAIN ANALYZE` runs it.
+//! * A `WITH` query also counts as every data-modifying command (`INSERT`, `UPDATE`, `DELETE`,
+//!   `MERGE`) named anywhere in it, outside of comments and quoted strings.
+//! * A `DO` block can run any statement, so a deny list refuses `DO` as well.
+//! * Functions called by an allowed statement are not looked into.
+//! * Only SQL over HTTP queries are checked. Websocket connections speak the postgres protocol
+//!   to compute directly and are not filtered.
+//!
+//! Roles with only the privileges they need are the way to actually restrict what clients do.
+
+use std::collections::HashSet;
+
+#[derive(Clone, Debug)]
+pub enum StatementFilter {
+    /// Only statements with these commands may run.
+    Allow(HashSet<String>),
+    /// Statements with these commands may not run.
+    Deny(HashSet<String>),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("statements not allowed on this endpoint: {}", .commands.join(", "))]
+pub(crate) struct StatementNotAllowed {
+    commands: Vec<String>,
+}
+
+impl StatementFilter {
+    pub fn allow(commands: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        Self::Allow(normalize(commands))
+    }
+
+    pub fn deny(commands: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        Self::Deny(normalize(commands))
+    }
+
+    fn allows(&self, command: &str) -> bool {
+        match self {
+            StatementFilter::Allow(commands) => commands.contains(command),
+            // the statements of a DO block cannot be checked against the list.
+            StatementFilter::Deny(commands) => !commands.contains(command) && command != "DO",
+        }
+    }
+
+    /// Checks every query of a request, reporting all the commands that are not allowed.
+    pub(crate) fn check<'a>(
+        &self,
+        queries: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), StatementNotAllowed> {
+        let mut denied = Vec::new();
+        for query in queries {
+            for command in commands(query) {
+                if !self.allows(&command) && !denied.contains(&command) {
+                    denied.push(command);
+                }
+            }
+        }
+
+        if denied.is_empty() {
+            Ok(())
+        } else {
+            Err(StatementNotAllowed { commands: denied })
+        }
+    }
+}
+
+fn normalize(commands: impl IntoIterator<Item = impl AsRef<str>>) -> HashSet<String> {
+    commands
+        .into_iter()
+        .map(|command| command.as_ref().trim().to_ascii_uppercase())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+/// Words that can come between `EXPLAIN` and the statement it explains.
+const EXPLAIN_OPTIONS: &[&str] = &[
+    "ANALYZE",
+    "ANALYSE",
+    "VERBOSE",
+    "COSTS",
+    "SETTINGS",
+    "GENERIC_PLAN",
+    "BUFFERS",
+    "SERIALIZE",
+    "WAL",
+    "TIMING",
+    "SUMMARY",
+    "MEMORY",
+    "FORMAT",
+    "TEXT",
+    "XML",
+    "JSON",
+    "YAML",
+    "BINARY",
+    "NONE",
+    "TRUE",
+    "FALSE",
+    "ON",
+    "OFF",
+];
+
+/// Commands that the sub-statements of a `WITH` query can run.
+const DATA_MODIFYING_COMMANDS: &[&str] = &["INSERT", "UPDATE", "DELETE", "MERGE"];
+
+/// The commands a query runs, in upper case. The first one is the command of the query: its
+/// first keyword, or the first keyword of the explained statement for `EXPLAIN`.
+fn commands(query: &str) -> Vec<String> {
+    let mut words = words(query);
+    let mut command = words.next().unwrap_or_default();
+    if command == "EXPLAIN" {
+        command = words
+            .by_ref()
+            .find(|word| !EXPLAIN_OPTIONS.contains(&word.as_str()))
+            .unwrap_or_default();
+    }
+
+    let is_with = command == "WITH";
+    let mut commands = vec![command];
+    if is_with {
+        for word in words {
+            if DATA_MODIFYING_COMMANDS.contains(&word.as_str()) && !commands.contains(&word) {
+                commands.push(word);
+            }
+        }
+    }
+    commands
+}
+
+/// The words of a query, in upper case, skipping comments, quoted strings and identifiers,
+/// and punctuation.
+fn words(query: &str) -> impl Iterator<Item = String> + '_ {
+    let mut rest = query;
+    std::iter::from_fn(move || {
+        loop {
+            rest = skip_comments(rest);
+            let mut chars = rest.chars();
+            let c = chars.next()?;
+            if c.is_ascii_alphabetic() || c == '_' {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let word = rest[..end].to_ascii_uppercase();
+                rest = &rest[end..];
+                return Some(word);
+            } else if c == '\'' || c == '"' {
+                rest = chars.as_str().split_once(c).map_or("", |(_, rest)| rest);
+            } else {
+                rest = chars.as_str();
+            }
+        }
+    })
+}
+
+/// Skips whitespace, opening parentheses and comments at the start of `s`.
+fn skip_comments(s: &str) -> &str {
+    let mut rest = s;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if rest.starts_with("/*") {
+            rest = skip_block_comment(rest);
+        } else {
+            return rest;
+        }
+    }
+}
+
+/// Skips the block comment at the start of `s`. Block comments nest in postgres.
+fn skip_block_comment(s: &str) -> &str {
+    let mut depth = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix("/*") {
+            depth += 1;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("*/") {
+            depth -= 1;
+            rest = r;
+            if depth == 0 {
+                return rest;
+            }
+        } else {
+            let mut chars = rest.chars();
+            chars.next();
+            rest = chars.as_str();
+        }
+    }
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(query: &str) -> String {
+        commands(query).swap_remove(0)
+    }
+
+    #[test]
+    fn parse_command() {
+        assert_eq!(command("select 1"), "SELECT");
+        assert_eq!(command("  \n\tCREATE TABLE t (x int)"), "CREATE");
+        assert_eq!(command("drop table t;"), "DROP");
+        assert_eq!(command("(SELECT 1) UNION (SELECT 2)"), "SELECT");
+        assert_eq!(command("-- a comment\nDELETE FROM t"), "DELETE");
+        assert_eq!(
+            command("/* outer /* nested */ still outer */ COPY t TO STDOUT"),
+            "COPY"
+        );
+        assert_eq!(command("/* unterminated"), "");
+        assert_eq!(command(""), "");
+
+        assert_eq!(command("EXPLAIN ANALYZE DELETE FROM t"), "DELETE");
+        assert_eq!(
+            command("explain (analyze, buffers on, format json) /* x */ update t set x = 1"),
+            "UPDATE"
+        );
+        assert_eq!(
+            commands("WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"),
+            ["WITH", "DELETE"]
+        );
+        assert_eq!(
+            commands("with x as (select 'delete' as \"update\") select * from x -- insert"),
+            ["WITH"]
+        );
+        assert_eq!(
+            commands("EXPLAIN ANALYZE WITH d AS (DELETE FROM t) SELECT 1"),
+            ["WITH", "DELETE"]
+        );
+    }
+
+    #[test]
+    fn allow_and_deny() {
+        let deny = StatementFilter::deny(["create", " DROP "]);
+        deny.check(["SELECT 1", "insert into t values (1)"])
+            .unwrap();
+        let err = deny
+            .check([
+                "CREATE TABLE a ()",
+                "SELECT 1",
+                "DROP TABLE a",
+                "create table b ()",
+            ])
+            .unwrap_err();
+        assert_eq!(err.commands, ["CREATE", "DROP"]);
+        assert_eq!(
+            err.to_string(),
+            "statements not allowed on this endpoint: CREATE, DROP"
+        );
+
+        let allow = StatementFilter::allow(["SELECT", "WITH"]);
+        allow
+            .check(["SELECT 1", "with x as (select 1) select * from x"])
+            .unwrap();
+        let err = allow.check(["TRUNCATE t"]).unwrap_err();
+        assert_eq!(err.commands, ["TRUNCATE"]);
+
+        // statements that run other statements are checked for those
+        let err = deny
+            .check([
+                "EXPLAIN ANALYZE DROP TABLE a",
+                "DO $$ BEGIN DROP TABLE a; END $$",
+            ])
+            .unwrap_err();
+        assert_eq!(err.commands, ["DROP", "DO"]);
+        let deny = StatementFilter::deny(["DELETE"]);
+        let err = deny
+            .check(["WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"])
+            .unwrap_err();
+        assert_eq!(err.commands, ["DELETE"]);
+        let err = allow
+            .check(["WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"])
+            .unwrap_err();
+        assert_eq!(err.commands, ["DELETE"]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Sticky sessions for SQL over HTTP.
+//!
+//! A request with a `Neon-Session-Id` header gets a dedicated connection to compute. Instead of
+//! being reset and returned to the pool, the connection is kept for the next request with the
+//! same session id and connection string, so session state (temporary tables, advisory locks,
+//! `SET` parameters, prepared statements) survives between requests. Requests of a session run
+//! one at a time.
+//!
+//! Every session holds a compute connection open for as long as it is used, and for
+//! `idle_timeout` after its last request, so a session costs a lot more than a pooled connection.
+//! `max_sessions` bounds how many of them can be open at once.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use clashmap::ClashMap;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tokio::time::Instant;
+use tracing::info;
+
+use super::conn_pool_lib::ConnInfo;
+use crate::error::{ErrorKind, ReportableError, UserFacingError};
+use crate::metrics::Metrics;
+
+/// Longest session id accepted from clients.
+pub(crate) const MAX_SESSION_ID_LEN: usize = 128;
+
+#[derive(Clone, Copy, Debug)]
+pub struct StickySessionConfig {
+    pub max_sessions: usize,
+    pub idle_timeout: Duration,
+}
+
+pub(crate) struct StickySessions<C> {
+    config: Option<StickySessionConfig>,
+    sessions: ClashMap<String, Arc<Mutex<StickySession<C>>>>,
+}
+
+pub(crate) struct StickySession<C> {
+    client: Option<C>,
+    last_used: Instant,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum StickySessionError {
+    #[error("sticky sessions are not enabled")]
+    Disabled,
+    #[error("too many sticky sessions (max is {0})")]
+    TooMany(usize),
+}
+
+impl ReportableError for StickySessionError {
+    fn get_error_kind(&self) -> ErrorKind {
+        match self {
+            StickySessionError::Disabled => ErrorKind::User,
+            StickySessionError::TooMany(_) => ErrorKind::RateLimit,
+        }
+    }
+}
+
+impl UserFacingError for StickySessionError {}
+
+impl<C> StickySessions<C> {
+    /// `None` disables sticky sessions.
+    pub(crate) fn new(config: Option<StickySessionConfig>) -> Self {
+        Self {
+            config,
+            sessions: ClashMap::default(),
+        }
+    }
+
+    /// Locks the session, opening it if it does not exist yet.
+    /// Waits for any other request of the same session to finish first.
+    pub(crate) async fn acquire(
+        &self,
+        conn_info: &ConnInfo,
+        session_id: &str,
+    ) -> Result<OwnedMutexGuard<StickySession<C>>, StickySessionError> {
+        let Some(config) = &self.config else {
+            return Err(StickySessionError::Disabled);
+        };
+
+        // the session only matches requests for the same role, database and endpoint.
+        let key = format!("{conn_info}#{}#{session_id}", conn_info.read_replica);
+        let existing = self.sessions.get(&key).map(|session| Arc::clone(&session));
+        let session = match existing {
+            Some(session) => session,
+            None => {
+                if self.sessions.len() >= config.max_sessions {
+                    return Err(StickySessionError::TooMany(config.max_sessions));
+                }
+                Arc::clone(
+                    &self
+                        .sessions
+                        .entry(key)
+                        .or_insert_with(|| Arc::new(Mutex::new(StickySession::new()))),
+                )
+            }
+        };
+
+        let mut session = session.lock_owned().await;
+        session.last_used = Instant::now();
+        Ok(session)
+    }
+
+    /// Closes the sessions that have been idle for longer than the idle timeout.
+    pub(crate) fn gc(&self) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let now = Instant::now();
+        self.sessions.retain(|_, session| match session.try_lock() {
+            Ok(session) => now < session.last_used + config.idle_timeout,
+            // in use
+            Err(_) => true,
+        });
+    }
+
+    pub(crate) async fn gc_worker(&self) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let mut interval =
+            tokio::time::interval(config.idle_timeout.div_f64(2.0).max(Duration::from_secs(1)));
+        loop {
+            interval.tick().await;
+            let before = self.sessions.len();
+            self.gc();
+            let closed = before.saturating_sub(self.sessions.len());
+            if closed > 0 {
+                info!(closed, "sticky sessions: closed idle sessions");
+            }
+        }
+    }
+}
+
+impl<C> StickySession<C> {
+    fn new() -> Self {
+        Metrics::get().proxy.http_sticky_sessions.get_metric().inc();
+        Self {
+            client: None,
+            last_used: Instant::now(),
+        }
+    }
+
+    /// The connection kept from the previous request of this session, if any.
+    pub(crate) fn take_client(&mut self) -> Option<C> {
+        self.client.take()
+    }
+
+    /// Keeps the connection for the next request of this session.
+    pub(crate) fn put_client(&mut self, client: C) {
+        self.client = Some(client);
+        self.last_used = Instant::now();
+    }
+}
+
+impl<C> Drop for StickySession<C> {
+    fn drop(&mut self) {
+        Metrics::get().proxy.http_sticky_sessions.get_metric().dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::backend::ComputeUserInfo;
+    use crate::proxy::{NeonOptions, PgSettings};
+
+    fn conn_info(user: &str) -> ConnInfo {
+        ConnInfo {
+            user_info: ComputeUserInfo {
+                user: user.into(),
+                endpoint: "endpoint".into(),
+                options: NeonOptions::default(),
+            },
+            dbname: "dbname".into(),
+            pg_settings: PgSettings::default(),
+            read_replica: false,
+            read_only: false,
+            pool_name: None,
+            pool_dbname: None,
+            application_name: None,
+        }
+    }
+
+    fn sessions(max_sessions: usize) -> StickySessions<u32> {
+        StickySessions::new(Some(StickySessionConfig {
+            max_sessions,
+            idle_timeout: Duration::from_secs(60),
+        }))
+    }
+
+    #[tokio::test]
+    async fn session_keeps_client() {
+        let sessions = sessions(10);
+
+        let mut session = sessions.acquire(&conn_info("alice"), "s1").await.unwrap();
+        assert_eq!(session.take_client(), None);
+        session.put_client(1);
+        drop(session);
+
+        // a different session id, or the same id for another role, is a different session
+        let mut other = sessions.acquire(&conn_info("alice"), "s2").await.unwrap();
+        assert_eq!(other.take_client(), None);
+        drop(other);
+        let mut other = sessions.acquire(&conn_info("bob"), "s1").await.unwrap();
+        assert_eq!(other.take_client(), None);
+        drop(other);
+
+        let mut session = sessions.acquire(&conn_info("alice"), "s1").await.unwrap();
+        assert_eq!(session.take_client(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn session_limit() {
+        let sessions = sessions(1);
+        drop(sessions.acquire(&conn_info("alice"), "s1").await.unwrap());
+
+        let err = sessions
+            .acquire(&conn_info("alice"), "s2")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StickySessionError::TooMany(1)));
+        // existing sessions are still usable
+        drop(sessions.acquire(&conn_info("alice"), "s1").await.unwrap());
+
+        let disabled = StickySessions::<u32>::new(None);
+        let err = disabled
+            .acquire(&conn_info("alice"), "s1")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, StickySessionError::Disabled));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_sessions_are_closed() {
+        let sessions = sessions(10);
+        let mut session = sessions.acquire(&conn_info("alice"), "s1").await.unwrap();
+        session.put_client(1);
+        drop(session);
+        let busy = sessions.acquire(&conn_info("alice"), "s2").await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        sessions.gc();
+        assert_eq!(sessions.sessions.len(), 2);
+
+        tokio::time::advance(Duration::from_secs(31)).await;
+        sessions.gc();
+        // sessions in use are kept
+        assert_eq!(sessions.sessions.len(), 1);
+        drop(busy);
+
+        let mut session = sessions.acquire(&conn_info("alice"), "s1").await.unwrap();
+        assert_eq!(session.take_client(), None);
+    }
+}
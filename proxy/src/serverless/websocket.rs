@@ -6,10 +6,13 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use framed_websockets::{Frame, OpCode, WebSocketServer};
 use futures::{Sink, Stream};
+use http::HeaderMap;
+use http::header::SEC_WEBSOCKET_PROTOCOL;
 use hyper::upgrade::OnUpgrade;
 use hyper_util::rt::TokioIo;
 use pin_project_lite::pin_project;
 use tokio::io::{self, AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+use tokio_util::sync::CancellationToken;
 use tracing::warn;
 
 use crate::cancellation::CancellationHandler;
@@ -124,6 +127,44 @@ fn consume(self: Pin<&mut Self>, amount: usize) {
     }
 }
 
+/// The only WebSocket subprotocol we serve: the postgres wire protocol, in binary frames.
+pub(crate) const POSTGRES_SUBPROTOCOL: &str = "postgres";
+
+/// Checks the subprotocols requested with `Sec-WebSocket-Protocol`.
+///
+/// Clients that don't request any subprotocol get `Ok(None)`, and are served the postgres
+/// wire protocol as before. Clients that request it get it echoed back, and clients that only
+/// request subprotocols we don't support get an error.
+pub(crate) fn negotiate_subprotocol(
+    headers: &HeaderMap,
+) -> Result<Option<&'static str>, UnsupportedSubprotocols> {
+    let mut requested = headers
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .peekable();
+
+    if requested.peek().is_none() {
+        return Ok(None);
+    }
+
+    let mut unsupported = Vec::new();
+    for protocol in requested {
+        if protocol == POSTGRES_SUBPROTOCOL {
+            return Ok(Some(POSTGRES_SUBPROTOCOL));
+        }
+        unsupported.push(protocol.to_owned());
+    }
+    Err(UnsupportedSubprotocols(unsupported))
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("none of the requested websocket subprotocols are supported: {}", .0.join(", "))]
+pub(crate) struct UnsupportedSubprotocols(Vec<String>);
+
 #[allow(clippy::too_many_arguments)]
 pub(crate) async fn serve_websocket(
     config: &'static ProxyConfig,
@@ -134,6 +175,7 @@ pub(crate) async fn serve_websocket(
     endpoint_rate_limiter: Arc<EndpointRateLimiter>,
     hostname: Option<String>,
     cancellations: tokio_util::task::task_tracker::TaskTracker,
+    shutdown: CancellationToken,
 ) -> anyhow::Result<()> {
     let websocket = websocket.await?;
     let websocket = WebSocketServer::after_handshake(TokioIo::new(websocket));
@@ -168,7 +210,7 @@ pub(crate) async fn serve_websocket(
         Ok(Some(p)) => {
             ctx.set_success();
             ctx.log_connect();
-            match p.proxy_pass().await {
+            match p.proxy_pass(shutdown).await {
                 Ok(()) => Ok(()),
                 Err(ErrorSource::Client(err)) => Err(err).context("client"),
                 Err(ErrorSource::Compute(err)) => Err(err).context("compute"),
@@ -189,7 +231,7 @@ mod tests {
     use tokio_tungstenite::tungstenite::Message;
     use tokio_tungstenite::tungstenite::protocol::Role;
 
-    use super::WebSocketRw;
+    use super::{POSTGRES_SUBPROTOCOL, WebSocketRw, negotiate_subprotocol};
 
     #[tokio::test]
     async fn websocket_stream_wrapper_happy_path() {
@@ -228,4 +270,25 @@ async fn websocket_stream_wrapper_happy_path() {
         js.join_next().await.unwrap().unwrap();
         js.join_next().await.unwrap().unwrap();
     }
+
+    #[test]
+    fn subprotocol_negotiation() {
+        let mut headers = http::HeaderMap::new();
+        assert_eq!(negotiate_subprotocol(&headers).unwrap(), None);
+
+        headers.insert(
+            http::header::SEC_WEBSOCKET_PROTOCOL,
+            http::HeaderValue::from_static("sql-json, postgres"),
+        );
+        assert_eq!(
+            negotiate_subprotocol(&headers).unwrap(),
+            Some(POSTGRES_SUBPROTOCOL)
+        );
+
+        headers.insert(
+            http::header::SEC_WEBSOCKET_PROTOCOL,
+            http::HeaderValue::from_static("sql-json"),
+        );
+        assert!(negotiate_subprotocol(&headers).is_err());
+    }
 }
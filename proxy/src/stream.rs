@@ -7,7 +7,7 @@
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio_rustls::server::TlsStream;
 
-use crate::error::{ErrorKind, ReportableError, UserFacingError};
+use crate::error::{ClientErrorVerbosity, ErrorKind, ReportableError, UserFacingError};
 use crate::metrics::Metrics;
 use crate::pqproto::{
     BeMessage, FE_PASSWORD_MESSAGE, FeStartupPacket, SQLSTATE_INTERNAL_ERROR, WriteBuf,
@@ -178,16 +178,18 @@ pub async fn flush_and_into_inner(mut self) -> io::Result<S> {
     ///
     /// Trait [`UserFacingError`] acts as an allowlist for error types.
     /// If `ctx` is provided and has testodrome_id set, error messages will be prefixed according to error kind.
+    /// `verbosity` controls how much of the error detail is sent.
     pub(crate) async fn throw_error<E>(
         &mut self,
         error: E,
         ctx: Option<&crate::context::RequestContext>,
+        verbosity: ClientErrorVerbosity,
     ) -> ReportedError
     where
         E: UserFacingError + Into<anyhow::Error>,
     {
         let error_kind = error.get_error_kind();
-        let msg = error.to_string_client();
+        let msg = error.to_string_client_with(verbosity);
 
         if error_kind != ErrorKind::RateLimit && error_kind != ErrorKind::User {
             tracing::info!(
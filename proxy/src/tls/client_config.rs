@@ -4,7 +4,50 @@
 use std::sync::Arc;
 
 use anyhow::{Context, bail};
-use rustls::crypto::ring;
+use rustls::crypto::ring::{self, sign};
+use rustls::sign::CertifiedKey;
+
+use super::server_config::parse_key_cert;
+
+/// Certificate the proxy presents when connecting to compute, so that computes can require
+/// TLS client certificates and verify that connections come from the proxy.
+#[derive(Clone, Debug)]
+pub struct ComputeClientCert {
+    pub key_path: PathBuf,
+    pub cert_path: PathBuf,
+}
+
+impl ComputeClientCert {
+    fn load(&self) -> anyhow::Result<Arc<CertifiedKey>> {
+        let (priv_key, cert_chain) = parse_key_cert(&self.key_path, &self.cert_path)?;
+        if cert_chain.is_empty() {
+            bail!(
+                "no compute client certificate found in '{}'",
+                self.cert_path.display()
+            );
+        }
+        let key = sign::any_supported_type(&priv_key).context("invalid compute client key")?;
+        Ok(Arc::new(CertifiedKey::new(cert_chain, key)))
+    }
+}
+
+/// Presents the same certificate to every compute.
+#[derive(Debug)]
+struct ComputeClientCertResolver(Arc<CertifiedKey>);
+
+impl rustls::client::ResolvesClientCert for ComputeClientCertResolver {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[rustls::SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.0))
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
 
 /// We use an internal certificate authority when establishing a TLS connection with compute.
 fn load_internal_certs(store: &mut rustls::RootCertStore) -> anyhow::Result<()> {
@@ -48,15 +91,20 @@ fn load_compute_certs() -> anyhow::Result<Arc<rustls::RootCertStore>> {
 }
 
 /// Loads the root certificates and constructs a client config suitable for connecting to the neon compute.
+/// With `client_cert`, the proxy authenticates itself to computes with that certificate.
 /// This function is blocking.
-pub fn compute_client_config_with_root_certs() -> anyhow::Result<rustls::ClientConfig> {
-    Ok(
-        rustls::ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
-            .with_safe_default_protocol_versions()
-            .expect("ring should support the default protocol versions")
-            .with_root_certificates(load_compute_certs()?)
-            .with_no_client_auth(),
-    )
+pub fn compute_client_config_with_root_certs(
+    client_cert: Option<&ComputeClientCert>,
+) -> anyhow::Result<rustls::ClientConfig> {
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .expect("ring should support the default protocol versions")
+        .with_root_certificates(load_compute_certs()?);
+    Ok(match client_cert {
+        Some(client_cert) => builder
+            .with_client_cert_resolver(Arc::new(ComputeClientCertResolver(client_cert.load()?))),
+        None => builder.with_no_client_auth(),
+    })
 }
 
 #[cfg(test)]
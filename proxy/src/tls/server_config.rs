@@ -79,6 +79,46 @@ pub fn configure_tls(
     })
 }
 
+/// Configure TLS for the management listener. Clients must present a certificate signed by
+/// one of the CAs in `client_ca_path`.
+pub fn configure_mgmt_tls(
+    key_path: &Path,
+    cert_path: &Path,
+    client_ca_path: &Path,
+) -> anyhow::Result<Arc<rustls::ServerConfig>> {
+    let (priv_key, cert_chain) = parse_key_cert(key_path, cert_path)?;
+
+    let ca = std::fs::read(client_ca_path).with_context(|| {
+        format!(
+            "Failed to read mgmt client CA at '{}'",
+            client_ca_path.display()
+        )
+    })?;
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &ca[..]) {
+        roots
+            .add(cert.context("could not parse mgmt client CA certificate")?)
+            .context("could not parse mgmt client CA certificate")?;
+    }
+
+    let provider = Arc::new(ring::default_provider());
+    let client_verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(
+        Arc::new(roots),
+        provider.clone(),
+    )
+    .build()
+    .context("invalid mgmt client CA")?;
+
+    let config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .context("ring should support TLS1.3")?
+        .with_client_cert_verifier(client_verifier)
+        .with_single_cert(cert_chain, priv_key)
+        .context("invalid mgmt TLS key or certificate")?;
+
+    Ok(Arc::new(config))
+}
+
 #[derive(Debug)]
 pub struct CertResolver {
     certs: HashMap<String, (Arc<rustls::sign::CertifiedKey>, TlsServerEndPoint)>,
@@ -123,7 +163,7 @@ pub fn get_common_names(&self) -> HashSet<String> {
     }
 }
 
-fn parse_key_cert(
+pub(super) fn parse_key_cert(
     key_path: &Path,
     cert_path: &Path,
 ) -> anyhow::Result<(PrivateKeyDer<'static>, Vec<CertificateDer<'static>>)> {
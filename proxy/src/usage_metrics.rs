@@ -1,6 +1,7 @@
 //! Periodically collect proxy consumption metrics
 //! and push them to a HTTP endpoint.
 use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::convert::Infallible;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
@@ -14,10 +15,12 @@
 use clashmap::mapref::entry::Entry;
 use consumption_metrics::{CHUNK_SIZE, Event, EventChunk, EventType, idempotency_key};
 use once_cell::sync::Lazy;
+use rand::{Rng, thread_rng};
 use remote_storage::{GenericRemoteStorage, RemotePath, TimeoutOrCancel};
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 use tokio::io::AsyncWriteExt;
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, instrument, trace, warn};
 use utils::backoff;
@@ -261,14 +264,17 @@ pub async fn task_main(config: &MetricCollectionConfig) -> anyhow::Result<Infall
         None
     };
 
+    let mut unsent = UnsentEvents::new(config.unsent_buffer_size);
     let mut prev = Utc::now();
-    let mut ticker = tokio::time::interval(config.interval);
+    let mut next_push = Instant::now() + initial_delay(config.interval, config.interval_jitter);
     loop {
-        ticker.tick().await;
+        tokio::time::sleep_until(next_push).await;
+        next_push += jittered(config.interval, config.interval_jitter);
 
         let now = Utc::now();
         collect_metrics_iteration(
             &USAGE_METRICS.endpoints,
+            &mut unsent,
             &http_client,
             &config.endpoint,
             storage.as_ref(),
@@ -282,6 +288,114 @@ pub async fn task_main(config: &MetricCollectionConfig) -> anyhow::Result<Infall
     }
 }
 
+/// With jitter, the first push is delayed by a random part of the interval, so that proxies
+/// started by the same deploy do not push at the same instants.
+fn initial_delay(interval: Duration, jitter: f64) -> Duration {
+    if jitter > 0.0 {
+        interval.mul_f64(thread_rng().gen_range(0.0..1.0))
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// The interval, randomly shortened or lengthened by up to `jitter` of it, so that it averages
+/// to `interval`.
+fn jittered(interval: Duration, jitter: f64) -> Duration {
+    if jitter > 0.0 {
+        interval.mul_f64(1.0 + thread_rng().gen_range(-jitter..=jitter))
+    } else {
+        interval
+    }
+}
+
+/// Pushes an empty batch of events to the collection endpoint, to make sure that
+/// usage can be reported before the proxy starts accepting connections.
+pub async fn check_endpoint(config: &MetricCollectionConfig) -> anyhow::Result<()> {
+    let http_client = http::new_client_with_timeout(
+        HTTP_REPORTING_REQUEST_TIMEOUT,
+        HTTP_REPORTING_RETRY_DURATION,
+    );
+
+    let chunk = EventChunk::<Event<Extra, &str>> {
+        events: Cow::Borrowed(&[]),
+    };
+    let res = http_client
+        .post(config.endpoint.clone())
+        .json(&chunk)
+        .send()
+        .await
+        .with_context(|| format!("failed to send metrics to {}", config.endpoint))?;
+
+    if !res.status().is_success() {
+        bail!(
+            "metrics endpoint {} refused the sent metrics: {}",
+            config.endpoint,
+            res.status()
+        );
+    }
+
+    info!(
+        "metrics endpoint {} accepted the first push",
+        config.endpoint
+    );
+    Ok(())
+}
+
+/// Events that could not be pushed to the collection endpoint, one entry per collection
+/// interval. They are sent again, oldest first, once a push succeeds.
+struct UnsentEvents {
+    intervals: VecDeque<Vec<Event<Extra, &'static str>>>,
+    capacity: usize,
+}
+
+impl UnsentEvents {
+    fn new(capacity: usize) -> Self {
+        Self {
+            intervals: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, events: Vec<Event<Extra, &'static str>>) {
+        if events.is_empty() {
+            return;
+        }
+        self.intervals.push_back(events);
+        while self.intervals.len() > self.capacity {
+            let Some(dropped) = self.intervals.pop_front() else {
+                break;
+            };
+            error!(
+                "dropping {} unsent usage events, the buffer is full",
+                dropped.len()
+            );
+            crate::metrics::Metrics::get()
+                .proxy
+                .usage_metrics_dropped_total
+                .inc_by(dropped.len() as u64);
+        }
+    }
+
+    /// Resends the buffered events, oldest first, until a push fails.
+    async fn replay(
+        &mut self,
+        client: &http::ClientWithMiddleware,
+        metric_collection_endpoint: &reqwest::Url,
+    ) {
+        while let Some(events) = self.intervals.pop_front() {
+            let len = events.len();
+            let failed =
+                upload_main_events_chunked(client, metric_collection_endpoint, &events, CHUNK_SIZE)
+                    .await;
+            if !failed.is_empty() {
+                self.intervals.push_front(failed);
+                break;
+            }
+            info!("resent {len} previously unsent usage events");
+        }
+    }
+}
+
 fn collect_and_clear_metrics<C: Clearable>(
     endpoints: &ClashMap<Ids, Arc<C>, FastHasher>,
 ) -> Vec<(Ids, BytesSent)> {
@@ -362,6 +476,7 @@ fn create_event_chunks<'a>(
 #[instrument(skip_all)]
 async fn collect_metrics_iteration(
     endpoints: &ClashMap<Ids, Arc<MetricCounter>, FastHasher>,
+    unsent: &mut UnsentEvents,
     client: &http::ClientWithMiddleware,
     metric_collection_endpoint: &reqwest::Url,
     storage: Option<&GenericRemoteStorage>,
@@ -385,15 +500,28 @@ async fn collect_metrics_iteration(
     let path_prefix = create_remote_path_prefix(now);
 
     // Send metrics.
+    let mut failed = Vec::new();
     for chunk in create_event_chunks(&metrics_to_send, hostname, prev, now, outer_chunk_size) {
-        tokio::join!(
-            upload_main_events_chunked(client, metric_collection_endpoint, &chunk, CHUNK_SIZE),
+        let (failed_chunk, ()) = tokio::join!(
+            upload_main_events_chunked(
+                client,
+                metric_collection_endpoint,
+                &chunk.events,
+                CHUNK_SIZE
+            ),
             async {
                 if let Err(e) = upload_backup_events(storage, &chunk, &path_prefix, &cancel).await {
                     error!("failed to upload consumption events to remote storage: {e:?}");
                 }
             }
         );
+        failed.extend(failed_chunk);
+    }
+
+    if failed.is_empty() {
+        unsent.replay(client, metric_collection_endpoint).await;
+    } else {
+        unsent.push(failed);
     }
 }
 
@@ -409,14 +537,17 @@ fn create_remote_path_prefix(now: DateTime<Utc>) -> String {
     )
 }
 
+/// Returns the events that could not be sent.
 async fn upload_main_events_chunked(
     client: &http::ClientWithMiddleware,
     metric_collection_endpoint: &reqwest::Url,
-    chunk: &EventChunk<'_, Event<Extra, &str>>,
+    events: &[Event<Extra, &'static str>],
     subchunk_size: usize,
-) {
+) -> Vec<Event<Extra, &'static str>> {
+    let mut failed = Vec::new();
+
     // Split into smaller chunks to avoid exceeding the max request size
-    for subchunk in chunk.events.chunks(subchunk_size).map(|c| EventChunk {
+    for subchunk in events.chunks(subchunk_size).map(|c| EventChunk {
         events: Cow::Borrowed(c),
     }) {
         let res = client
@@ -428,20 +559,39 @@ async fn upload_main_events_chunked(
         let res = match res {
             Ok(x) => x,
             Err(err) => {
-                // TODO: retry?
                 error!("failed to send metrics: {:?}", err);
+                failed.extend_from_slice(&subchunk.events);
                 continue;
             }
         };
 
-        if !res.status().is_success() {
+        let status = res.status();
+        if !status.is_success() {
             error!("metrics endpoint refused the sent metrics: {:?}", res);
             for metric in subchunk.events.iter().filter(|e| e.value > (1u64 << 40)) {
                 // Report if the metric value is suspiciously large
                 warn!("potentially abnormal metric value: {:?}", metric);
             }
+            if status.is_server_error()
+                || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::REQUEST_TIMEOUT
+            {
+                failed.extend_from_slice(&subchunk.events);
+            } else {
+                // the same events would be refused again, they are only kept in the backup storage.
+                error!(
+                    "dropping {} usage events refused with {status}",
+                    subchunk.events.len()
+                );
+                crate::metrics::Metrics::get()
+                    .proxy
+                    .usage_metrics_dropped_total
+                    .inc_by(subchunk.events.len() as u64);
+            }
         }
     }
+
+    failed
 }
 
 async fn upload_backup_events(
@@ -557,6 +707,7 @@ async fn metrics() {
         });
 
         let metrics = Metrics::default();
+        let mut unsent = UnsentEvents::new(10);
         let client = http::new_client();
         let endpoint = Url::parse(&format!("http://{addr}")).unwrap();
         let now = Utc::now();
@@ -580,6 +731,7 @@ async fn metrics() {
         // no counters have been registered
         collect_metrics_iteration(
             &metrics.endpoints,
+            &mut unsent,
             &client,
             &endpoint,
             Some(&storage),
@@ -603,6 +755,7 @@ async fn metrics() {
         // the counter should be observed despite 0 egress
         collect_metrics_iteration(
             &metrics.endpoints,
+            &mut unsent,
             &client,
             &endpoint,
             Some(&storage),
@@ -630,6 +783,7 @@ async fn metrics() {
         // egress should be observered
         collect_metrics_iteration(
             &metrics.endpoints,
+            &mut unsent,
             &client,
             &endpoint,
             Some(&storage),
@@ -654,6 +808,7 @@ async fn metrics() {
         // we do not observe the counter
         collect_metrics_iteration(
             &metrics.endpoints,
+            &mut unsent,
             &client,
             &endpoint,
             Some(&storage),
@@ -701,4 +856,164 @@ async fn metrics() {
         stored_chunks.sort_by_cached_key(|c| c.events[0].idempotency_key.clone());
         assert_eq!(pushed_chunks, stored_chunks);
     }
+
+    #[tokio::test]
+    async fn unsent_metrics_are_replayed() {
+        type Report = EventChunk<'static, Event<Extra, String>>;
+        let reports: Arc<Mutex<Vec<Report>>> = Arc::default();
+        // the status the endpoint responds with, 0 to accept the events.
+        let reject = Arc::new(std::sync::atomic::AtomicU16::new(503));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn({
+            let reports = reports.clone();
+            let reject = reject.clone();
+            async move {
+                loop {
+                    if let Ok((stream, _addr)) = listener.accept().await {
+                        let reports = reports.clone();
+                        let reject = reject.clone();
+                        http1::Builder::new()
+                            .serve_connection(
+                                TokioIo::new(stream),
+                                service_fn(move |req: Request<Incoming>| {
+                                    let reports = reports.clone();
+                                    let reject = reject.clone();
+                                    async move {
+                                        let mut response = Response::new(String::new());
+                                        let status = reject.load(Ordering::Relaxed);
+                                        if status != 0 {
+                                            *response.status_mut() =
+                                                hyper::StatusCode::from_u16(status)?;
+                                            return Ok::<_, Error>(response);
+                                        }
+                                        let bytes = req.into_body().collect().await?.to_bytes();
+                                        let events = serde_json::from_slice(&bytes)?;
+                                        reports.lock().unwrap().push(events);
+                                        Ok::<_, Error>(response)
+                                    }
+                                }),
+                            )
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+        });
+
+        let metrics = Metrics::default();
+        let mut unsent = UnsentEvents::new(10);
+        let client = http::new_client();
+        let endpoint = Url::parse(&format!("http://{addr}")).unwrap();
+        let now = Utc::now();
+
+        let counter = metrics.register(Ids {
+            endpoint_id: (&EndpointId::from("e1")).into(),
+            branch_id: (&BranchId::from("b1")).into(),
+            private_link_id: None,
+        });
+
+        // the endpoint is down, the events are kept
+        counter.record_egress(1);
+        collect_metrics_iteration(
+            &metrics.endpoints,
+            &mut unsent,
+            &client,
+            &endpoint,
+            None,
+            1000,
+            "foo",
+            now,
+            now,
+        )
+        .await;
+        assert!(reports.lock().unwrap().is_empty());
+        assert_eq!(unsent.intervals.len(), 1);
+
+        // once it is back, the new events are sent first, then the backlog
+        reject.store(0, Ordering::Relaxed);
+        counter.record_egress(2);
+        collect_metrics_iteration(
+            &metrics.endpoints,
+            &mut unsent,
+            &client,
+            &endpoint,
+            None,
+            1000,
+            "foo",
+            now,
+            now,
+        )
+        .await;
+        let r = std::mem::take(&mut *reports.lock().unwrap());
+        assert_eq!(r.len(), 2);
+        assert_eq!(r[0].events[0].value, 2);
+        assert_eq!(r[1].events[0].value, 1);
+        assert!(unsent.intervals.is_empty());
+
+        // events the endpoint refuses for good are not kept
+        reject.store(400, Ordering::Relaxed);
+        counter.record_egress(3);
+        collect_metrics_iteration(
+            &metrics.endpoints,
+            &mut unsent,
+            &client,
+            &endpoint,
+            None,
+            1000,
+            "foo",
+            now,
+            now,
+        )
+        .await;
+        assert!(reports.lock().unwrap().is_empty());
+        assert!(unsent.intervals.is_empty());
+    }
+
+    #[test]
+    fn unsent_metrics_buffer_is_bounded() {
+        let event = |value| Event {
+            kind: EventType::Absolute { time: Utc::now() },
+            metric: PROXY_IO_BYTES_PER_CLIENT,
+            idempotency_key: idempotency_key("foo"),
+            value,
+            extra: Extra {
+                ids: Ids {
+                    endpoint_id: (&EndpointId::from("e1")).into(),
+                    branch_id: (&BranchId::from("b1")).into(),
+                    private_link_id: None,
+                },
+                direction: TrafficDirection::Egress,
+            },
+        };
+
+        let mut unsent = UnsentEvents::new(2);
+        unsent.push(vec![]);
+        assert!(unsent.intervals.is_empty());
+
+        for value in 1..=3 {
+            unsent.push(vec![event(value)]);
+        }
+        let values: Vec<u64> = unsent.intervals.iter().map(|e| e[0].value).collect();
+        assert_eq!(values, [2, 3]);
+    }
+
+    #[test]
+    fn jittered_interval() {
+        let interval = Duration::from_secs(60);
+        assert_eq!(jittered(interval, 0.0), interval);
+        assert_eq!(initial_delay(interval, 0.0), Duration::ZERO);
+
+        let mut total = Duration::ZERO;
+        for _ in 0..1000 {
+            let jittered = jittered(interval, 0.1);
+            assert!(jittered >= Duration::from_secs(54) && jittered <= Duration::from_secs(66));
+            total += jittered;
+            assert!(initial_delay(interval, 0.1) < interval);
+        }
+        // the average stays close to the configured interval
+        let average = total / 1000;
+        assert!(average > Duration::from_secs(59) && average < Duration::from_secs(61));
+    }
 }
@@ -544,6 +544,32 @@ def test_http_pool_begin(static_proxy: NeonProxy):
     query(200, "SELECT 1;")  # Query that should succeed regardless of the transaction
 
 
+# Session state set by one request should not be visible to the next request,
+# even if it reuses the same pooled connection.
+def test_sql_over_http_pool_reset(static_proxy: NeonProxy):
+    static_proxy.safe_psql("create user http_auth with password 'http' superuser")
+
+    def query(status: int, query: str) -> Any:
+        return static_proxy.http_query(
+            query,
+            [],
+            user="http_auth",
+            password="http",
+            expected_code=status,
+        )
+
+    pid1 = query(200, GET_CONNECTION_PID_QUERY)["rows"][0]["pid"]
+    time.sleep(0.02)
+    query(200, "SET neon_test.dirty = 'yes'")
+    time.sleep(0.02)
+    rows = query(
+        200,
+        "SELECT pg_backend_pid() as pid, current_setting('neon_test.dirty', true) as dirty",
+    )["rows"]
+    assert rows[0]["pid"] == pid1
+    assert rows[0]["dirty"] != "yes"
+
+
 def test_sql_over_http_pool_idle(static_proxy: NeonProxy):
     static_proxy.safe_psql("create user http_auth2 with password 'http' superuser")
 